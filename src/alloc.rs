@@ -0,0 +1,153 @@
+//! Allocation of the `Buffer`s returned by de/compression functions. By default each is allocated
+//! by the global allocator as it's needed; `cramjam.set_output_allocator("slab", ...)` instead keeps
+//! a fixed number of preallocated buffers, slabs, which outputs are written into while they fit,
+//! and which are kept for reuse once the `Buffer` holding one is dropped.
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use pyo3::wrap_pyfunction;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+pub(crate) fn init_py_module(m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(set_output_allocator, m)?)?;
+    m.add_function(wrap_pyfunction!(output_allocator_stats, m)?)?;
+    Ok(())
+}
+
+/// Size of each slab when none is given, 1MiB
+const DEFAULT_SLAB_SIZE: usize = 1 << 20;
+
+/// Number of slabs when none is given
+const DEFAULT_SLAB_COUNT: usize = 8;
+
+/// Slabs of the `"slab"` strategy, or `None` for the default allocator
+static SLABS: Mutex<Option<Slabs>> = Mutex::new(None);
+
+struct Slabs {
+    slab_size: usize,
+    slab_count: usize,
+    free: Vec<Vec<u8>>,
+    hits: u64,
+    misses: u64,
+}
+
+fn slabs() -> MutexGuard<'static, Option<Slabs>> {
+    // the slabs are left consistent between each of their fields' updates, so a panic can't spoil them
+    SLABS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Select how the output buffers of de/compression functions are allocated; `strategy` is
+/// `"default"`, the global allocator, or `"slab"`, `slab_count` buffers of `slab_size` bytes each
+/// (by default 8 of 1MiB), allocated up front. Outputs no longer than a slab are written into a
+/// free one, and others, or ones beyond the free slabs, are allocated as by default. Slabs are
+/// reused once the `Buffer` holding them is dropped. Selecting a strategy frees any existing slabs.
+/// Raises `ValueError` for an unknown `strategy`, or a `slab_size` or `slab_count` of 0.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.set_output_allocator("slab", slab_size=64 * 1024, slab_count=4)
+/// >>> cramjam.output_allocator_stats()
+/// {'strategy': 'slab', 'slab_size': 65536, 'slab_count': 4, 'free_slabs': 4, 'hits': 0, 'misses': 0}
+/// >>> cramjam.set_output_allocator("default")
+/// ```
+#[pyfunction]
+pub fn set_output_allocator(strategy: &str, slab_size: Option<usize>, slab_count: Option<usize>) -> PyResult<()> {
+    let new = match strategy {
+        "default" => None,
+        "slab" => {
+            let slab_size = slab_size.unwrap_or(DEFAULT_SLAB_SIZE);
+            let slab_count = slab_count.unwrap_or(DEFAULT_SLAB_COUNT);
+            if slab_size == 0 || slab_count == 0 {
+                return Err(pyo3::exceptions::PyValueError::new_err(
+                    "slab_size and slab_count must be greater than 0",
+                ));
+            }
+            Some(Slabs {
+                slab_size,
+                slab_count,
+                free: (0..slab_count).map(|_| Vec::with_capacity(slab_size)).collect(),
+                hits: 0,
+                misses: 0,
+            })
+        }
+        _ => {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Unknown strategy '{}', should be one of 'default' or 'slab'",
+                strategy
+            )))
+        }
+    };
+    *slabs() = new;
+    Ok(())
+}
+
+/// Statistics of the allocator selected with `set_output_allocator`, as a `dict`; its `"strategy"`,
+/// and with `"slab"`, its `"slab_size"`, `"slab_count"`, the number of `"free_slabs"`, and the number
+/// of outputs since it was selected which were written into a slab, `"hits"`, or weren't, `"misses"`.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.output_allocator_stats()
+/// {'strategy': 'default'}
+/// ```
+#[pyfunction]
+pub fn output_allocator_stats(py: Python<'_>) -> PyResult<&PyDict> {
+    let stats = PyDict::new(py);
+    match &*slabs() {
+        None => stats.set_item("strategy", "default")?,
+        Some(slabs) => {
+            stats.set_item("strategy", "slab")?;
+            stats.set_item("slab_size", slabs.slab_size)?;
+            stats.set_item("slab_count", slabs.slab_count)?;
+            stats.set_item("free_slabs", slabs.free.len())?;
+            stats.set_item("hits", slabs.hits)?;
+            stats.set_item("misses", slabs.misses)?;
+        }
+    }
+    Ok(stats)
+}
+
+/// Output buffer of `len` zeros, or empty to be grown when `None`, from a free slab if it fits in one
+pub(crate) fn output_vec(len: Option<usize>) -> Vec<u8> {
+    match len {
+        Some(len) => {
+            let mut output = output_with_capacity(len);
+            output.resize(len, 0);
+            output
+        }
+        None => output_with_capacity(0),
+    }
+}
+
+/// Empty output buffer with room for `capacity` bytes, from a free slab if it fits in one
+pub(crate) fn output_with_capacity(capacity: usize) -> Vec<u8> {
+    if let Some(slabs) = slabs().as_mut() {
+        match slabs.free.pop() {
+            Some(slab) if capacity <= slabs.slab_size => {
+                slabs.hits += 1;
+                return slab;
+            }
+            slab => {
+                slabs.free.extend(slab);
+                slabs.misses += 1;
+            }
+        }
+    }
+    Vec::with_capacity(capacity)
+}
+
+/// Keep `buffer` as a free slab, if it's at least as large as one and not all slabs are free
+pub(crate) fn release(mut buffer: Vec<u8>) {
+    if buffer.capacity() == 0 {
+        return;
+    }
+    if let Some(slabs) = slabs().as_mut() {
+        if buffer.capacity() >= slabs.slab_size && slabs.free.len() < slabs.slab_count {
+            // outputs grown beyond their slab are trimmed back to it, so slabs stay within their size
+            buffer.clear();
+            buffer.shrink_to(slabs.slab_size);
+            slabs.free.push(buffer);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Apache Arrow IPC buffer de/compression interface. Each compressed buffer in the body of an Arrow
+//! record batch is prefixed with its uncompressed length, a little endian `int64`, or -1 if the data
+//! following it was left uncompressed; the body itself is an lz4 frame or a zstd frame.
+use crate::exceptions::{CompressionError, DecompressionError};
+use crate::io::{AsBytes, RustyBuffer};
+use crate::{to_py_err, BytesType};
+use pyo3::prelude::*;
+use pyo3::wrap_pyfunction;
+use pyo3::PyResult;
+use std::convert::TryFrom;
+use std::io::{Cursor, Write};
+
+pub(crate) fn init_py_module(m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(compress_buffer, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_buffer, m)?)?;
+    Ok(())
+}
+
+/// Length of the uncompressed length prefixing each buffer
+const PREFIX_LEN: usize = 8;
+
+/// Uncompressed length recorded for a buffer which was left uncompressed
+const UNCOMPRESSED: i64 = -1;
+
+/// Compress `data` into a buffer of an Arrow IPC record batch body, with `algorithm`, `"lz4"` for
+/// Arrow's `LZ4_FRAME` codec or `"zstd"` for its `ZSTD` codec, at `level`, or the algorithm's default.
+/// Raises `ValueError` for any other algorithm.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.arrow.compress_buffer(b'some bytes here', "zstd", level=Optional[int])
+/// ```
+#[pyfunction]
+pub fn compress_buffer(mut data: BytesType, algorithm: &str, level: Option<i32>) -> PyResult<RustyBuffer> {
+    let len = data.remaining_len()?;
+    let mut output = Cursor::new(vec![]);
+    output.write_all(&(len as i64).to_le_bytes())?;
+    match algorithm {
+        "lz4" => {
+            let level = level
+                .map(u32::try_from)
+                .transpose()
+                .map_err(|_| CompressionError::new_err("lz4 levels can't be negative"))?;
+            to_py_err!(CompressionError -> crate::lz4::internal::compress(&mut data, &mut output, level))?
+        }
+        "zstd" => {
+            let options = crate::zstd::internal::CompressOptions {
+                level,
+                content_size: Some(len),
+                ..Default::default()
+            };
+            to_py_err!(CompressionError -> crate::zstd::internal::compress(data, &mut output, &options))?
+        }
+        _ => return Err(unknown_algorithm(algorithm)),
+    };
+    Ok(RustyBuffer::from(output.into_inner()))
+}
+
+/// Decompress a buffer of an Arrow IPC record batch body, compressed with `algorithm`, `"lz4"` or
+/// `"zstd"` as for `compress_buffer`, which is the codec recorded in the record batch's metadata.
+/// Buffers with a length of -1 were stored uncompressed, and are returned as they are, and empty
+/// buffers, which Arrow writes without a length, are returned empty. Raises `DecompressionError`
+/// if the buffer doesn't decompress to the length it records.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.arrow.decompress_buffer(compressed_bytes, "zstd")
+/// ```
+#[pyfunction]
+pub fn decompress_buffer(data: BytesType, algorithm: &str) -> PyResult<RustyBuffer> {
+    if !["lz4", "zstd"].contains(&algorithm) {
+        return Err(unknown_algorithm(algorithm));
+    }
+    let bytes = data.as_bytes();
+    if bytes.is_empty() {
+        return Ok(RustyBuffer::from(vec![]));
+    }
+    if bytes.len() < PREFIX_LEN {
+        return Err(DecompressionError::new_err(format!(
+            "Truncated Arrow buffer of {} bytes, expected at least its {} byte length",
+            bytes.len(),
+            PREFIX_LEN
+        )));
+    }
+    let (prefix, body) = bytes.split_at(PREFIX_LEN);
+    let len = i64::from_le_bytes(<[u8; PREFIX_LEN]>::try_from(prefix).unwrap());
+    if len == UNCOMPRESSED {
+        return Ok(RustyBuffer::from(body.to_vec()));
+    }
+    let len =
+        u64::try_from(len).map_err(|_| DecompressionError::new_err(format!("Invalid Arrow buffer length {}", len)))?;
+
+    // the recorded length is only trusted so far for allocating, as it may be corrupt
+    let capacity = len.min((body.len() as u64).saturating_mul(crate::MAX_PRESIZE_RATIO));
+    let mut output = Vec::with_capacity(capacity as usize);
+    match algorithm {
+        "lz4" => to_py_err!(DecompressionError -> crate::lz4::internal::decompress(body, &mut output))?,
+        _ => to_py_err!(DecompressionError -> crate::zstd::internal::decompress(body, &mut output))?,
+    };
+    if output.len() as u64 != len {
+        return Err(DecompressionError::new_err(format!(
+            "Arrow buffer decompressed to {} bytes, but records a length of {}",
+            output.len(),
+            len
+        )));
+    }
+    Ok(RustyBuffer::from(output))
+}
+
+fn unknown_algorithm(algorithm: &str) -> PyErr {
+    pyo3::exceptions::PyValueError::new_err(format!(
+        "Unknown algorithm '{}', should be one of 'lz4' or 'zstd'",
+        algorithm
+    ))
+}
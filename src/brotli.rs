@@ -1,76 +1,526 @@
 //! brotli de/compression interface
 use crate::exceptions::{CompressionError, DecompressionError};
-use crate::io::RustyBuffer;
-use crate::{to_py_err, BytesType};
+use crate::io::{RustyBuffer, RustyLines, RustyStridedNumpyArray};
+use crate::{to_py_err, BytesType, Level};
+use brotli2::CompressMode;
+use numpy::PyArray1;
 use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyString};
 use pyo3::wrap_pyfunction;
 use pyo3::PyResult;
-use std::io::Cursor;
+use std::io::{Cursor, Read};
 
 pub(crate) fn init_py_module(m: &PyModule) -> PyResult<()> {
+    m.add("DEFAULT_LEVEL", internal::DEFAULT_LEVEL)?;
+    m.add("MAX_LEVEL", internal::MAX_LEVEL)?;
     m.add_function(wrap_pyfunction!(compress, m)?)?;
     m.add_function(wrap_pyfunction!(decompress, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_bound, m)?)?;
     m.add_function(wrap_pyfunction!(compress_into, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_reuse, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_strided, m)?)?;
     m.add_function(wrap_pyfunction!(decompress_into, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_to_memmap, m)?)?;
+    #[cfg(unix)]
+    m.add_function(wrap_pyfunction!(compress_fd, m)?)?;
+    #[cfg(unix)]
+    m.add_function(wrap_pyfunction!(decompress_fd, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_lines, m)?)?;
+    m.add_function(wrap_pyfunction!(recompress_range, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_str, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_str, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_many, m)?)?;
+    m.add_function(wrap_pyfunction!(is_valid, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_many_into, m)?)?;
+    m.add_function(wrap_pyfunction!(maybe_compress, m)?)?;
+    m.add_function(wrap_pyfunction!(maybe_decompress, m)?)?;
     Ok(())
 }
 
 /// Brotli decompression.
 ///
+/// With `recover=True`, decoding stops at the first corrupted or truncated section of the input
+/// rather than raising; a tuple of the `Buffer` decompressed up to that point, and whether
+/// the input was found to be damaged, is returned instead.
+///
+/// With `timeout_ms`, `DecompressionError` is raised if decompression takes longer than that
+/// many milliseconds, ie. to limit the time spent on untrusted input.
+///
+/// With `pad_to`, the input is taken to be the padded output of `compress` with the same `pad_to`.
+///
+/// With `trailer_checksum`, the trailer appended by `compress` with the same `trailer_checksum` is
+/// checked before decompressing, raising `DecompressionError` if the compressed data was corrupted.
+///
+/// With `exact=True`, and no `output_len`, the data is decompressed twice, first only to count its
+/// decompressed length, so the output is allocated once rather than grown as it's decompressed.
+///
 /// Python Example
 /// --------------
 /// ```python
-/// >>> cramjam.brotli.decompress(compressed_bytes, output_len=Optional[int])
+/// >>> cramjam.brotli.decompress(compressed_bytes, output_len=Optional[int], recover=False, timeout_ms=Optional[int], pad_to=Optional[int], trailer_checksum=Optional[str], exact=False)
 /// ```
 #[pyfunction]
-pub fn decompress(data: BytesType, output_len: Option<usize>) -> PyResult<RustyBuffer> {
-    crate::generic!(decompress(data), output_len = output_len)
+#[allow(clippy::too_many_arguments)]
+pub fn decompress(
+    py: Python,
+    mut data: BytesType,
+    output_len: Option<usize>,
+    recover: Option<bool>,
+    timeout_ms: Option<u64>,
+    pad_to: Option<usize>,
+    trailer_checksum: Option<&str>,
+    exact: Option<bool>,
+) -> PyResult<PyObject> {
+    let recover = recover.unwrap_or(false);
+    let compressed = crate::unpadded(&mut data, pad_to)?;
+    let compressed = crate::TrailerChecked::new(compressed, trailer_checksum)?;
+    if recover || timeout_ms.is_some() {
+        return crate::decompress_with(py, internal::decoder(compressed), recover, timeout_ms, false);
+    }
+    if exact.unwrap_or(false) && output_len.is_none() {
+        return crate::decompress_exact(compressed, |input, output| internal::decompress(input, output))
+            .map(|buffer| buffer.into_py(py));
+    }
+    crate::generic!(decompress(compressed), output_len = output_len).map(|buffer: RustyBuffer| buffer.into_py(py))
 }
 
 /// Brotli compression.
 ///
+/// `level` ranges from 0, the fastest, to 11; it defaults to 11 when `None` or omitted.
+/// `CompressionError` is raised for any other level, rather than it being clamped to 11.
+///
+/// `lgwin` is the base 2 logarithm of the window size, from 10 to 24, and defaults to brotli's 22;
+/// a smaller window needs less memory to compress and decompress, ie. to suit a constrained decoder,
+/// at some cost to the ratio. `CompressionError` is raised for any other `lgwin`.
+///
+/// `mode` tunes the encoder to the input; `"generic"`, the default, `"text"` for UTF-8 text, or
+/// `"font"` for WOFF 2.0 fonts. Any mode decompresses the same way.
+///
+/// With `pad_to`, the output is padded with zeros to a multiple of that many bytes, hiding its exact
+/// length, ie. before encrypting it; decompress it with the same `pad_to`.
+///
+/// `trailer_checksum="crc32"`, or `"xxh3"` which is faster for large outputs, appends a checksum
+/// of the compressed data, before any padding, so corruption in transport is caught before
+/// decompressing; decompress it with the same `trailer_checksum`.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.brotli.compress(b'some bytes here', level=9, output_len=Option[int])
+/// >>> cramjam.brotli.compress(b'some bytes here', lgwin=16)
+/// >>> cramjam.brotli.compress(b'<html>some text here</html>', mode="text")
+/// >>> cramjam.brotli.compress(b'some bytes here', level="auto")  # 11 for inputs below 1MiB, 6 below 64MiB, otherwise 3
+/// ```
+#[pyfunction]
+pub fn compress(
+    mut data: BytesType,
+    level: Option<Level<u32>>,
+    output_len: Option<usize>,
+    pad_to: Option<usize>,
+    trailer_checksum: Option<&str>,
+    lgwin: Option<u32>,
+    mode: Option<&str>,
+) -> PyResult<RustyBuffer> {
+    let level = level.map(|level| level.resolve(&mut data, auto_level)).transpose()?;
+    to_py_err!(CompressionError -> internal::checked_level(level))?;
+    to_py_err!(CompressionError -> internal::checked_lgwin(lgwin))?;
+    let mode = compress_mode(mode)?;
+    crate::generic!(
+        compress_with_params(data),
+        output_len = output_len,
+        level = level,
+        lgwin = lgwin,
+        mode = mode
+    )
+    .and_then(|compressed| crate::with_trailer(compressed, trailer_checksum))
+    .and_then(|compressed| crate::pad(compressed, pad_to))
+}
+
+/// Encoder mode named by `mode`, of `compress`
+fn compress_mode(mode: Option<&str>) -> PyResult<CompressMode> {
+    match mode.unwrap_or("generic") {
+        "generic" => Ok(CompressMode::Generic),
+        "text" => Ok(CompressMode::Text),
+        "font" => Ok(CompressMode::Font),
+        mode => Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "Unknown mode '{}', should be one of 'generic', 'text' or 'font'",
+            mode
+        ))),
+    }
+}
+
+/// Level used with `level="auto"`, by the length of the input
+fn auto_level(input_len: u64) -> u32 {
+    match input_len {
+        len if len < crate::AUTO_LEVEL_SMALL_INPUT => 11,
+        len if len < crate::AUTO_LEVEL_LARGE_INPUT => 6,
+        _ => 3,
+    }
+}
+
+/// Compress `text`, a `str`, encoded with `encoding` (default `"utf-8"`); `level` is as for `compress`.
+///
 /// Python Example
 /// --------------
 /// ```python
-/// >>> cramjam.brotli.compress(b'some bytes here', level=9, output_len=Option[int])  # level defaults to 11
+/// >>> cramjam.brotli.compress_str("some text here", encoding="utf-8", level=Optional[int])
 /// ```
 #[pyfunction]
-pub fn compress(data: BytesType, level: Option<u32>, output_len: Option<usize>) -> PyResult<RustyBuffer> {
-    crate::generic!(compress(data), output_len = output_len, level = level)
+pub fn compress_str(text: &PyString, encoding: Option<&str>, level: Option<Level<u32>>) -> PyResult<RustyBuffer> {
+    compress(crate::encode_str(text, encoding)?, level, None, None, None, None, None)
 }
 
-/// Compress directly into an output buffer
+/// Decompress to a `str`, decoding the decompressed data with `encoding` (default `"utf-8"`).
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.brotli.decompress_str(compressed_bytes, encoding="utf-8")
+/// 'some text here'
+/// ```
 #[pyfunction]
-pub fn compress_into(input: BytesType, mut output: BytesType, level: Option<u32>) -> PyResult<usize> {
-    let r = internal::compress(input, &mut output, level)?;
-    Ok(r)
+pub fn decompress_str<'a>(py: Python<'a>, data: BytesType, encoding: Option<&str>) -> PyResult<&'a PyString> {
+    let mut output = vec![];
+    to_py_err!(DecompressionError -> internal::decompress(data, &mut output))?;
+    crate::decode_str(py, &output, encoding)
+}
+
+/// Check `data` decompresses without error, returning `True` if it does and `False` if it doesn't,
+/// ie. it's truncated or corrupt; the decompressed data is discarded as it's decoded, rather than
+/// kept in an output buffer.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.brotli.is_valid(compressed_bytes)
+/// True
+/// ```
+#[pyfunction]
+pub fn is_valid(data: BytesType) -> bool {
+    internal::decompress(data, &mut std::io::sink()).is_ok()
+}
+
+/// Check each of `items` decompresses, and when given, to the corresponding item of `expected`;
+/// returns the index of the first which doesn't, or `None` if all of them do. The items are
+/// checked in parallel with the GIL released.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.brotli.verify_many([compressed_bytes, other_compressed_bytes], expected=Optional[List[bytes]])
+/// ```
+#[pyfunction]
+pub fn verify_many(py: Python, items: Vec<BytesType>, expected: Option<Vec<BytesType>>) -> PyResult<Option<usize>> {
+    crate::verify_many(py, items, expected, |input, output| internal::decompress(input, output))
+}
+
+/// Decompress each of `items` into successive regions of `array`, a `numpy.array` with `dtype=np.uint8`,
+/// returning the `(offset, length)` of each region; raises `BufferError` if the array fills up first,
+/// and `ValueError` if an item overlaps the array, ie. is a view of it.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> array = np.empty(total_len, dtype=np.uint8)
+/// >>> regions = cramjam.brotli.decompress_many_into([compressed_bytes, other_compressed_bytes], array)
+/// ```
+#[pyfunction]
+pub fn decompress_many_into(items: Vec<BytesType>, array: &PyArray1<u8>) -> PyResult<Vec<(usize, usize)>> {
+    crate::decompress_many_into(items, array, |input, output| internal::decompress(input, output))
+}
+
+/// Compress, unless that doesn't achieve a ratio of uncompressed to compressed length of at least
+/// `min_ratio` (default 1.1); returns a tuple of the compressed data and `True`, or a copy of the
+/// data as is and `False`, to be given to `maybe_decompress`. `level` is as for `compress`.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> data, compressed = cramjam.brotli.maybe_compress(b'some bytes here', min_ratio=1.1, level=Optional[int])
+/// >>> bytes(cramjam.brotli.maybe_decompress(data, compressed))
+/// b'some bytes here'
+/// ```
+#[pyfunction]
+pub fn maybe_compress(
+    mut data: BytesType,
+    min_ratio: Option<f64>,
+    level: Option<Level<u32>>,
+) -> PyResult<(RustyBuffer, bool)> {
+    let level = level.map(|level| level.resolve(&mut data, auto_level)).transpose()?;
+    crate::maybe_compress(data, min_ratio, |input, output| {
+        internal::compress(input, output, level)
+    })
+}
+
+/// Decompress the data returned by `maybe_compress` if its flag, `compressed`, is `True`, otherwise
+/// return a copy of it as is.
+#[pyfunction]
+pub fn maybe_decompress(data: BytesType, compressed: bool) -> PyResult<RustyBuffer> {
+    crate::maybe_decompress(data, compressed, |input, output| internal::decompress(input, output))
+}
+
+/// Get the maximum length of the compressed output for `data_len` bytes of input, the size
+/// of buffer which is always big enough to be passed to `compress_into`
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> output = bytearray(cramjam.brotli.compress_bound(len(data)))
+/// >>> n_bytes = cramjam.brotli.compress_into(data, output)
+/// ```
+#[pyfunction]
+pub fn compress_bound(data_len: usize) -> PyResult<usize> {
+    crate::checked_bound(internal::compress_bound(data_len), data_len)
+}
+
+/// Compress directly into an output buffer, returning the compressed length; `CompressionError` is
+/// raised with `compress_bound` of the input's length if the buffer is too small to hold it.
+#[pyfunction]
+pub fn compress_into(input: BytesType, output: BytesType, level: Option<u32>) -> PyResult<usize> {
+    crate::compress_into(input, output, internal::compress_bound, |input, output| {
+        internal::compress(input, output, level)
+    })
+}
+
+/// Compress using `scratch`, a `numpy.array` with `dtype=np.uint8`, as the output buffer, returning
+/// a copy of the compressed data as `bytes`. The array is resized as needed, and can be reused across
+/// calls to avoid allocating a new output buffer each time; as with `numpy.ndarray.resize`, it must
+/// own its data and not be referenced by any other array.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> scratch = np.empty(0, dtype=np.uint8)
+/// >>> for data in chunks:
+/// ...     compressed = cramjam.brotli.compress_reuse(data, scratch, level=Optional[int])
+/// ```
+#[pyfunction]
+pub fn compress_reuse<'a>(
+    py: Python<'a>,
+    data: BytesType,
+    scratch: &PyArray1<u8>,
+    level: Option<u32>,
+) -> PyResult<&'a PyBytes> {
+    crate::io::compress_reuse(py, scratch, |output| internal::compress(data, output, level))
+}
+
+/// Compress a `numpy.array` of any dtype, including non-contiguous views such as `arr[::2]`.
+/// The elements are gathered into a contiguous buffer, in C order, before being compressed.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> arr = np.arange(100, dtype=np.float64).reshape(10, 10)
+/// >>> cramjam.brotli.compress_strided(arr[::2, 1::3], level=Optional[int], output_len=Optional[int])
+/// ```
+#[pyfunction]
+pub fn compress_strided(
+    array: RustyStridedNumpyArray,
+    level: Option<u32>,
+    output_len: Option<usize>,
+) -> PyResult<RustyBuffer> {
+    crate::generic!(compress(array), output_len = output_len, level = level)
+}
+
+/// Decompress into a new `numpy.memmap` of `dtype` (default `"uint8"`) at `path`, which is created
+/// or overwritten, returning it; for data too large to decompress into memory. The data is
+/// decompressed twice, first to find its length.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> array = cramjam.brotli.decompress_to_memmap(compressed_bytes, "/tmp/data.bin", dtype="float64")
+/// ```
+#[pyfunction]
+pub fn decompress_to_memmap<'a>(
+    py: Python<'a>,
+    data: BytesType,
+    path: &str,
+    dtype: Option<&str>,
+) -> PyResult<&'a PyAny> {
+    crate::decompress_to_memmap(py, data, path, dtype, None, |input, output| {
+        internal::decompress(input, output)
+    })
 }
 
 /// Decompress directly into an output buffer
 #[pyfunction]
 pub fn decompress_into(input: BytesType, mut output: BytesType) -> PyResult<usize> {
+    output.check_writable()?;
     let r = internal::decompress(input, &mut output)?;
     Ok(r)
 }
 
+/// Compress from the file descriptor `in_fd` until its end, writing to `out_fd`, ie. either end of
+/// an `os.pipe()`, returning the number of bytes written; neither descriptor is closed.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.brotli.compress_fd(in_fd, out_fd, level=Optional[int])
+/// ```
+#[cfg(unix)]
+#[pyfunction]
+pub fn compress_fd(py: Python, in_fd: i32, out_fd: i32, level: Option<u32>) -> PyResult<usize> {
+    crate::io::with_fds::<CompressionError, _>(py, in_fd, out_fd, |input, output| {
+        internal::compress(input, output, level).map(|_| ())
+    })
+}
+
+/// Decompress from the file descriptor `in_fd` until its end, writing to `out_fd`, returning
+/// the number of bytes written; neither descriptor is closed.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.brotli.decompress_fd(in_fd, out_fd)
+/// ```
+#[cfg(unix)]
+#[pyfunction]
+pub fn decompress_fd(py: Python, in_fd: i32, out_fd: i32) -> PyResult<usize> {
+    crate::io::with_fds::<DecompressionError, _>(py, in_fd, out_fd, |input, output| {
+        internal::decompress(input, output).map(|_| ())
+    })
+}
+
+/// Decompress line by line; returns an iterator over the lines of decompressed data
+/// as `bytes`, without materializing the entire decompressed data. See [`Lines`](../io/struct.RustyLines.html)
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> for line in cramjam.brotli.decompress_lines(compressed_bytes):
+/// ...     print(line)
+/// ```
+#[pyfunction]
+pub fn decompress_lines(mut data: BytesType) -> PyResult<RustyLines> {
+    let mut compressed = vec![];
+    data.read_to_end(&mut compressed)?;
+    let decoder = to_py_err!(DecompressionError -> internal::decoder(Cursor::new(compressed)))?;
+    Ok(RustyLines::new(decoder))
+}
+
+/// Replace the `[start, end)` range of the decompressed data with `replacement`, returning the edited
+/// data compressed again, without returning the decompressed data to Python. Raises `ValueError`
+/// if `start` is greater than `end`, and `DecompressionError` if the range is beyond the end of the data.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.brotli.recompress_range(compressed_bytes, start, end, b'replacement', level=Optional[int])
+/// ```
+#[pyfunction]
+pub fn recompress_range(
+    data: BytesType,
+    start: u64,
+    end: u64,
+    replacement: BytesType,
+    level: Option<u32>,
+) -> PyResult<RustyBuffer> {
+    crate::io::recompress_range(internal::decoder(data), start, end, replacement, |input, output| {
+        internal::compress(input, output, level)
+    })
+}
+
 pub(crate) mod internal {
 
-    use brotli2::read::{BrotliDecoder, BrotliEncoder};
+    use crate::io::FinishWrite;
+    use brotli2::bufread::BrotliEncoder;
+    use brotli2::read::BrotliDecoder;
+    use brotli2::write::BrotliEncoder as BrotliWriteEncoder;
+    use brotli2::{CompressMode, CompressParams};
     use std::io::prelude::*;
-    use std::io::Error;
+    use std::io::{BufReader, Error, ErrorKind};
+
+    /// Compression level used when none is given
+    pub(crate) const DEFAULT_LEVEL: u32 = 11;
+
+    /// Highest compression level
+    pub(crate) const MAX_LEVEL: u32 = 11;
+
+    /// `level`, or the default, checked to be at most [`MAX_LEVEL`], rather than clamped to it as brotli does
+    pub(crate) fn checked_level(level: Option<u32>) -> Result<u32, Error> {
+        crate::checked_level("brotli", level, DEFAULT_LEVEL, 0, MAX_LEVEL)
+    }
+
+    /// Smallest and largest base 2 logarithm of the window size, `lgwin`
+    pub(crate) const MIN_LGWIN: u32 = 10;
+    pub(crate) const MAX_LGWIN: u32 = 24;
+
+    /// `lgwin`, or brotli's default of 22, checked to be from [`MIN_LGWIN`] to [`MAX_LGWIN`]
+    pub(crate) fn checked_lgwin(lgwin: Option<u32>) -> Result<u32, Error> {
+        let lgwin = lgwin.unwrap_or(brotli_sys::BROTLI_DEFAULT_WINDOW);
+        if !(MIN_LGWIN..=MAX_LGWIN).contains(&lgwin) {
+            let msg = format!(
+                "brotli lgwin must be between {} and {}, got {}",
+                MIN_LGWIN, MAX_LGWIN, lgwin
+            );
+            return Err(Error::new(ErrorKind::InvalidInput, msg));
+        }
+        Ok(lgwin)
+    }
+
+    /// Length of the reads of brotli2's `BrotliEncoder` from its input, the size of its `BufReader`
+    const READ_LEN: usize = 8 * 1024;
+
+    /// Largest output of `compress` for `input_len` bytes of input. brotli's own bound is for
+    /// compressing it at once, where incompressible data is stored in meta-blocks of up to 16MiB,
+    /// but streaming it, the fastest levels end a meta-block for each read, of up to 4 more bytes.
+    /// `None` if it's too large to be represented, which brotli's bound signals with 0.
+    pub fn compress_bound(input_len: usize) -> Option<usize> {
+        let n_reads = input_len.div_ceil(READ_LEN);
+        let max_len = unsafe { brotli_sys::BrotliEncoderMaxCompressedSize(input_len) };
+        if max_len < input_len.max(1) {
+            return None;
+        }
+        max_len.checked_add(n_reads.checked_mul(4)?)
+    }
+
+    /// Decoder reading compressed data from `input`
+    pub fn decoder<R: Read>(input: R) -> Result<BrotliDecoder<R>, Error> {
+        Ok(BrotliDecoder::new(input))
+    }
+
+    /// Encoder writing compressed data to `output`
+    pub fn encoder<W: Write>(output: W, level: Option<u32>) -> Result<BrotliWriteEncoder<W>, Error> {
+        let level = checked_level(level)?;
+        Ok(BrotliWriteEncoder::new(output, level))
+    }
 
     /// Decompress via Brotli
     pub fn decompress<W: Write + ?Sized, R: Read>(input: R, output: &mut W) -> Result<usize, Error> {
-        let mut decoder = BrotliDecoder::new(input);
+        let mut decoder = decoder(input)?;
         let n_bytes = std::io::copy(&mut decoder, output)?;
         Ok(n_bytes as usize)
     }
 
     /// Compress via Brotli
     pub fn compress<W: Write + ?Sized, R: Read>(input: R, output: &mut W, level: Option<u32>) -> Result<usize, Error> {
-        let level = level.unwrap_or_else(|| 11);
-        let mut encoder = BrotliEncoder::new(input, level);
+        compress_with_params(input, output, level, None, CompressMode::Generic)
+    }
+
+    /// Compress via Brotli, with a window of `2 ** lgwin` bytes, or brotli's default when `None`,
+    /// and the encoder tuned to the input by `mode`
+    pub fn compress_with_params<W: Write + ?Sized, R: Read>(
+        input: R,
+        output: &mut W,
+        level: Option<u32>,
+        lgwin: Option<u32>,
+        mode: CompressMode,
+    ) -> Result<usize, Error> {
+        let level = checked_level(level)?;
+        let lgwin = checked_lgwin(lgwin)?;
+        let mut params = CompressParams::new();
+        params.quality(level).lgwin(lgwin).mode(mode);
+        // brotli2's read encoder would buffer its input by the window size; this keeps it to READ_LEN
+        let mut encoder = BrotliEncoder::from_params(BufReader::with_capacity(READ_LEN, input), &params);
         let n_bytes = std::io::copy(&mut encoder, output)?;
         Ok(n_bytes as usize)
     }
+
+    impl<W: Write + Send> FinishWrite for BrotliWriteEncoder<W> {
+        fn finish(self: Box<Self>) -> Result<(), Error> {
+            BrotliWriteEncoder::finish(*self)?.flush()
+        }
+    }
 }
@@ -0,0 +1,122 @@
+//! bzip2 de/compression interface
+use crate::exceptions::{CompressionError, DecompressionError};
+use crate::io::RustyBuffer;
+use crate::{to_py_err, BytesType};
+use pyo3::prelude::*;
+use pyo3::wrap_pyfunction;
+use pyo3::PyResult;
+use std::io::Cursor;
+
+pub(crate) fn init_py_module(m: &PyModule) -> PyResult<()> {
+    m.add("DEFAULT_LEVEL", internal::DEFAULT_LEVEL)?;
+    m.add("MAX_LEVEL", internal::MAX_LEVEL)?;
+    m.add_function(wrap_pyfunction!(compress, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_bound, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_into, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_into, m)?)?;
+    Ok(())
+}
+
+/// bzip2 decompression.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.bzip2.decompress(compressed_bytes, output_len=Optional[int])
+/// ```
+#[pyfunction]
+pub fn decompress(data: BytesType, output_len: Option<usize>) -> PyResult<RustyBuffer> {
+    crate::generic!(decompress(data), output_len = output_len)
+}
+
+/// bzip2 compression.
+///
+/// `level` is the block size, in units of 100KiB, from 1 to 9; it defaults to 9, like the `bzip2`
+/// command, when `None` or omitted. `CompressionError` is raised for any other level.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.bzip2.compress(b'some bytes here', level=9, output_len=Optional[int])
+/// ```
+#[pyfunction]
+pub fn compress(data: BytesType, level: Option<u32>, output_len: Option<usize>) -> PyResult<RustyBuffer> {
+    to_py_err!(CompressionError -> internal::checked_level(level))?;
+    crate::generic!(compress(data), output_len = output_len, level = level)
+}
+
+/// Get the maximum length of the compressed output for `data_len` bytes of input, the size
+/// of buffer which is always big enough to be passed to `compress_into`
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> output = bytearray(cramjam.bzip2.compress_bound(len(data)))
+/// >>> n_bytes = cramjam.bzip2.compress_into(data, output)
+/// ```
+#[pyfunction]
+pub fn compress_bound(data_len: usize) -> PyResult<usize> {
+    crate::checked_bound(internal::compress_bound(data_len), data_len)
+}
+
+/// Compress directly into an output buffer, returning the compressed length; `CompressionError` is
+/// raised with `compress_bound` of the input's length if the buffer is too small to hold it.
+#[pyfunction]
+pub fn compress_into(input: BytesType, output: BytesType, level: Option<u32>) -> PyResult<usize> {
+    crate::compress_into(input, output, internal::compress_bound, |input, output| {
+        internal::compress(input, output, level)
+    })
+}
+
+/// Decompress directly into an output buffer
+#[pyfunction]
+pub fn decompress_into(input: BytesType, mut output: BytesType) -> PyResult<usize> {
+    output.check_writable()?;
+    let r = to_py_err!(DecompressionError -> internal::decompress(input, &mut output))?;
+    Ok(r)
+}
+
+pub(crate) mod internal {
+    use ::bzip2::read::{BzDecoder, BzEncoder};
+    use ::bzip2::Compression;
+    use std::io::prelude::*;
+    use std::io::Error;
+
+    /// Compression level used when none is given, the largest block size
+    pub(crate) const DEFAULT_LEVEL: u32 = 9;
+
+    /// Highest compression level, of the largest block size
+    pub(crate) const MAX_LEVEL: u32 = 9;
+
+    /// `level`, or the default, checked to be within 1 to [`MAX_LEVEL`]
+    pub(crate) fn checked_level(level: Option<u32>) -> Result<u32, Error> {
+        crate::checked_level("bzip2", level, DEFAULT_LEVEL, 1, MAX_LEVEL)
+    }
+
+    /// Largest output of `compress` for `input_len` bytes of input, 1% larger plus 600 bytes as
+    /// documented by libbzip2; `None` if it's too large to be represented
+    pub fn compress_bound(input_len: usize) -> Option<usize> {
+        input_len.checked_add(input_len / 100 + 1)?.checked_add(600)
+    }
+
+    /// Decoder reading compressed data from `input`
+    pub fn decoder<R: Read>(input: R) -> Result<BzDecoder<R>, Error> {
+        Ok(BzDecoder::new(input))
+    }
+
+    /// Decompress bzip2 data
+    pub fn decompress<W: Write + ?Sized, R: Read>(input: R, output: &mut W) -> Result<usize, Error> {
+        let mut decoder = decoder(input)?;
+        let n_bytes = std::io::copy(&mut decoder, output)?;
+        Ok(n_bytes as usize)
+    }
+
+    /// Compress bzip2 data
+    pub fn compress<W: Write + ?Sized, R: Read>(input: R, output: &mut W, level: Option<u32>) -> Result<usize, Error> {
+        let level = checked_level(level)?;
+        let mut encoder = BzEncoder::new(input, Compression::new(level));
+        let n_bytes = std::io::copy(&mut encoder, output)?;
+        Ok(n_bytes as usize)
+    }
+}
@@ -1,78 +1,559 @@
 //! deflate de/compression interface
 use crate::exceptions::{CompressionError, DecompressionError};
-use crate::io::RustyBuffer;
-use crate::{to_py_err, BytesType};
+use crate::io::{RustyBuffer, RustyLines, RustyStridedNumpyArray};
+use crate::{to_py_err, BytesType, Level};
+use numpy::PyArray1;
 use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyString};
 use pyo3::wrap_pyfunction;
 use pyo3::PyResult;
-use std::io::Cursor;
+use std::io::{Cursor, Read};
 
 pub(crate) fn init_py_module(m: &PyModule) -> PyResult<()> {
+    m.add("DEFAULT_LEVEL", internal::DEFAULT_LEVEL)?;
+    m.add("MAX_LEVEL", internal::MAX_LEVEL)?;
     m.add_function(wrap_pyfunction!(compress, m)?)?;
     m.add_function(wrap_pyfunction!(decompress, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_bound, m)?)?;
     m.add_function(wrap_pyfunction!(compress_into, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_reuse, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_strided, m)?)?;
     m.add_function(wrap_pyfunction!(decompress_into, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_to_memmap, m)?)?;
+    #[cfg(unix)]
+    m.add_function(wrap_pyfunction!(compress_fd, m)?)?;
+    #[cfg(unix)]
+    m.add_function(wrap_pyfunction!(decompress_fd, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_lines, m)?)?;
+    m.add_function(wrap_pyfunction!(recompress_range, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_str, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_str, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_many, m)?)?;
+    m.add_function(wrap_pyfunction!(is_valid, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_many_into, m)?)?;
+    m.add_function(wrap_pyfunction!(maybe_compress, m)?)?;
+    m.add_function(wrap_pyfunction!(maybe_decompress, m)?)?;
     Ok(())
 }
 
 /// Deflate decompression.
 ///
+/// With `recover=True`, decoding stops at the first corrupted or truncated section of the input
+/// rather than raising; a tuple of the `Buffer` decompressed up to that point, and whether
+/// the input was found to be damaged, is returned instead.
+///
+/// With `timeout_ms`, `DecompressionError` is raised if decompression takes longer than that
+/// many milliseconds, ie. to limit the time spent on untrusted input.
+///
+/// With `pad_to`, the input is taken to be the padded output of `compress` with the same `pad_to`.
+///
+/// With `trailer_checksum`, the trailer appended by `compress` with the same `trailer_checksum` is
+/// checked before decompressing, raising `DecompressionError` if the compressed data was corrupted.
+///
+/// With `exact=True`, and no `output_len`, the data is decompressed twice, first only to count its
+/// decompressed length, so the output is allocated once rather than grown as it's decompressed.
+///
+/// `store_size` must be `True` if it was given to `compress`; the output is then allocated once, of
+/// the stored size, and `DecompressionError` is raised if the data decompresses to any other length.
+/// It can't be combined with `output_len`, `recover`, `timeout_ms` or `exact`.
+///
+/// `wbits` is accepted as in Python's `zlib.decompress`, but only as `-15`, the raw deflate format.
+///
 /// Python Example
 /// --------------
 /// ```python
-/// >>> cramjam.deflate.decompress(compressed_bytes, output_len=Optional[int])
+/// >>> cramjam.deflate.decompress(compressed_bytes, output_len=Optional[int], recover=False, timeout_ms=Optional[int], pad_to=Optional[int], trailer_checksum=Optional[str], exact=False, store_size=False)
+/// >>> cramjam.deflate.decompress(compressed_bytes, wbits=-15)  # as zlib.decompress(compressed_bytes, wbits=-15)
 /// ```
 #[pyfunction]
-pub fn decompress(data: BytesType, output_len: Option<usize>) -> PyResult<RustyBuffer> {
-    crate::generic!(decompress(data), output_len = output_len)
+#[allow(clippy::too_many_arguments)]
+pub fn decompress(
+    py: Python,
+    mut data: BytesType,
+    output_len: Option<usize>,
+    recover: Option<bool>,
+    timeout_ms: Option<u64>,
+    pad_to: Option<usize>,
+    trailer_checksum: Option<&str>,
+    exact: Option<bool>,
+    wbits: Option<i32>,
+    store_size: Option<bool>,
+) -> PyResult<PyObject> {
+    check_wbits(wbits)?;
+    let recover = recover.unwrap_or(false);
+    let store_size = store_size.unwrap_or(false);
+    if store_size && (output_len.is_some() || recover || timeout_ms.is_some() || exact.unwrap_or(false)) {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "store_size can't be combined with output_len, recover, timeout_ms or exact",
+        ));
+    }
+    let compressed = crate::unpadded(&mut data, pad_to)?;
+    let mut compressed = crate::TrailerChecked::new(compressed, trailer_checksum)?;
+    if store_size {
+        let mut input = vec![];
+        compressed.read_to_end(&mut input)?;
+        let (stored_len, input) = to_py_err!(DecompressionError -> crate::split_size_prefix(&input))?;
+        let mut output = crate::presized_output(Some(stored_len as u64), input.len() as u64);
+        to_py_err!(DecompressionError -> internal::decompress(input, &mut output))?;
+        crate::check_stored_size(output.len(), stored_len)?;
+        return Ok(RustyBuffer::from(output).into_py(py));
+    }
+    if recover || timeout_ms.is_some() {
+        return crate::decompress_with(py, internal::decoder(compressed), recover, timeout_ms, false);
+    }
+    if exact.unwrap_or(false) && output_len.is_none() {
+        return crate::decompress_exact(compressed, |input, output| internal::decompress(input, output))
+            .map(|buffer| buffer.into_py(py));
+    }
+    crate::generic!(decompress(compressed), output_len = output_len).map(|buffer: RustyBuffer| buffer.into_py(py))
 }
 
 /// Deflate compression.
 ///
+/// `level` ranges from 0, which stores the data without compressing it, to 9; it defaults to 6
+/// when `None` or omitted. `wbits` is accepted as in Python's `zlib.compress`, but only as `-15`,
+/// the raw deflate format with a 32KiB window, which is what's always written. `CompressionError` is
+/// raised for levels beyond 9.
+///
+/// With `pad_to`, the output is padded with zeros to a multiple of that many bytes, hiding its exact
+/// length, ie. before encrypting it; decompress it with the same `pad_to`.
+///
+/// `trailer_checksum="crc32"`, or `"xxh3"` which is faster for large outputs, appends a checksum
+/// of the compressed data, before any padding, so corruption in transport is caught before
+/// decompressing; decompress it with the same `trailer_checksum`.
+///
+/// With `deterministic=True`, the same input and `level` give the same output byte for byte, ie. for
+/// reproducible zip archives: the whole input is given to the encoder at once, so the output doesn't
+/// depend on how it's read, with the raw deflate format and flate2's fixed 32KiB window. This holds for
+/// a given version of flate2's backend, miniz_oxide, which cramjam's tests check against known output;
+/// it isn't the output of other deflate implementations, ie. zlib. It can't be combined with `output_len`.
+///
+/// `store_size` (default `False`) prefixes the output with the input's length as a little-endian u32,
+/// as `lz4.compress_block` does, so `decompress` with `store_size=True` allocates its output once;
+/// it comes before any trailer or padding.
+///
 /// Python Example
 /// --------------
 /// ```python
-/// >>> cramjam.deflate.compress(b'some bytes here', level=5, output_len=Optional[int])  # level defaults to 6
+/// >>> cramjam.deflate.compress(b'some bytes here', level=5, output_len=Optional[int], deterministic=False, store_size=False)
+/// >>> cramjam.deflate.compress(b'some bytes here', level="auto")  # 9 for inputs below 1MiB, 6 below 64MiB, otherwise 1
+/// >>> cramjam.deflate.compress(b'some bytes here', level=9, wbits=-15)  # as zlib.compress(b'some bytes here', 9, wbits=-15)
 /// ```
 #[pyfunction]
-pub fn compress(data: BytesType, level: Option<u32>, output_len: Option<usize>) -> PyResult<RustyBuffer> {
-    crate::generic!(compress(data), output_len = output_len, level = level)
+#[allow(clippy::too_many_arguments)]
+pub fn compress(
+    mut data: BytesType,
+    level: Option<Level<u32>>,
+    output_len: Option<usize>,
+    pad_to: Option<usize>,
+    trailer_checksum: Option<&str>,
+    deterministic: Option<bool>,
+    wbits: Option<i32>,
+    store_size: Option<bool>,
+) -> PyResult<RustyBuffer> {
+    check_wbits(wbits)?;
+    let input_len = data.remaining_len()? as usize;
+    let level = level.map(|level| level.resolve(&mut data, auto_level)).transpose()?;
+    to_py_err!(CompressionError -> internal::checked_level(level))?;
+    let deterministic = deterministic.unwrap_or(false);
+    if deterministic && output_len.is_some() {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "deterministic can't be combined with output_len",
+        ));
+    }
+    let compressed = if deterministic {
+        let mut input = vec![];
+        data.read_to_end(&mut input)?;
+        to_py_err!(CompressionError -> internal::compress_deterministic(&input, level)).map(RustyBuffer::from)
+    } else {
+        crate::generic!(compress(data), output_len = output_len, level = level)
+    };
+    compressed
+        .and_then(|compressed| {
+            if !store_size.unwrap_or(false) {
+                return Ok(compressed);
+            }
+            let compressed = compressed.into_vec();
+            to_py_err!(CompressionError -> crate::with_size_prefix(input_len, &compressed)).map(RustyBuffer::from)
+        })
+        .and_then(|compressed| crate::with_trailer(compressed, trailer_checksum))
+        .and_then(|compressed| crate::pad(compressed, pad_to))
 }
 
-/// Compress directly into an output buffer
+/// `wbits` of Python's `zlib` for raw deflate with the largest window, the only format read and written here
+const RAW_DEFLATE_WBITS: i32 = -15;
+
+fn check_wbits(wbits: Option<i32>) -> PyResult<()> {
+    match wbits {
+        None | Some(RAW_DEFLATE_WBITS) => Ok(()),
+        Some(wbits) => Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "Unsupported wbits {}, only {} (raw deflate) is supported; use gzip for the gzip format",
+            wbits, RAW_DEFLATE_WBITS
+        ))),
+    }
+}
+
+/// Level used with `level="auto"`, by the length of the input
+fn auto_level(input_len: u64) -> u32 {
+    match input_len {
+        len if len < crate::AUTO_LEVEL_SMALL_INPUT => 9,
+        len if len < crate::AUTO_LEVEL_LARGE_INPUT => 6,
+        _ => 1,
+    }
+}
+
+/// Compress `text`, a `str`, encoded with `encoding` (default `"utf-8"`); `level` is as for `compress`.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.deflate.compress_str("some text here", encoding="utf-8", level=Optional[int])
+/// ```
 #[pyfunction]
-pub fn compress_into(input: BytesType, mut output: BytesType, level: Option<u32>) -> PyResult<usize> {
-    let r = internal::compress(input, &mut output, level)?;
-    Ok(r)
+pub fn compress_str(text: &PyString, encoding: Option<&str>, level: Option<Level<u32>>) -> PyResult<RustyBuffer> {
+    compress(
+        crate::encode_str(text, encoding)?,
+        level,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+}
+
+/// Decompress to a `str`, decoding the decompressed data with `encoding` (default `"utf-8"`).
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.deflate.decompress_str(compressed_bytes, encoding="utf-8")
+/// 'some text here'
+/// ```
+#[pyfunction]
+pub fn decompress_str<'a>(py: Python<'a>, data: BytesType, encoding: Option<&str>) -> PyResult<&'a PyString> {
+    let mut output = vec![];
+    to_py_err!(DecompressionError -> internal::decompress(data, &mut output))?;
+    crate::decode_str(py, &output, encoding)
+}
+
+/// Check `data` decompresses without error, returning `True` if it does and `False` if it doesn't,
+/// ie. it's truncated or corrupt; the decompressed data is discarded as it's decoded, rather than
+/// kept in an output buffer.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.deflate.is_valid(compressed_bytes)
+/// True
+/// ```
+#[pyfunction]
+pub fn is_valid(data: BytesType) -> bool {
+    internal::decompress(data, &mut std::io::sink()).is_ok()
+}
+
+/// Check each of `items` decompresses, and when given, to the corresponding item of `expected`;
+/// returns the index of the first which doesn't, or `None` if all of them do. The items are
+/// checked in parallel with the GIL released.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.deflate.verify_many([compressed_bytes, other_compressed_bytes], expected=Optional[List[bytes]])
+/// ```
+#[pyfunction]
+pub fn verify_many(py: Python, items: Vec<BytesType>, expected: Option<Vec<BytesType>>) -> PyResult<Option<usize>> {
+    crate::verify_many(py, items, expected, |input, output| internal::decompress(input, output))
+}
+
+/// Decompress each of `items` into successive regions of `array`, a `numpy.array` with `dtype=np.uint8`,
+/// returning the `(offset, length)` of each region; raises `BufferError` if the array fills up first,
+/// and `ValueError` if an item overlaps the array, ie. is a view of it.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> array = np.empty(total_len, dtype=np.uint8)
+/// >>> regions = cramjam.deflate.decompress_many_into([compressed_bytes, other_compressed_bytes], array)
+/// ```
+#[pyfunction]
+pub fn decompress_many_into(items: Vec<BytesType>, array: &PyArray1<u8>) -> PyResult<Vec<(usize, usize)>> {
+    crate::decompress_many_into(items, array, |input, output| internal::decompress(input, output))
+}
+
+/// Compress, unless that doesn't achieve a ratio of uncompressed to compressed length of at least
+/// `min_ratio` (default 1.1); returns a tuple of the compressed data and `True`, or a copy of the
+/// data as is and `False`, to be given to `maybe_decompress`. `level` is as for `compress`.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> data, compressed = cramjam.deflate.maybe_compress(b'some bytes here', min_ratio=1.1, level=Optional[int])
+/// >>> bytes(cramjam.deflate.maybe_decompress(data, compressed))
+/// b'some bytes here'
+/// ```
+#[pyfunction]
+pub fn maybe_compress(
+    mut data: BytesType,
+    min_ratio: Option<f64>,
+    level: Option<Level<u32>>,
+) -> PyResult<(RustyBuffer, bool)> {
+    let level = level.map(|level| level.resolve(&mut data, auto_level)).transpose()?;
+    crate::maybe_compress(data, min_ratio, |input, output| {
+        internal::compress(input, output, level)
+    })
+}
+
+/// Decompress the data returned by `maybe_compress` if its flag, `compressed`, is `True`, otherwise
+/// return a copy of it as is.
+#[pyfunction]
+pub fn maybe_decompress(data: BytesType, compressed: bool) -> PyResult<RustyBuffer> {
+    crate::maybe_decompress(data, compressed, |input, output| internal::decompress(input, output))
+}
+
+/// Get the maximum length of the compressed output for `data_len` bytes of input, the size
+/// of buffer which is always big enough to be passed to `compress_into`
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> output = bytearray(cramjam.deflate.compress_bound(len(data)))
+/// >>> n_bytes = cramjam.deflate.compress_into(data, output)
+/// ```
+#[pyfunction]
+pub fn compress_bound(data_len: usize) -> PyResult<usize> {
+    crate::checked_bound(internal::compress_bound(data_len), data_len)
+}
+
+/// Compress directly into an output buffer, returning the compressed length; `CompressionError` is
+/// raised with `compress_bound` of the input's length if the buffer is too small to hold it.
+#[pyfunction]
+pub fn compress_into(input: BytesType, output: BytesType, level: Option<u32>) -> PyResult<usize> {
+    crate::compress_into(input, output, internal::compress_bound, |input, output| {
+        internal::compress(input, output, level)
+    })
+}
+
+/// Compress using `scratch`, a `numpy.array` with `dtype=np.uint8`, as the output buffer, returning
+/// a copy of the compressed data as `bytes`. The array is resized as needed, and can be reused across
+/// calls to avoid allocating a new output buffer each time; as with `numpy.ndarray.resize`, it must
+/// own its data and not be referenced by any other array.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> scratch = np.empty(0, dtype=np.uint8)
+/// >>> for data in chunks:
+/// ...     compressed = cramjam.deflate.compress_reuse(data, scratch, level=Optional[int])
+/// ```
+#[pyfunction]
+pub fn compress_reuse<'a>(
+    py: Python<'a>,
+    data: BytesType,
+    scratch: &PyArray1<u8>,
+    level: Option<u32>,
+) -> PyResult<&'a PyBytes> {
+    crate::io::compress_reuse(py, scratch, |output| internal::compress(data, output, level))
+}
+
+/// Compress a `numpy.array` of any dtype, including non-contiguous views such as `arr[::2]`.
+/// The elements are gathered into a contiguous buffer, in C order, before being compressed.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> arr = np.arange(100, dtype=np.float64).reshape(10, 10)
+/// >>> cramjam.deflate.compress_strided(arr[::2, 1::3], level=Optional[int], output_len=Optional[int])
+/// ```
+#[pyfunction]
+pub fn compress_strided(
+    array: RustyStridedNumpyArray,
+    level: Option<u32>,
+    output_len: Option<usize>,
+) -> PyResult<RustyBuffer> {
+    crate::generic!(compress(array), output_len = output_len, level = level)
+}
+
+/// Decompress into a new `numpy.memmap` of `dtype` (default `"uint8"`) at `path`, which is created
+/// or overwritten, returning it; for data too large to decompress into memory. The data is
+/// decompressed twice, first to find its length.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> array = cramjam.deflate.decompress_to_memmap(compressed_bytes, "/tmp/data.bin", dtype="float64")
+/// ```
+#[pyfunction]
+pub fn decompress_to_memmap<'a>(
+    py: Python<'a>,
+    data: BytesType,
+    path: &str,
+    dtype: Option<&str>,
+) -> PyResult<&'a PyAny> {
+    crate::decompress_to_memmap(py, data, path, dtype, None, |input, output| {
+        internal::decompress(input, output)
+    })
 }
 
 /// Decompress directly into an output buffer
 #[pyfunction]
 pub fn decompress_into(input: BytesType, mut output: BytesType) -> PyResult<usize> {
+    output.check_writable()?;
     let r = internal::decompress(input, &mut output)?;
     Ok(r)
 }
 
+/// Compress from the file descriptor `in_fd` until its end, writing to `out_fd`, ie. either end of
+/// an `os.pipe()`, returning the number of bytes written; neither descriptor is closed.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.deflate.compress_fd(in_fd, out_fd, level=Optional[int])
+/// ```
+#[cfg(unix)]
+#[pyfunction]
+pub fn compress_fd(py: Python, in_fd: i32, out_fd: i32, level: Option<u32>) -> PyResult<usize> {
+    crate::io::with_fds::<CompressionError, _>(py, in_fd, out_fd, |input, output| {
+        internal::compress(input, output, level).map(|_| ())
+    })
+}
+
+/// Decompress from the file descriptor `in_fd` until its end, writing to `out_fd`, returning
+/// the number of bytes written; neither descriptor is closed.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.deflate.decompress_fd(in_fd, out_fd)
+/// ```
+#[cfg(unix)]
+#[pyfunction]
+pub fn decompress_fd(py: Python, in_fd: i32, out_fd: i32) -> PyResult<usize> {
+    crate::io::with_fds::<DecompressionError, _>(py, in_fd, out_fd, |input, output| {
+        internal::decompress(input, output).map(|_| ())
+    })
+}
+
+/// Decompress line by line; returns an iterator over the lines of decompressed data
+/// as `bytes`, without materializing the entire decompressed data. See [`Lines`](../io/struct.RustyLines.html)
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> for line in cramjam.deflate.decompress_lines(compressed_bytes):
+/// ...     print(line)
+/// ```
+#[pyfunction]
+pub fn decompress_lines(mut data: BytesType) -> PyResult<RustyLines> {
+    let mut compressed = vec![];
+    data.read_to_end(&mut compressed)?;
+    let decoder = to_py_err!(DecompressionError -> internal::decoder(Cursor::new(compressed)))?;
+    Ok(RustyLines::new(decoder))
+}
+
+/// Replace the `[start, end)` range of the decompressed data with `replacement`, returning the edited
+/// data compressed again, without returning the decompressed data to Python. Raises `ValueError`
+/// if `start` is greater than `end`, and `DecompressionError` if the range is beyond the end of the data.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.deflate.recompress_range(compressed_bytes, start, end, b'replacement', level=Optional[int])
+/// ```
+#[pyfunction]
+pub fn recompress_range(
+    data: BytesType,
+    start: u64,
+    end: u64,
+    replacement: BytesType,
+    level: Option<u32>,
+) -> PyResult<RustyBuffer> {
+    crate::io::recompress_range(internal::decoder(data), start, end, replacement, |input, output| {
+        internal::compress(input, output, level)
+    })
+}
+
 pub(crate) mod internal {
 
+    use crate::io::FinishWrite;
     use flate2::read::{DeflateDecoder, DeflateEncoder};
-    use flate2::Compression;
+    use flate2::write::DeflateEncoder as DeflateWriteEncoder;
+    use flate2::{Compress, Compression, FlushCompress, Status};
     use std::io::prelude::*;
     use std::io::Error;
 
+    /// Compression level used when none is given
+    pub(crate) const DEFAULT_LEVEL: u32 = 6;
+
+    /// Highest compression level
+    pub(crate) const MAX_LEVEL: u32 = 9;
+
+    /// `level`, or the default, checked to be at most [`MAX_LEVEL`], as flate2 panics beyond it
+    pub(crate) fn checked_level(level: Option<u32>) -> Result<u32, Error> {
+        crate::checked_level("deflate", level, DEFAULT_LEVEL, 0, MAX_LEVEL)
+    }
+
+    /// Largest output of `compress` for `input_len` bytes of input, that of miniz, flate2's backend;
+    /// `None` if it's too large to be represented
+    pub fn compress_bound(input_len: usize) -> Option<usize> {
+        let by_ratio = input_len.checked_add(input_len / 10)?.checked_add(128)?;
+        let by_blocks = (input_len / (31 * 1024) + 1)
+            .checked_mul(5)?
+            .checked_add(input_len)?
+            .checked_add(128)?;
+        Some(by_ratio.max(by_blocks))
+    }
+
+    /// Decoder reading compressed data from `input`
+    pub fn decoder<R: Read>(input: R) -> Result<DeflateDecoder<R>, Error> {
+        Ok(DeflateDecoder::new(input))
+    }
+
+    /// Encoder writing compressed data to `output`
+    pub fn encoder<W: Write>(output: W, level: Option<u32>) -> Result<DeflateWriteEncoder<W>, Error> {
+        let level = checked_level(level)?;
+        Ok(DeflateWriteEncoder::new(output, Compression::new(level)))
+    }
+
     /// Decompress gzip data
     pub fn decompress<W: Write + ?Sized, R: Read>(input: R, output: &mut W) -> Result<usize, Error> {
-        let mut decoder = DeflateDecoder::new(input);
+        let mut decoder = decoder(input)?;
         let n_bytes = std::io::copy(&mut decoder, output)?;
         Ok(n_bytes as usize)
     }
 
     /// Compress gzip data
     pub fn compress<W: Write + ?Sized, R: Read>(input: R, output: &mut W, level: Option<u32>) -> Result<usize, Error> {
-        let level = level.unwrap_or_else(|| 6);
+        let level = checked_level(level)?;
 
         let mut encoder = DeflateEncoder::new(input, Compression::new(level));
         let n_bytes = std::io::copy(&mut encoder, output)?;
         Ok(n_bytes as usize)
     }
+
+    /// Compress `input` given at once to flate2's `Compress`, finishing the stream, so the output
+    /// only depends on `input`, `level`, and the version of flate2's backend
+    pub fn compress_deterministic(input: &[u8], level: Option<u32>) -> Result<Vec<u8>, Error> {
+        let level = checked_level(level)?;
+        let mut compress = Compress::new(Compression::new(level), false);
+        let mut output = Vec::with_capacity(input.len() / 2 + 64);
+        loop {
+            let consumed = compress.total_in() as usize;
+            let status = compress
+                .compress_vec(&input[consumed..], &mut output, FlushCompress::Finish)
+                .map_err(|err| Error::other(err.to_string()))?;
+            if status == Status::StreamEnd {
+                return Ok(output);
+            }
+            output.reserve(output.capacity().max(64));
+        }
+    }
+
+    impl<W: Write + Send> FinishWrite for DeflateWriteEncoder<W> {
+        fn finish(self: Box<Self>) -> Result<(), Error> {
+            DeflateWriteEncoder::finish(*self)?.flush()
+        }
+    }
 }
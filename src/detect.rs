@@ -0,0 +1,801 @@
+//! Detection of data which is already compressed, by the magic numbers of common formats
+//! and an estimate of the entropy of its bytes, and decompression of streams in the detected format;
+//! also recommending an algorithm for data from trials on a sample of it.
+use crate::exceptions::{CompressionError, DecompressionError};
+use crate::io::{check_buffered, AsBytes, BufferedOutput};
+use crate::{to_py_err, BytesType};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+use pyo3::wrap_pyfunction;
+use pyo3::PyObjectProtocol;
+use std::collections::VecDeque;
+use std::io::{Cursor, Error, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use zstd::stream::raw::{InBuffer, Operation, OutBuffer};
+
+/// Number of bytes examined from the start of the data
+const SAMPLE_LEN: u64 = 64 * 1024;
+
+/// Entropy, in bits per byte, above which data is considered compressed; text is typically
+/// around 4 to 5, while compressed or random data approaches the maximum of 8.
+const COMPRESSED_ENTROPY: f64 = 7.5;
+
+/// Minimum number of bytes to estimate entropy from; too few bytes can't approach 8 bits per byte.
+const MIN_ENTROPY_SAMPLE_LEN: usize = 1024;
+
+/// Chunk at the start of each snappy frame stream
+const SNAPPY_STREAM_IDENTIFIER: &[u8] = b"\xff\x06\x00\x00sNaPpY";
+
+/// Magic numbers at the start of compressed formats, by the name of the format
+const MAGIC_NUMBERS: &[(&str, &[u8])] = &[
+    ("gzip", &[0x1f, 0x8b]),
+    ("zstd", &[0x28, 0xb5, 0x2f, 0xfd]),
+    ("lz4", &[0x04, 0x22, 0x4d, 0x18]),
+    ("snappy", SNAPPY_STREAM_IDENTIFIER),
+    ("bzip2", b"BZh"),
+    ("xz", &[0xfd, b'7', b'z', b'X', b'Z', 0x00]),
+    ("zip", b"PK\x03\x04"),
+];
+
+pub(crate) fn init_py_module(m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(is_probably_compressed, m)?)?;
+    m.add_function(wrap_pyfunction!(recommend_algorithm, m)?)?;
+    m.add_function(wrap_pyfunction!(entropy, m)?)?;
+    m.add_function(wrap_pyfunction!(peek, m)?)?;
+    m.add_class::<AutoDecompressor>()?;
+    Ok(())
+}
+
+/// Whether the data looks like it's already compressed, so compressing it again would be of
+/// little use; that is either it starts with the magic number of a common compressed format
+/// (gzip, zstd, lz4 and snappy frames, bzip2, xz, zip), or its bytes are close to random.
+/// Only the first 64KiB from the current position are examined, and the position is unchanged;
+/// data without a magic number needs to be at least 1KiB to estimate its entropy.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.is_probably_compressed(cramjam.gzip.compress(b'some bytes here'))
+/// True
+/// >>> cramjam.is_probably_compressed(b'some bytes here')
+/// False
+/// ```
+#[pyfunction]
+pub fn is_probably_compressed(mut data: BytesType) -> PyResult<bool> {
+    let sample = read_sample(&mut data, SAMPLE_LEN)?;
+    Ok(magic_number(&sample).is_some()
+        || (sample.len() >= MIN_ENTROPY_SAMPLE_LEN && byte_entropy(&sample) > COMPRESSED_ENTROPY))
+}
+
+/// Number of bytes read by `peek`, enough for the longest of the headers it parses
+pub(crate) const MAX_HEADER_LEN: u64 = 32;
+
+/// Structural metadata from the header of a gzip, zstd, lz4 or snappy frame, in the format detected
+/// from its magic number, without decompressing any of it. Returns a `dict` of its `format`, and
+/// its decompressed `content_size` and `dict_id` when the header records them, otherwise `None`,
+/// and the flags byte of the header as `flags`, which is `None` for snappy as it has none.
+/// Raises `DecompressionError` for any other data. Only the first bytes from the current position
+/// are read, and the position is unchanged.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.peek(cramjam.zstd.compress(b'some bytes here'))
+/// {'format': 'zstd', 'content_size': 15, 'dict_id': None, 'flags': 32}
+/// ```
+#[pyfunction]
+pub fn peek<'a>(py: Python<'a>, mut data: BytesType) -> PyResult<&'a PyDict> {
+    peek_header(py, &mut data, None)
+}
+
+/// The metadata returned by `peek`, for `cramjam.peek` or, with `algorithm`, the `peek` of that
+/// algorithm's submodule, which raises `DecompressionError` if `data` is in another format.
+pub(crate) fn peek_header<'a>(py: Python<'a>, data: &mut BytesType, algorithm: Option<&str>) -> PyResult<&'a PyDict> {
+    let header = read_sample(data, MAX_HEADER_LEN)?;
+    let format = match (magic_number(&header), algorithm) {
+        (Some(format), None) if ["gzip", "zstd", "lz4", "snappy"].contains(&format) => format,
+        (Some(format), Some(algorithm)) if format == algorithm => format,
+        (_, Some(algorithm)) => {
+            return Err(DecompressionError::new_err(format!(
+                "Unrecognized format, expected a {} frame",
+                algorithm
+            )))
+        }
+        (_, None) => {
+            return Err(DecompressionError::new_err(
+                "Unrecognized format, expected a gzip, zstd, lz4 or snappy frame",
+            ))
+        }
+    };
+    let truncated = || DecompressionError::new_err(format!("Truncated {} frame header", format));
+    let (content_size, dict_id, flags) = match format {
+        "gzip" => (None, None, Some(*header.get(3).ok_or_else(truncated)?)),
+        "zstd" => {
+            let content_size = match zstd_safe::get_frame_content_size(&header) {
+                zstd_safe::CONTENTSIZE_ERROR => return Err(truncated()),
+                zstd_safe::CONTENTSIZE_UNKNOWN => None,
+                content_size => Some(content_size),
+            };
+            let dict_id = Some(zstd_safe::get_dict_id_from_frame(&header)).filter(|id| *id != 0);
+            (content_size, dict_id, Some(header[4]))
+        }
+        "lz4" => {
+            // the FLG and BD bytes, then the optional content size and dictionary id, little endian
+            let flags = *header.get(4).ok_or_else(truncated)?;
+            let mut pos = 6;
+            let mut field = |len: usize| {
+                let bytes = header.get(pos..pos + len).ok_or_else(truncated)?;
+                pos += len;
+                Ok::<_, PyErr>(bytes.iter().rev().fold(0u64, |value, byte| value << 8 | *byte as u64))
+            };
+            let content_size = if flags & 0x08 != 0 { Some(field(8)?) } else { None };
+            let dict_id = if flags & 0x01 != 0 {
+                Some(field(4)? as u32)
+            } else {
+                None
+            };
+            (content_size, dict_id, Some(flags))
+        }
+        _ => (None, None, None),
+    };
+    let metadata = PyDict::new(py);
+    metadata.set_item("format", format)?;
+    metadata.set_item("content_size", content_size)?;
+    metadata.set_item("dict_id", dict_id)?;
+    metadata.set_item("flags", flags)?;
+    Ok(metadata)
+}
+
+/// Shannon entropy of the bytes of the data, in bits per byte from 0 to 8; random or compressed
+/// data approaches 8, while text is typically around 4 to 5. With `sample_bytes`, only up to that
+/// many bytes are examined. The bytes are read from the current position, which is unchanged.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.entropy(b'aaaa')
+/// 0.0
+/// >>> cramjam.entropy(os.urandom(100_000), sample_bytes=Optional[int])
+/// 7.998...
+/// ```
+#[pyfunction]
+pub fn entropy(mut data: BytesType, sample_bytes: Option<u64>) -> PyResult<f64> {
+    let sample = read_sample(&mut data, sample_bytes.unwrap_or(u64::MAX))?;
+    Ok(byte_entropy(&sample))
+}
+
+/// Ratio of compressed to uncompressed length above which `recommend_algorithm` considers a sample
+/// incompressible, so the fastest algorithm is recommended regardless of the preference
+const INCOMPRESSIBLE_RATIO: f64 = 0.95;
+
+/// Recommend an algorithm for data like `sample`, by compressing it with each algorithm at a fast
+/// level; with `prefer="ratio"` (the default) the one with the smallest output, or with
+/// `prefer="speed"` the fastest one. When no algorithm compresses the sample by more than 5%,
+/// the fastest one is recommended either way. The trials run with the GIL released.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> algorithm = cramjam.recommend_algorithm(sample, prefer="ratio")
+/// >>> compressed = cramjam.compress(data, algorithm)
+/// ```
+#[pyfunction]
+pub fn recommend_algorithm(py: Python, mut sample: BytesType, prefer: Option<&str>) -> PyResult<&'static str> {
+    let prefer_ratio = match prefer.unwrap_or("ratio") {
+        "ratio" => true,
+        "speed" => false,
+        prefer => {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Invalid prefer '{}', should be one of 'ratio' or 'speed'",
+                prefer
+            )))
+        }
+    };
+    let mut data = vec![];
+    sample.read_to_end(&mut data)?;
+
+    let trials = py.allow_threads(|| {
+        crate::registry::BUILTIN_ALGORITHMS
+            .iter()
+            .map(|algorithm| {
+                let start = Instant::now();
+                let compressed_len = trial(algorithm, &data)?;
+                Ok((*algorithm, compressed_len, start.elapsed()))
+            })
+            .collect::<std::io::Result<Vec<(&'static str, usize, Duration)>>>()
+    });
+    let trials = to_py_err!(CompressionError -> trials)?;
+
+    let smallest = trials.iter().min_by_key(|(_, compressed_len, _)| *compressed_len);
+    let fastest = trials.iter().min_by_key(|(_, _, elapsed)| *elapsed);
+    let compressible = smallest
+        .map(|(_, compressed_len, _)| (*compressed_len as f64) < data.len() as f64 * INCOMPRESSIBLE_RATIO)
+        .unwrap_or(false);
+    let (algorithm, _, _) = match prefer_ratio && compressible {
+        true => smallest,
+        false => fastest,
+    }
+    .expect("at least one algorithm");
+    Ok(algorithm)
+}
+
+/// Compressed length of `data` with `algorithm` at a fast level
+fn trial(algorithm: &str, data: &[u8]) -> std::io::Result<usize> {
+    let mut output = Cursor::new(vec![]);
+    match algorithm {
+        "snappy" => crate::snappy::internal::compress(data, &mut output, None),
+        "brotli" => crate::brotli::internal::compress(data, &mut output, Some(1)),
+        "lz4" => crate::lz4::internal::compress(&mut &data[..], &mut output, Some(0)),
+        "gzip" => crate::gzip::internal::compress(data, &mut output, Some(1)),
+        "deflate" => crate::deflate::internal::compress(data, &mut output, Some(1)),
+        "zstd" => crate::zstd::internal::compress(
+            data,
+            &mut output,
+            &crate::zstd::internal::CompressOptions {
+                level: Some(1),
+                content_size: Some(data.len() as u64),
+                ..Default::default()
+            },
+        ),
+        _ => unreachable!("not a built-in algorithm"),
+    }
+}
+
+/// Read up to `len` bytes from the current position of `data`, seeking back afterwards
+pub(crate) fn read_sample(data: &mut BytesType, len: u64) -> std::io::Result<Vec<u8>> {
+    let position = data.stream_position()?;
+    let mut sample = vec![];
+    Read::by_ref(data).take(len).read_to_end(&mut sample)?;
+    data.seek(SeekFrom::Start(position))?;
+    Ok(sample)
+}
+
+/// Name of the format whose magic number `data` starts with, if any
+pub(crate) fn magic_number(data: &[u8]) -> Option<&'static str> {
+    MAGIC_NUMBERS
+        .iter()
+        .find(|(_, magic)| data.starts_with(magic))
+        .map(|(name, _)| *name)
+}
+
+/// Submodules which could decompress data starting with `data`, in the order to try them; the one of
+/// the format whose magic number it starts with, or without one, zlib should it start with a zlib
+/// header, then brotli, which has no header to tell it by. Empty for formats cramjam can't decompress.
+pub(crate) fn detect_algorithms(data: &[u8]) -> &'static [&'static str] {
+    match magic_number(data) {
+        Some("gzip") => &["gzip"],
+        Some("zstd") => &["zstd"],
+        Some("lz4") => &["lz4"],
+        Some("snappy") => &["snappy"],
+        Some("bzip2") => &["bzip2"],
+        Some("xz") => &["lzma"],
+        Some(_) => &[],
+        None if is_zlib_header(data) => &["zlib", "brotli"],
+        None => &["brotli"],
+    }
+}
+
+/// Whether `data` starts with a zlib header, of a deflate stream with a window of at most 32KiB
+/// and no preset dictionary, whose two bytes are a multiple of 31 as its check requires
+fn is_zlib_header(data: &[u8]) -> bool {
+    match data {
+        [cmf, flg, ..] => {
+            cmf & 0x0f == 8 && cmf >> 4 <= 7 && flg & 0x20 == 0 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0
+        }
+        _ => false,
+    }
+}
+
+/// Whether `data` could be the start of one of the magic numbers, needing more bytes to tell
+fn may_be_magic_number(data: &[u8]) -> bool {
+    MAGIC_NUMBERS.iter().any(|(_, magic)| magic.starts_with(data))
+}
+
+/// Shannon entropy of the bytes of `data`, in bits per byte; 0 for empty data
+fn byte_entropy(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.;
+    }
+    let mut counts = [0usize; 256];
+    for byte in data {
+        counts[*byte as usize] += 1;
+    }
+    let len = data.len() as f64;
+    counts
+        .iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let p = *count as f64 / len;
+            p * (1. / p).log2()
+        })
+        .sum()
+}
+
+/// Decompress a stream given in chunks, in the format detected from its first bytes; one of
+/// gzip, zstd, lz4 or snappy frames. The first chunks are buffered until there are enough bytes
+/// to detect the format, so decompressing them may return no data yet.
+///
+/// ### Python Example
+/// ```python
+/// >>> decompressor = cramjam.AutoDecompressor()
+/// >>> decompressor.format is None
+/// True
+/// >>> out = decompressor.decompress(compressed[:2])  # b'' until the format is known
+/// >>> out += decompressor.decompress(compressed[2:])
+/// >>> out += decompressor.finish()
+/// >>> decompressor.format
+/// 'zstd'
+/// >>> decompressor.stats
+/// {'bytes_in': ..., 'bytes_out': ..., 'elapsed_ns': ...}
+/// ```
+///
+/// With `resumable=True`, `get_state()` returns `bytes` from which the decompression can be resumed
+/// by `set_state` of another `AutoDecompressor`, ie. in another process after a restart, and given
+/// the rest of the stream from where it left off. Only zstd and snappy streams can be resumed, as
+/// their decoders hold no state between frames and chunks respectively; for zstd, the compressed
+/// bytes of the current frame are kept to replay it when resuming, which is as much as a frame's
+/// compressed length. `ValueError` is raised for gzip and lz4 streams.
+///
+/// With `max_buffered`, `DecompressionError("output backpressure exceeded")` is raised should a chunk
+/// decompress to more than that many bytes, which would otherwise all be held until returned, ie.
+/// when chunks arrive faster than their output is consumed; the decompressor can't be used afterwards.
+///
+/// ```python
+/// >>> decompressor = cramjam.AutoDecompressor(resumable=True)
+/// >>> out = decompressor.decompress(compressed[:1000])
+/// >>> state = decompressor.get_state()
+/// >>> resumed = cramjam.AutoDecompressor()
+/// >>> resumed.set_state(state)
+/// >>> out += resumed.decompress(compressed[1000:]) + resumed.finish()
+/// ```
+#[pyclass]
+pub struct AutoDecompressor {
+    /// The first bytes of the stream, until there are enough to detect its format
+    header: Vec<u8>,
+    decoder: Option<StreamDecoder>,
+    format: Option<&'static str>,
+    finished: bool,
+    resumable: bool,
+    max_buffered: Option<usize>,
+    bytes_in: u64,
+    bytes_out: u64,
+    /// Time spent in `decompress` and `finish`
+    elapsed: Duration,
+}
+
+#[pymethods]
+impl AutoDecompressor {
+    /// Instantiate a decompressor for a stream of any of the detected formats, which can be resumed
+    /// from `get_state` with `resumable=True`, holding at most `max_buffered` bytes of output
+    #[new]
+    pub fn __init__(resumable: Option<bool>, max_buffered: Option<usize>) -> Self {
+        Self {
+            header: vec![],
+            decoder: None,
+            format: None,
+            finished: false,
+            resumable: resumable.unwrap_or(false),
+            max_buffered,
+            bytes_in: 0,
+            bytes_out: 0,
+            elapsed: Duration::default(),
+        }
+    }
+    /// Decompress the next chunk of the stream, returning the `bytes` decompressed from it so far.
+    /// Raises `DecompressionError` if the stream isn't in one of the detected formats.
+    pub fn decompress<'a>(&mut self, py: Python<'a>, mut data: BytesType) -> PyResult<&'a PyBytes> {
+        if self.finished {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "AutoDecompressor already finished",
+            ));
+        }
+        let start = Instant::now();
+        let mut input = vec![];
+        data.read_to_end(&mut input)?;
+        self.bytes_in += input.len() as u64;
+        if self.decoder.is_none() {
+            self.header.extend_from_slice(&input);
+            let format = match magic_number(&self.header) {
+                Some(format) => format,
+                None if may_be_magic_number(&self.header) => {
+                    self.elapsed += start.elapsed();
+                    return Ok(PyBytes::new(py, &[]));
+                }
+                None => {
+                    return Err(DecompressionError::new_err(
+                        "Unrecognized format, expected a gzip, zstd, lz4 or snappy frame",
+                    ))
+                }
+            };
+            self.decoder = Some(StreamDecoder::new(format, self.resumable)?);
+            self.format = Some(format);
+            input = std::mem::take(&mut self.header);
+        }
+        let decoder = self.decoder.as_mut().expect("set above");
+        let output = decoder.decompress(&input, self.max_buffered).map_err(|err| {
+            self.finished = true;
+            DecompressionError::new_err(err.to_string())
+        })?;
+        self.bytes_out += output.len() as u64;
+        self.elapsed += start.elapsed();
+        Ok(PyBytes::new(py, &output))
+    }
+    /// End the stream, returning any remaining decompressed `bytes`; raises `DecompressionError`
+    /// if the stream is incomplete, or its format was never detected.
+    pub fn finish<'a>(&mut self, py: Python<'a>) -> PyResult<&'a PyBytes> {
+        self.finished = true;
+        let start = Instant::now();
+        let decoder = self
+            .decoder
+            .take()
+            .ok_or_else(|| DecompressionError::new_err("Stream ended before its format could be detected"))?;
+        let output = decoder
+            .finish()
+            .map_err(|err| DecompressionError::new_err(err.to_string()))?;
+        self.bytes_out += output.len() as u64;
+        self.elapsed += start.elapsed();
+        Ok(PyBytes::new(py, &output))
+    }
+    /// State of the decompression so far, as `bytes` to resume it from with `set_state`. Raises
+    /// `ValueError` unless instantiated with `resumable=True`, once finished, or for a stream in a
+    /// format which can't be resumed.
+    pub fn get_state<'a>(&self, py: Python<'a>) -> PyResult<&'a PyBytes> {
+        if !self.resumable {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "AutoDecompressor(resumable=True) is required to get its state",
+            ));
+        }
+        if self.finished {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "AutoDecompressor already finished",
+            ));
+        }
+        let (format, pending, replayed_len, stream_started) = match &self.decoder {
+            None => (0, self.header.as_slice(), 0, false),
+            Some(decoder) => decoder.state().ok_or_else(|| {
+                pyo3::exceptions::PyValueError::new_err(format!(
+                    "{} streams can't be resumed, only zstd and snappy streams",
+                    self.format.unwrap_or("unknown")
+                ))
+            })?,
+        };
+        let mut state = vec![crate::FRAME_VERSION, format, stream_started as u8];
+        for value in &[
+            self.bytes_in,
+            self.bytes_out,
+            self.elapsed.as_nanos() as u64,
+            replayed_len,
+        ] {
+            state.extend_from_slice(&crate::frame_u64(*value));
+        }
+        state.extend_from_slice(pending);
+        Ok(PyBytes::new(py, &state))
+    }
+    /// Resume the decompression from the `state` given by `get_state`, replacing any state this
+    /// decompressor had; raises `ValueError` if it isn't a valid state.
+    pub fn set_state(&mut self, state: BytesType) -> PyResult<()> {
+        let state = state.as_bytes();
+        let invalid = || pyo3::exceptions::PyValueError::new_err("Invalid AutoDecompressor state");
+        if state.len() < STATE_HEADER_LEN {
+            return Err(invalid());
+        }
+        crate::check_frame_version(state[0]).map_err(|err| pyo3::exceptions::PyValueError::new_err(err.to_string()))?;
+        let value = |i: usize| {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(&state[3 + 8 * i..3 + 8 * (i + 1)]);
+            crate::read_frame_u64(bytes)
+        };
+        let pending = &state[STATE_HEADER_LEN..];
+        let (format, stream_started) = (state[1], state[2] != 0);
+        let (header, decoder, format) = match format {
+            0 => (pending.to_vec(), None, None),
+            _ => {
+                let name = *RESUMABLE_FORMATS.get(format as usize - 1).ok_or_else(invalid)?;
+                let decoder = StreamDecoder::resume(name, pending, value(3), stream_started)
+                    .map_err(|err| pyo3::exceptions::PyValueError::new_err(err.to_string()))?;
+                (vec![], Some(decoder), Some(name))
+            }
+        };
+        *self = Self {
+            header,
+            decoder,
+            format,
+            finished: false,
+            resumable: true,
+            // a setting of this decompressor, rather than state of the stream
+            max_buffered: self.max_buffered,
+            bytes_in: value(0),
+            bytes_out: value(1),
+            elapsed: Duration::from_nanos(value(2)),
+        };
+        Ok(())
+    }
+    /// Name of the detected format, or `None` until enough of the stream is given to detect it
+    #[getter]
+    pub fn format(&self) -> Option<&'static str> {
+        self.format
+    }
+    /// Cumulative counts over the calls to `decompress` and `finish` so far, as a `dict` of the
+    /// compressed `bytes_in` given, the decompressed `bytes_out` returned, and the wall clock time
+    /// spent in those calls as `elapsed_ns`.
+    #[getter]
+    pub fn stats<'a>(&self, py: Python<'a>) -> PyResult<&'a PyDict> {
+        let stats = PyDict::new(py);
+        stats.set_item("bytes_in", self.bytes_in)?;
+        stats.set_item("bytes_out", self.bytes_out)?;
+        stats.set_item("elapsed_ns", self.elapsed.as_nanos() as u64)?;
+        Ok(stats)
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for AutoDecompressor {
+    fn __repr__(&self) -> String {
+        format!(
+            "cramjam.AutoDecompressor(format={})",
+            self.format
+                .map(|format| format!("'{}'", format))
+                .unwrap_or_else(|| "None".to_string())
+        )
+    }
+}
+
+/// Input of a decoder which reads it, given more data as the stream arrives; reading returns
+/// 0 bytes while it's empty, which the lz4 and snappy decoders resume from once there's more.
+#[derive(Clone, Default)]
+struct Feed(Arc<Mutex<VecDeque<u8>>>);
+
+impl Feed {
+    fn push(&self, data: &[u8]) {
+        self.0.lock().unwrap().extend(data);
+    }
+    fn is_empty(&self) -> bool {
+        self.0.lock().unwrap().is_empty()
+    }
+}
+
+impl Read for Feed {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().read(buf)
+    }
+}
+
+/// Formats `AutoDecompressor` can be resumed in, by their number in its state less 1, 0 being
+/// a stream whose format isn't yet detected
+const RESUMABLE_FORMATS: &[&str] = &["zstd", "snappy"];
+
+/// Length of the header of `AutoDecompressor`'s state; `crate::FRAME_VERSION`, the number of the
+/// format, whether a snappy stream's identifier was read, then as u64s the `bytes_in`, `bytes_out`
+/// and `elapsed_ns` of its stats and the length of the output of the pending input which follows.
+const STATE_HEADER_LEN: usize = 3 + 4 * 8;
+
+/// Decoder of a stream given in chunks
+enum StreamDecoder {
+    Gzip(flate2::write::MultiGzDecoder<BufferedOutput>),
+    Zstd {
+        decoder: zstd::stream::raw::Decoder<'static>,
+        frame_done: bool,
+        /// When resumable, the input of the current frame, and the length of its output so far
+        frame_input: Option<Vec<u8>>,
+        frame_output_len: u64,
+    },
+    Lz4 {
+        feed: Feed,
+        decoder: lz4::Decoder<Feed>,
+    },
+    Snappy {
+        feed: Feed,
+        decoder: snap::read::FrameDecoder<Feed>,
+        /// Bytes of an incomplete chunk, which the decoder can't resume reading from
+        partial_chunk: Vec<u8>,
+        /// Whether the stream identifier the decoder expects first was given to it
+        stream_started: bool,
+    },
+}
+
+impl StreamDecoder {
+    /// Decoder of a stream in `format`; a zstd stream is `resumable` from its [`state`](Self::state)
+    fn new(format: &str, resumable: bool) -> PyResult<Self> {
+        let decoder = match format {
+            "gzip" => Self::Gzip(flate2::write::MultiGzDecoder::new(BufferedOutput::default())),
+            "zstd" => Self::Zstd {
+                decoder: zstd::stream::raw::Decoder::new()?,
+                frame_done: false,
+                frame_input: match resumable {
+                    true => Some(vec![]),
+                    false => None,
+                },
+                frame_output_len: 0,
+            },
+            "lz4" => {
+                let feed = Feed::default();
+                Self::Lz4 {
+                    decoder: lz4::Decoder::new(feed.clone())?,
+                    feed,
+                }
+            }
+            "snappy" => {
+                let feed = Feed::default();
+                Self::Snappy {
+                    decoder: snap::read::FrameDecoder::new(feed.clone()),
+                    feed,
+                    partial_chunk: vec![],
+                    stream_started: false,
+                }
+            }
+            _ => {
+                return Err(DecompressionError::new_err(format!(
+                    "Detected {} data, which AutoDecompressor can't decompress",
+                    format
+                )))
+            }
+        };
+        Ok(decoder)
+    }
+
+    /// Number of the format in [`RESUMABLE_FORMATS`], the input which is pending and the length of
+    /// the output already returned from it, and whether a snappy stream's identifier was read; all
+    /// given back to [`resume`](Self::resume). `None` for a stream which can't be resumed.
+    fn state(&self) -> Option<(u8, &[u8], u64, bool)> {
+        match self {
+            Self::Zstd {
+                frame_input: Some(frame_input),
+                frame_output_len,
+                ..
+            } => Some((1, frame_input.as_slice(), *frame_output_len, false)),
+            Self::Snappy {
+                partial_chunk,
+                stream_started,
+                ..
+            } => Some((2, partial_chunk.as_slice(), 0, *stream_started)),
+            _ => None,
+        }
+    }
+
+    /// Decoder resuming a stream in `format` from its [`state`](Self::state); the pending input is
+    /// decompressed again, checking it gives `replayed_len` bytes, and its output discarded.
+    fn resume(format: &str, pending: &[u8], replayed_len: u64, stream_started: bool) -> std::io::Result<Self> {
+        let mut decoder = Self::new(format, true).map_err(Error::other)?;
+        if let Self::Snappy {
+            feed,
+            decoder,
+            stream_started: started,
+            ..
+        } = &mut decoder
+        {
+            if stream_started {
+                feed.push(SNAPPY_STREAM_IDENTIFIER);
+                decoder.read_to_end(&mut vec![])?;
+                *started = true;
+            }
+        }
+        let replayed = decoder.decompress(pending, None)?;
+        if replayed.len() as u64 != replayed_len {
+            return Err(Error::new(ErrorKind::InvalidData, "Invalid AutoDecompressor state"));
+        }
+        if let Self::Zstd { frame_done, .. } = &mut decoder {
+            // without pending input, it was resumed between frames
+            *frame_done |= pending.is_empty();
+        }
+        Ok(decoder)
+    }
+
+    /// Decompress the next chunk of the stream, returning all it's possible to decompress so far;
+    /// failing should that be more than `max_buffered` bytes
+    fn decompress(&mut self, input: &[u8], max_buffered: Option<usize>) -> std::io::Result<Vec<u8>> {
+        let mut output = vec![];
+        match self {
+            Self::Gzip(decoder) => {
+                decoder.get_mut().max_buffered = max_buffered;
+                decoder.write_all(input)?;
+                output = std::mem::take(&mut decoder.get_mut().buffer);
+            }
+            Self::Zstd {
+                decoder,
+                frame_done,
+                frame_input,
+                frame_output_len,
+            } => {
+                let mut input = InBuffer::around(input);
+                let mut buffer = vec![0; zstd_safe::DCtx::out_size()];
+                loop {
+                    let mut out = OutBuffer::around(buffer.as_mut_slice());
+                    let start = input.pos;
+                    // a hint of 0 means the frame is complete
+                    *frame_done = decoder.run(&mut input, &mut out)? == 0;
+                    let (n_bytes, full) = (out.pos, out.pos == buffer.len());
+                    output.extend_from_slice(&buffer[..n_bytes]);
+                    check_buffered(output.len(), max_buffered)?;
+                    if let Some(frame_input) = frame_input {
+                        // decoding stops at the end of each frame, so the next frame's input starts after it
+                        match *frame_done {
+                            true => frame_input.clear(),
+                            false => frame_input.extend_from_slice(&input.src[start..input.pos]),
+                        }
+                        *frame_output_len = match *frame_done {
+                            true => 0,
+                            false => *frame_output_len + n_bytes as u64,
+                        };
+                    }
+                    if input.pos == input.src.len() && !full {
+                        break;
+                    }
+                }
+            }
+            Self::Lz4 { feed, decoder } => {
+                feed.push(input);
+                read_buffered(decoder, &mut output, max_buffered)?;
+            }
+            Self::Snappy {
+                feed,
+                decoder,
+                partial_chunk,
+                stream_started,
+            } => {
+                partial_chunk.extend_from_slice(input);
+                let complete = complete_snappy_chunks(partial_chunk);
+                *stream_started |= complete > 0;
+                feed.push(&partial_chunk[..complete]);
+                partial_chunk.drain(..complete);
+                read_buffered(decoder, &mut output, max_buffered)?;
+            }
+        }
+        Ok(output)
+    }
+
+    /// End the stream, returning any remaining decompressed data
+    fn finish(self) -> std::io::Result<Vec<u8>> {
+        let truncated = || {
+            Error::new(
+                ErrorKind::UnexpectedEof,
+                "Stream ended before the end of its last frame",
+            )
+        };
+        match self {
+            Self::Gzip(mut decoder) => {
+                decoder.try_finish().map_err(|_| truncated())?;
+                Ok(std::mem::take(&mut decoder.get_mut().buffer))
+            }
+            Self::Zstd { frame_done, .. } => match frame_done {
+                true => Ok(vec![]),
+                false => Err(truncated()),
+            },
+            Self::Lz4 { decoder, .. } => {
+                decoder.finish().1.map_err(|_| truncated())?;
+                Ok(vec![])
+            }
+            Self::Snappy {
+                feed, partial_chunk, ..
+            } => match feed.is_empty() && partial_chunk.is_empty() {
+                true => Ok(vec![]),
+                false => Err(truncated()),
+            },
+        }
+    }
+}
+
+/// Read all of `decoder` into `output`, failing once that's more than `max_buffered` bytes
+fn read_buffered<R: Read>(decoder: &mut R, output: &mut Vec<u8>, max_buffered: Option<usize>) -> std::io::Result<()> {
+    match max_buffered {
+        Some(max_buffered) => {
+            decoder.take(max_buffered as u64 + 1).read_to_end(output)?;
+            check_buffered(output.len(), Some(max_buffered))
+        }
+        None => decoder.read_to_end(output).map(drop),
+    }
+}
+
+/// Length of the complete chunks at the start of `data`, a part of a snappy frame; each chunk is a
+/// byte of its type and its length as a 3 byte little endian integer, followed by that many bytes.
+fn complete_snappy_chunks(data: &[u8]) -> usize {
+    let mut pos = 0;
+    while let Some(header) = data.get(pos..pos + 4) {
+        let chunk_len = u32::from_le_bytes([header[1], header[2], header[3], 0]) as usize;
+        if data.len() < pos + 4 + chunk_len {
+            break;
+        }
+        pos += 4 + chunk_len;
+    }
+    pos
+}
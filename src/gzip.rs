@@ -1,76 +1,726 @@
 //! gzip de/compression interface
 use crate::exceptions::{CompressionError, DecompressionError};
-use crate::io::RustyBuffer;
-use crate::{to_py_err, BytesType};
+use crate::io::{AsBytes, BufferedOutput, RustyBuffer, RustyLines, RustyStridedNumpyArray};
+use crate::{to_py_err, BytesType, Level};
+use numpy::PyArray1;
 use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyString};
 use pyo3::wrap_pyfunction;
-use pyo3::PyResult;
-use std::io::Cursor;
+use pyo3::{PyObjectProtocol, PyResult};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 
 pub(crate) fn init_py_module(m: &PyModule) -> PyResult<()> {
+    m.add("DEFAULT_LEVEL", internal::DEFAULT_LEVEL)?;
+    m.add("MAX_LEVEL", internal::MAX_LEVEL)?;
     m.add_function(wrap_pyfunction!(compress, m)?)?;
     m.add_function(wrap_pyfunction!(decompress, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_bound, m)?)?;
     m.add_function(wrap_pyfunction!(compress_into, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_into_file, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_reuse, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_strided, m)?)?;
     m.add_function(wrap_pyfunction!(decompress_into, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_to_memmap, m)?)?;
+    #[cfg(unix)]
+    m.add_function(wrap_pyfunction!(compress_fd, m)?)?;
+    #[cfg(unix)]
+    m.add_function(wrap_pyfunction!(decompress_fd, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_lines, m)?)?;
+    m.add_function(wrap_pyfunction!(recompress_range, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_str, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_str, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_many, m)?)?;
+    m.add_function(wrap_pyfunction!(is_valid, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_many_into, m)?)?;
+    m.add_function(wrap_pyfunction!(maybe_compress, m)?)?;
+    m.add_function(wrap_pyfunction!(maybe_decompress, m)?)?;
+    m.add_function(wrap_pyfunction!(peek, m)?)?;
+    m.add_function(wrap_pyfunction!(read_header, m)?)?;
+    m.add_function(wrap_pyfunction!(crc32, m)?)?;
+    m.add_class::<Decompressor>()?;
     Ok(())
 }
 
 /// Gzip decompression.
 ///
+/// Concatenated gzip members, ie. as written by `cat a.gz b.gz`, are decompressed one after the
+/// other, as by Python's `gzip.decompress`.
+///
+/// With `recover=True`, decoding stops at the first corrupted or truncated section of the input
+/// rather than raising; a tuple of the `Buffer` decompressed up to that point, and whether
+/// the input was found to be damaged, is returned instead.
+///
+/// With `partial=True`, data which ends part way into a member, ie. as received so far over a
+/// network, decompresses to what it holds so far rather than raising; unlike `recover`, corrupted
+/// data still raises `DecompressionError`, and the `Buffer` alone is returned.
+///
+/// With `timeout_ms`, `DecompressionError` is raised if decompression takes longer than that
+/// many milliseconds, ie. to limit the time spent on untrusted input.
+///
+/// With `pad_to`, the input is taken to be the padded output of `compress` with the same `pad_to`.
+///
+/// With `trailer_checksum`, the trailer appended by `compress` with the same `trailer_checksum` is
+/// checked before decompressing, raising `DecompressionError` if the compressed data was corrupted.
+///
+/// With `exact=True`, and no `output_len`, the data is decompressed twice, first only to count its
+/// decompressed length, so the output is allocated once rather than grown as it's decompressed.
+/// Without either, the output is still allocated once for a single gzip member under 4GiB, from
+/// the decompressed length recorded in its trailer.
+///
 /// Python Example
 /// --------------
 /// ```python
-/// >>> cramjam.gzip.decompress(compressed_bytes, output_len=Optional[int])
+/// >>> cramjam.gzip.decompress(compressed_bytes, output_len=Optional[int], recover=False, timeout_ms=Optional[int], pad_to=Optional[int], trailer_checksum=Optional[str], exact=False)
+/// >>> cramjam.gzip.decompress(compressed_bytes[:1000], partial=True)
 /// ```
 #[pyfunction]
-pub fn decompress(data: BytesType, output_len: Option<usize>) -> PyResult<RustyBuffer> {
-    crate::generic!(decompress(data), output_len = output_len)
+#[allow(clippy::too_many_arguments)]
+pub fn decompress(
+    py: Python,
+    mut data: BytesType,
+    output_len: Option<usize>,
+    recover: Option<bool>,
+    timeout_ms: Option<u64>,
+    pad_to: Option<usize>,
+    trailer_checksum: Option<&str>,
+    exact: Option<bool>,
+    partial: Option<bool>,
+) -> PyResult<PyObject> {
+    let recover = recover.unwrap_or(false);
+    let partial = partial.unwrap_or(false);
+    // the gzip trailer is only at the end of the data without cramjam's own header or trailer
+    let decompressed_len = match (pad_to, trailer_checksum) {
+        (None, None) => trailer_isize(&mut data)?,
+        _ => None,
+    };
+    let compressed_len = data.remaining_len()?;
+    let compressed = crate::unpadded(&mut data, pad_to)?;
+    let compressed = crate::TrailerChecked::new(compressed, trailer_checksum)?;
+    if recover || partial || timeout_ms.is_some() {
+        return crate::decompress_with(py, internal::decoder(compressed), recover, timeout_ms, partial);
+    }
+    if exact.unwrap_or(false) && output_len.is_none() {
+        return crate::decompress_exact(compressed, |input, output| internal::decompress(input, output))
+            .map(|buffer| buffer.into_py(py));
+    }
+    if output_len.is_none() {
+        let mut output = crate::presized_output(decompressed_len, compressed_len);
+        to_py_err!(DecompressionError -> internal::decompress(compressed, &mut output))?;
+        return Ok(RustyBuffer::from(output).into_py(py));
+    }
+    crate::generic!(decompress(compressed), output_len = output_len).map(|buffer: RustyBuffer| buffer.into_py(py))
 }
 
 /// Gzip compression.
 ///
+/// `level` ranges from 0, which stores the data without compressing it, to 9; it defaults to 6
+/// when `None` or omitted. `compresslevel` is accepted as an alias of it, as in Python's `gzip.compress`.
+/// `CompressionError` is raised for any other level.
+///
+/// `filename`, `mtime` and `comment` are written into the gzip header, as shown by `gzip -l -N`
+/// and read back by `read_header`; without them the header has none, and an `mtime` of 0, so the
+/// output only depends on the data and level, ie. for reproducible builds. `filename` and `comment`
+/// must be latin-1, as gzip's header fields are.
+///
+/// With `pad_to`, the output is padded with zeros to a multiple of that many bytes, hiding its exact
+/// length, ie. before encrypting it; decompress it with the same `pad_to`.
+///
+/// `trailer_checksum="crc32"`, or `"xxh3"` which is faster for large outputs, appends a checksum
+/// of the compressed data, before any padding, so corruption in transport is caught before
+/// decompressing; decompress it with the same `trailer_checksum`.
+///
 /// Python Example
 /// --------------
 /// ```python
-/// >>> cramjam.gzip.compress(b'some bytes here', level=2, output_len=Optional[int])  # Level defaults to 6
+/// >>> cramjam.gzip.compress(b'some bytes here', level=2, output_len=Optional[int])
+/// >>> cramjam.gzip.compress(b'some bytes here', level="auto")  # 9 for inputs below 1MiB, 6 below 64MiB, otherwise 1
+/// >>> cramjam.gzip.compress(b'some bytes here', compresslevel=9)  # the same as level=9
+/// >>> cramjam.gzip.compress(b'some bytes here', filename="data.txt", mtime=1600000000, comment=Optional[str])
 /// ```
 #[pyfunction]
-pub fn compress(data: BytesType, level: Option<u32>, output_len: Option<usize>) -> PyResult<RustyBuffer> {
-    crate::generic!(compress(data), output_len = output_len, level = level)
+#[allow(clippy::too_many_arguments)]
+pub fn compress(
+    mut data: BytesType,
+    level: Option<Level<u32>>,
+    output_len: Option<usize>,
+    pad_to: Option<usize>,
+    trailer_checksum: Option<&str>,
+    compresslevel: Option<Level<u32>>,
+    filename: Option<&str>,
+    mtime: Option<u32>,
+    comment: Option<&str>,
+) -> PyResult<RustyBuffer> {
+    let level = Level::or_alias(level, compresslevel, "compresslevel")?;
+    let level = level.map(|level| level.resolve(&mut data, auto_level)).transpose()?;
+    to_py_err!(CompressionError -> internal::checked_level(level))?;
+    let header = internal::Header {
+        filename: filename
+            .map(|filename| latin1_field(filename, "filename"))
+            .transpose()?,
+        comment: comment.map(|comment| latin1_field(comment, "comment")).transpose()?,
+        mtime,
+    };
+    crate::generic!(
+        compress_with_header(data),
+        output_len = output_len,
+        level = level,
+        header = &header
+    )
+    .and_then(|compressed| crate::with_trailer(compressed, trailer_checksum))
+    .and_then(|compressed| crate::pad(compressed, pad_to))
+}
+
+/// `value` of the header field `name` encoded as latin-1, as gzip's header fields are and as Python's
+/// `gzip` module writes the file name; raises `ValueError` for other characters, or a null, which
+/// would end the field.
+fn latin1_field(value: &str, name: &str) -> PyResult<Vec<u8>> {
+    value
+        .chars()
+        .map(|c| match c {
+            '\u{1}'..='\u{ff}' => Some(c as u8),
+            _ => None,
+        })
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(|| {
+            pyo3::exceptions::PyValueError::new_err(format!(
+                "gzip {} must be latin-1 without null characters, got {:?}",
+                name, value
+            ))
+        })
 }
 
-/// Compress directly into an output buffer
+/// Level used with `level="auto"`, by the length of the input
+fn auto_level(input_len: u64) -> u32 {
+    match input_len {
+        len if len < crate::AUTO_LEVEL_SMALL_INPUT => 9,
+        len if len < crate::AUTO_LEVEL_LARGE_INPUT => 6,
+        _ => 1,
+    }
+}
+
+/// Compress `text`, a `str`, encoded with `encoding` (default `"utf-8"`); `level` is as for `compress`.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.gzip.compress_str("some text here", encoding="utf-8", level=Optional[int])
+/// ```
 #[pyfunction]
-pub fn compress_into(input: BytesType, mut output: BytesType, level: Option<u32>) -> PyResult<usize> {
-    let r = internal::compress(input, &mut output, level)?;
-    Ok(r)
+pub fn compress_str(text: &PyString, encoding: Option<&str>, level: Option<Level<u32>>) -> PyResult<RustyBuffer> {
+    compress(
+        crate::encode_str(text, encoding)?,
+        level,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+}
+
+/// Decompress to a `str`, decoding the decompressed data with `encoding` (default `"utf-8"`).
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.gzip.decompress_str(compressed_bytes, encoding="utf-8")
+/// 'some text here'
+/// ```
+#[pyfunction]
+pub fn decompress_str<'a>(py: Python<'a>, data: BytesType, encoding: Option<&str>) -> PyResult<&'a PyString> {
+    let mut output = vec![];
+    to_py_err!(DecompressionError -> internal::decompress(data, &mut output))?;
+    crate::decode_str(py, &output, encoding)
+}
+
+/// Check `data` decompresses without error, returning `True` if it does and `False` if it doesn't,
+/// ie. it's truncated or corrupt; the decompressed data is discarded as it's decoded, rather than
+/// kept in an output buffer.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.gzip.is_valid(compressed_bytes)
+/// True
+/// ```
+#[pyfunction]
+pub fn is_valid(data: BytesType) -> bool {
+    internal::decompress(data, &mut std::io::sink()).is_ok()
+}
+
+/// Check each of `items` decompresses, and when given, to the corresponding item of `expected`;
+/// returns the index of the first which doesn't, or `None` if all of them do. The items are
+/// checked in parallel with the GIL released.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.gzip.verify_many([compressed_bytes, other_compressed_bytes], expected=Optional[List[bytes]])
+/// ```
+#[pyfunction]
+pub fn verify_many(py: Python, items: Vec<BytesType>, expected: Option<Vec<BytesType>>) -> PyResult<Option<usize>> {
+    crate::verify_many(py, items, expected, |input, output| internal::decompress(input, output))
+}
+
+/// Decompress each of `items` into successive regions of `array`, a `numpy.array` with `dtype=np.uint8`,
+/// returning the `(offset, length)` of each region; raises `BufferError` if the array fills up first,
+/// and `ValueError` if an item overlaps the array, ie. is a view of it.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> array = np.empty(total_len, dtype=np.uint8)
+/// >>> regions = cramjam.gzip.decompress_many_into([compressed_bytes, other_compressed_bytes], array)
+/// ```
+#[pyfunction]
+pub fn decompress_many_into(items: Vec<BytesType>, array: &PyArray1<u8>) -> PyResult<Vec<(usize, usize)>> {
+    crate::decompress_many_into(items, array, |input, output| internal::decompress(input, output))
+}
+
+/// Compress, unless that doesn't achieve a ratio of uncompressed to compressed length of at least
+/// `min_ratio` (default 1.1); returns a tuple of the compressed data and `True`, or a copy of the
+/// data as is and `False`, to be given to `maybe_decompress`. `level` is as for `compress`.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> data, compressed = cramjam.gzip.maybe_compress(b'some bytes here', min_ratio=1.1, level=Optional[int])
+/// >>> bytes(cramjam.gzip.maybe_decompress(data, compressed))
+/// b'some bytes here'
+/// ```
+#[pyfunction]
+pub fn maybe_compress(
+    mut data: BytesType,
+    min_ratio: Option<f64>,
+    level: Option<Level<u32>>,
+) -> PyResult<(RustyBuffer, bool)> {
+    let level = level.map(|level| level.resolve(&mut data, auto_level)).transpose()?;
+    crate::maybe_compress(data, min_ratio, |input, output| {
+        internal::compress(input, output, level)
+    })
+}
+
+/// Decompress the data returned by `maybe_compress` if its flag, `compressed`, is `True`, otherwise
+/// return a copy of it as is.
+#[pyfunction]
+pub fn maybe_decompress(data: BytesType, compressed: bool) -> PyResult<RustyBuffer> {
+    crate::maybe_decompress(data, compressed, |input, output| internal::decompress(input, output))
+}
+
+/// Metadata from the header of the gzip member at the current position of the data, which is
+/// unchanged, as a `dict` of its `filename` and `comment`, decoded from latin-1, or `None` if it has
+/// none, its `mtime` as a Unix timestamp, 0 if it has none, and `os`, the operating system byte,
+/// 255 when unknown as written by `compress`. Raises `DecompressionError` if the data doesn't start
+/// with a gzip header.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> compressed = cramjam.gzip.compress(b'some bytes here', filename="data.txt", mtime=1600000000)
+/// >>> cramjam.gzip.read_header(compressed)
+/// {'filename': 'data.txt', 'comment': None, 'mtime': 1600000000, 'os': 255}
+/// ```
+#[pyfunction]
+pub fn read_header<'a>(py: Python<'a>, mut data: BytesType) -> PyResult<&'a PyDict> {
+    let position = data.stream_position()?;
+    let header = flate2::read::GzDecoder::new(Read::by_ref(&mut data)).header().cloned();
+    data.seek(SeekFrom::Start(position))?;
+    let header = header.ok_or_else(|| DecompressionError::new_err("Invalid or truncated gzip header"))?;
+
+    let latin1 = |field: Option<&[u8]>| field.map(|bytes| bytes.iter().map(|byte| *byte as char).collect::<String>());
+    let metadata = PyDict::new(py);
+    metadata.set_item("filename", latin1(header.filename()))?;
+    metadata.set_item("comment", latin1(header.comment()))?;
+    metadata.set_item("mtime", header.mtime())?;
+    metadata.set_item("os", header.operating_system())?;
+    Ok(metadata)
+}
+
+/// Structural metadata from the header of a gzip frame, without decompressing it; see `cramjam.peek`.
+/// Raises `DecompressionError` if the data doesn't start with a gzip frame.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.gzip.peek(compressed_bytes)
+/// ```
+#[pyfunction]
+pub fn peek<'a>(py: Python<'a>, mut data: BytesType) -> PyResult<&'a PyDict> {
+    crate::detect::peek_header(py, &mut data, Some("gzip"))
+}
+
+/// Decompressor of a gzip stream given in chunks, ie. as read off a socket, which needn't align
+/// with anything in the stream; parts of the header, deflate blocks or trailer are kept until the
+/// rest of them arrives. Concatenated gzip members are decompressed one after the other, as by the
+/// `gzip` command.
+///
+/// With `max_buffered`, `DecompressionError("output backpressure exceeded")` is raised should a chunk
+/// decompress to more than that many bytes, which would otherwise all be held until returned, ie.
+/// when chunks arrive faster than their output is consumed; the decompressor can't be used afterwards.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> decompressor = cramjam.gzip.Decompressor(max_buffered=Optional[int])
+/// >>> decompressed = decompressor.decompress(compressed[:5])  # b'' until there's a whole block
+/// >>> decompressed += decompressor.decompress(compressed[5:])
+/// >>> decompressed += decompressor.finish()
+/// ```
+#[pyclass]
+pub struct Decompressor {
+    decoder: Option<flate2::write::MultiGzDecoder<BufferedOutput>>,
+}
+
+#[pymethods]
+impl Decompressor {
+    /// Instantiate the decompressor, holding at most `max_buffered` bytes of output
+    #[new]
+    pub fn __init__(max_buffered: Option<usize>) -> Self {
+        Self {
+            decoder: Some(flate2::write::MultiGzDecoder::new(BufferedOutput::new(max_buffered))),
+        }
+    }
+    /// Decompress the next `chunk` of the stream, anything in [`BytesType`](../enum.BytesType.html),
+    /// returning the `bytes` decompressed from it so far; raises `DecompressionError` if it isn't gzip.
+    pub fn decompress<'a>(&mut self, py: Python<'a>, chunk: BytesType) -> PyResult<&'a PyBytes> {
+        let decoder = self.decoder()?;
+        if let Err(err) = decoder.write_all(chunk.as_bytes()) {
+            self.decoder = None;
+            return Err(DecompressionError::new_err(err.to_string()));
+        }
+        Ok(PyBytes::new(py, &std::mem::take(&mut self.decoder()?.get_mut().buffer)))
+    }
+    /// End the stream, returning any remaining decompressed `bytes`; raises `DecompressionError` if
+    /// it ended part way into a member. The decompressor can't be given more chunks afterwards.
+    pub fn finish<'a>(&mut self, py: Python<'a>) -> PyResult<&'a PyBytes> {
+        let decoder = self.decoder()?;
+        to_py_err!(DecompressionError -> decoder.try_finish())?;
+        let output = std::mem::take(&mut decoder.get_mut().buffer);
+        self.decoder = None;
+        Ok(PyBytes::new(py, &output))
+    }
+}
+
+impl Decompressor {
+    fn decoder(&mut self) -> PyResult<&mut flate2::write::MultiGzDecoder<BufferedOutput>> {
+        self.decoder
+            .as_mut()
+            .ok_or_else(|| pyo3::exceptions::PyValueError::new_err("Decompressor is already finished"))
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for Decompressor {
+    fn __repr__(&self) -> String {
+        format!(
+            "cramjam.gzip.Decompressor(finished={})",
+            if self.decoder.is_none() { "True" } else { "False" }
+        )
+    }
+}
+
+/// CRC-32 of `data`, the checksum in each gzip member's trailer, as by Python's `zlib.crc32`.
+/// `value` is the CRC-32 of any data before it, by default 0, to checksum data given in chunks.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.gzip.crc32(b'some bytes here')
+/// 3249009414
+/// >>> cramjam.gzip.crc32(b' here', value=cramjam.gzip.crc32(b'some bytes'))
+/// 3249009414
+/// ```
+#[pyfunction]
+pub fn crc32(data: BytesType, value: Option<u32>) -> u32 {
+    let mut hasher = crc32fast::Hasher::new_with_initial(value.unwrap_or(0));
+    hasher.update(data.as_bytes());
+    hasher.finalize()
+}
+
+/// Get the maximum length of the compressed output for `data_len` bytes of input, the size
+/// of buffer which is always big enough to be passed to `compress_into`
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> output = bytearray(cramjam.gzip.compress_bound(len(data)))
+/// >>> n_bytes = cramjam.gzip.compress_into(data, output)
+/// ```
+#[pyfunction]
+pub fn compress_bound(data_len: usize) -> PyResult<usize> {
+    crate::checked_bound(internal::compress_bound(data_len), data_len)
+}
+
+/// Compress directly into an output buffer, returning the compressed length; `CompressionError` is
+/// raised with `compress_bound` of the input's length if the buffer is too small to hold it.
+#[pyfunction]
+pub fn compress_into(input: BytesType, output: BytesType, level: Option<u32>) -> PyResult<usize> {
+    crate::compress_into(input, output, internal::compress_bound, |input, output| {
+        internal::compress(input, output, level)
+    })
+}
+
+/// Compress directly to `fileobj`, any object with a `write` method, ie. `io.BytesIO` or a socket's
+/// `makefile("wb")`, returning the number of bytes written. The compressed data is passed to
+/// `fileobj.write` as it's produced, rather than collected first, and `fileobj.flush`, if any, is
+/// called at the end; an exception raised by either is raised as is.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> with open("data.gz", "wb") as f:
+/// ...     n_bytes = cramjam.gzip.compress_into_file(b'some bytes here', f, level=Optional[int])
+/// ```
+#[pyfunction]
+pub fn compress_into_file(data: BytesType, fileobj: &PyAny, level: Option<u32>) -> PyResult<usize> {
+    to_py_err!(CompressionError -> internal::checked_level(level))?;
+    crate::io::with_fileobj(fileobj, |output| internal::compress(data, output, level).map(|_| ()))
+}
+
+/// Compress using `scratch`, a `numpy.array` with `dtype=np.uint8`, as the output buffer, returning
+/// a copy of the compressed data as `bytes`. The array is resized as needed, and can be reused across
+/// calls to avoid allocating a new output buffer each time; as with `numpy.ndarray.resize`, it must
+/// own its data and not be referenced by any other array.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> scratch = np.empty(0, dtype=np.uint8)
+/// >>> for data in chunks:
+/// ...     compressed = cramjam.gzip.compress_reuse(data, scratch, level=Optional[int])
+/// ```
+#[pyfunction]
+pub fn compress_reuse<'a>(
+    py: Python<'a>,
+    data: BytesType,
+    scratch: &PyArray1<u8>,
+    level: Option<u32>,
+) -> PyResult<&'a PyBytes> {
+    crate::io::compress_reuse(py, scratch, |output| internal::compress(data, output, level))
+}
+
+/// Compress a `numpy.array` of any dtype, including non-contiguous views such as `arr[::2]`.
+/// The elements are gathered into a contiguous buffer, in C order, before being compressed.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> arr = np.arange(100, dtype=np.float64).reshape(10, 10)
+/// >>> cramjam.gzip.compress_strided(arr[::2, 1::3], level=Optional[int], output_len=Optional[int])
+/// ```
+#[pyfunction]
+pub fn compress_strided(
+    array: RustyStridedNumpyArray,
+    level: Option<u32>,
+    output_len: Option<usize>,
+) -> PyResult<RustyBuffer> {
+    crate::generic!(compress(array), output_len = output_len, level = level)
+}
+
+/// Decompress into a new `numpy.memmap` of `dtype` (default `"uint8"`) at `path`, which is created
+/// or overwritten, returning it; for data too large to decompress into memory. The data is
+/// decompressed twice, first to find its length.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> array = cramjam.gzip.decompress_to_memmap(compressed_bytes, "/tmp/data.bin", dtype="float64")
+/// ```
+#[pyfunction]
+pub fn decompress_to_memmap<'a>(
+    py: Python<'a>,
+    data: BytesType,
+    path: &str,
+    dtype: Option<&str>,
+) -> PyResult<&'a PyAny> {
+    crate::decompress_to_memmap(py, data, path, dtype, None, |input, output| {
+        internal::decompress(input, output)
+    })
 }
 
 /// Decompress directly into an output buffer
 #[pyfunction]
-pub fn decompress_into(input: BytesType, mut output: BytesType) -> PyResult<usize> {
-    let r = internal::decompress(input, &mut output)?;
+pub fn decompress_into(mut input: BytesType, mut output: BytesType) -> PyResult<usize> {
+    output.check_writable()?;
+    let decompressed_len = trailer_isize(&mut input)?;
+    let r = crate::decompress_into_presized(input, &mut output, decompressed_len, |input, output| {
+        internal::decompress(input, output)
+    })?;
     Ok(r)
 }
 
+/// Shortest gzip member, a header and trailer around an empty deflate stream
+const MIN_MEMBER_LEN: u64 = 20;
+
+/// Decompressed length modulo 4GiB recorded in the trailer of the gzip data from the current position
+/// of `data`, which is unchanged; that of the data if it's a single member, and under 4GiB.
+fn trailer_isize(data: &mut BytesType) -> std::io::Result<Option<u64>> {
+    if crate::detect::read_sample(data, 2)? != [0x1f, 0x8b] || data.remaining_len()? < MIN_MEMBER_LEN {
+        return Ok(None);
+    }
+    let position = data.stream_position()?;
+    let mut isize = [0; 4];
+    data.seek(SeekFrom::End(-4))?;
+    let result = data.read_exact(&mut isize);
+    data.seek(SeekFrom::Start(position))?;
+    result?;
+    Ok(Some(u32::from_le_bytes(isize) as u64))
+}
+
+/// Compress from the file descriptor `in_fd` until its end, writing to `out_fd`, ie. either end of
+/// an `os.pipe()`, returning the number of bytes written; neither descriptor is closed.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.gzip.compress_fd(in_fd, out_fd, level=Optional[int])
+/// ```
+#[cfg(unix)]
+#[pyfunction]
+pub fn compress_fd(py: Python, in_fd: i32, out_fd: i32, level: Option<u32>) -> PyResult<usize> {
+    crate::io::with_fds::<CompressionError, _>(py, in_fd, out_fd, |input, output| {
+        internal::compress(input, output, level).map(|_| ())
+    })
+}
+
+/// Decompress from the file descriptor `in_fd` until its end, writing to `out_fd`, returning
+/// the number of bytes written; neither descriptor is closed.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.gzip.decompress_fd(in_fd, out_fd)
+/// ```
+#[cfg(unix)]
+#[pyfunction]
+pub fn decompress_fd(py: Python, in_fd: i32, out_fd: i32) -> PyResult<usize> {
+    crate::io::with_fds::<DecompressionError, _>(py, in_fd, out_fd, |input, output| {
+        internal::decompress(input, output).map(|_| ())
+    })
+}
+
+/// Decompress line by line; returns an iterator over the lines of decompressed data
+/// as `bytes`, without materializing the entire decompressed data. See [`Lines`](../io/struct.RustyLines.html)
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> for line in cramjam.gzip.decompress_lines(compressed_bytes):
+/// ...     print(line)
+/// ```
+#[pyfunction]
+pub fn decompress_lines(mut data: BytesType) -> PyResult<RustyLines> {
+    let mut compressed = vec![];
+    data.read_to_end(&mut compressed)?;
+    let decoder = to_py_err!(DecompressionError -> internal::decoder(Cursor::new(compressed)))?;
+    Ok(RustyLines::new(decoder))
+}
+
+/// Replace the `[start, end)` range of the decompressed data with `replacement`, returning the edited
+/// data compressed again, without returning the decompressed data to Python. Raises `ValueError`
+/// if `start` is greater than `end`, and `DecompressionError` if the range is beyond the end of the data.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.gzip.recompress_range(compressed_bytes, start, end, b'replacement', level=Optional[int])
+/// ```
+#[pyfunction]
+pub fn recompress_range(
+    data: BytesType,
+    start: u64,
+    end: u64,
+    replacement: BytesType,
+    level: Option<u32>,
+) -> PyResult<RustyBuffer> {
+    crate::io::recompress_range(internal::decoder(data), start, end, replacement, |input, output| {
+        internal::compress(input, output, level)
+    })
+}
+
 pub(crate) mod internal {
-    use flate2::read::{GzDecoder, GzEncoder};
-    use flate2::Compression;
+    use crate::io::FinishWrite;
+    use flate2::read::MultiGzDecoder;
+    use flate2::write::GzEncoder as GzWriteEncoder;
+    use flate2::{Compression, GzBuilder};
     use std::io::prelude::*;
     use std::io::Error;
 
+    /// Compression level used when none is given
+    pub(crate) const DEFAULT_LEVEL: u32 = 6;
+
+    /// Highest compression level
+    pub(crate) const MAX_LEVEL: u32 = 9;
+
+    /// `level`, or the default, checked to be at most [`MAX_LEVEL`], as flate2 panics beyond it
+    pub(crate) fn checked_level(level: Option<u32>) -> Result<u32, Error> {
+        crate::checked_level("gzip", level, DEFAULT_LEVEL, 0, MAX_LEVEL)
+    }
+
+    /// gzip's header, without a file name or comment, and trailer of its checksum and length
+    const MEMBER_OVERHEAD: usize = 18;
+
+    /// Largest output of `compress` for `input_len` bytes of input; `None` if it's too large to be represented
+    pub fn compress_bound(input_len: usize) -> Option<usize> {
+        crate::deflate::internal::compress_bound(input_len)?.checked_add(MEMBER_OVERHEAD)
+    }
+
+    /// Decoder reading compressed data from `input`, through any concatenated members
+    pub fn decoder<R: Read>(input: R) -> Result<MultiGzDecoder<R>, Error> {
+        Ok(MultiGzDecoder::new(input))
+    }
+
+    /// Encoder writing compressed data to `output`
+    pub fn encoder<W: Write>(output: W, level: Option<u32>) -> Result<GzWriteEncoder<W>, Error> {
+        let level = checked_level(level)?;
+        Ok(GzWriteEncoder::new(output, Compression::new(level)))
+    }
+
     /// Decompress gzip data
     pub fn decompress<W: Write + ?Sized, R: Read>(input: R, output: &mut W) -> Result<usize, Error> {
-        let mut decoder = GzDecoder::new(input);
+        let mut decoder = decoder(input)?;
         let n_bytes = std::io::copy(&mut decoder, output)?;
         Ok(n_bytes as usize)
     }
 
+    /// Optional fields of a gzip member's header; by default there are none, and its mtime is 0
+    #[derive(Default)]
+    pub struct Header {
+        pub filename: Option<Vec<u8>>,
+        pub comment: Option<Vec<u8>>,
+        pub mtime: Option<u32>,
+    }
+
     /// Compress gzip data
     pub fn compress<W: Write + ?Sized, R: Read>(input: R, output: &mut W, level: Option<u32>) -> Result<usize, Error> {
-        let level = level.unwrap_or_else(|| 6);
-        let mut encoder = GzEncoder::new(input, Compression::new(level));
+        compress_with_header(input, output, level, &Header::default())
+    }
+
+    /// Compress gzip data, with `header`'s fields in the member's header
+    pub fn compress_with_header<W: Write + ?Sized, R: Read>(
+        input: R,
+        output: &mut W,
+        level: Option<u32>,
+        header: &Header,
+    ) -> Result<usize, Error> {
+        let level = checked_level(level)?;
+        let mut builder = GzBuilder::new().mtime(header.mtime.unwrap_or(0));
+        if let Some(filename) = &header.filename {
+            builder = builder.filename(filename.clone());
+        }
+        if let Some(comment) = &header.comment {
+            builder = builder.comment(comment.clone());
+        }
+        let mut encoder = builder.read(input, Compression::new(level));
         let n_bytes = std::io::copy(&mut encoder, output)?;
         Ok(n_bytes as usize)
     }
+
+    impl<W: Write + Send> FinishWrite for GzWriteEncoder<W> {
+        fn finish(self: Box<Self>) -> Result<(), Error> {
+            GzWriteEncoder::finish(*self)?.flush()
+        }
+    }
 }
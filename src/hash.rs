@@ -0,0 +1,46 @@
+//! xxHash hashing interface, the hashes LZ4 and zstd frames check their content with; an lz4
+//! frame's content checksum is the `xxh32` of its content, and a zstd frame's is the low 32 bits of
+//! its `xxh64`, each with a seed of 0.
+use crate::io::AsBytes;
+use crate::BytesType;
+use pyo3::prelude::*;
+use pyo3::wrap_pyfunction;
+use pyo3::PyResult;
+use std::hash::Hasher;
+use twox_hash::{XxHash32, XxHash64};
+
+pub(crate) fn init_py_module(m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(xxh32, m)?)?;
+    m.add_function(wrap_pyfunction!(xxh64, m)?)?;
+    Ok(())
+}
+
+/// 32 bit xxHash of `data`, with `seed`, or 0 when `None` or omitted.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.hash.xxh32(b'abc')
+/// 852579327
+/// ```
+#[pyfunction]
+pub fn xxh32(data: BytesType, seed: Option<u32>) -> u32 {
+    let mut hasher = XxHash32::with_seed(seed.unwrap_or(0));
+    hasher.write(data.as_bytes());
+    hasher.finish() as u32
+}
+
+/// 64 bit xxHash of `data`, with `seed`, or 0 when `None` or omitted.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.hash.xxh64(b'abc')
+/// 4952883123889572249
+/// ```
+#[pyfunction]
+pub fn xxh64(data: BytesType, seed: Option<u64>) -> u64 {
+    let mut hasher = XxHash64::with_seed(seed.unwrap_or(0));
+    hasher.write(data.as_bytes());
+    hasher.finish()
+}
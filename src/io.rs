@@ -2,17 +2,61 @@
 //! which wrap native Python objects to provide additional functionality
 //! or tighter integration with de/compression algorithms.
 //!
+//! ### Thread safety
+//! Every `#[pyclass]` here is `Send`, which is checked at compile time below; an object may
+//! be moved to, and used from, any Python thread. Access is serialized by the GIL and pyo3's
+//! borrow checking, so a concurrent mutable borrow raises a `RuntimeError` instead of racing.
+//! Any future pyclass holding state which is _not_ safe to move between threads must be
+//! declared `#[pyclass(unsendable)]`, in which case pyo3 raises when it's used from another thread.
+//!
 use std::fs::{File, OpenOptions};
-use std::io::{copy, Cursor, Read, Seek, SeekFrom, Write};
+use std::io::{copy, BufRead, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
 
+use crate::exceptions::{CompressionError, DecompressionError};
 use crate::BytesType;
+use numpy::npyffi::{PyArrayObject, PyArray_Check};
 use numpy::PyArray1;
 use pyo3::class::buffer::PyBufferProtocol;
 use pyo3::prelude::*;
-use pyo3::types::{PyByteArray, PyBytes};
-use pyo3::{ffi, PySequenceProtocol};
-use pyo3::{AsPyPointer, PyObjectProtocol};
+use pyo3::types::{PyByteArray, PyBytes, PyDict};
+use pyo3::{ffi, wrap_pyfunction, PyIterProtocol, PySequenceProtocol};
+use pyo3::{AsPyPointer, PyNativeType, PyObjectProtocol};
+use std::convert::TryFrom;
+#[cfg(unix)]
+use std::mem::ManuallyDrop;
+#[cfg(unix)]
+use std::os::unix::io::{FromRawFd, RawFd};
 use std::path::PathBuf;
+use std::pin::Pin;
+
+/// Add the functions here, ie. `open`, to the top-level `cramjam` module; `wrap_pyfunction!` only
+/// takes a function in scope, by name.
+pub(crate) fn add_functions(m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(open, m)?)?;
+    Ok(())
+}
+
+// Compile time guarantee the pyclasses can be shared between Python threads.
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    assert_send::<RustyFile>();
+    assert_send::<RustyBuffer>();
+    assert_send::<RustyLines>();
+    assert_send::<RustyCompressedFile>();
+    assert_send::<RustyRingCompressor>();
+    assert_send::<crate::registry::Pipeline>();
+    assert_send::<crate::detect::AutoDecompressor>();
+    assert_send::<crate::zstd::Compressor>();
+    assert_send::<crate::zstd::AdaptiveCompressor>();
+    assert_send::<crate::gzip::Decompressor>();
+};
+
+/// A `Write` of compressed data, which must be finished to write any remaining data, such as
+/// the frame footer, when there is nothing more to compress.
+pub(crate) trait FinishWrite: Write + Send {
+    /// Write any remaining compressed data, then flush the underlying writer.
+    fn finish(self: Box<Self>) -> std::io::Result<()>;
+}
 
 pub(crate) trait AsBytes {
     fn as_bytes(&self) -> &[u8];
@@ -76,6 +120,70 @@ impl<'a> Seek for RustyNumpyArray<'a> {
     }
 }
 
+/// Internal wrapper for a `numpy.array` of any dtype and memory layout, including
+/// non-contiguous views such as `arr[::2]`. The elements are gathered, in C order,
+/// into a contiguous buffer when extracted; `TypeError` is raised for dtypes holding
+/// Python objects, whose elements are only pointers to them.
+pub struct RustyStridedNumpyArray {
+    pub(crate) inner: Cursor<Vec<u8>>,
+}
+impl RustyStridedNumpyArray {
+    /// Number of gathered bytes
+    pub(crate) fn len(&self) -> usize {
+        self.inner.get_ref().len()
+    }
+}
+impl<'a> FromPyObject<'a> for RustyStridedNumpyArray {
+    fn extract(ob: &'a PyAny) -> PyResult<Self> {
+        if unsafe { PyArray_Check(ob.as_ptr()) } == 0 {
+            return Err(pyo3::exceptions::PyTypeError::new_err("Expected a numpy.ndarray"));
+        }
+        // the elements of such an array are pointers to Python objects, rather than data
+        if ob.getattr("dtype")?.getattr("hasobject")?.extract()? {
+            return Err(pyo3::exceptions::PyTypeError::new_err(
+                "Arrays of a dtype holding Python objects can't be compressed",
+            ));
+        }
+        let buf = unsafe {
+            let array = &*(ob.as_ptr() as *const PyArrayObject);
+            let ndim = array.nd as usize;
+            let dims = std::slice::from_raw_parts(array.dimensions as *const usize, ndim);
+            let strides = std::slice::from_raw_parts(array.strides as *const isize, ndim);
+            let itemsize = (*array.descr).elsize as usize;
+
+            let mut buf = Vec::with_capacity(dims.iter().product::<usize>() * itemsize);
+            gather(array.data as *const u8, dims, strides, itemsize, &mut buf);
+            buf
+        };
+        Ok(Self {
+            inner: Cursor::new(buf),
+        })
+    }
+}
+impl Read for RustyStridedNumpyArray {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+/// Copy the elements of an n-dimensional strided array into `out`, in C order.
+///
+/// # Safety
+/// `data`, `dims`, `strides` and `itemsize` must describe valid memory, as numpy does for an array.
+pub(crate) unsafe fn gather(data: *const u8, dims: &[usize], strides: &[isize], itemsize: usize, out: &mut Vec<u8>) {
+    match dims.split_first() {
+        None => out.extend_from_slice(std::slice::from_raw_parts(data, itemsize)),
+        Some((&len, [])) if strides[0] == itemsize as isize => {
+            out.extend_from_slice(std::slice::from_raw_parts(data, len * itemsize))
+        }
+        Some((&len, dims)) => {
+            for i in 0..len as isize {
+                gather(data.offset(i * strides[0]), dims, &strides[1..], itemsize, out);
+            }
+        }
+    }
+}
+
 /// Internal wrapper for `bytes`/`PyBytes`, to provide Read + Write and other traits
 pub struct RustyPyBytes<'a> {
     pub(crate) inner: &'a PyBytes,
@@ -128,6 +236,175 @@ impl<'a> Seek for RustyPyBytes<'a> {
     }
 }
 
+/// Internal wrapper for any other object implementing the buffer protocol, ie. `memoryview`
+/// or ctypes' `create_string_buffer` and `(c_char * n)` arrays, to provide Read + Write and
+/// other traits. The buffer must be C contiguous with at most one dimension, raising `TypeError`
+/// otherwise, and is treated as raw bytes regardless of its element type. It's requested read-only,
+/// as inputs may be, then again as writable when first written to as an output, which raises an
+/// error for a read-only buffer. `numpy.array`s of any number of dimensions are accepted, as long
+/// as they're C contiguous, their bytes being those of `arr.tobytes()`.
+pub struct RustyPyBuffer<'a> {
+    pub(crate) inner: &'a PyAny,
+    // pyo3's `PyBuffer<u8>` rejects ctypes' `<c` format on little endian platforms, so the
+    // buffer is requested directly; boxed as the exporter may point into it.
+    view: Pin<Box<ffi::Py_buffer>>,
+    writable: bool,
+    position: u64,
+}
+impl<'a> AsBytes for RustyPyBuffer<'a> {
+    fn as_bytes(&self) -> &[u8] {
+        match self.view.len {
+            0 => &[],
+            len => unsafe { std::slice::from_raw_parts(self.view.buf as *const u8, len as usize) },
+        }
+    }
+    /// Panics unless [`RustyPyBuffer::make_writable`] succeeded, as outputs check with `check_writable`
+    fn as_bytes_mut(&mut self) -> &mut [u8] {
+        assert!(self.writable, "Buffer wasn't requested as writable");
+        match self.view.len {
+            0 => &mut [],
+            len => unsafe { std::slice::from_raw_parts_mut(self.view.buf as *mut u8, len as usize) },
+        }
+    }
+}
+impl<'a> RustyPyBuffer<'a> {
+    /// Request the buffer again as writable, for an output, raising `BufferError` if it's read-only
+    pub(crate) fn make_writable(&mut self) -> PyResult<()> {
+        if self.writable {
+            return Ok(());
+        }
+        let mut view = Box::pin(ffi::Py_buffer::new());
+        let flags = ffi::PyBUF_STRIDES | ffi::PyBUF_WRITABLE;
+        if unsafe { ffi::PyObject_GetBuffer(self.inner.as_ptr(), &mut *view, flags) } == -1 {
+            // the exporter's own error only says it isn't writable
+            PyErr::fetch(self.inner.py());
+            return Err(pyo3::exceptions::PyBufferError::new_err("output buffer is read-only"));
+        }
+        let mut view = std::mem::replace(&mut self.view, view);
+        unsafe { ffi::PyBuffer_Release(&mut *view) };
+        self.writable = true;
+        Ok(())
+    }
+
+    /// Cursor over the buffer at the current position, which `op` may move
+    fn with_cursor<T>(&mut self, op: impl FnOnce(&mut Cursor<&mut [u8]>) -> T) -> T {
+        let position = self.position;
+        let mut cursor = Cursor::new(self.as_bytes_mut());
+        cursor.set_position(position);
+        let result = op(&mut cursor);
+        self.position = cursor.position();
+        result
+    }
+}
+impl<'a> FromPyObject<'a> for RustyPyBuffer<'a> {
+    fn extract(ob: &'a PyAny) -> PyResult<Self> {
+        let mut view = Box::pin(ffi::Py_buffer::new());
+        // requested with strides rather than as contiguous, which exporters refuse with errors of
+        // their own, so non-contiguous buffers get the same `TypeError` from any of them
+        unsafe {
+            if ffi::PyObject_GetBuffer(ob.as_ptr(), &mut *view, ffi::PyBUF_STRIDES) == -1 {
+                return Err(PyErr::fetch(ob.py()));
+            }
+            let unsupported = if ffi::PyBuffer_IsContiguous(&*view, b'C' as _) == 0 {
+                Some(match is_numpy_array(ob) {
+                    true => (
+                        "a C contiguous array",
+                        "is not C contiguous; copy it with numpy.ascontiguousarray() first".to_string(),
+                    ),
+                    false => (
+                        "a one dimensional, C contiguous buffer",
+                        "is not C contiguous; copy it with bytes() first".to_string(),
+                    ),
+                })
+            } else if view.ndim > 1 && !is_numpy_array(ob) {
+                Some((
+                    "a one dimensional, C contiguous buffer",
+                    format!(
+                        "has {} dimensions; flatten it first, ie. with memoryview(obj).cast('B')",
+                        view.ndim
+                    ),
+                ))
+            } else {
+                None
+            };
+            if let Some((expected, reason)) = unsupported {
+                ffi::PyBuffer_Release(&mut *view);
+                return Err(pyo3::exceptions::PyTypeError::new_err(format!(
+                    "Expected {}, but the '{}' object's buffer {}",
+                    expected,
+                    ob.get_type().name()?,
+                    reason
+                )));
+            }
+        }
+        Ok(Self {
+            inner: ob,
+            view,
+            writable: false,
+            position: 0,
+        })
+    }
+}
+
+/// Whether `ob` is a `numpy.ndarray`, without importing numpy when it isn't already
+fn is_numpy_array(ob: &PyAny) -> bool {
+    let py = ob.py();
+    py.import("sys")
+        .and_then(|sys| sys.getattr("modules"))
+        .and_then(|modules| modules.get_item("numpy"))
+        .and_then(|numpy| numpy.getattr("ndarray"))
+        .map(
+            |ndarray| match unsafe { ffi::PyObject_IsInstance(ob.as_ptr(), ndarray.as_ptr()) } {
+                // the error is cleared, as the object is then read as any other buffer
+                -1 => {
+                    PyErr::fetch(py);
+                    false
+                }
+                is_instance => is_instance == 1,
+            },
+        )
+        .unwrap_or(false)
+}
+
+impl<'a> Drop for RustyPyBuffer<'a> {
+    fn drop(&mut self) {
+        unsafe { ffi::PyBuffer_Release(&mut *self.view) }
+    }
+}
+impl<'a> ToPyObject for RustyPyBuffer<'a> {
+    fn to_object(&self, py: Python<'_>) -> PyObject {
+        self.inner.to_object(py)
+    }
+}
+impl<'a> Read for RustyPyBuffer<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut cursor = Cursor::new(self.as_bytes());
+        cursor.set_position(self.position);
+        let n_bytes = cursor.read(buf)?;
+        self.position = cursor.position();
+        Ok(n_bytes)
+    }
+}
+impl<'a> Write for RustyPyBuffer<'a> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.make_writable().is_err() {
+            return Err(std::io::Error::other("Buffer is read-only"));
+        }
+        self.with_cursor(|cursor| cursor.write(buf))
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+impl<'a> Seek for RustyPyBuffer<'a> {
+    fn seek(&mut self, style: SeekFrom) -> std::io::Result<u64> {
+        let mut cursor = Cursor::new(self.as_bytes());
+        cursor.set_position(self.position);
+        self.position = cursor.seek(style)?;
+        Ok(self.position)
+    }
+}
+
 /// Internal wrapper for `bytearray`/`PyByteArray`, to provide Read + Write and other traits
 pub struct RustyPyByteArray<'a> {
     pub(crate) inner: &'a PyByteArray,
@@ -160,22 +437,28 @@ impl<'a> ToPyObject for RustyPyByteArray<'a> {
         self.inner.to_object(py)
     }
 }
+impl<'a> RustyPyByteArray<'a> {
+    /// Resize the bytearray to `len` bytes, keeping the current position
+    pub(crate) fn resize(&mut self, len: usize) -> std::io::Result<()> {
+        let position = self.cursor.position();
+        self.inner
+            .resize(len)
+            .map_err(|err| std::io::Error::other(format!("Unable to resize bytearray: {}", err)))?;
+        self.cursor = Cursor::new(unsafe { self.inner.as_bytes_mut() });
+        self.cursor.set_position(position);
+        Ok(())
+    }
+}
 impl<'a> Write for RustyPyByteArray<'a> {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         if (self.cursor.position() as usize + buf.len()) > self.inner.len() {
-            let previous_pos = self.cursor.position();
-            self.inner.resize(self.cursor.position() as usize + buf.len()).unwrap();
-            self.cursor = Cursor::new(unsafe { self.inner.as_bytes_mut() });
-            self.cursor.set_position(previous_pos);
+            self.resize(self.cursor.position() as usize + buf.len())?;
         }
         self.cursor.write(buf)
     }
     fn flush(&mut self) -> std::io::Result<()> {
         if self.inner.len() != self.cursor.position() as usize {
-            let prev_pos = self.cursor.position();
-            self.inner.resize(self.cursor.position() as usize).unwrap();
-            self.cursor = Cursor::new(unsafe { self.inner.as_bytes_mut() });
-            self.cursor.set_position(prev_pos);
+            self.resize(self.cursor.position() as usize)?;
         }
         Ok(())
     }
@@ -207,6 +490,10 @@ impl<'a> Seek for RustyPyByteArray<'a> {
 /// Presently, the file's handle is managed by Rust's lifetime rules, in that
 /// once it's garbage collected from Python's side, it will be closed.
 ///
+/// The object is `Send`; it can be used from any thread, though the underlying
+/// file position is shared, so interleaved reads/writes from several threads
+/// should be coordinated by the caller.
+///
 #[pyclass(name = "File")]
 pub struct RustyFile {
     pub(crate) path: PathBuf,
@@ -292,6 +579,7 @@ impl RustyFile {
     }
     /// Read from the file in its current position, into a [`BytesType`](../enum.BytesType.html) object.
     pub fn readinto(&mut self, mut output: BytesType) -> PyResult<usize> {
+        output.check_writable()?;
         let r = copy(self, &mut output)?;
         Ok(r as usize)
     }
@@ -357,6 +645,10 @@ impl RustyFile {
 /// b'bytes'
 /// ```
 ///
+/// ### Notes
+/// The object is `Send`; each `read`/`write` call completes while holding the GIL,
+/// so calls from several threads never observe a partially written buffer.
+///
 #[pyclass(name = "Buffer")]
 #[derive(Default)]
 pub struct RustyBuffer {
@@ -378,6 +670,19 @@ impl From<Vec<u8>> for RustyBuffer {
     }
 }
 
+impl RustyBuffer {
+    /// The buffer's bytes, taken out of it rather than released with it
+    pub(crate) fn into_vec(mut self) -> Vec<u8> {
+        std::mem::take(self.inner.get_mut())
+    }
+}
+
+impl Drop for RustyBuffer {
+    fn drop(&mut self) {
+        crate::alloc::release(std::mem::take(self.inner.get_mut()));
+    }
+}
+
 #[pyproto]
 impl PyBufferProtocol for RustyBuffer {
     fn bf_getbuffer(slf: PyRefMut<Self>, view: *mut ffi::Py_buffer, flags: std::os::raw::c_int) -> PyResult<()> {
@@ -486,6 +791,7 @@ impl RustyBuffer {
     }
     /// Read from the buffer in its current position, into a [BytesType](../enum.BytesType.html) object.
     pub fn readinto(&mut self, mut output: BytesType) -> PyResult<usize> {
+        output.check_writable()?;
         let r = copy(self, &mut output)?;
         Ok(r as usize)
     }
@@ -531,12 +837,470 @@ impl RustyBuffer {
     }
 }
 
+/// Iterator over the lines of decompressed data, returned from `decompress_lines` of each
+/// de/compression module. Lines are decompressed as they're iterated over, so the entire
+/// decompressed data is never held in memory.
+///
+/// Each line is yielded as `bytes` without its `\n` or `\r\n` terminator; the last
+/// line is yielded even if it isn't terminated.
+///
+/// ### Python Example
+/// ```python
+/// >>> compressed = cramjam.gzip.compress(b"first\r\nsecond\nthird")
+/// >>> list(cramjam.gzip.decompress_lines(compressed))
+/// [b'first', b'second', b'third']
+/// ```
+#[pyclass(name = "Lines")]
+pub struct RustyLines {
+    inner: BufReader<Box<dyn Read + Send>>,
+}
+
+impl RustyLines {
+    pub(crate) fn new<R: Read + Send + 'static>(decoder: R) -> Self {
+        Self {
+            inner: BufReader::new(Box::new(decoder)),
+        }
+    }
+}
+
+#[pyproto]
+impl PyIterProtocol for RustyLines {
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
+        let mut line = vec![];
+        let n_bytes = slf
+            .inner
+            .read_until(b'\n', &mut line)
+            .map_err(|err| DecompressionError::new_err(err.to_string()))?;
+        if n_bytes == 0 {
+            return Ok(None);
+        }
+        if line.ends_with(b"\n") {
+            line.pop();
+            if line.ends_with(b"\r") {
+                line.pop();
+            }
+        }
+        Ok(Some(PyBytes::new(slf.py(), &line).into()))
+    }
+}
+
+/// Open a compressed file, returning a [`cramjam.CompressedFile`](struct.RustyCompressedFile.html);
+/// similar to `gzip.open` for any of the supported algorithms.
+///
+/// `mode` is one of `"rb"` (or `"r"`) to decompress while reading, or `"wb"` (or `"w"`) to
+/// compress while writing; the file is truncated when opened for writing. `algorithm` is the
+/// name of the de/compression submodule, ie. `"zstd"`. `level` is the compression level,
+/// defaulting to that of the algorithm's `compress` when not given; it's ignored when reading.
+/// `ValueError` is raised if it's given when writing snappy, which has no levels.
+///
+/// ### Python Example
+/// ```python
+/// >>> with cramjam.open("/tmp/file.txt.zst", "wb", "zstd") as f:
+/// ...     f.write(b"bytes")
+/// ...
+/// 5
+/// >>> with cramjam.open("/tmp/file.txt.zst", "rb", "zstd") as f:
+/// ...     f.read()
+/// ...
+/// b'bytes'
+/// ```
+#[pyfunction]
+pub fn open(path: &str, mode: &str, algorithm: &str, level: Option<i32>) -> PyResult<RustyCompressedFile> {
+    let mut effective_level = None;
+    let (mode, stream) = match mode {
+        "r" | "rb" => {
+            let file = BufReader::new(File::open(path)?);
+            let decoder: Box<dyn Read + Send> = match algorithm {
+                "snappy" => Box::new(crate::snappy::internal::decoder(file)?),
+                "brotli" => Box::new(crate::brotli::internal::decoder(file)?),
+                "lz4" => Box::new(to_decompression_err(crate::lz4::internal::decoder(file))?),
+                "gzip" => Box::new(crate::gzip::internal::decoder(file)?),
+                "deflate" => Box::new(crate::deflate::internal::decoder(file)?),
+                "zstd" => Box::new(to_decompression_err(crate::zstd::internal::decoder(file))?),
+                _ => return Err(unknown_algorithm(algorithm)),
+            };
+            ("rb", CompressedStream::Read(BufReader::new(decoder)))
+        }
+        "w" | "wb" => {
+            let level = level.or_else(|| default_level(algorithm));
+            // before the file is created, so an existing one isn't truncated by a failing open
+            check_level(algorithm, level)?;
+            let file = BufWriter::new(File::create(path)?);
+            effective_level = level;
+            ("wb", CompressedStream::Write(encoder(algorithm, file, level)?))
+        }
+        _ => {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Invalid mode '{}', should be one of 'rb' or 'wb'",
+                mode
+            )))
+        }
+    };
+    Ok(RustyCompressedFile {
+        path: PathBuf::from(path),
+        algorithm: algorithm.to_string(),
+        mode,
+        level: effective_level,
+        stream: Some(stream),
+    })
+}
+
+/// Encoder of `algorithm` writing compressed data to `output`, at `level` or the algorithm's default.
+fn encoder<W: Write + Send + 'static>(algorithm: &str, output: W, level: Option<i32>) -> PyResult<Box<dyn FinishWrite>> {
+    let encoder: Box<dyn FinishWrite> = match algorithm {
+        "snappy" => Box::new(crate::snappy::internal::encoder(output)?),
+        "brotli" => Box::new(crate::brotli::internal::encoder(output, unsigned_level(level)?)?),
+        "lz4" => Box::new(crate::lz4::internal::encoder(output, unsigned_level(level)?)?),
+        "gzip" => Box::new(crate::gzip::internal::encoder(output, unsigned_level(level)?)?),
+        "deflate" => Box::new(crate::deflate::internal::encoder(output, unsigned_level(level)?)?),
+        "zstd" => Box::new(crate::zstd::internal::encoder(output, level)?),
+        _ => return Err(unknown_algorithm(algorithm)),
+    };
+    Ok(encoder)
+}
+
+/// Check `algorithm` is one `open` supports, and `level` is valid for it, raising `CompressionError` if it isn't.
+fn check_level(algorithm: &str, level: Option<i32>) -> PyResult<()> {
+    let checked = match algorithm {
+        "snappy" if level.is_some() => {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "snappy has no compression levels, level can't be given for it",
+            ))
+        }
+        "snappy" => Ok(()),
+        "brotli" => crate::brotli::internal::checked_level(unsigned_level(level)?).map(drop),
+        "lz4" => crate::lz4::internal::checked_level(unsigned_level(level)?).map(drop),
+        "gzip" => crate::gzip::internal::checked_level(unsigned_level(level)?).map(drop),
+        "deflate" => crate::deflate::internal::checked_level(unsigned_level(level)?).map(drop),
+        "zstd" => crate::zstd::internal::checked_level(level).map(drop),
+        _ => return Err(unknown_algorithm(algorithm)),
+    };
+    checked.map_err(|err| CompressionError::new_err(err.to_string()))
+}
+
+/// Compression level used by `algorithm` when none is given; `None` for those without levels.
+fn default_level(algorithm: &str) -> Option<i32> {
+    match algorithm {
+        "brotli" => Some(crate::brotli::internal::DEFAULT_LEVEL as i32),
+        "lz4" => Some(crate::lz4::internal::DEFAULT_LEVEL as i32),
+        "gzip" => Some(crate::gzip::internal::DEFAULT_LEVEL as i32),
+        "deflate" => Some(crate::deflate::internal::DEFAULT_LEVEL as i32),
+        "zstd" => Some(crate::zstd::internal::DEFAULT_LEVEL),
+        _ => None,
+    }
+}
+
+fn to_decompression_err<T>(result: std::io::Result<T>) -> PyResult<T> {
+    result.map_err(|err| DecompressionError::new_err(err.to_string()))
+}
+
+fn unsigned_level(level: Option<i32>) -> PyResult<Option<u32>> {
+    level
+        .map(|level| u32::try_from(level).map_err(|_| CompressionError::new_err(format!("Invalid level {}", level))))
+        .transpose()
+}
+
+fn unknown_algorithm(algorithm: &str) -> PyErr {
+    pyo3::exceptions::PyValueError::new_err(format!(
+        "Unknown algorithm '{}', should be one of 'snappy', 'brotli', 'lz4', 'gzip', 'deflate' or 'zstd'",
+        algorithm
+    ))
+}
+
+enum CompressedStream {
+    Read(BufReader<Box<dyn Read + Send>>),
+    Write(Box<dyn FinishWrite>),
+}
+
+/// A file-like object returned from [`cramjam.open`](fn.open.html), which decompresses
+/// the file's contents when reading, or compresses when writing.
+///
+/// ### Python Example
+/// ```python
+/// >>> f = cramjam.open("/tmp/file.txt.gz", "wb", "gzip", level=9)
+/// >>> f.write(b"bytes")
+/// 5
+/// >>> f.close()  # writes the remaining compressed data, ie. gzip's footer
+/// ```
+///
+/// A file opened for writing which is garbage collected without being closed is closed then, but
+/// any error writing its remaining compressed data can't be raised; close it, or use it in a `with`
+/// block, to see them.
+#[pyclass(name = "CompressedFile")]
+pub struct RustyCompressedFile {
+    path: PathBuf,
+    algorithm: String,
+    mode: &'static str,
+    level: Option<i32>,
+    stream: Option<CompressedStream>,
+}
+
+impl Drop for RustyCompressedFile {
+    fn drop(&mut self) {
+        if let Some(CompressedStream::Write(encoder)) = self.stream.take() {
+            // errors can't be raised from here; `close` is the way to see them
+            let _ = encoder.finish();
+        }
+    }
+}
+
+impl RustyCompressedFile {
+    fn stream(&mut self) -> PyResult<&mut CompressedStream> {
+        self.stream
+            .as_mut()
+            .ok_or_else(|| pyo3::exceptions::PyValueError::new_err("I/O operation on closed file"))
+    }
+}
+
+#[pymethods]
+impl RustyCompressedFile {
+    /// Compress and write some bytes to the file, where input data can be anything in [`BytesType`](../enum.BytesType.html);
+    /// returns the number of uncompressed bytes written.
+    pub fn write(&mut self, mut input: BytesType) -> PyResult<usize> {
+        match self.stream()? {
+            CompressedStream::Write(encoder) => {
+                let r = write(&mut input, encoder).map_err(|err| CompressionError::new_err(err.to_string()))?;
+                Ok(r as usize)
+            }
+            CompressedStream::Read(_) => Err(pyo3::exceptions::PyValueError::new_err("File not open for writing")),
+        }
+    }
+    /// Read and decompress from the file, returns `bytes`; optionally specify number of
+    /// decompressed bytes to read.
+    pub fn read<'a>(&mut self, py: Python<'a>, n_bytes: Option<usize>) -> PyResult<&'a PyBytes> {
+        match self.stream()? {
+            CompressedStream::Read(decoder) => {
+                let mut buf = vec![];
+                let result = match n_bytes {
+                    Some(n) => decoder.take(n as u64).read_to_end(&mut buf),
+                    None => decoder.read_to_end(&mut buf),
+                };
+                result.map_err(|err| DecompressionError::new_err(err.to_string()))?;
+                Ok(PyBytes::new(py, &buf))
+            }
+            CompressedStream::Write(_) => Err(pyo3::exceptions::PyValueError::new_err("File not open for reading")),
+        }
+    }
+    /// Flush the compressed data written so far to the file; this does not end the
+    /// compressed stream, see `close`.
+    pub fn flush(&mut self) -> PyResult<()> {
+        if let CompressedStream::Write(encoder) = self.stream()? {
+            encoder.flush()?;
+        }
+        Ok(())
+    }
+    /// Close the file; when writing, the remaining compressed data is written
+    /// to the file first. Closing an already closed file has no effect.
+    pub fn close(&mut self) -> PyResult<()> {
+        if let Some(CompressedStream::Write(encoder)) = self.stream.take() {
+            encoder
+                .finish()
+                .map_err(|err| CompressionError::new_err(err.to_string()))?;
+        }
+        Ok(())
+    }
+    /// Whether the file is closed
+    #[getter]
+    pub fn closed(&self) -> bool {
+        self.stream.is_none()
+    }
+    /// Whether the file was opened for reading
+    pub fn readable(&self) -> bool {
+        matches!(self.stream, Some(CompressedStream::Read(_)))
+    }
+    /// Whether the file was opened for writing
+    pub fn writable(&self) -> bool {
+        matches!(self.stream, Some(CompressedStream::Write(_)))
+    }
+    /// Whether the file is seekable; always False.
+    pub fn seekable(&self) -> bool {
+        false
+    }
+    /// The effective settings of the file's compressed stream, as a `dict` of its `algorithm`,
+    /// `mode` (`'rb'` or `'wb'`) and compression `level`, which is the algorithm's default when
+    /// none was given to `open`; `level` is `None` when reading, or for snappy which has no levels.
+    pub fn params<'a>(&self, py: Python<'a>) -> PyResult<&'a PyDict> {
+        let params = PyDict::new(py);
+        params.set_item("algorithm", &self.algorithm)?;
+        params.set_item("mode", self.mode)?;
+        params.set_item("level", self.level)?;
+        Ok(params)
+    }
+    fn __enter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+    fn __exit__(&mut self, _exc_type: &PyAny, _exc_value: &PyAny, _traceback: &PyAny) -> PyResult<bool> {
+        self.close()?;
+        Ok(false)
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for RustyCompressedFile {
+    fn __repr__(&self) -> String {
+        format!(
+            "cramjam.CompressedFile(path={}, algorithm={}, closed={})",
+            self.path.to_string_lossy(),
+            self.algorithm,
+            if self.closed() { "True" } else { "False" }
+        )
+    }
+}
+
+/// A compressor writing its compressed output into `ring`, a fixed size buffer provided by the caller,
+/// such as a `bytearray` or `numpy.array`, and calling `drain` with the number of bytes in it each time
+/// it fills; `drain` must consume `ring[:n]` before returning, as `ring` is then written over from the
+/// start. No output buffer is allocated beyond `ring`, so an unbounded stream can be compressed in
+/// constant memory; if it's very small, `drain` is called for every few bytes. `drain` is also called
+/// with fewer bytes than fit in `ring` on `flush` and `finish`, to consume what's there so far.
+///
+/// `algorithm` is the name of the de/compression submodule, ie. `"zstd"`, and `level` its compression
+/// level, defaulting to that of its `compress`; the output is that of the submodule's streaming
+/// compressors, as written by [`cramjam.open`](fn.open.html). Exceptions raised by `drain` propagate
+/// from the `write`, `flush` or `finish` which called it.
+///
+/// ### Python Example
+/// ```python
+/// >>> ring, drained = bytearray(4096), []
+/// >>> compressor = cramjam.RingCompressor("zstd", ring, lambda n: drained.append(bytes(ring[:n])))
+/// >>> compressor.write(b"some bytes here")
+/// 15
+/// >>> compressor.finish()
+/// >>> bytes(cramjam.zstd.decompress(b"".join(drained)))
+/// b'some bytes here'
+/// ```
+#[pyclass(name = "RingCompressor")]
+pub struct RustyRingCompressor {
+    algorithm: String,
+    encoder: Option<Box<dyn FinishWrite>>,
+}
+
+#[pymethods]
+impl RustyRingCompressor {
+    /// Instantiate the compressor, writing into `ring`; see above for the arguments.
+    #[new]
+    pub fn __init__(
+        py: Python,
+        algorithm: &str,
+        mut ring: BytesType,
+        drain: PyObject,
+        level: Option<i32>,
+    ) -> PyResult<Self> {
+        ring.check_writable()?;
+        if ring.as_bytes().is_empty() {
+            return Err(pyo3::exceptions::PyValueError::new_err("ring must not be empty"));
+        }
+        let output = RingWriter {
+            ring: ring.into_py(py),
+            drain,
+            filled: 0,
+        };
+        Ok(Self {
+            algorithm: algorithm.to_string(),
+            encoder: Some(encoder(algorithm, output, level)?),
+        })
+    }
+    /// Compress `input`, anything in [`BytesType`](../enum.BytesType.html), draining the ring as it
+    /// fills; returns the number of uncompressed bytes written.
+    pub fn write(&mut self, mut input: BytesType) -> PyResult<usize> {
+        let r = write(&mut input, self.encoder()?).map_err(ring_err)?;
+        Ok(r as usize)
+    }
+    /// Write the compressed data for the input so far into the ring, and drain it; this does not
+    /// end the compressed stream, see `finish`.
+    pub fn flush(&mut self) -> PyResult<()> {
+        self.encoder()?.flush().map_err(ring_err)
+    }
+    /// End the compressed stream, writing the remaining compressed data, and drain the ring. The
+    /// compressor can't be written to afterwards; finishing it again has no effect.
+    pub fn finish(&mut self) -> PyResult<()> {
+        if let Some(encoder) = self.encoder.take() {
+            encoder.finish().map_err(ring_err)?;
+        }
+        Ok(())
+    }
+}
+
+impl RustyRingCompressor {
+    fn encoder(&mut self) -> PyResult<&mut Box<dyn FinishWrite>> {
+        self.encoder
+            .as_mut()
+            .ok_or_else(|| pyo3::exceptions::PyValueError::new_err("Compressor is already finished"))
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for RustyRingCompressor {
+    fn __repr__(&self) -> String {
+        format!(
+            "cramjam.RingCompressor(algorithm={}, finished={})",
+            self.algorithm,
+            if self.encoder.is_none() { "True" } else { "False" }
+        )
+    }
+}
+
+/// Output of a `RingCompressor`, copying into the ring and calling `drain` whenever it's full
+struct RingWriter {
+    ring: PyObject,
+    drain: PyObject,
+    filled: usize,
+}
+
+impl RingWriter {
+    fn drain(&mut self, py: Python) -> std::io::Result<()> {
+        if self.filled > 0 {
+            let n_bytes = std::mem::replace(&mut self.filled, 0);
+            self.drain.call1(py, (n_bytes,)).map_err(std::io::Error::other)?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for RingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        Python::with_gil(|py| {
+            // extracted for each write, as `drain` may have replaced the ring's contents meanwhile
+            let ring = self.ring.clone_ref(py);
+            let mut ring: BytesType = ring.extract(py).map_err(std::io::Error::other)?;
+            ring.check_writable().map_err(std::io::Error::other)?;
+            let ring = ring.as_bytes_mut();
+            if self.filled >= ring.len() {
+                self.drain(py)?;
+            }
+            let n_bytes = buf.len().min(ring.len() - self.filled);
+            ring[self.filled..self.filled + n_bytes].copy_from_slice(&buf[..n_bytes]);
+            self.filled += n_bytes;
+            if self.filled == ring.len() {
+                self.drain(py)?;
+            }
+            Ok(n_bytes)
+        })
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Python::with_gil(|py| self.drain(py))
+    }
+}
+
+/// The exception raised by a `RingCompressor`'s `drain`, if that's what failed, otherwise a `CompressionError`
+fn ring_err(err: std::io::Error) -> PyErr {
+    match err.into_inner().map(|inner| inner.downcast::<PyErr>()) {
+        Some(Ok(err)) => *err,
+        Some(Err(inner)) => CompressionError::new_err(inner.to_string()),
+        None => CompressionError::new_err("Failed to write to the ring"),
+    }
+}
+
 fn write<W: Write>(input: &mut BytesType, output: &mut W) -> std::io::Result<u64> {
     let result = match input {
         BytesType::RustyFile(data) => copy(&mut data.borrow_mut().inner, output)?,
         BytesType::RustyBuffer(data) => copy(&mut data.borrow_mut().inner, output)?,
         BytesType::ByteArray(data) => copy(data, output)?,
         BytesType::NumpyArray(array) => copy(array, output)?,
+        BytesType::PyBuffer(buffer) => copy(buffer, output)?,
         BytesType::Bytes(data) => {
             let buffer = data.as_bytes();
             copy(&mut Cursor::new(buffer), output)?
@@ -545,6 +1309,394 @@ fn write<W: Write>(input: &mut BytesType, output: &mut W) -> std::io::Result<u64
     Ok(result)
 }
 
+/// Decompress with `decoder`, replacing the `[start, end)` range of the decompressed data with
+/// `replacement`, and compress the result with `compress`; the decompressed data is streamed from
+/// the decoder to the encoder rather than held in memory.
+pub(crate) fn recompress_range<R, F>(
+    decoder: std::io::Result<R>,
+    start: u64,
+    end: u64,
+    mut replacement: BytesType,
+    compress: F,
+) -> PyResult<RustyBuffer>
+where
+    R: Read,
+    F: FnOnce(&mut Splice<R>, &mut Cursor<&mut Vec<u8>>) -> std::io::Result<usize>,
+{
+    if start > end {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "start ({}) must not be greater than end ({})",
+            start, end
+        )));
+    }
+    let decoder = decoder.map_err(|err| DecompressionError::new_err(err.to_string()))?;
+    let mut replacement_bytes = vec![];
+    replacement.read_to_end(&mut replacement_bytes)?;
+
+    let mut spliced = Splice {
+        inner: decoder,
+        position: 0,
+        start,
+        end,
+        replacement: Cursor::new(replacement_bytes),
+        decode_error: false,
+    };
+    let mut output = vec![];
+    if let Err(err) = compress(&mut spliced, &mut Cursor::new(&mut output)) {
+        return match spliced.decode_error {
+            true => Err(DecompressionError::new_err(err.to_string())),
+            false => Err(CompressionError::new_err(err.to_string())),
+        };
+    }
+    Ok(RustyBuffer::from(output))
+}
+
+/// Reads the decompressed data of `inner`, with the `[start, end)` range replaced by `replacement`.
+pub(crate) struct Splice<R> {
+    inner: R,
+    position: u64,
+    start: u64,
+    end: u64,
+    replacement: Cursor<Vec<u8>>,
+    decode_error: bool,
+}
+
+impl<R: Read> Splice<R> {
+    fn read_inner(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n_bytes = self.inner.read(buf).inspect_err(|_| self.decode_error = true)?;
+        self.position += n_bytes as u64;
+        Ok(n_bytes)
+    }
+    fn out_of_range(&mut self) -> std::io::Error {
+        self.decode_error = true;
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!(
+                "Range [{}, {}) is beyond the end of the decompressed data, of length {}",
+                self.start, self.end, self.position
+            ),
+        )
+    }
+}
+
+impl<R: Read> Read for Splice<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.position < self.start {
+            let len = buf.len().min((self.start - self.position) as usize);
+            return match self.read_inner(&mut buf[..len])? {
+                0 => Err(self.out_of_range()),
+                n_bytes => Ok(n_bytes),
+            };
+        }
+        if self.position < self.end {
+            let to_skip = self.end - self.position;
+            let skipped = copy(&mut (&mut self.inner).take(to_skip), &mut std::io::sink())
+                .inspect_err(|_| self.decode_error = true)?;
+            self.position += skipped;
+            if skipped < to_skip {
+                return Err(self.out_of_range());
+            }
+        }
+        match self.replacement.read(buf)? {
+            0 => self.read_inner(buf),
+            n_bytes => Ok(n_bytes),
+        }
+    }
+}
+
+/// Compress with `compress` into the caller's `scratch` array, which is grown as needed and
+/// kept for reuse by the caller, returning a copy of the compressed data as `bytes`.
+pub(crate) fn compress_reuse<'a, F>(py: Python<'a>, scratch: &PyArray1<u8>, compress: F) -> PyResult<&'a PyBytes>
+where
+    F: FnOnce(&mut ScratchWriter) -> std::io::Result<usize>,
+{
+    let mut writer = ScratchWriter {
+        array: scratch,
+        position: 0,
+        len: 0,
+    };
+    compress(&mut writer).map_err(|err| CompressionError::new_err(err.to_string()))?;
+    let compressed = unsafe { scratch.as_slice()? };
+    Ok(PyBytes::new(py, &compressed[..writer.len]))
+}
+
+/// Writes into a `numpy.array` from its start, growing it when full; see [`compress_reuse`]
+pub(crate) struct ScratchWriter<'a> {
+    array: &'a PyArray1<u8>,
+    position: usize,
+    len: usize,
+}
+
+impl<'a> Write for ScratchWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let end = self.position + buf.len();
+        if end > self.array.len() {
+            // numpy only allows resizing arrays which own their data and have no other references
+            let new_len = end.max(self.array.len() * 2);
+            self.array
+                .resize(new_len)
+                .map_err(|err| std::io::Error::other(format!("Unable to resize scratch array: {}", err)))?;
+        }
+        let slice = unsafe { self.array.as_slice_mut() }.map_err(|err| std::io::Error::other(err.to_string()))?;
+        slice[self.position..end].copy_from_slice(buf);
+        self.position = end;
+        self.len = self.len.max(end);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> Seek for ScratchWriter<'a> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => (self.len as u64).checked_add_signed(n),
+            SeekFrom::Current(n) => (self.position as u64).checked_add_signed(n),
+        };
+        let position = position.ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid seek to a negative position")
+        })?;
+        self.position = position as usize;
+        Ok(position)
+    }
+}
+
+/// Decompressed output a streaming decompressor holds until it's returned, of at most `max_buffered`
+/// bytes when given; writing more fails with [`backpressure_exceeded`], rather than holding however
+/// much a chunk decompresses to.
+#[derive(Default)]
+pub(crate) struct BufferedOutput {
+    pub(crate) buffer: Vec<u8>,
+    pub(crate) max_buffered: Option<usize>,
+}
+
+impl BufferedOutput {
+    pub(crate) fn new(max_buffered: Option<usize>) -> Self {
+        Self {
+            buffer: vec![],
+            max_buffered,
+        }
+    }
+}
+
+impl Write for BufferedOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        check_buffered(self.buffer.len() + buf.len(), self.max_buffered)?;
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Check `len` bytes of decompressed output are within `max_buffered`, see [`BufferedOutput`]
+pub(crate) fn check_buffered(len: usize, max_buffered: Option<usize>) -> std::io::Result<()> {
+    match max_buffered {
+        Some(max_buffered) if len > max_buffered => Err(backpressure_exceeded()),
+        _ => Ok(()),
+    }
+}
+
+pub(crate) fn backpressure_exceeded() -> std::io::Error {
+    std::io::Error::other("output backpressure exceeded")
+}
+
+/// De/compress with `op` from the raw file descriptor `in_fd` to `out_fd`, returning the number of
+/// bytes written; the descriptors remain owned by the caller, and are left open. The GIL is released
+/// meanwhile, as either end may be a pipe another Python thread is reading from or writing to.
+/// Raises `ValueError` for a negative descriptor, and errors of `op` as `E`.
+#[cfg(unix)]
+pub(crate) fn with_fds<E, F>(py: Python, in_fd: RawFd, out_fd: RawFd, op: F) -> PyResult<usize>
+where
+    E: pyo3::type_object::PyTypeObject,
+    F: FnOnce(&mut File, &mut FdWriter) -> std::io::Result<()> + Send,
+{
+    for fd in [in_fd, out_fd] {
+        if fd < 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Invalid file descriptor {}, should be at least 0",
+                fd
+            )));
+        }
+    }
+    let result: std::io::Result<usize> = py.allow_threads(|| {
+        let mut input = ManuallyDrop::new(unsafe { File::from_raw_fd(in_fd) });
+        let output = ManuallyDrop::new(unsafe { File::from_raw_fd(out_fd) });
+        let mut writer = FdWriter {
+            inner: BufWriter::new(&*output),
+            position: 0,
+        };
+        op(&mut input, &mut writer)?;
+        writer.flush()?;
+        Ok(writer.position as usize)
+    });
+    result.map_err(|err| PyErr::new::<E, _>(err.to_string()))
+}
+
+/// Buffered writer to a file descriptor counting the bytes written; see [`with_fds`]. It only
+/// seeks to report its position, with `SeekFrom::Current(0)`, as a pipe can't seek otherwise.
+#[cfg(unix)]
+pub(crate) struct FdWriter<'a> {
+    inner: BufWriter<&'a File>,
+    position: u64,
+}
+
+#[cfg(unix)]
+impl<'a> Write for FdWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n_bytes = self.inner.write(buf)?;
+        self.position += n_bytes as u64;
+        Ok(n_bytes)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(unix)]
+impl<'a> Seek for FdWriter<'a> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match pos {
+            SeekFrom::Current(0) => Ok(self.position),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "Unable to seek in a file descriptor's output",
+            )),
+        }
+    }
+}
+
+/// De/compress with `op` from the file at `input_path` to the one at `output_path`, which is created
+/// or truncated, returning the number of bytes `op` wrote; both are streamed through, rather than
+/// read or written whole. The GIL is released meanwhile. Raises `ValueError` should both be the
+/// same file, which would be truncated before it's read.
+pub(crate) fn with_paths<F>(py: Python, input_path: &str, output_path: &str, op: F) -> PyResult<usize>
+where
+    F: FnOnce(&mut File, &mut BufWriter<File>) -> PyResult<usize> + Send,
+{
+    let mut input = File::open(input_path)?;
+    if let (Ok(input_path), Ok(output_path)) = (std::fs::canonicalize(input_path), std::fs::canonicalize(output_path)) {
+        if input_path == output_path {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "input_path and output_path are the same file, {}",
+                input_path.display()
+            )));
+        }
+    }
+    let mut output = BufWriter::new(File::create(output_path)?);
+    py.allow_threads(|| {
+        let n_bytes = op(&mut input, &mut output)?;
+        output.flush()?;
+        Ok(n_bytes)
+    })
+}
+
+/// Compress with `op` to `fileobj`, any Python object with a `write` method, ie. `io.BytesIO` or a
+/// socket's `makefile("wb")`, returning the number of bytes written; each write `op` makes is passed
+/// straight to `fileobj.write`, and its `flush`, if any, is called once `op` is done. An exception
+/// raised by either is raised as is, otherwise an error of `op` is raised as `CompressionError`.
+pub(crate) fn with_fileobj<F>(fileobj: &PyAny, op: F) -> PyResult<usize>
+where
+    F: FnOnce(&mut PyFileWriter) -> std::io::Result<()>,
+{
+    let mut writer = PyFileWriter {
+        inner: fileobj,
+        position: 0,
+        err: None,
+    };
+    match op(&mut writer).and_then(|_| writer.flush()) {
+        Ok(()) => Ok(writer.position as usize),
+        Err(err) => Err(writer
+            .err
+            .take()
+            .unwrap_or_else(|| CompressionError::new_err(err.to_string()))),
+    }
+}
+
+/// Writer to a Python file-like object counting the bytes written; see [`with_fileobj`]. The
+/// exception of a failed call to the object is kept, to be raised in place of the `io::Error`.
+pub(crate) struct PyFileWriter<'a> {
+    inner: &'a PyAny,
+    position: u64,
+    err: Option<PyErr>,
+}
+
+impl<'a> PyFileWriter<'a> {
+    fn failed(&mut self, err: PyErr) -> std::io::Error {
+        let io_err = std::io::Error::other(err.to_string());
+        self.err = Some(err);
+        io_err
+    }
+}
+
+impl<'a> Write for PyFileWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let py = self.inner.py();
+        let result = self.inner.call_method1("write", (PyBytes::new(py, buf),));
+        // raw files return the number of bytes written, which may be fewer, and others `None`
+        let n_bytes = result.and_then(|n_bytes| match n_bytes.is_none() {
+            true => Ok(buf.len()),
+            false => n_bytes.extract::<usize>(),
+        });
+        let n_bytes = n_bytes.map_err(|err| self.failed(err))?;
+        self.position += n_bytes as u64;
+        Ok(n_bytes)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.inner.hasattr("flush").unwrap_or(false) {
+            self.inner.call_method0("flush").map_err(|err| self.failed(err))?;
+        }
+        Ok(())
+    }
+}
+
+/// Reader from a Python file-like object, any object with a `read(n)` method returning `bytes` or
+/// another object implementing the buffer protocol, such as `bytearray` or `memoryview`; ie.
+/// `io.BytesIO`, a file opened with `"rb"`, or a socket's `makefile("rb")`. Each read calls it once,
+/// so the object is read as it's needed rather than whole. An exception raised by `read` is
+/// returned as the error of the read. Raises `TypeError` if there's no `read` method.
+pub struct PyFileReader<'a> {
+    pub(crate) inner: &'a PyAny,
+}
+
+impl<'a> FromPyObject<'a> for PyFileReader<'a> {
+    fn extract(ob: &'a PyAny) -> PyResult<Self> {
+        if !ob.hasattr("read")? {
+            return Err(pyo3::exceptions::PyTypeError::new_err(format!(
+                "Expected a file-like object with a read method, got '{}'",
+                ob.get_type().name()?
+            )));
+        }
+        Ok(Self { inner: ob })
+    }
+}
+
+impl<'a> Read for PyFileReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let chunk = self
+            .inner
+            .call_method1("read", (buf.len(),))
+            .and_then(|chunk| chunk.extract::<RustyPyBuffer>())
+            .map_err(|err| std::io::Error::other(format!("Unable to read from the file-like object: {}", err)))?;
+        let chunk = chunk.as_bytes();
+        if chunk.len() > buf.len() {
+            return Err(std::io::Error::other(format!(
+                "read({}) of the file-like object returned {} bytes",
+                buf.len(),
+                chunk.len()
+            )));
+        }
+        buf[..chunk.len()].copy_from_slice(chunk);
+        Ok(chunk.len())
+    }
+}
+
 fn read<'a, R: Read>(reader: &mut R, py: Python<'a>, n_bytes: Option<usize>) -> PyResult<&'a PyBytes> {
     match n_bytes {
         Some(n) => PyBytes::new_with(py, n, |buf| {
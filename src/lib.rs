@@ -9,9 +9,14 @@
 //!  - `numpy.array` (dtype=np.uint8)
 //!  - `bytes`
 //!  - `bytearray`
+//!  - any other object implementing the buffer protocol, ie. `memoryview` or ctypes arrays
 //!  - [`cramjam.File`](io/struct.RustyFile.html)
 //!  - [`cramjam.Buffer`](./io/struct.RustyBuffer.html)
 //!
+//! Each algorithm is also available by name through `cramjam.compress(data, "zstd")` and
+//! `cramjam.decompress`, along with any codecs registered from Python; see the [registry module](registry/index.html).
+//! Without an algorithm, `cramjam.decompress` detects it from the data's magic number.
+//!
 //! ### Simple Python Example:
 //!
 //! ```python
@@ -50,21 +55,40 @@
 //! >>> decompressed
 //! b'some bytes here'
 //! ```
+//!
+//! ### Thread safety
+//!
+//! The de/compression functions of every submodule hold no state between calls and may be called
+//! from any thread. The `cramjam.File` and `cramjam.Buffer` objects are `Send`; see the
+//! [io module](io/index.html) for details.
 
+pub mod alloc;
+pub mod arrow;
 pub mod brotli;
+pub mod bzip2;
 pub mod deflate;
+pub mod detect;
 pub mod exceptions;
 pub mod gzip;
+pub mod hash;
 pub mod io;
 pub mod lz4;
+pub mod lzma;
+pub mod records;
+pub mod registry;
 pub mod snappy;
+pub mod zlib;
 pub mod zstd;
 
 use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyString};
+use pyo3::AsPyPointer;
 
-use crate::io::{AsBytes, RustyBuffer, RustyFile, RustyNumpyArray, RustyPyByteArray, RustyPyBytes};
+use crate::io::{AsBytes, RustyBuffer, RustyFile, RustyNumpyArray, RustyPyBuffer, RustyPyByteArray, RustyPyBytes};
 use exceptions::{CompressionError, DecompressionError};
+use std::convert::TryFrom;
 use std::io::{Read, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 #[cfg(feature = "mimallocator")]
 #[global_allocator]
@@ -73,23 +97,104 @@ static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 /// Any possible input/output to de/compression algorithms.
 /// Typically, as a Python user, you never have to worry about this object. It's exposed here in
 /// the documentation to see what types are acceptable for de/compression functions.
-#[derive(FromPyObject)]
 pub enum BytesType<'a> {
     /// `bytes`
-    #[pyo3(transparent, annotation = "bytes")]
     Bytes(RustyPyBytes<'a>),
     /// `bytearray`
-    #[pyo3(transparent, annotation = "bytearray")]
     ByteArray(RustyPyByteArray<'a>),
     /// [`cramjam.File`](io/struct.RustyFile.html)
-    #[pyo3(transparent, annotation = "File")]
     RustyFile(&'a PyCell<RustyFile>),
     /// [`cramjam.Buffer`](io/struct.RustyBuffer.html)
-    #[pyo3(transparent, annotation = "Buffer")]
     RustyBuffer(&'a PyCell<RustyBuffer>),
+    /// Any other object implementing the buffer protocol, ie. `memoryview`, ctypes arrays, or the
+    /// `pickle.PickleBuffer`s of out-of-band pickling, which must be C contiguous and have at most one
+    /// dimension; `TypeError` is raised otherwise. Tried before `numpy.array`, so these don't require
+    /// numpy to be installed. `numpy.array`s of any dtype and number of dimensions are read as the
+    /// raw bytes of their elements here too, as long as they're C contiguous.
+    PyBuffer(RustyPyBuffer<'a>),
     /// `numpy.array` with `dtype=np.uint8`
-    #[pyo3(transparent, annotation = "numpy")]
-    NumpyArray(RustyNumpyArray<'a>)
+    NumpyArray(RustyNumpyArray<'a>),
+}
+
+impl<'a> FromPyObject<'a> for BytesType<'a> {
+    fn extract(ob: &'a PyAny) -> PyResult<Self> {
+        if let Ok(bytes) = ob.extract() {
+            return Ok(Self::Bytes(bytes));
+        }
+        if let Ok(byte_array) = ob.extract() {
+            return Ok(Self::ByteArray(byte_array));
+        }
+        if let Ok(file) = ob.extract() {
+            return Ok(Self::RustyFile(file));
+        }
+        if let Ok(buffer) = ob.extract() {
+            return Ok(Self::RustyBuffer(buffer));
+        }
+        // anything else exporting a buffer is read from it, or rejected here with why it can't be
+        if unsafe { pyo3::ffi::PyObject_CheckBuffer(ob.as_ptr()) } == 1 {
+            return Ok(Self::PyBuffer(ob.extract()?));
+        }
+        if let Ok(array) = ob.extract() {
+            return Ok(Self::NumpyArray(array));
+        }
+        Err(pyo3::exceptions::PyTypeError::new_err(format!(
+            "'{}' object cannot be converted to 'Union[bytes, bytearray, File, Buffer, buffer, numpy]'",
+            ob.get_type().name()?
+        )))
+    }
+}
+
+/// Compression level; either an integer level of the algorithm, or `"auto"` to choose the level
+/// by the size of the input, trading ratio for speed as the input grows. See each algorithm's
+/// `compress` for its levels.
+pub enum Level<T> {
+    /// An explicit level
+    Value(T),
+    /// `"auto"`
+    Auto,
+}
+
+/// Inputs smaller than this use the highest levels with `level="auto"`
+pub(crate) const AUTO_LEVEL_SMALL_INPUT: u64 = 1 << 20;
+/// Inputs at least this large use the fastest levels with `level="auto"`
+pub(crate) const AUTO_LEVEL_LARGE_INPUT: u64 = 64 << 20;
+
+impl<T> Level<T> {
+    /// Resolve to the level to compress `data` with, using `auto` to pick the level from
+    /// the number of bytes remaining in `data` with `"auto"`.
+    pub(crate) fn resolve(self, data: &mut BytesType, auto: fn(u64) -> T) -> PyResult<T> {
+        match self {
+            Self::Value(level) => Ok(level),
+            Self::Auto => Ok(auto(data.remaining_len()?)),
+        }
+    }
+
+    /// `level`, or `alias` if given instead, named `alias_name` after the keyword of Python's
+    /// standard library, ie. `compresslevel` of `gzip.compress`; `TypeError` if both are given.
+    pub(crate) fn or_alias(level: Option<Self>, alias: Option<Self>, alias_name: &str) -> PyResult<Option<Self>> {
+        match (level, alias) {
+            (Some(_), Some(_)) => Err(pyo3::exceptions::PyTypeError::new_err(format!(
+                "Only one of level and its alias {} may be given",
+                alias_name
+            ))),
+            (level, alias) => Ok(level.or(alias)),
+        }
+    }
+}
+
+impl<'a, T: FromPyObject<'a>> FromPyObject<'a> for Level<T> {
+    fn extract(ob: &'a PyAny) -> PyResult<Self> {
+        if let Ok(level) = ob.extract::<&str>() {
+            return match level {
+                "auto" => Ok(Self::Auto),
+                _ => Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "Invalid level '{}', should be an integer or 'auto'",
+                    level
+                ))),
+            };
+        }
+        Ok(Self::Value(ob.extract()?))
+    }
 }
 
 impl<'a> AsBytes for BytesType<'a> {
@@ -98,6 +203,7 @@ impl<'a> AsBytes for BytesType<'a> {
             BytesType::Bytes(b) => b.as_bytes(),
             BytesType::ByteArray(b) => b.as_bytes(),
             BytesType::NumpyArray(b) => b.as_bytes(),
+            BytesType::PyBuffer(b) => b.as_bytes(),
             BytesType::RustyBuffer(b) => {
                 let py_ref = b.borrow();
                 let bytes = py_ref.as_bytes();
@@ -115,6 +221,7 @@ impl<'a> AsBytes for BytesType<'a> {
             BytesType::Bytes(b) => b.as_bytes_mut(),
             BytesType::ByteArray(b) => b.as_bytes_mut(),
             BytesType::NumpyArray(b) => b.as_bytes_mut(),
+            BytesType::PyBuffer(b) => b.as_bytes_mut(),
             BytesType::RustyBuffer(b) => {
                 let mut py_ref = b.borrow_mut();
                 let bytes = py_ref.as_bytes_mut();
@@ -136,6 +243,7 @@ impl<'a> Write for BytesType<'a> {
             BytesType::RustyBuffer(out) => out.borrow_mut().inner.write(buf)?,
             BytesType::ByteArray(out) => out.write(buf)?,
             BytesType::NumpyArray(out) => out.write(buf)?,
+            BytesType::PyBuffer(out) => out.write(buf)?,
             BytesType::Bytes(out) => out.write(buf)?,
         };
         Ok(result)
@@ -144,7 +252,7 @@ impl<'a> Write for BytesType<'a> {
         match self {
             BytesType::RustyFile(f) => f.borrow_mut().flush(),
             BytesType::RustyBuffer(b) => b.borrow_mut().flush(),
-            BytesType::ByteArray(_) | BytesType::Bytes(_) | BytesType::NumpyArray(_) => Ok(()),
+            BytesType::ByteArray(_) | BytesType::Bytes(_) | BytesType::NumpyArray(_) | BytesType::PyBuffer(_) => Ok(()),
         }
     }
 }
@@ -155,6 +263,7 @@ impl<'a> Read for BytesType<'a> {
             BytesType::RustyBuffer(data) => data.borrow_mut().inner.read(buf),
             BytesType::ByteArray(data) => data.read(buf),
             BytesType::NumpyArray(array) => array.read(buf),
+            BytesType::PyBuffer(buffer) => buffer.read(buf),
             BytesType::Bytes(data) => data.read(buf),
         }
     }
@@ -166,6 +275,7 @@ impl<'a> Seek for BytesType<'a> {
             BytesType::RustyBuffer(b) => b.borrow_mut().inner.seek(style),
             BytesType::ByteArray(a) => a.seek(style),
             BytesType::NumpyArray(a) => a.seek(style),
+            BytesType::PyBuffer(b) => b.seek(style),
             BytesType::Bytes(b) => b.seek(style),
         }
     }
@@ -175,6 +285,21 @@ impl<'a> BytesType<'a> {
     fn len(&self) -> usize {
         self.as_bytes().len()
     }
+    /// Number of bytes left to be read from the current position
+    fn remaining_len(&mut self) -> std::io::Result<u64> {
+        let position = self.stream_position()?;
+        let end = self.seek(SeekFrom::End(0))?;
+        self.seek(SeekFrom::Start(position))?;
+        Ok(end - position)
+    }
+    /// Request a buffer as writable, raising `BufferError` if it's read-only, which is fine as an
+    /// input but can't be written to as the output of the `*_into` functions
+    pub(crate) fn check_writable(&mut self) -> PyResult<()> {
+        match self {
+            Self::PyBuffer(buffer) => buffer.make_writable(),
+            _ => Ok(()),
+        }
+    }
 }
 
 impl<'a> IntoPy<PyObject> for BytesType<'a> {
@@ -185,6 +310,7 @@ impl<'a> IntoPy<PyObject> for BytesType<'a> {
             Self::RustyFile(file) => file.to_object(py),
             Self::RustyBuffer(buffer) => buffer.into_py(py),
             Self::NumpyArray(array) => array.to_object(py),
+            Self::PyBuffer(buffer) => buffer.to_object(py),
         }
     }
 }
@@ -192,24 +318,676 @@ impl<'a> IntoPy<PyObject> for BytesType<'a> {
 /// Macro for generating the implementation of de/compression against a variant interface
 #[macro_export]
 macro_rules! generic {
-    ($op:ident($input:expr), output_len=$output_len:ident $(, level=$level:ident)?) => {
+    ($op:ident($input:expr), output_len=$output_len:ident $(, $argname:ident=$arg:expr)*) => {
         {
             use crate::io::RustyBuffer;
 
-            let mut output: Vec<u8> = match $output_len {
-                Some(len) => vec![0; len],
-                None => vec![]
-            };
-            if stringify!($op) == "compress" {
-                to_py_err!(CompressionError -> self::internal::$op($input, &mut Cursor::new(&mut output) $(, $level)? ))?;
+            let mut output: Vec<u8> = crate::alloc::output_vec($output_len);
+            if stringify!($op).starts_with("compress") {
+                to_py_err!(CompressionError -> self::internal::$op($input, &mut Cursor::new(&mut output) $(, $arg)* ))?;
             } else {
-                to_py_err!(DecompressionError -> self::internal::$op($input, &mut Cursor::new(&mut output) $(, $level)? ))?;
+                to_py_err!(DecompressionError -> self::internal::$op($input, &mut Cursor::new(&mut output) $(, $arg)* ))?;
             }
             Ok(RustyBuffer::from(output))
         }
     }
 }
 
+/// Version of the headers cramjam adds around compressed data, ie. with `pad_to` or `compress_chunked`,
+/// which is recorded in them and checked when decompressing. Exposed as `cramjam.FRAME_VERSION`.
+pub const FRAME_VERSION: u8 = 1;
+
+/// Byte order of the integers in cramjam's headers, in the form taken by Python's `int.from_bytes`.
+/// Exposed as `cramjam.FRAME_BYTE_ORDER`; see [`frame_u32`] and [`read_frame_u32`].
+pub const FRAME_BYTE_ORDER: &str = "little";
+
+/// `value` as written in cramjam's headers, in [`FRAME_BYTE_ORDER`]
+pub(crate) fn frame_u32(value: u32) -> [u8; 4] {
+    value.to_le_bytes()
+}
+
+/// `value` as written in cramjam's headers, in [`FRAME_BYTE_ORDER`]
+pub(crate) fn frame_u64(value: u64) -> [u8; 8] {
+    value.to_le_bytes()
+}
+
+/// Read a value written by [`frame_u32`]
+pub(crate) fn read_frame_u32(bytes: [u8; 4]) -> u32 {
+    u32::from_le_bytes(bytes)
+}
+
+/// Read a value written by [`frame_u64`]
+pub(crate) fn read_frame_u64(bytes: [u8; 8]) -> u64 {
+    u64::from_le_bytes(bytes)
+}
+
+/// Check the version recorded in a cramjam header is [`FRAME_VERSION`]
+pub(crate) fn check_frame_version(version: u8) -> std::io::Result<()> {
+    if version != FRAME_VERSION {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "Unsupported cramjam frame version {}, expected {}; was it written by a newer version of cramjam?",
+                version, FRAME_VERSION
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Prefix `compressed` with the length of its decompressed data, `decompressed_len`, for formats
+/// which don't record it themselves; used by `store_size=True`. The prefix is a u32 in [`FRAME_BYTE_ORDER`],
+/// matching the `store_size` header of python-lz4's block format, so unlike cramjam's other headers it
+/// has no [`FRAME_VERSION`].
+pub(crate) fn with_size_prefix(decompressed_len: usize, compressed: &[u8]) -> std::io::Result<Vec<u8>> {
+    let len = u32::try_from(decompressed_len).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Input too large to store its size, use store_size=False",
+        )
+    })?;
+    let mut output = Vec::with_capacity(4 + compressed.len());
+    output.extend_from_slice(&frame_u32(len));
+    output.extend_from_slice(compressed);
+    Ok(output)
+}
+
+/// Split data written by [`with_size_prefix`] into the decompressed length and compressed data.
+pub(crate) fn split_size_prefix(data: &[u8]) -> std::io::Result<(usize, &[u8])> {
+    if data.len() < 4 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "Data too short to hold its size; was it compressed with store_size=True?",
+        ));
+    }
+    let (prefix, compressed) = data.split_at(4);
+    let len = read_frame_u32([prefix[0], prefix[1], prefix[2], prefix[3]]);
+    Ok((len as usize, compressed))
+}
+
+/// Check data split by [`split_size_prefix`] decompressed to `stored_len` bytes, the size prefixed
+/// to it, raising `DecompressionError` otherwise.
+pub(crate) fn check_stored_size(decompressed_len: usize, stored_len: usize) -> PyResult<()> {
+    if decompressed_len != stored_len {
+        return Err(DecompressionError::new_err(format!(
+            "Decompressed {} bytes rather than the stored size of {}",
+            decompressed_len, stored_len
+        )));
+    }
+    Ok(())
+}
+
+/// Pad `compressed` with zeros up to a multiple of `pad_to` bytes, for `compress(pad_to=..)`, hiding
+/// its exact length; it's prefixed by [`FRAME_VERSION`] and that length as a u32, for [`unpadded`].
+pub(crate) fn pad(compressed: RustyBuffer, pad_to: Option<usize>) -> PyResult<RustyBuffer> {
+    let pad_to = match pad_to {
+        Some(0) => return Err(CompressionError::new_err("pad_to must be greater than 0")),
+        Some(pad_to) => pad_to,
+        None => return Ok(compressed),
+    };
+    let compressed = compressed.into_vec();
+    let len = u32::try_from(compressed.len())
+        .map_err(|_| CompressionError::new_err("Compressed data too large to pad, use pad_to=None"))?;
+    let padded_len = (PAD_HEADER_LEN + compressed.len()).div_ceil(pad_to) * pad_to;
+    let mut output = Vec::with_capacity(padded_len);
+    output.push(FRAME_VERSION);
+    output.extend_from_slice(&frame_u32(len));
+    output.extend_from_slice(&compressed);
+    output.resize(padded_len, 0);
+    Ok(RustyBuffer::from(output))
+}
+
+/// Length of the header written by [`pad`], its version and the length of the compressed data
+const PAD_HEADER_LEN: usize = 5;
+
+/// The compressed data of `data` from its current position, for `decompress`; everything remaining,
+/// or with `pad_to`, only the compressed data of the output of [`pad`], without its prefix and padding.
+pub(crate) fn unpadded<'a, 'b>(
+    data: &'b mut BytesType<'a>,
+    pad_to: Option<usize>,
+) -> PyResult<std::io::Take<&'b mut BytesType<'a>>> {
+    let remaining = data.remaining_len()?;
+    let pad_to = match pad_to {
+        Some(0) => return Err(DecompressionError::new_err("pad_to must be greater than 0")),
+        Some(pad_to) => pad_to as u64,
+        None => return Ok(data.take(remaining)),
+    };
+    if remaining < PAD_HEADER_LEN as u64 || remaining % pad_to != 0 {
+        return Err(DecompressionError::new_err(format!(
+            "Padded data of {} bytes isn't a multiple of pad_to={}; was it compressed with the same pad_to?",
+            remaining, pad_to
+        )));
+    }
+    let mut header = [0; PAD_HEADER_LEN];
+    data.read_exact(&mut header)?;
+    to_py_err!(DecompressionError -> check_frame_version(header[0]))?;
+    let len = read_frame_u32([header[1], header[2], header[3], header[4]]) as u64;
+    let remaining = remaining - PAD_HEADER_LEN as u64;
+    if len > remaining {
+        return Err(DecompressionError::new_err(format!(
+            "Invalid padding, the recorded length of {} bytes exceeds the {} bytes of data",
+            len, remaining
+        )));
+    }
+    Ok(data.take(len))
+}
+
+/// Names accepted by `trailer_checksum`
+const TRAILER_CHECKSUMS: &[&str] = &["crc32", "xxh3"];
+
+/// Checksum of the trailer appended by [`with_trailer`], as named by `trailer_checksum`
+#[derive(Clone, Copy)]
+enum TrailerChecksum {
+    /// crc32, the checksum of gzip, as a u32
+    Crc32,
+    /// The 64 bit XXH3, faster than crc32 for large data, as a u64
+    Xxh3,
+}
+
+impl TrailerChecksum {
+    /// Length of the checksum in the trailer
+    fn len(self) -> usize {
+        match self {
+            Self::Crc32 => 4,
+            Self::Xxh3 => 8,
+        }
+    }
+
+    fn checksum(self, data: &[u8]) -> u64 {
+        match self {
+            Self::Crc32 => crc32fast::hash(data) as u64,
+            Self::Xxh3 => twox_hash::xxh3::hash64(data),
+        }
+    }
+
+    /// `checksum`, as written in the trailer in [`FRAME_BYTE_ORDER`]
+    fn encode(self, checksum: u64) -> Vec<u8> {
+        match self {
+            Self::Crc32 => frame_u32(checksum as u32).to_vec(),
+            Self::Xxh3 => frame_u64(checksum).to_vec(),
+        }
+    }
+
+    /// Read a checksum written by [`TrailerChecksum::encode`]
+    fn decode(self, bytes: &[u8]) -> u64 {
+        match self {
+            Self::Crc32 => read_frame_u32([bytes[0], bytes[1], bytes[2], bytes[3]]) as u64,
+            Self::Xxh3 => read_frame_u64(<[u8; 8]>::try_from(bytes).unwrap()),
+        }
+    }
+}
+
+/// The checksum named by `trailer_checksum`, if given, raising `ValueError` if it's not a known checksum
+fn trailer_checksum(trailer_checksum: Option<&str>) -> PyResult<Option<TrailerChecksum>> {
+    match trailer_checksum {
+        None => Ok(None),
+        Some("crc32") => Ok(Some(TrailerChecksum::Crc32)),
+        Some("xxh3") => Ok(Some(TrailerChecksum::Xxh3)),
+        Some(name) => Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "Unknown trailer_checksum '{}', should be one of {}",
+            name,
+            TRAILER_CHECKSUMS
+                .iter()
+                .map(|name| format!("'{}'", name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+    }
+}
+
+/// Append a trailer of [`FRAME_VERSION`] and the checksum of `compressed` named by `trailer_checksum`,
+/// for `compress(trailer_checksum=...)`; it's checked and removed again by [`TrailerChecked::new`].
+pub(crate) fn with_trailer(compressed: RustyBuffer, trailer_checksum: Option<&str>) -> PyResult<RustyBuffer> {
+    let checksum = match self::trailer_checksum(trailer_checksum)? {
+        Some(checksum) => checksum,
+        None => return Ok(compressed),
+    };
+    let mut output = compressed.into_vec();
+    let encoded = checksum.encode(checksum.checksum(&output));
+    output.push(FRAME_VERSION);
+    output.extend_from_slice(&encoded);
+    Ok(RustyBuffer::from(output))
+}
+
+/// The compressed data read by `decompress`; with `trailer_checksum`, it's read entirely up front
+/// to check the trailer appended by [`with_trailer`] before decompressing any of it.
+pub(crate) enum TrailerChecked<R> {
+    Unchecked(R),
+    Checked(std::io::Cursor<Vec<u8>>),
+}
+
+impl<R: Read> TrailerChecked<R> {
+    pub(crate) fn new(mut compressed: R, trailer_checksum: Option<&str>) -> PyResult<Self> {
+        let checksum = match self::trailer_checksum(trailer_checksum)? {
+            Some(checksum) => checksum,
+            None => return Ok(Self::Unchecked(compressed)),
+        };
+        let trailer_len = 1 + checksum.len();
+        let mut data = vec![];
+        compressed.read_to_end(&mut data)?;
+        if data.len() < trailer_len {
+            return Err(DecompressionError::new_err(
+                "Data too short to hold its trailer checksum; was it compressed with the same trailer_checksum?",
+            ));
+        }
+        let trailer = data.split_off(data.len() - trailer_len);
+        to_py_err!(DecompressionError -> check_frame_version(trailer[0]))?;
+        let expected = checksum.decode(&trailer[1..]);
+        let actual = checksum.checksum(&data);
+        if actual != expected {
+            let width = 2 + 2 * checksum.len();
+            return Err(DecompressionError::new_err(format!(
+                "Trailer checksum mismatch (expected {:#0width$x}, got {:#0width$x}); the compressed data is corrupted",
+                expected,
+                actual,
+                width = width
+            )));
+        }
+        Ok(Self::Checked(std::io::Cursor::new(data)))
+    }
+}
+
+impl<R: Read> Read for TrailerChecked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Unchecked(compressed) => compressed.read(buf),
+            Self::Checked(compressed) => compressed.read(buf),
+        }
+    }
+}
+
+/// Largest ratio of decompressed to compressed length that a frame's recorded decompressed length
+/// is trusted up to when pre-sizing an output, that of deflate, so a corrupt or malicious header
+/// can't have a huge output allocated up front.
+pub(crate) const MAX_PRESIZE_RATIO: u64 = 1032;
+
+/// Decompress `input` into `output` with `decompress`, first growing a `bytearray` output to fit
+/// `decompressed_len` more bytes, as recorded in the frame, so it isn't grown again as it's written.
+/// Should the data decompress to less, or fail to, the bytearray is shrunk back to the end of what
+/// was written, or its original length if that's longer.
+pub(crate) fn decompress_into_presized<'a, F>(
+    mut input: BytesType<'a>,
+    output: &mut BytesType,
+    decompressed_len: Option<u64>,
+    decompress: F,
+) -> std::io::Result<usize>
+where
+    F: FnOnce(BytesType<'a>, &mut BytesType) -> std::io::Result<usize>,
+{
+    let mut grown_from = None;
+    if let (BytesType::ByteArray(array), Some(len)) = (&mut *output, decompressed_len) {
+        let end = array.cursor.position().saturating_add(len);
+        if len <= input.remaining_len()?.saturating_mul(MAX_PRESIZE_RATIO) && end > array.inner.len() as u64 {
+            grown_from = Some(array.inner.len());
+            array.resize(end as usize)?;
+        }
+    }
+    let result = decompress(input, output);
+    if let (BytesType::ByteArray(array), Some(original_len)) = (output, grown_from) {
+        let written = array.cursor.position() as usize;
+        if written < array.inner.len() {
+            array.resize(written.max(original_len))?;
+        }
+    }
+    result
+}
+
+/// Empty output for decompressing `compressed_len` bytes, with room for `decompressed_len` bytes,
+/// as recorded in the frame, if known and plausible as for `decompress_into_presized`, so that
+/// it's allocated once rather than grown as it's written.
+pub(crate) fn presized_output(decompressed_len: Option<u64>, compressed_len: u64) -> Vec<u8> {
+    let capacity = decompressed_len
+        .filter(|len| *len <= compressed_len.saturating_mul(MAX_PRESIZE_RATIO))
+        .and_then(|len| usize::try_from(len).ok())
+        .unwrap_or(0);
+    crate::alloc::output_with_capacity(capacity)
+}
+
+/// Decompress `input` with `decompress` into a new `numpy.memmap` of `dtype`, by default `"uint8"`,
+/// created at `path`, for `decompress_to_memmap`. It's sized to `decompressed_len`, as recorded in
+/// the frame, if known and plausible as for `decompress_into_presized`, otherwise to the length given
+/// by a dry run of `decompress`, which discards what it decompresses; the data is never held in
+/// memory besides in the map.
+pub(crate) fn decompress_to_memmap<'a, F>(
+    py: Python<'a>,
+    mut input: BytesType,
+    path: &str,
+    dtype: Option<&str>,
+    decompressed_len: Option<u64>,
+    decompress: F,
+) -> PyResult<&'a PyAny>
+where
+    F: Fn(&mut BytesType, &mut dyn Write) -> std::io::Result<usize>,
+{
+    let start = input.stream_position()?;
+    let len = match decompressed_len {
+        Some(len) if len <= input.remaining_len()?.saturating_mul(MAX_PRESIZE_RATIO) => len,
+        _ => {
+            let len = to_py_err!(DecompressionError -> decompress(&mut input, &mut std::io::sink()))?;
+            input.seek(SeekFrom::Start(start))?;
+            len as u64
+        }
+    };
+
+    // numpy.memmap can't map an empty file
+    if len == 0 {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "Decompressed length is 0, which can't be mapped by numpy.memmap",
+        ));
+    }
+    let numpy = py.import("numpy")?;
+    let dtype = numpy.call1("dtype", (dtype.unwrap_or("uint8"),))?;
+    let item_size: u64 = dtype.getattr("itemsize")?.extract()?;
+    if len % item_size != 0 {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "Decompressed length {} is not a multiple of the dtype's size of {} bytes",
+            len, item_size
+        )));
+    }
+    let kwargs = pyo3::types::PyDict::new(py);
+    kwargs.set_item("dtype", dtype)?;
+    kwargs.set_item("mode", "w+")?;
+    kwargs.set_item("shape", (len / item_size,))?;
+    let memmap = numpy.getattr("memmap")?.call((path,), Some(kwargs))?;
+
+    let mut output: BytesType = memmap.extract()?;
+    let written = to_py_err!(DecompressionError -> decompress(&mut input, &mut output))?;
+    if written as u64 != len {
+        return Err(DecompressionError::new_err(format!(
+            "Decompressed to {} bytes, but the frame records a length of {}",
+            written, len
+        )));
+    }
+    memmap.call_method0("flush")?;
+    Ok(memmap)
+}
+
+/// `level`, or `default`, checked to be within `min` to `max`, for a codec's `checked_level`; the
+/// error names `codec` and its range.
+pub(crate) fn checked_level<T>(codec: &str, level: Option<T>, default: T, min: T, max: T) -> std::io::Result<T>
+where
+    T: PartialOrd + std::fmt::Display,
+{
+    let level = level.unwrap_or(default);
+    if level < min || level > max {
+        let msg = format!("{} level must be between {} and {}, got {}", codec, min, max, level);
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, msg));
+    }
+    Ok(level)
+}
+
+/// `bound`, a codec's largest output for `data_len` bytes of input, for `compress_bound`; raises
+/// `OverflowError` if it's `None`, as it's too large to be represented.
+pub(crate) fn checked_bound(bound: Option<usize>, data_len: usize) -> PyResult<usize> {
+    bound.ok_or_else(|| {
+        pyo3::exceptions::PyOverflowError::new_err(format!(
+            "The compressed length of {} bytes of input is too large to be represented",
+            data_len
+        ))
+    })
+}
+
+/// `text` encoded with `encoding`, by default `"utf-8"`, for `compress_str`; errors are raised as by `str.encode`.
+pub(crate) fn encode_str<'a>(text: &'a PyString, encoding: Option<&str>) -> PyResult<BytesType<'a>> {
+    text.call_method1("encode", (encoding.unwrap_or("utf-8"),))?.extract()
+}
+
+/// `data` decoded with `encoding`, by default `"utf-8"`, for `decompress_str`; errors are raised as by `bytes.decode`.
+pub(crate) fn decode_str<'a>(py: Python<'a>, data: &[u8], encoding: Option<&str>) -> PyResult<&'a PyString> {
+    // not PyString::from_object, which passes encoding to Python without a terminating nul
+    let text = PyBytes::new(py, data).call_method1("decode", (encoding.unwrap_or("utf-8"),))?;
+    Ok(text.downcast()?)
+}
+
+/// Index of the first of `items` which fails to `decompress`, or whose decompressed data differs
+/// from the corresponding item of `expected`, for `verify_many`; `None` if they all pass. The items
+/// are copied before being checked in parallel with the GIL released, across all available cores.
+pub(crate) fn verify_many(
+    py: Python,
+    items: Vec<BytesType>,
+    expected: Option<Vec<BytesType>>,
+    decompress: fn(&[u8], &mut Vec<u8>) -> std::io::Result<usize>,
+) -> PyResult<Option<usize>> {
+    if let Some(expected) = &expected {
+        if expected.len() != items.len() {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Got {} expected items for {} items",
+                expected.len(),
+                items.len()
+            )));
+        }
+    }
+    let read_all = |mut data: BytesType| {
+        let mut bytes = vec![];
+        data.read_to_end(&mut bytes).map(|_| bytes)
+    };
+    let items = items.into_iter().map(read_all).collect::<std::io::Result<Vec<_>>>()?;
+    let expected = expected
+        .map(|expected| expected.into_iter().map(read_all).collect::<std::io::Result<Vec<_>>>())
+        .transpose()?;
+
+    let n_items = items.len();
+    let first_failure = AtomicUsize::new(usize::MAX);
+    let passes = |index: usize| {
+        let mut output = vec![];
+        decompress(&items[index], &mut output).is_ok()
+            && expected.as_ref().is_none_or(|expected| output == expected[index])
+    };
+    py.allow_threads(|| {
+        let n_threads = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .min(n_items);
+        std::thread::scope(|scope| {
+            for thread in 0..n_threads {
+                let (first_failure, passes) = (&first_failure, &passes);
+                // each thread checks every n_threads'th item in order, so may stop once an earlier item failed
+                scope.spawn(move || {
+                    for index in (thread..n_items).step_by(n_threads) {
+                        if index > first_failure.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        if !passes(index) {
+                            first_failure.fetch_min(index, Ordering::Relaxed);
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+    });
+    Ok(Some(first_failure.into_inner()).filter(|index| *index != usize::MAX))
+}
+
+/// Output written by [`compress_into`]'s `compress`, which lz4 also seeks in
+pub(crate) trait WriteSeek: Write + Seek {}
+impl<T: Write + Seek> WriteSeek for T {}
+
+/// Compress `input` into `output` with `compress`, for `compress_into`, returning the compressed
+/// length. Should `output` be too small to hold it, `CompressionError` is raised with the length
+/// which is always enough, `bound` of the length of `input`, the codec's `compress_bound`.
+pub(crate) fn compress_into<'a>(
+    mut input: BytesType<'a>,
+    mut output: BytesType<'a>,
+    bound: fn(usize) -> Option<usize>,
+    compress: impl Fn(&mut BytesType<'a>, &mut dyn WriteSeek) -> std::io::Result<usize>,
+) -> PyResult<usize> {
+    output.check_writable()?;
+    let input_len = input.remaining_len()? as usize;
+    match compress(&mut input, &mut output) {
+        Err(err) if err.kind() == std::io::ErrorKind::WriteZero => {
+            let needed = checked_bound(bound(input_len), input_len)?;
+            Err(CompressionError::new_err(format!(
+                "output buffer too small: needed up to {} bytes",
+                needed
+            )))
+        }
+        result => to_py_err!(CompressionError -> result),
+    }
+}
+
+/// Decompress each of `items` with `decompress` into successive regions of `array`, for
+/// `decompress_many_into`, returning the offset and decompressed length of each; `BufferError` is
+/// raised if the array fills up before all of them are decompressed, and `ValueError` if any of
+/// them overlaps the array, as it'd be overwritten while it's decompressed.
+pub(crate) fn decompress_many_into(
+    items: Vec<BytesType>,
+    array: &numpy::PyArray1<u8>,
+    decompress: fn(BytesType, &mut std::io::Cursor<&mut [u8]>) -> std::io::Result<usize>,
+) -> PyResult<Vec<(usize, usize)>> {
+    let array_range = unsafe { array.as_slice()? }.as_ptr_range();
+    for (index, item) in items.iter().enumerate() {
+        // a file's data isn't in memory, so can't be in the array
+        let item = match item {
+            BytesType::RustyFile(_) => continue,
+            item => item.as_bytes().as_ptr_range(),
+        };
+        if item.start < array_range.end && array_range.start < item.end {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Item {} overlaps the array it'd be decompressed into",
+                index
+            )));
+        }
+    }
+
+    let mut output = std::io::Cursor::new(unsafe { array.as_slice_mut()? });
+    let mut regions = Vec::with_capacity(items.len());
+    for (index, item) in items.into_iter().enumerate() {
+        let start = output.position();
+        match decompress(item, &mut output) {
+            Ok(_) => regions.push((start as usize, (output.position() - start) as usize)),
+            Err(err) if err.kind() == std::io::ErrorKind::WriteZero => {
+                return Err(pyo3::exceptions::PyBufferError::new_err(format!(
+                    "Array of {} bytes is full, unable to decompress item {} into it",
+                    array.len(),
+                    index
+                )))
+            }
+            Err(err) => {
+                return Err(DecompressionError::new_err(format!(
+                    "Unable to decompress item {}: {}",
+                    index, err
+                )))
+            }
+        }
+    }
+    Ok(regions)
+}
+
+/// `data` compressed with `compress`, and `True`, for `maybe_compress`; or when that doesn't achieve
+/// a ratio of uncompressed to compressed length of at least `min_ratio` (default 1.1), a copy of
+/// `data` as is, and `False`.
+pub(crate) fn maybe_compress(
+    mut data: BytesType,
+    min_ratio: Option<f64>,
+    compress: impl FnOnce(&[u8], &mut std::io::Cursor<Vec<u8>>) -> std::io::Result<usize>,
+) -> PyResult<(RustyBuffer, bool)> {
+    let mut input = vec![];
+    data.read_to_end(&mut input)?;
+    let mut output = std::io::Cursor::new(vec![]);
+    to_py_err!(CompressionError -> compress(&input, &mut output))?;
+    let output = output.into_inner();
+    match input.len() as f64 / output.len() as f64 >= min_ratio.unwrap_or(1.1) {
+        true => Ok((RustyBuffer::from(output), true)),
+        false => Ok((RustyBuffer::from(input), false)),
+    }
+}
+
+/// `data` decompressed with `decompress` if `compressed`, the flag returned by `maybe_compress`,
+/// otherwise a copy of it as is; for `maybe_decompress`.
+pub(crate) fn maybe_decompress(
+    mut data: BytesType,
+    compressed: bool,
+    decompress: fn(BytesType, &mut Vec<u8>) -> std::io::Result<usize>,
+) -> PyResult<RustyBuffer> {
+    let mut output = vec![];
+    match compressed {
+        true => to_py_err!(DecompressionError -> decompress(data, &mut output))?,
+        false => data.read_to_end(&mut output)?,
+    };
+    Ok(RustyBuffer::from(output))
+}
+
+/// Decompress `compressed` with `decompress` twice, for `decompress(exact=True)`: first only counting
+/// the decompressed bytes, then into an output allocated once with exactly that length.
+pub(crate) fn decompress_exact<R: Read, F: Fn(&[u8], &mut dyn Write) -> std::io::Result<usize>>(
+    mut compressed: R,
+    decompress: F,
+) -> PyResult<RustyBuffer> {
+    let mut input = vec![];
+    compressed.read_to_end(&mut input)?;
+    let len = to_py_err!(DecompressionError -> decompress(&input, &mut std::io::sink()))?;
+    let mut output = vec![0; len];
+    to_py_err!(DecompressionError -> decompress(&input, &mut std::io::Cursor::new(&mut output[..])))?;
+    Ok(RustyBuffer::from(output))
+}
+
+/// Decompress everything `decoder` will decode, for the options of `decompress` which need control
+/// of the decoding loop.
+///
+/// With `recover`, decoding stops at the first error rather than raising it, returning a tuple of the
+/// [`RustyBuffer`](io/struct.RustyBuffer.html) decoded so far and whether an error was encountered.
+/// With `timeout_ms`, `DecompressionError` is raised once decoding has taken longer, even with `recover`.
+/// With `partial`, the [`RustyBuffer`](io/struct.RustyBuffer.html) decoded so far is returned should
+/// the input end part way into the data, while any other error is still raised.
+pub(crate) fn decompress_with<R: Read>(
+    py: Python,
+    decoder: std::io::Result<R>,
+    recover: bool,
+    timeout_ms: Option<u64>,
+    partial: bool,
+) -> PyResult<PyObject> {
+    let mut output = vec![];
+    // read_to_end keeps all the bytes from the reads preceding an error
+    let result = decoder.and_then(|decoder| TimeLimit::new(decoder, timeout_ms).read_to_end(&mut output));
+    match result {
+        Ok(_) if recover => Ok((RustyBuffer::from(output), false).into_py(py)),
+        Ok(_) => Ok(RustyBuffer::from(output).into_py(py)),
+        Err(err) if recover && err.kind() != std::io::ErrorKind::TimedOut => {
+            Ok((RustyBuffer::from(output), true).into_py(py))
+        }
+        Err(err) if partial && err.kind() == std::io::ErrorKind::UnexpectedEof => {
+            Ok(RustyBuffer::from(output).into_py(py))
+        }
+        Err(err) => Err(DecompressionError::new_err(err.to_string())),
+    }
+}
+
+/// Reader which fails with a `TimedOut` error once reading from `inner` has taken as long as the
+/// timeout, so always with a timeout of 0; reads are limited to `TimeLimit::CHUNK_SIZE` bytes so
+/// the time is checked regularly.
+struct TimeLimit<R> {
+    inner: R,
+    deadline: Option<std::time::Instant>,
+}
+
+impl<R> TimeLimit<R> {
+    const CHUNK_SIZE: usize = 64 * 1024;
+
+    fn new(inner: R, timeout_ms: Option<u64>) -> Self {
+        let deadline = timeout_ms.map(|ms| std::time::Instant::now() + std::time::Duration::from_millis(ms));
+        Self { inner, deadline }
+    }
+}
+
+impl<R: Read> Read for TimeLimit<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self.deadline {
+            Some(deadline) => {
+                if std::time::Instant::now() >= deadline {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "decompression timed out",
+                    ));
+                }
+                let len = buf.len().min(Self::CHUNK_SIZE);
+                self.inner.read(&mut buf[..len])
+            }
+            None => self.inner.read(buf),
+        }
+    }
+}
+
 /// Macro to convert an error into a specific Python exception.
 #[macro_export]
 macro_rules! to_py_err {
@@ -229,16 +1007,30 @@ macro_rules! make_submodule {
 #[pymodule]
 fn cramjam(py: Python, m: &PyModule) -> PyResult<()> {
     m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    m.add("FRAME_VERSION", FRAME_VERSION)?;
+    m.add("FRAME_BYTE_ORDER", FRAME_BYTE_ORDER)?;
     m.add("CompressionError", py.get_type::<CompressionError>())?;
     m.add("DecompressionError", py.get_type::<DecompressionError>())?;
     m.add_class::<crate::io::RustyFile>()?;
     m.add_class::<crate::io::RustyBuffer>()?;
+    m.add_class::<crate::io::RustyCompressedFile>()?;
+    m.add_class::<crate::io::RustyRingCompressor>()?;
+    io::add_functions(m)?;
     make_submodule!(py -> m -> snappy);
     make_submodule!(py -> m -> brotli);
     make_submodule!(py -> m -> lz4);
     make_submodule!(py -> m -> gzip);
     make_submodule!(py -> m -> deflate);
     make_submodule!(py -> m -> zstd);
+    make_submodule!(py -> m -> bzip2);
+    make_submodule!(py -> m -> lzma);
+    make_submodule!(py -> m -> zlib);
+    make_submodule!(py -> m -> arrow);
+    make_submodule!(py -> m -> hash);
+    registry::init_py_module(py, m)?;
+    detect::init_py_module(m)?;
+    records::init_py_module(m)?;
+    alloc::init_py_module(m)?;
 
     Ok(())
 }
@@ -246,6 +1038,7 @@ fn cramjam(py: Python, m: &PyModule) -> PyResult<()> {
 #[cfg(test)]
 mod tests {
 
+    use std::convert::TryInto;
     use std::io::Cursor;
 
     // Default testing data
@@ -258,7 +1051,7 @@ mod tests {
 
     // Single test generation
     macro_rules! round_trip {
-        ($name:ident($compress_output:ident -> $decompress_output:ident), variant=$variant:ident, compressed_len=$compressed_len:literal, $(level=$level:tt)?) => {
+        ($name:ident($compress_output:ident -> $decompress_output:ident), variant=$variant:ident, compressed_len=$compressed_len:literal, $($argname:ident=$arg:expr),*) => {
             #[test]
             fn $name() {
                 let data = gen_data();
@@ -268,9 +1061,9 @@ mod tests {
                 let compressed_size = if stringify!($decompress_output) == "Slice" {
                         compressed = (0..data.len()).map(|_| 0).collect::<Vec<u8>>();
                         let mut cursor = Cursor::new(compressed.as_mut_slice());
-                        crate::$variant::internal::compress(&mut Cursor::new(data.as_slice()), &mut cursor $(, $level)?).unwrap()
+                        crate::$variant::internal::compress(&mut Cursor::new(data.as_slice()), &mut cursor $(, $arg)*).unwrap()
                     } else {
-                        crate::$variant::internal::compress(&mut Cursor::new(data.as_slice()), &mut Cursor::new(&mut compressed) $(, $level)?).unwrap()
+                        crate::$variant::internal::compress(&mut Cursor::new(data.as_slice()), &mut Cursor::new(&mut compressed) $(, $arg)*).unwrap()
                     };
 
                 assert_eq!(compressed_size, $compressed_len);
@@ -295,22 +1088,91 @@ mod tests {
 
     // macro to generate each variation of Output::* roundtrip.
     macro_rules! test_variant {
-        ($variant:ident, compressed_len=$compressed_len:literal, $(level=$level:tt)?) => {
+        ($variant:ident, compressed_len=$compressed_len:literal, $($argname:ident=$arg:expr),*) => {
          #[cfg(test)]
          mod $variant {
             use super::*;
-            round_trip!(roundtrip_compress_via_slice_decompress_via_slice(Slice -> Slice), variant=$variant, compressed_len=$compressed_len, $(level=$level)? );
-            round_trip!(roundtrip_compress_via_slice_decompress_via_vector(Slice -> Vector), variant=$variant, compressed_len=$compressed_len, $(level=$level)? );
-            round_trip!(roundtrip_compress_via_vector_decompress_via_slice(Vector -> Slice), variant=$variant, compressed_len=$compressed_len, $(level=$level)? );
-            round_trip!(roundtrip_compress_via_vector_decompress_via_vector(Vector -> Vector), variant=$variant, compressed_len=$compressed_len, $(level=$level)? );
+            round_trip!(roundtrip_compress_via_slice_decompress_via_slice(Slice -> Slice), variant=$variant, compressed_len=$compressed_len, $($argname=$arg),* );
+            round_trip!(roundtrip_compress_via_slice_decompress_via_vector(Slice -> Vector), variant=$variant, compressed_len=$compressed_len, $($argname=$arg),* );
+            round_trip!(roundtrip_compress_via_vector_decompress_via_slice(Vector -> Slice), variant=$variant, compressed_len=$compressed_len, $($argname=$arg),* );
+            round_trip!(roundtrip_compress_via_vector_decompress_via_vector(Vector -> Vector), variant=$variant, compressed_len=$compressed_len, $($argname=$arg),* );
          }
         }
     }
 
-    test_variant!(snappy, compressed_len = 2572398,);
+    test_variant!(snappy, compressed_len = 2572398, chunk_size = None);
     test_variant!(gzip, compressed_len = 157192, level = None);
     test_variant!(brotli, compressed_len = 729, level = None);
     test_variant!(deflate, compressed_len = 157174, level = None);
-    test_variant!(zstd, compressed_len = 4990, level = None);
+    test_variant!(
+        zstd,
+        compressed_len = 4990,
+        options = &crate::zstd::internal::CompressOptions::default()
+    );
     test_variant!(lz4, compressed_len = 303278, level = None);
+    test_variant!(bzip2, compressed_len = 9416, level = None);
+    test_variant!(lzma, compressed_len = 8020, level = None);
+    test_variant!(zlib, compressed_len = 157180, level = None);
+
+    #[test]
+    fn gather_strided_array() {
+        // 3x4 array of u16 in C order, viewed as arr[::2, 1::2]
+        let data: Vec<u8> = (0..24).collect();
+        let mut out = vec![];
+        unsafe { crate::io::gather(data.as_ptr().offset(2), &[2, 2], &[16, 4], 2, &mut out) };
+        assert_eq!(out, vec![2, 3, 6, 7, 18, 19, 22, 23]);
+
+        // contiguous rows are copied whole
+        let mut out = vec![];
+        unsafe { crate::io::gather(data.as_ptr(), &[3, 4], &[8, 2], 2, &mut out) };
+        assert_eq!(out, data);
+    }
+
+    #[test]
+    fn zstd_content_size_in_frame_header() {
+        let data = gen_data();
+
+        let mut compressed = vec![];
+        crate::zstd::internal::compress(
+            Cursor::new(&data),
+            &mut compressed,
+            &crate::zstd::internal::CompressOptions {
+                content_size: Some(data.len() as u64),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(zstd_safe::get_frame_content_size(&compressed), data.len() as u64);
+
+        let mut compressed = vec![];
+        crate::zstd::internal::compress(
+            Cursor::new(&data),
+            &mut compressed,
+            &crate::zstd::internal::CompressOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            zstd_safe::get_frame_content_size(&compressed),
+            zstd_safe::CONTENTSIZE_UNKNOWN
+        );
+    }
+    #[test]
+    fn gzip_isize_presizes_output() {
+        let data = b"oh what a beautiful morning, oh what a beautiful day!!".repeat(10000);
+        let mut compressed = vec![];
+        crate::gzip::internal::compress(Cursor::new(&data), &mut compressed, None).unwrap();
+        let isize = u32::from_le_bytes(compressed[compressed.len() - 4..].try_into().unwrap());
+        assert_eq!(isize as usize, data.len());
+
+        // allocated once for the recorded length, and not grown as it's decompressed into
+        let mut output = super::presized_output(Some(isize as u64), compressed.len() as u64);
+        assert_eq!(output.capacity(), data.len());
+        crate::gzip::internal::decompress(Cursor::new(&compressed), &mut output).unwrap();
+        assert_eq!(output, data);
+        assert_eq!(output.capacity(), data.len());
+
+        // a recorded length implausibly long for the compressed length isn't allocated up front
+        let output = super::presized_output(Some(u32::MAX as u64), compressed.len() as u64);
+        assert_eq!(output.capacity(), 0);
+    }
 }
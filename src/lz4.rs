@@ -1,77 +1,432 @@
 //! lz4 de/compression interface
 use crate::exceptions::{CompressionError, DecompressionError};
-use crate::io::{AsBytes, RustyBuffer};
-use crate::{to_py_err, BytesType};
+use crate::io::{AsBytes, RustyBuffer, RustyLines, RustyStridedNumpyArray};
+use crate::{to_py_err, BytesType, Level};
+use numpy::PyArray1;
 use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyString};
 use pyo3::wrap_pyfunction;
 use pyo3::PyResult;
-use std::io::Cursor;
+use std::convert::TryFrom;
+use std::io::{Cursor, Read};
 
 pub(crate) fn init_py_module(m: &PyModule) -> PyResult<()> {
+    m.add("DEFAULT_LEVEL", internal::DEFAULT_LEVEL)?;
+    m.add("MAX_LEVEL", internal::MAX_LEVEL)?;
     m.add_function(wrap_pyfunction!(compress, m)?)?;
     m.add_function(wrap_pyfunction!(decompress, m)?)?;
     m.add_function(wrap_pyfunction!(compress_block, m)?)?;
     m.add_function(wrap_pyfunction!(decompress_block, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_bound, m)?)?;
     m.add_function(wrap_pyfunction!(compress_into, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_reuse, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_strided, m)?)?;
     m.add_function(wrap_pyfunction!(decompress_into, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_to_memmap, m)?)?;
+    #[cfg(unix)]
+    m.add_function(wrap_pyfunction!(compress_fd, m)?)?;
+    #[cfg(unix)]
+    m.add_function(wrap_pyfunction!(decompress_fd, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_lines, m)?)?;
+    m.add_function(wrap_pyfunction!(recompress_range, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_str, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_str, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_many, m)?)?;
+    m.add_function(wrap_pyfunction!(is_valid, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_many_into, m)?)?;
+    m.add_function(wrap_pyfunction!(maybe_compress, m)?)?;
+    m.add_function(wrap_pyfunction!(maybe_decompress, m)?)?;
+    m.add_function(wrap_pyfunction!(peek, m)?)?;
     Ok(())
 }
 
 /// LZ4 compression.
 ///
+/// With `recover=True`, decoding stops at the first corrupted or truncated section of the input
+/// rather than raising; a tuple of the `Buffer` decompressed up to that point, and whether
+/// the input was found to be damaged, is returned instead.
+///
+/// With `timeout_ms`, `DecompressionError` is raised if decompression takes longer than that
+/// many milliseconds, ie. to limit the time spent on untrusted input.
+///
+/// With `pad_to`, the input is taken to be the padded output of `compress` with the same `pad_to`.
+///
+/// With `trailer_checksum`, the trailer appended by `compress` with the same `trailer_checksum` is
+/// checked before decompressing, raising `DecompressionError` if the compressed data was corrupted.
+///
+/// With `exact=True`, and no `output_len`, the data is decompressed twice, first only to count its
+/// decompressed length, so the output is allocated once rather than grown as it's decompressed.
+///
 /// Python Example
 /// --------------
 /// ```python
 /// >>> # Note, output_len is currently ignored; underlying algorithm does not support reading to slice at this time
-/// >>> cramjam.lz4.decompress(compressed_bytes, output_len=Optional[int])
+/// >>> cramjam.lz4.decompress(compressed_bytes, output_len=Optional[int], recover=False, timeout_ms=Optional[int], pad_to=Optional[int], trailer_checksum=Optional[str], exact=False)
 /// ```
 #[pyfunction]
-pub fn decompress(data: BytesType, output_len: Option<usize>) -> PyResult<RustyBuffer> {
-    crate::generic!(decompress(data), output_len = output_len)
+#[allow(clippy::too_many_arguments)]
+pub fn decompress(
+    py: Python,
+    mut data: BytesType,
+    output_len: Option<usize>,
+    recover: Option<bool>,
+    timeout_ms: Option<u64>,
+    pad_to: Option<usize>,
+    trailer_checksum: Option<&str>,
+    exact: Option<bool>,
+) -> PyResult<PyObject> {
+    let recover = recover.unwrap_or(false);
+    let compressed = crate::unpadded(&mut data, pad_to)?;
+    let compressed = crate::TrailerChecked::new(compressed, trailer_checksum)?;
+    if recover || timeout_ms.is_some() {
+        return crate::decompress_with(py, internal::decoder(compressed), recover, timeout_ms, false);
+    }
+    if exact.unwrap_or(false) && output_len.is_none() {
+        return crate::decompress_exact(compressed, |input, output| internal::decompress(input, output))
+            .map(|buffer| buffer.into_py(py));
+    }
+    crate::generic!(decompress(compressed), output_len = output_len).map(|buffer: RustyBuffer| buffer.into_py(py))
 }
 
 /// lZ4 compression.
 ///
+/// `level` ranges from 0 to 12, where levels below 3 use lz4's fast mode and level 0 is its
+/// fastest; it defaults to 4 when `None` or omitted. `CompressionError` is raised for any other
+/// level, rather than it being clamped to 12.
+///
+/// The output is an lz4 frame, as read and written by the `lz4` command; see `compress_block` for
+/// bare lz4 blocks. `include_content_size` (default `False`, as for the `lz4` command) writes the
+/// length of the input into the frame header, for `cramjam.peek` and other readers of it; frames
+/// decompress without an `output_len` either way. `content_checksum` (default `True`) writes an
+/// xxHash of the input after the frame's last block, which decompressing checks; without it
+/// compressing is faster and the output 4 bytes shorter, for data whose integrity is checked
+/// otherwise. Each block's own checksum is written either way.
+///
+/// With `pad_to`, the output is padded with zeros to a multiple of that many bytes, hiding its exact
+/// length, ie. before encrypting it; decompress it with the same `pad_to`.
+///
+/// `trailer_checksum="crc32"`, or `"xxh3"` which is faster for large outputs, appends a checksum
+/// of the compressed data, before any padding, so corruption in transport is caught before
+/// decompressing; decompress it with the same `trailer_checksum`.
+///
 /// Python Example
 /// --------------
 /// ```python
 /// >>> # Note, output_len is currently ignored; underlying algorithm does not support reading to slice at this time
-/// >>> cramjam.lz4.compress(b'some bytes here', output_len=Optional[int])
+/// >>> cramjam.lz4.compress(b'some bytes here', output_len=Optional[int], include_content_size=False, content_checksum=True)
+/// >>> cramjam.lz4.compress(b'some bytes here', level="auto")  # 9 for inputs below 1MiB, 4 below 64MiB, otherwise 0
 /// ```
 #[pyfunction]
-pub fn compress(mut data: BytesType, level: Option<u32>, output_len: Option<usize>) -> PyResult<RustyBuffer> {
-    crate::generic!(compress(&mut data), output_len = output_len, level = level)
+pub fn compress(
+    mut data: BytesType,
+    level: Option<Level<u32>>,
+    output_len: Option<usize>,
+    pad_to: Option<usize>,
+    trailer_checksum: Option<&str>,
+    include_content_size: Option<bool>,
+    content_checksum: Option<bool>,
+) -> PyResult<RustyBuffer> {
+    let level = level.map(|level| level.resolve(&mut data, auto_level)).transpose()?;
+    to_py_err!(CompressionError -> internal::checked_level(level))?;
+    let content_size = match include_content_size.unwrap_or(false) {
+        true => Some(data.remaining_len()?),
+        false => None,
+    };
+    crate::generic!(
+        compress_with_frame_options(&mut data),
+        output_len = output_len,
+        level = level,
+        content_size = content_size,
+        content_checksum = content_checksum.unwrap_or(true)
+    )
+    .and_then(|compressed| crate::with_trailer(compressed, trailer_checksum))
+    .and_then(|compressed| crate::pad(compressed, pad_to))
+}
+
+/// Level used with `level="auto"`, by the length of the input
+fn auto_level(input_len: u64) -> u32 {
+    match input_len {
+        len if len < crate::AUTO_LEVEL_SMALL_INPUT => 9,
+        len if len < crate::AUTO_LEVEL_LARGE_INPUT => 4,
+        _ => 0,
+    }
 }
 
-/// Compress directly into an output buffer
+/// Compress `text`, a `str`, encoded with `encoding` (default `"utf-8"`); `level` is as for `compress`.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.lz4.compress_str("some text here", encoding="utf-8", level=Optional[int])
+/// ```
 #[pyfunction]
-pub fn compress_into(mut input: BytesType, mut output: BytesType, level: Option<u32>) -> PyResult<usize> {
-    let r = internal::compress(&mut input, &mut output, level)?;
-    Ok(r)
+pub fn compress_str(text: &PyString, encoding: Option<&str>, level: Option<Level<u32>>) -> PyResult<RustyBuffer> {
+    compress(crate::encode_str(text, encoding)?, level, None, None, None, None, None)
+}
+
+/// Decompress to a `str`, decoding the decompressed data with `encoding` (default `"utf-8"`).
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.lz4.decompress_str(compressed_bytes, encoding="utf-8")
+/// 'some text here'
+/// ```
+#[pyfunction]
+pub fn decompress_str<'a>(py: Python<'a>, data: BytesType, encoding: Option<&str>) -> PyResult<&'a PyString> {
+    let mut output = vec![];
+    to_py_err!(DecompressionError -> internal::decompress(data, &mut output))?;
+    crate::decode_str(py, &output, encoding)
+}
+
+/// Check `data` decompresses without error, returning `True` if it does and `False` if it doesn't,
+/// ie. it's truncated or corrupt; the decompressed data is discarded as it's decoded, rather than
+/// kept in an output buffer.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.lz4.is_valid(compressed_bytes)
+/// True
+/// ```
+#[pyfunction]
+pub fn is_valid(data: BytesType) -> bool {
+    internal::decompress(data, &mut std::io::sink()).is_ok()
+}
+
+/// Check each of `items` decompresses, and when given, to the corresponding item of `expected`;
+/// returns the index of the first which doesn't, or `None` if all of them do. The items are
+/// checked in parallel with the GIL released.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.lz4.verify_many([compressed_bytes, other_compressed_bytes], expected=Optional[List[bytes]])
+/// ```
+#[pyfunction]
+pub fn verify_many(py: Python, items: Vec<BytesType>, expected: Option<Vec<BytesType>>) -> PyResult<Option<usize>> {
+    crate::verify_many(py, items, expected, |input, output| internal::decompress(input, output))
+}
+
+/// Decompress each of `items` into successive regions of `array`, a `numpy.array` with `dtype=np.uint8`,
+/// returning the `(offset, length)` of each region; raises `BufferError` if the array fills up first,
+/// and `ValueError` if an item overlaps the array, ie. is a view of it.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> array = np.empty(total_len, dtype=np.uint8)
+/// >>> regions = cramjam.lz4.decompress_many_into([compressed_bytes, other_compressed_bytes], array)
+/// ```
+#[pyfunction]
+pub fn decompress_many_into(items: Vec<BytesType>, array: &PyArray1<u8>) -> PyResult<Vec<(usize, usize)>> {
+    crate::decompress_many_into(items, array, |input, output| internal::decompress(input, output))
+}
+
+/// Compress, unless that doesn't achieve a ratio of uncompressed to compressed length of at least
+/// `min_ratio` (default 1.1); returns a tuple of the compressed data and `True`, or a copy of the
+/// data as is and `False`, to be given to `maybe_decompress`. `level` is as for `compress`.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> data, compressed = cramjam.lz4.maybe_compress(b'some bytes here', min_ratio=1.1, level=Optional[int])
+/// >>> bytes(cramjam.lz4.maybe_decompress(data, compressed))
+/// b'some bytes here'
+/// ```
+#[pyfunction]
+pub fn maybe_compress(
+    mut data: BytesType,
+    min_ratio: Option<f64>,
+    level: Option<Level<u32>>,
+) -> PyResult<(RustyBuffer, bool)> {
+    let level = level.map(|level| level.resolve(&mut data, auto_level)).transpose()?;
+    crate::maybe_compress(data, min_ratio, |input, output| {
+        internal::compress(&mut &input[..], output, level)
+    })
+}
+
+/// Decompress the data returned by `maybe_compress` if its flag, `compressed`, is `True`, otherwise
+/// return a copy of it as is.
+#[pyfunction]
+pub fn maybe_decompress(data: BytesType, compressed: bool) -> PyResult<RustyBuffer> {
+    crate::maybe_decompress(data, compressed, |input, output| internal::decompress(input, output))
+}
+
+/// Structural metadata from the header of a lz4 frame, without decompressing it; see `cramjam.peek`.
+/// Raises `DecompressionError` if the data doesn't start with a lz4 frame.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.lz4.peek(compressed_bytes)
+/// ```
+#[pyfunction]
+pub fn peek<'a>(py: Python<'a>, mut data: BytesType) -> PyResult<&'a PyDict> {
+    crate::detect::peek_header(py, &mut data, Some("lz4"))
+}
+
+/// Get the maximum length of the compressed output for `data_len` bytes of input, the size
+/// of buffer which is always big enough to be passed to `compress_into`
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> output = bytearray(cramjam.lz4.compress_bound(len(data)))
+/// >>> n_bytes = cramjam.lz4.compress_into(data, output)
+/// ```
+#[pyfunction]
+pub fn compress_bound(data_len: usize) -> PyResult<usize> {
+    crate::checked_bound(internal::compress_bound(data_len), data_len)
+}
+
+/// Compress directly into an output buffer, returning the compressed length; `CompressionError` is
+/// raised with `compress_bound` of the input's length if the buffer is too small to hold it.
+#[pyfunction]
+pub fn compress_into(input: BytesType, output: BytesType, level: Option<u32>) -> PyResult<usize> {
+    crate::compress_into(input, output, internal::compress_bound, |input, output| {
+        internal::compress(input, output, level)
+    })
+}
+
+/// Compress using `scratch`, a `numpy.array` with `dtype=np.uint8`, as the output buffer, returning
+/// a copy of the compressed data as `bytes`. The array is resized as needed, and can be reused across
+/// calls to avoid allocating a new output buffer each time; as with `numpy.ndarray.resize`, it must
+/// own its data and not be referenced by any other array.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> scratch = np.empty(0, dtype=np.uint8)
+/// >>> for data in chunks:
+/// ...     compressed = cramjam.lz4.compress_reuse(data, scratch, level=Optional[int])
+/// ```
+#[pyfunction]
+pub fn compress_reuse<'a>(
+    py: Python<'a>,
+    mut data: BytesType,
+    scratch: &PyArray1<u8>,
+    level: Option<u32>,
+) -> PyResult<&'a PyBytes> {
+    crate::io::compress_reuse(py, scratch, |output| internal::compress(&mut data, output, level))
+}
+
+/// Compress a `numpy.array` of any dtype, including non-contiguous views such as `arr[::2]`.
+/// The elements are gathered into a contiguous buffer, in C order, before being compressed.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> arr = np.arange(100, dtype=np.float64).reshape(10, 10)
+/// >>> cramjam.lz4.compress_strided(arr[::2, 1::3], level=Optional[int], output_len=Optional[int])
+/// ```
+#[pyfunction]
+pub fn compress_strided(
+    mut array: RustyStridedNumpyArray,
+    level: Option<u32>,
+    output_len: Option<usize>,
+) -> PyResult<RustyBuffer> {
+    crate::generic!(compress(&mut array), output_len = output_len, level = level)
+}
+
+/// Decompress into a new `numpy.memmap` of `dtype` (default `"uint8"`) at `path`, which is created
+/// or overwritten, returning it; for data too large to decompress into memory. The data is
+/// decompressed twice, first to find its length.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> array = cramjam.lz4.decompress_to_memmap(compressed_bytes, "/tmp/data.bin", dtype="float64")
+/// ```
+#[pyfunction]
+pub fn decompress_to_memmap<'a>(
+    py: Python<'a>,
+    data: BytesType,
+    path: &str,
+    dtype: Option<&str>,
+) -> PyResult<&'a PyAny> {
+    crate::decompress_to_memmap(py, data, path, dtype, None, |input, output| {
+        internal::decompress(input, output)
+    })
 }
 
 /// Decompress directly into an output buffer
 #[pyfunction]
 pub fn decompress_into(input: BytesType, mut output: BytesType) -> PyResult<usize> {
+    output.check_writable()?;
     let r = internal::decompress(input, &mut output)?;
     Ok(r)
 }
 
+/// Compress from the file descriptor `in_fd` until its end, writing to `out_fd`, ie. either end of
+/// an `os.pipe()`, returning the number of bytes written; neither descriptor is closed.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.lz4.compress_fd(in_fd, out_fd, level=Optional[int])
+/// ```
+#[cfg(unix)]
+#[pyfunction]
+pub fn compress_fd(py: Python, in_fd: i32, out_fd: i32, level: Option<u32>) -> PyResult<usize> {
+    crate::io::with_fds::<CompressionError, _>(py, in_fd, out_fd, |input, output| {
+        internal::compress(input, output, level).map(|_| ())
+    })
+}
+
+/// Decompress from the file descriptor `in_fd` until its end, writing to `out_fd`, returning
+/// the number of bytes written; neither descriptor is closed.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.lz4.decompress_fd(in_fd, out_fd)
+/// ```
+#[cfg(unix)]
+#[pyfunction]
+pub fn decompress_fd(py: Python, in_fd: i32, out_fd: i32) -> PyResult<usize> {
+    crate::io::with_fds::<DecompressionError, _>(py, in_fd, out_fd, |input, output| {
+        internal::decompress(input, output).map(|_| ())
+    })
+}
+
 /// LZ4 _block_ decompression.
 ///
 /// `output_len` is optional, it's the upper bound length of decompressed data; if it's not provided,
 /// then it's assumed `store_size=True` was used during compression and length will then be taken
 /// from the header.
 ///
+/// `dict` must be the dictionary given to `compress_block`, if any. Without it, or with another
+/// one, decompression will usually fail; but a block can't identify its dictionary, so another
+/// one of the same length may also decompress to the wrong data without `store_size`.
+///
 /// Python Example
 /// --------------
 /// ```python
-/// >>> cramjam.lz4.decompress_block(compressed_bytes, output_len=Optional[int])
+/// >>> cramjam.lz4.decompress_block(compressed_bytes, output_len=Optional[int], dict=Optional[bytes])
 /// ```
 #[pyfunction]
-pub fn decompress_block(data: BytesType, output_len: Option<usize>) -> PyResult<RustyBuffer> {
+pub fn decompress_block(data: BytesType, output_len: Option<usize>, dict: Option<BytesType>) -> PyResult<RustyBuffer> {
     use lz4::block;
-    let out = to_py_err!(DecompressionError -> block::decompress(data.as_bytes(), output_len.map(|v| v as i32)))?;
+    let stored_size = output_len.is_none();
+    let (output_len, compressed) = match output_len {
+        Some(len) => (len, data.as_bytes()),
+        None => to_py_err!(DecompressionError -> crate::split_size_prefix(data.as_bytes()))?,
+    };
+    if let Some(dict) = dict {
+        let out = to_py_err!(DecompressionError -> internal::decompress_block_with_dict(compressed, dict.as_bytes(), output_len))?;
+        if stored_size && out.len() != output_len {
+            return Err(DecompressionError::new_err(format!(
+                "Decompressed {} bytes rather than the stored size of {}; was it compressed with the same dict?",
+                out.len(),
+                output_len
+            )));
+        }
+        return Ok(RustyBuffer::from(out));
+    }
+    let output_len = i32::try_from(output_len)
+        .map_err(|_| DecompressionError::new_err(format!("Invalid output_len {}", output_len)))?;
+    let out = to_py_err!(DecompressionError -> block::decompress(compressed, Some(output_len)))?;
     Ok(RustyBuffer::from(out))
 }
 
@@ -79,6 +434,15 @@ pub fn decompress_block(data: BytesType, output_len: Option<usize>) -> PyResult<
 ///
 /// The kwargs mostly follow the same definition found in [python-lz4 block.compress](https://python-lz4.readthedocs.io/en/stable/lz4.block.html#module-lz4.block)
 ///
+/// `dict` is a dictionary, such as a sample of typical data, which the block may refer back into;
+/// of it only the last 64KiB are used. This improves the ratio for small payloads resembling it,
+/// which must then be decompressed with the same `dict`. It's only supported with the default and
+/// fast `mode`s.
+///
+/// `acceleration` is the factor of `mode="fast"`, trading ratio for speed; 1, the default, is the
+/// slowest and densest, and higher is faster. Given without a `mode`, it selects `mode="fast"`, while
+/// as in python-lz4 it's ignored with the other modes.
+///
 /// Python Example
 /// --------------
 /// ```python
@@ -88,7 +452,8 @@ pub fn decompress_block(data: BytesType, output_len: Option<usize>) -> PyResult<
 /// ...     mode=Option[str],
 /// ...     acceleration=Option[int],
 /// ...     compression=Option[int],
-/// ...     store_size=Option[bool]
+/// ...     store_size=Option[bool],
+/// ...     dict=Option[bytes]
 /// ... )
 /// ```
 #[pyfunction]
@@ -100,10 +465,30 @@ pub fn compress_block(
     acceleration: Option<i32>,
     compression: Option<i32>,
     store_size: Option<bool>,
+    dict: Option<BytesType>,
 ) -> PyResult<RustyBuffer> {
     use lz4::{block, block::CompressionMode};
 
     let store_size = store_size.unwrap_or(true);
+    let mode = mode.or(acceleration.map(|_| "fast"));
+    if let Some(dict) = dict {
+        let acceleration = match mode {
+            None | Some("default") => 1,
+            Some("fast") => acceleration.unwrap_or(1),
+            Some(_) => {
+                return Err(CompressionError::new_err(
+                    "dict is only supported with mode='default' or mode='fast'",
+                ))
+            }
+        };
+        let out = internal::compress_block_with_dict(data.as_bytes(), dict.as_bytes(), acceleration);
+        let out = to_py_err!(CompressionError -> out)?;
+        if store_size {
+            let out = to_py_err!(CompressionError -> crate::with_size_prefix(data.len(), &out))?;
+            return Ok(RustyBuffer::from(out));
+        }
+        return Ok(RustyBuffer::from(out));
+    }
     let mode = match mode {
         Some(m) => match m {
             "default" => CompressionMode::DEFAULT,
@@ -113,19 +498,154 @@ pub fn compress_block(
         },
         None => CompressionMode::DEFAULT,
     };
-    let out = to_py_err!(CompressionError -> block::compress(data.as_bytes(), Some(mode), store_size))?;
+    let out = to_py_err!(CompressionError -> block::compress(data.as_bytes(), Some(mode), false))?;
+    if store_size {
+        let out = to_py_err!(CompressionError -> crate::with_size_prefix(data.len(), &out))?;
+        return Ok(RustyBuffer::from(out));
+    }
     Ok(RustyBuffer::from(out))
 }
 
+/// Decompress line by line; returns an iterator over the lines of decompressed data
+/// as `bytes`, without materializing the entire decompressed data. See [`Lines`](../io/struct.RustyLines.html)
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> for line in cramjam.lz4.decompress_lines(compressed_bytes):
+/// ...     print(line)
+/// ```
+#[pyfunction]
+pub fn decompress_lines(mut data: BytesType) -> PyResult<RustyLines> {
+    let mut compressed = vec![];
+    data.read_to_end(&mut compressed)?;
+    let decoder = to_py_err!(DecompressionError -> internal::decoder(Cursor::new(compressed)))?;
+    Ok(RustyLines::new(decoder))
+}
+
+/// Replace the `[start, end)` range of the decompressed data with `replacement`, returning the edited
+/// data compressed again, without returning the decompressed data to Python. Raises `ValueError`
+/// if `start` is greater than `end`, and `DecompressionError` if the range is beyond the end of the data.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.lz4.recompress_range(compressed_bytes, start, end, b'replacement', level=Optional[int])
+/// ```
+#[pyfunction]
+pub fn recompress_range(
+    data: BytesType,
+    start: u64,
+    end: u64,
+    replacement: BytesType,
+    level: Option<u32>,
+) -> PyResult<RustyBuffer> {
+    crate::io::recompress_range(internal::decoder(data), start, end, replacement, |input, output| {
+        internal::compress(input, output, level)
+    })
+}
+
 pub(crate) mod internal {
-    use lz4::{Decoder, EncoderBuilder};
-    use std::io::{Error, Read, Seek, SeekFrom, Write};
+    use crate::io::FinishWrite;
+    use lz4::liblz4::{
+        LZ4StreamEncode, LZ4_compressBound, LZ4_createStream, LZ4_createStreamDecode, LZ4_decompress_safe_continue,
+        LZ4_freeStream, LZ4_freeStreamDecode, LZ4_setStreamDecode,
+    };
+    use lz4::{ContentChecksum, Decoder, Encoder, EncoderBuilder};
+    use std::convert::TryFrom;
+    use std::io::{Error, ErrorKind, Read, Seek, SeekFrom, Write};
+    use std::os::raw::c_int;
+
+    /// lz4's `Decoder` reads a truncated frame as though it ended early, only reporting the
+    /// truncation from `finish`; this raises it as an error from `read` once the input is exhausted.
+    pub struct FrameDecoder<R>(Option<Decoder<R>>);
+
+    impl<R: Read> Read for FrameDecoder<R> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+            let n_bytes = match self.0.as_mut() {
+                Some(decoder) => decoder.read(buf)?,
+                None => return Ok(0),
+            };
+            if n_bytes == 0 && !buf.is_empty() {
+                if let Some(decoder) = self.0.take() {
+                    decoder
+                        .finish()
+                        .1
+                        .map_err(|err| Error::new(ErrorKind::UnexpectedEof, err.to_string()))?;
+                }
+            }
+            Ok(n_bytes)
+        }
+    }
+
+    /// Compression level used when none is given
+    pub(crate) const DEFAULT_LEVEL: u32 = 4;
+
+    /// Highest compression level, of the high compression mode; higher levels are the same as it
+    pub(crate) const MAX_LEVEL: u32 = 12;
+
+    /// `level`, or the default, checked to be at most [`MAX_LEVEL`], rather than clamped to it as lz4 does
+    pub(crate) fn checked_level(level: Option<u32>) -> Result<u32, Error> {
+        crate::checked_level("lz4", level, DEFAULT_LEVEL, 0, MAX_LEVEL)
+    }
+
+    /// Largest frame header without a dictionary id: magic number, FLG, BD, content size and checksum
+    const MAX_FRAME_HEADER_LEN: usize = 15;
+
+    /// The frame's end mark and content checksum
+    const FRAME_FOOTER_LEN: usize = 8;
+
+    /// Each block's length and checksum
+    const BLOCK_OVERHEAD: usize = 8;
+
+    /// Length of the writes `std::io::copy` gives the auto flushing `Encoder`, each made into a block
+    const WRITE_LEN: usize = 8 * 1024;
+
+    /// Largest output of `compress` for `input_len` bytes of input; blocks which don't compress are
+    /// stored as is. `None` if it's too large to be represented.
+    pub fn compress_bound(input_len: usize) -> Option<usize> {
+        let n_blocks = input_len.div_ceil(WRITE_LEN);
+        n_blocks
+            .checked_mul(BLOCK_OVERHEAD)?
+            .checked_add(input_len)?
+            .checked_add(MAX_FRAME_HEADER_LEN + FRAME_FOOTER_LEN)
+    }
+
+    /// Decoder reading compressed data from `input`
+    pub fn decoder<R: Read>(input: R) -> Result<FrameDecoder<R>, Error> {
+        Ok(FrameDecoder(Some(Decoder::new(input)?)))
+    }
+
+    /// Encoder writing compressed data to `output`
+    pub fn encoder<W: Write>(output: W, level: Option<u32>) -> Result<Encoder<W>, Error> {
+        encoder_with_frame_options(output, level, None, true)
+    }
+
+    /// Encoder writing compressed data to `output`, with `content_size`, the length of the data it'll
+    /// be given, if any, in the frame header, and a checksum of that data after the frame's last block
+    /// with `content_checksum`
+    fn encoder_with_frame_options<W: Write>(
+        output: W,
+        level: Option<u32>,
+        content_size: Option<u64>,
+        content_checksum: bool,
+    ) -> Result<Encoder<W>, Error> {
+        let checksum = match content_checksum {
+            true => ContentChecksum::ChecksumEnabled,
+            false => ContentChecksum::NoChecksum,
+        };
+        EncoderBuilder::new()
+            .auto_flush(true)
+            .level(checked_level(level)?)
+            .content_size(content_size.unwrap_or(0))
+            .checksum(checksum)
+            .build(output)
+    }
 
     /// Decompress lz4 data
     pub fn decompress<W: Write + ?Sized, R: Read>(input: R, output: &mut W) -> Result<usize, Error> {
-        let mut decoder = Decoder::new(input)?;
+        let mut decoder = decoder(input)?;
         let n_bytes = std::io::copy(&mut decoder, output)?;
-        decoder.finish().1?;
         Ok(n_bytes as usize)
     }
 
@@ -134,12 +654,21 @@ pub(crate) mod internal {
         input: &mut R,
         output: &mut W,
         level: Option<u32>,
+    ) -> Result<usize, Error> {
+        compress_with_frame_options(input, output, level, None, true)
+    }
+
+    /// Compress lz4 data, with `content_size`, the length of `input`, if any, in the frame header,
+    /// and a checksum of `input` after the frame's last block with `content_checksum`
+    pub fn compress_with_frame_options<W: Write + ?Sized + Seek, R: Read>(
+        input: &mut R,
+        output: &mut W,
+        level: Option<u32>,
+        content_size: Option<u64>,
+        content_checksum: bool,
     ) -> Result<usize, Error> {
         let start_pos = output.seek(SeekFrom::Current(0))?;
-        let mut encoder = EncoderBuilder::new()
-            .auto_flush(true)
-            .level(level.unwrap_or_else(|| 4))
-            .build(output)?;
+        let mut encoder = encoder_with_frame_options(output, level, content_size, content_checksum)?;
 
         // this returns, bytes read from uncompressed, input; we want bytes written
         // but lz4 only implements Read for Encoder
@@ -149,4 +678,96 @@ pub(crate) mod internal {
         let ending_pos = w.seek(SeekFrom::Current(0))?;
         Ok((ending_pos - start_pos) as usize)
     }
+
+    impl<W: Write + Send> FinishWrite for Encoder<W> {
+        fn finish(self: Box<Self>) -> Result<(), Error> {
+            let (mut output, result) = Encoder::finish(*self);
+            result?;
+            output.flush()
+        }
+    }
+
+    /// Number of bytes at the end of a dictionary which lz4 blocks can refer back into
+    const DICT_WINDOW: usize = 64 * 1024;
+
+    fn block_len(len: usize) -> Result<c_int, Error> {
+        c_int::try_from(len).map_err(|_| Error::new(ErrorKind::InvalidInput, "Input too large for an lz4 block"))
+    }
+
+    // part of liblz4's stable API, which lz4-sys doesn't declare
+    extern "C" {
+        fn LZ4_loadDict(stream: *mut LZ4StreamEncode, dictionary: *const u8, dict_size: c_int) -> c_int;
+        fn LZ4_compress_fast_continue(
+            stream: *mut LZ4StreamEncode,
+            source: *const u8,
+            dest: *mut u8,
+            input_size: c_int,
+            dest_capacity: c_int,
+            acceleration: c_int,
+        ) -> c_int;
+    }
+
+    /// Compress `input` as an lz4 block which may refer back into `dict`, as though it followed
+    /// the last 64KiB of it, with `acceleration` as for the fast mode, 1 being the default mode;
+    /// decompress it with [`decompress_block_with_dict`] and the same `dict`.
+    pub fn compress_block_with_dict(input: &[u8], dict: &[u8], acceleration: i32) -> Result<Vec<u8>, Error> {
+        let dict = &dict[dict.len().saturating_sub(DICT_WINDOW)..];
+        let (input_len, dict_len) = (block_len(input.len())?, block_len(dict.len())?);
+        let bound = unsafe { LZ4_compressBound(input_len) };
+        let mut output = vec![0; bound as usize];
+        let n_bytes = unsafe {
+            let stream = LZ4_createStream();
+            if stream.is_null() {
+                return Err(Error::other("Unable to create an lz4 stream"));
+            }
+            // the dictionary is referenced rather than copied, as the history the input's block refers back into
+            LZ4_loadDict(stream, dict.as_ptr(), dict_len);
+            let n_bytes = LZ4_compress_fast_continue(
+                stream,
+                input.as_ptr(),
+                output.as_mut_ptr(),
+                input_len,
+                bound,
+                acceleration,
+            );
+            LZ4_freeStream(stream);
+            n_bytes
+        };
+        if n_bytes <= 0 {
+            return Err(Error::other("Unable to compress the lz4 block"));
+        }
+        output.truncate(n_bytes as usize);
+        Ok(output)
+    }
+
+    /// Decompress a block from [`compress_block_with_dict`], of at most `max_len` bytes
+    pub fn decompress_block_with_dict(input: &[u8], dict: &[u8], max_len: usize) -> Result<Vec<u8>, Error> {
+        let dict = &dict[dict.len().saturating_sub(DICT_WINDOW)..];
+        let (input_len, dict_len) = (block_len(input.len())?, block_len(dict.len())?);
+        let mut output = vec![0; max_len];
+        let n_bytes = unsafe {
+            let stream = LZ4_createStreamDecode();
+            if stream.is_null() {
+                return Err(Error::other("Unable to create an lz4 stream"));
+            }
+            LZ4_setStreamDecode(stream, dict.as_ptr(), dict_len);
+            let n_bytes = LZ4_decompress_safe_continue(
+                stream,
+                input.as_ptr(),
+                output.as_mut_ptr(),
+                input_len,
+                block_len(max_len)?,
+            );
+            LZ4_freeStreamDecode(stream);
+            n_bytes
+        };
+        if n_bytes < 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Invalid lz4 block; was it compressed with the same dict?",
+            ));
+        }
+        output.truncate(n_bytes as usize);
+        Ok(output)
+    }
 }
@@ -0,0 +1,129 @@
+//! xz/LZMA de/compression interface, of the `.xz` format written by the `xz` command
+use crate::exceptions::{CompressionError, DecompressionError};
+use crate::io::RustyBuffer;
+use crate::{to_py_err, BytesType};
+use pyo3::prelude::*;
+use pyo3::wrap_pyfunction;
+use pyo3::PyResult;
+use std::io::Cursor;
+
+pub(crate) fn init_py_module(m: &PyModule) -> PyResult<()> {
+    m.add("DEFAULT_LEVEL", internal::DEFAULT_LEVEL)?;
+    m.add("MAX_LEVEL", internal::MAX_LEVEL)?;
+    m.add_function(wrap_pyfunction!(compress, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_bound, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_into, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_into, m)?)?;
+    Ok(())
+}
+
+/// xz decompression.
+///
+/// Concatenated `.xz` streams, ie. as written by `xz` appending to an existing file, are decompressed
+/// one after the other.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.lzma.decompress(compressed_bytes, output_len=Optional[int])
+/// ```
+#[pyfunction]
+pub fn decompress(data: BytesType, output_len: Option<usize>) -> PyResult<RustyBuffer> {
+    crate::generic!(decompress(data), output_len = output_len)
+}
+
+/// xz compression.
+///
+/// `level` is the LZMA preset, from 0 to 9; it defaults to 6, like the `xz` command, when `None`
+/// or omitted. `CompressionError` is raised for any other level.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.lzma.compress(b'some bytes here', level=6, output_len=Optional[int])
+/// ```
+#[pyfunction]
+pub fn compress(data: BytesType, level: Option<u32>, output_len: Option<usize>) -> PyResult<RustyBuffer> {
+    to_py_err!(CompressionError -> internal::checked_level(level))?;
+    crate::generic!(compress(data), output_len = output_len, level = level)
+}
+
+/// Get the maximum length of the compressed output for `data_len` bytes of input, the size
+/// of buffer which is always big enough to be passed to `compress_into`
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> output = bytearray(cramjam.lzma.compress_bound(len(data)))
+/// >>> n_bytes = cramjam.lzma.compress_into(data, output)
+/// ```
+#[pyfunction]
+pub fn compress_bound(data_len: usize) -> PyResult<usize> {
+    crate::checked_bound(internal::compress_bound(data_len), data_len)
+}
+
+/// Compress directly into an output buffer, returning the compressed length; `CompressionError` is
+/// raised with `compress_bound` of the input's length if the buffer is too small to hold it.
+#[pyfunction]
+pub fn compress_into(input: BytesType, output: BytesType, level: Option<u32>) -> PyResult<usize> {
+    crate::compress_into(input, output, internal::compress_bound, |input, output| {
+        internal::compress(input, output, level)
+    })
+}
+
+/// Decompress directly into an output buffer
+#[pyfunction]
+pub fn decompress_into(input: BytesType, mut output: BytesType) -> PyResult<usize> {
+    output.check_writable()?;
+    let r = to_py_err!(DecompressionError -> internal::decompress(input, &mut output))?;
+    Ok(r)
+}
+
+pub(crate) mod internal {
+    use std::io::prelude::*;
+    use std::io::Error;
+    use xz2::read::{XzDecoder, XzEncoder};
+
+    /// Compression level used when none is given
+    pub(crate) const DEFAULT_LEVEL: u32 = 6;
+
+    /// Highest compression level
+    pub(crate) const MAX_LEVEL: u32 = 9;
+
+    /// `level`, or the default, checked to be at most [`MAX_LEVEL`]
+    pub(crate) fn checked_level(level: Option<u32>) -> Result<u32, Error> {
+        crate::checked_level("lzma", level, DEFAULT_LEVEL, 0, MAX_LEVEL)
+    }
+
+    /// Largest output of `compress` for `input_len` bytes of input, as by liblzma's
+    /// `lzma_stream_buffer_bound`; should it not compress, a single block of LZMA2 chunks of up to
+    /// 64KiB stored as is. `None` if it's too large to be represented.
+    pub fn compress_bound(input_len: usize) -> Option<usize> {
+        const CHUNK_MAX: usize = 1 << 16;
+        let chunks = input_len.checked_add(CHUNK_MAX - 1)? / CHUNK_MAX;
+        let lzma2_len = input_len.checked_add(chunks.checked_mul(3)?)?.checked_add(1)?;
+        // padded to 4 bytes, then the block's header and check, and the stream's header, footer and index
+        (lzma2_len.checked_add(3)? & !3).checked_add(92 + 48)
+    }
+
+    /// Decoder reading compressed data from `input`, through any concatenated streams
+    pub fn decoder<R: Read>(input: R) -> Result<XzDecoder<R>, Error> {
+        Ok(XzDecoder::new_multi_decoder(input))
+    }
+
+    /// Decompress xz data
+    pub fn decompress<W: Write + ?Sized, R: Read>(input: R, output: &mut W) -> Result<usize, Error> {
+        let mut decoder = decoder(input)?;
+        let n_bytes = std::io::copy(&mut decoder, output)?;
+        Ok(n_bytes as usize)
+    }
+
+    /// Compress xz data
+    pub fn compress<W: Write + ?Sized, R: Read>(input: R, output: &mut W, level: Option<u32>) -> Result<usize, Error> {
+        let level = checked_level(level)?;
+        let mut encoder = XzEncoder::new(input, level);
+        let n_bytes = std::io::copy(&mut encoder, output)?;
+        Ok(n_bytes as usize)
+    }
+}
@@ -0,0 +1,113 @@
+//! Splitting of length-prefixed records, as packed by record oriented formats, ie. Hadoop's
+//! sequence files, into views of each record without copying them.
+use crate::io::AsBytes;
+use crate::BytesType;
+use pyo3::prelude::*;
+use pyo3::types::PySlice;
+use pyo3::wrap_pyfunction;
+use pyo3::{ffi, AsPyPointer};
+use std::convert::TryFrom;
+use std::ops::Range;
+
+pub(crate) fn init_py_module(m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(split_records, m)?)?;
+    Ok(())
+}
+
+/// Names accepted by `split_records`'s `prefix`
+const PREFIXES: &[&str] = &["u16le", "u32le", "u64le", "varint"];
+
+/// Split `data`, records each prefixed with its length, into a `memoryview` of each record, which
+/// refer to `data` rather than copy it. `prefix` is the encoding of the lengths; one of `"u16le"`,
+/// `"u32le"` (the default) and `"u64le"`, unsigned little endian integers of that many bits, or
+/// `"varint"`, the unsigned LEB128 of protobuf. Raises `ValueError` if the data ends part way into
+/// a length or record, or for an unknown `prefix`. `data` must support the buffer protocol.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> records = cramjam.split_records(b'\x02\x00\x00\x00hi\x05\x00\x00\x00there', prefix="u32le")
+/// >>> [bytes(record) for record in records]
+/// [b'hi', b'there']
+/// ```
+#[pyfunction]
+pub fn split_records<'a>(py: Python<'a>, data: BytesType<'a>, prefix: Option<&str>) -> PyResult<Vec<&'a PyAny>> {
+    let prefix = prefix.unwrap_or("u32le");
+    let ranges = match prefix {
+        "u16le" => record_ranges(data.as_bytes(), |bytes| fixed_len(bytes, 2)),
+        "u32le" => record_ranges(data.as_bytes(), |bytes| fixed_len(bytes, 4)),
+        "u64le" => record_ranges(data.as_bytes(), |bytes| fixed_len(bytes, 8)),
+        "varint" => record_ranges(data.as_bytes(), varint_len),
+        _ => {
+            let names = PREFIXES.iter().map(|name| format!("'{}'", name)).collect::<Vec<_>>();
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Unknown prefix '{}', should be one of {}",
+                prefix,
+                names.join(", ")
+            )));
+        }
+    }
+    .map_err(pyo3::exceptions::PyValueError::new_err)?;
+
+    let data = data.into_py(py);
+    let view: &PyAny = unsafe { py.from_owned_ptr_or_err(ffi::PyMemoryView_FromObject(data.as_ptr()))? };
+    ranges
+        .into_iter()
+        .map(|range| view.get_item(PySlice::new(py, range.start as isize, range.end as isize, 1)))
+        .collect()
+}
+
+/// Decoded length and the length of its prefix, from the prefix at the start of `bytes`, or `None`
+/// if `bytes` ends before the prefix does
+type PrefixDecoder = fn(&[u8]) -> Result<Option<(u64, usize)>, String>;
+
+/// Ranges of each record in `data`, with their lengths decoded by `decode_prefix`
+fn record_ranges(data: &[u8], decode_prefix: PrefixDecoder) -> Result<Vec<Range<usize>>, String> {
+    let mut ranges = vec![];
+    let mut pos = 0;
+    while pos < data.len() {
+        let (len, prefix_len) =
+            decode_prefix(&data[pos..])?.ok_or_else(|| format!("Truncated record length at offset {}", pos))?;
+        let start = pos + prefix_len;
+        let end = usize::try_from(len)
+            .ok()
+            .and_then(|len| start.checked_add(len))
+            .filter(|end| *end <= data.len())
+            .ok_or_else(|| {
+                format!(
+                    "Truncated record at offset {}, of {} bytes with {} remaining",
+                    pos,
+                    len,
+                    data.len() - start
+                )
+            })?;
+        ranges.push(start..end);
+        pos = end;
+    }
+    Ok(ranges)
+}
+
+/// Little endian unsigned integer of `len` bytes
+fn fixed_len(bytes: &[u8], len: usize) -> Result<Option<(u64, usize)>, String> {
+    Ok(bytes.get(..len).map(|prefix| {
+        (
+            prefix.iter().rev().fold(0u64, |value, byte| value << 8 | *byte as u64),
+            len,
+        )
+    }))
+}
+
+/// Unsigned LEB128, of up to 10 bytes for a u64
+fn varint_len(bytes: &[u8]) -> Result<Option<(u64, usize)>, String> {
+    let mut value = 0u64;
+    for (i, byte) in bytes.iter().enumerate() {
+        if i == 9 && *byte > 1 {
+            return Err("Invalid varint record length, longer than 64 bits".to_string());
+        }
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(Some((value, i + 1)));
+        }
+    }
+    Ok(None)
+}
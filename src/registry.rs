@@ -0,0 +1,381 @@
+//! Generic de/compression by algorithm name, ie. `cramjam.compress(data, "zstd")`, dispatching
+//! to the submodule of that name or to a codec registered from Python with
+//! [`register_codec`](fn.register_codec.html).
+use crate::exceptions::{CompressionError, DecompressionError};
+use crate::io::RustyBuffer;
+use crate::{to_py_err, BytesType};
+use pyo3::once_cell::GILOnceCell;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyTuple};
+use pyo3::wrap_pyfunction;
+use pyo3::{PyNativeType, PyObjectProtocol};
+use std::convert::TryFrom;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+/// Names of the algorithms implemented by cramjam, each a submodule; an algorithm's index is its
+/// id in the header of [`compress_tagged`](fn.compress_tagged.html), so new ones go at the end.
+pub(crate) const BUILTIN_ALGORITHMS: &[&str] = &["snappy", "brotli", "lz4", "gzip", "deflate", "zstd"];
+
+/// Names of the other algorithms implemented by cramjam, each a submodule too, which
+/// [`compress_tagged`](fn.compress_tagged.html) has no id for and `recommend_algorithm` doesn't try
+pub(crate) const OTHER_ALGORITHMS: &[&str] = &["bzip2", "lzma", "zlib"];
+
+/// Algorithm of `cramjam.compress` when none is given
+const DEFAULT_ALGORITHM: &str = "zstd";
+
+/// Algorithm name -> `(compress, decompress)` callables
+static CODECS: GILOnceCell<Py<PyDict>> = GILOnceCell::new();
+
+fn codecs(py: Python<'_>) -> &PyDict {
+    CODECS.get_or_init(py, || PyDict::new(py).into()).as_ref(py)
+}
+
+fn codec(py: Python, algorithm: &str) -> PyResult<(PyObject, PyObject)> {
+    match codecs(py).get_item(algorithm) {
+        Some(codec) => codec.extract(),
+        None => {
+            let names = codecs(py)
+                .keys()
+                .iter()
+                .map(|name| format!("'{}'", name))
+                .collect::<Vec<_>>();
+            Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Unknown algorithm '{}', should be one of {}",
+                algorithm,
+                names.join(", ")
+            )))
+        }
+    }
+}
+
+/// The `cramjam` module, whose submodules `decompress` dispatches to by the format it detects
+static MODULE: GILOnceCell<Py<PyModule>> = GILOnceCell::new();
+
+/// Register the built-in algorithms' submodules of `m`, and add the generic functions to it.
+pub(crate) fn init_py_module(py: Python, m: &PyModule) -> PyResult<()> {
+    let _ = MODULE.set(py, m.into());
+    for name in BUILTIN_ALGORITHMS.iter().chain(OTHER_ALGORITHMS) {
+        let submodule = m.getattr(name)?;
+        let codec = (submodule.getattr("compress")?, submodule.getattr("decompress")?);
+        codecs(py).set_item(name, codec)?;
+    }
+    m.add_function(wrap_pyfunction!(compress, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress, m)?)?;
+    m.add_function(wrap_pyfunction!(register_codec, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_tagged, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_tagged, m)?)?;
+    m.add_class::<Pipeline>()?;
+    Ok(())
+}
+
+/// Compress with the algorithm of the given name, by default `"zstd"`; any keyword arguments,
+/// ie. `level` or `output_len`, are passed through to that algorithm's `compress`. Each of the
+/// submodules is an algorithm, as is any codec registered with `register_codec`; `ValueError`
+/// is raised, listing them, for any other name.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.compress(b'some bytes here', "zstd", level=3)
+/// >>> cramjam.compress(b'some bytes here', algorithm=config["codec"], level=Optional[int], output_len=Optional[int])
+/// ```
+#[pyfunction(algorithm = "None", kwargs = "**")]
+pub fn compress(py: Python, data: &PyAny, algorithm: Option<&str>, kwargs: Option<&PyDict>) -> PyResult<PyObject> {
+    let (compress, _) = codec(py, algorithm.unwrap_or(DEFAULT_ALGORITHM))?;
+    compress.call(py, (data,), kwargs)
+}
+
+/// Decompress with the algorithm of the given name; any keyword arguments are passed
+/// through to that algorithm's `decompress`.
+///
+/// Without an algorithm, it's detected from the magic number at the start of the data, for gzip,
+/// zstd, lz4 and snappy frames, bzip2 and xz; data with none is tried as zlib, should it start with
+/// a zlib header, then as brotli. Raises `DecompressionError` if none of them decompress it.
+/// The data is read from its current position, which is only moved by decompressing it.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.decompress(compressed_bytes, "zstd")
+/// >>> cramjam.decompress(cramjam.gzip.compress(b'some bytes here'), output_len=Optional[int])
+/// ```
+#[pyfunction(algorithm = "None", kwargs = "**")]
+pub fn decompress(py: Python, data: &PyAny, algorithm: Option<&str>, kwargs: Option<&PyDict>) -> PyResult<PyObject> {
+    if let Some(algorithm) = algorithm {
+        let (_, decompress) = codec(py, algorithm)?;
+        return decompress.call(py, (data,), kwargs);
+    }
+
+    let mut bytes: BytesType = data.extract()?;
+    let sample = crate::detect::read_sample(&mut bytes, crate::detect::MAX_HEADER_LEN)?;
+    let algorithms = crate::detect::detect_algorithms(&sample);
+    let position = bytes.stream_position()?;
+    let module = MODULE.get(py).expect("cramjam module is initialized").as_ref(py);
+    for algorithm in algorithms {
+        // a failed attempt may have read part of a file
+        bytes.seek(SeekFrom::Start(position))?;
+        let result = module.getattr(algorithm)?.getattr("decompress")?.call((data,), kwargs);
+        match result {
+            Err(err) if err.is_instance::<DecompressionError>(py) && crate::detect::magic_number(&sample).is_none() => {
+                continue
+            }
+            result => return result.map(|output| output.into()),
+        }
+    }
+    Err(DecompressionError::new_err(
+        match crate::detect::magic_number(&sample) {
+            Some(format) => format!("Detected {} data, which cramjam can't decompress", format),
+            None => "Unable to detect the algorithm of the data; it starts with no magic number of gzip, zstd, lz4 \
+                 or snappy frames, bzip2 or xz, and doesn't decompress as zlib or brotli"
+                .to_string(),
+        },
+    ))
+}
+
+/// Register a codec implemented in Python, making it available to the generic
+/// `cramjam.compress`/`cramjam.decompress` under `name`. `compress_fn` and `decompress_fn`
+/// are called with the data and any keyword arguments given. Registering an existing custom
+/// codec's name replaces it; the built-in algorithms can't be replaced.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.register_codec("reverse", lambda data: bytes(data)[::-1], lambda data: bytes(data)[::-1])
+/// >>> cramjam.compress(b'some bytes here', "reverse")
+/// b'ereh setyb emos'
+/// ```
+#[pyfunction]
+pub fn register_codec(py: Python, name: &str, compress_fn: PyObject, decompress_fn: PyObject) -> PyResult<()> {
+    if BUILTIN_ALGORITHMS.contains(&name) || OTHER_ALGORITHMS.contains(&name) {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "Cannot replace the built-in algorithm '{}'",
+            name
+        )));
+    }
+    for f in [&compress_fn, &decompress_fn].iter() {
+        if !f.as_ref(py).is_callable() {
+            return Err(pyo3::exceptions::PyTypeError::new_err(format!(
+                "Codec functions must be callable, got {}",
+                f.as_ref(py).get_type().name()?
+            )));
+        }
+    }
+    codecs(py).set_item(name, (compress_fn, decompress_fn))
+}
+
+/// Magic number at the start of the header written by [`compress_tagged`](fn.compress_tagged.html)
+const TAGGED_MAGIC: &[u8; 4] = b"CJTG";
+
+/// Length of the header written by [`compress_tagged`](fn.compress_tagged.html); its magic number,
+/// [`FRAME_VERSION`](../constant.FRAME_VERSION.html), the algorithm's id and the level
+const TAGGED_HEADER_LEN: usize = 7;
+
+/// Level byte recorded when compressing without a level
+const TAGGED_NO_LEVEL: i8 = i8::MIN;
+
+/// Compress with the built-in algorithm of the given name, prefixing the output with a header
+/// recording the algorithm and `level`, so `decompress_tagged` needs neither; the header is a
+/// magic number, `cramjam.FRAME_VERSION`, the algorithm's id and the level as a signed byte.
+/// Custom codecs can't be tagged, and snappy has no levels.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> compressed = cramjam.compress_tagged(b'some bytes here', "zstd", level=Optional[int])
+/// >>> bytes(cramjam.decompress_tagged(compressed))
+/// b'some bytes here'
+/// ```
+#[pyfunction]
+pub fn compress_tagged(mut data: BytesType, algorithm: &str, level: Option<i32>) -> PyResult<RustyBuffer> {
+    let id = BUILTIN_ALGORITHMS
+        .iter()
+        .position(|name| *name == algorithm)
+        .ok_or_else(|| {
+            let names = BUILTIN_ALGORITHMS
+                .iter()
+                .map(|name| format!("'{}'", name))
+                .collect::<Vec<_>>();
+            pyo3::exceptions::PyValueError::new_err(format!(
+                "Unknown algorithm '{}' for compress_tagged, should be one of {}",
+                algorithm,
+                names.join(", ")
+            ))
+        })?;
+    let level_byte = match level {
+        None => TAGGED_NO_LEVEL,
+        Some(_) if algorithm == "snappy" => return Err(CompressionError::new_err("snappy has no levels")),
+        Some(level) => match i8::try_from(level) {
+            Ok(byte) if byte != TAGGED_NO_LEVEL => byte,
+            _ => {
+                return Err(CompressionError::new_err(format!(
+                    "Level {} can't be recorded in the tagged header",
+                    level
+                )))
+            }
+        },
+    };
+    let unsigned_level = level
+        .map(u32::try_from)
+        .transpose()
+        .map_err(|_| CompressionError::new_err(format!("{} levels can't be negative", algorithm)));
+
+    let mut output = Cursor::new(vec![]);
+    output.write_all(TAGGED_MAGIC)?;
+    output.write_all(&[crate::FRAME_VERSION, id as u8, level_byte as u8])?;
+    match algorithm {
+        "snappy" => to_py_err!(CompressionError -> crate::snappy::internal::compress(data, &mut output, None)),
+        "brotli" => {
+            to_py_err!(CompressionError -> crate::brotli::internal::compress(data, &mut output, unsigned_level?))
+        }
+        "lz4" => to_py_err!(CompressionError -> crate::lz4::internal::compress(&mut data, &mut output, unsigned_level?)),
+        "gzip" => to_py_err!(CompressionError -> crate::gzip::internal::compress(data, &mut output, unsigned_level?)),
+        "deflate" => {
+            to_py_err!(CompressionError -> crate::deflate::internal::compress(data, &mut output, unsigned_level?))
+        }
+        "zstd" => {
+            let content_size = Some(data.remaining_len()?);
+            let options = crate::zstd::internal::CompressOptions {
+                level,
+                content_size,
+                ..Default::default()
+            };
+            to_py_err!(CompressionError -> crate::zstd::internal::compress(data, &mut output, &options))
+        }
+        _ => unreachable!("not a built-in algorithm"),
+    }?;
+    Ok(RustyBuffer::from(output.into_inner()))
+}
+
+/// Decompress the output of `compress_tagged`, with the algorithm recorded in its header.
+/// Raises `DecompressionError` if the header is missing, or records an unknown algorithm.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.decompress_tagged(compressed_bytes)
+/// ```
+#[pyfunction]
+pub fn decompress_tagged(mut data: BytesType) -> PyResult<RustyBuffer> {
+    let mut header = [0; TAGGED_HEADER_LEN];
+    if data.read_exact(&mut header).is_err() || &header[..4] != TAGGED_MAGIC {
+        return Err(DecompressionError::new_err(
+            "Missing tagged header, was the data compressed with compress_tagged?",
+        ));
+    }
+    to_py_err!(DecompressionError -> crate::check_frame_version(header[4]))?;
+    let algorithm = BUILTIN_ALGORITHMS.get(header[5] as usize).ok_or_else(|| {
+        DecompressionError::new_err(format!(
+            "Unknown algorithm id {} in the tagged header; was it written by a newer version of cramjam?",
+            header[5]
+        ))
+    })?;
+
+    let mut output = vec![];
+    match *algorithm {
+        "snappy" => to_py_err!(DecompressionError -> crate::snappy::internal::decompress(data, &mut output)),
+        "brotli" => to_py_err!(DecompressionError -> crate::brotli::internal::decompress(data, &mut output)),
+        "lz4" => to_py_err!(DecompressionError -> crate::lz4::internal::decompress(data, &mut output)),
+        "gzip" => to_py_err!(DecompressionError -> crate::gzip::internal::decompress(data, &mut output)),
+        "deflate" => to_py_err!(DecompressionError -> crate::deflate::internal::decompress(data, &mut output)),
+        "zstd" => to_py_err!(DecompressionError -> crate::zstd::internal::decompress(data, &mut output)),
+        _ => unreachable!("not a built-in algorithm"),
+    }?;
+    Ok(RustyBuffer::from(output))
+}
+
+/// A stage of a [`Pipeline`](struct.Pipeline.html); see there for the forms accepted from Python
+pub struct Stage {
+    name: String,
+    compress: PyObject,
+    decompress: PyObject,
+    compress_kwargs: Option<Py<PyDict>>,
+    decompress_kwargs: Option<Py<PyDict>>,
+}
+
+impl<'a> FromPyObject<'a> for Stage {
+    fn extract(ob: &'a PyAny) -> PyResult<Self> {
+        let (name, compress_kwargs, decompress_kwargs) = match ob.extract::<String>() {
+            Ok(name) => (name, None, None),
+            Err(_) => {
+                let stage: &PyTuple = ob.extract()?;
+                match stage.len() {
+                    2 => (stage.get_item(0).extract()?, stage.get_item(1).extract()?, None),
+                    3 => (
+                        stage.get_item(0).extract()?,
+                        stage.get_item(1).extract()?,
+                        stage.get_item(2).extract()?,
+                    ),
+                    _ => return Err(pyo3::exceptions::PyValueError::new_err(
+                        "Pipeline stages should be a name, or a tuple of (name, compress_kwargs[, decompress_kwargs])",
+                    )),
+                }
+            }
+        };
+        let (compress, decompress) = codec(ob.py(), &name)?;
+        Ok(Self {
+            name,
+            compress,
+            decompress,
+            compress_kwargs,
+            decompress_kwargs,
+        })
+    }
+}
+
+/// A sequence of de/compression stages by algorithm name, including any registered codecs;
+/// `compress` applies each stage in order, and `decompress` reverses them.
+///
+/// Each stage is either the algorithm's name, or a tuple of its name and a `dict` of keyword
+/// arguments for its `compress`, optionally followed by a `dict` for its `decompress`.
+///
+/// ### Python Example
+/// ```python
+/// >>> pipeline = cramjam.Pipeline([("gzip", {"level": 1}), "zstd"])
+/// >>> compressed = pipeline.compress(b"some bytes here")
+/// >>> bytes(pipeline.decompress(compressed))
+/// b'some bytes here'
+/// ```
+#[pyclass]
+pub struct Pipeline {
+    stages: Vec<Stage>,
+}
+
+#[pymethods]
+impl Pipeline {
+    /// Instantiate the pipeline from its stages; raises `ValueError` for an unknown algorithm.
+    #[new]
+    pub fn __init__(stages: Vec<Stage>) -> PyResult<Self> {
+        if stages.is_empty() {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "Pipeline requires at least one stage",
+            ));
+        }
+        Ok(Self { stages })
+    }
+    /// Compress `data` with each stage in order
+    pub fn compress(&self, py: Python, data: PyObject) -> PyResult<PyObject> {
+        self.stages.iter().try_fold(data, |data, stage| {
+            let kwargs = stage.compress_kwargs.as_ref().map(|kwargs| kwargs.as_ref(py));
+            stage.compress.call(py, (data,), kwargs)
+        })
+    }
+    /// Decompress `data` with each stage in reverse order
+    pub fn decompress(&self, py: Python, data: PyObject) -> PyResult<PyObject> {
+        self.stages.iter().rev().try_fold(data, |data, stage| {
+            let kwargs = stage.decompress_kwargs.as_ref().map(|kwargs| kwargs.as_ref(py));
+            stage.decompress.call(py, (data,), kwargs)
+        })
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for Pipeline {
+    fn __repr__(&self) -> String {
+        let names = self
+            .stages
+            .iter()
+            .map(|stage| format!("'{}'", stage.name))
+            .collect::<Vec<_>>();
+        format!("cramjam.Pipeline(stages=[{}])", names.join(", "))
+    }
+}
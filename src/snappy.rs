@@ -1,101 +1,461 @@
 //! snappy de/compression interface
 use crate::exceptions::{CompressionError, DecompressionError};
-use crate::io::{AsBytes, RustyBuffer};
+use crate::io::{AsBytes, RustyBuffer, RustyLines, RustyStridedNumpyArray};
 use crate::{to_py_err, BytesType};
+use numpy::PyArray1;
 use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyString};
 use pyo3::wrap_pyfunction;
 use pyo3::PyResult;
-use std::io::Cursor;
+use std::io::{Cursor, Read};
 
 pub(crate) fn init_py_module(m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(compress, m)?)?;
     m.add_function(wrap_pyfunction!(decompress, m)?)?;
     m.add_function(wrap_pyfunction!(compress_raw, m)?)?;
     m.add_function(wrap_pyfunction!(decompress_raw, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_bound, m)?)?;
     m.add_function(wrap_pyfunction!(compress_into, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_reuse, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_strided, m)?)?;
     m.add_function(wrap_pyfunction!(decompress_into, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_to_memmap, m)?)?;
+    #[cfg(unix)]
+    m.add_function(wrap_pyfunction!(compress_fd, m)?)?;
+    #[cfg(unix)]
+    m.add_function(wrap_pyfunction!(decompress_fd, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_lines, m)?)?;
+    m.add_function(wrap_pyfunction!(recompress_range, m)?)?;
     m.add_function(wrap_pyfunction!(compress_raw_into, m)?)?;
     m.add_function(wrap_pyfunction!(decompress_raw_into, m)?)?;
     m.add_function(wrap_pyfunction!(compress_raw_max_len, m)?)?;
     m.add_function(wrap_pyfunction!(decompress_raw_len, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_len, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_str, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_str, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_many, m)?)?;
+    m.add_function(wrap_pyfunction!(is_valid, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_many_into, m)?)?;
+    m.add_function(wrap_pyfunction!(maybe_compress, m)?)?;
+    m.add_function(wrap_pyfunction!(maybe_decompress, m)?)?;
+    m.add_function(wrap_pyfunction!(peek, m)?)?;
     Ok(())
 }
 
 /// Snappy decompression.
 ///
+/// With `recover=True`, decoding stops at the first corrupted or truncated section of the input
+/// rather than raising; a tuple of the `Buffer` decompressed up to that point, and whether
+/// the input was found to be damaged, is returned instead.
+///
+/// With `timeout_ms`, `DecompressionError` is raised if decompression takes longer than that
+/// many milliseconds, ie. to limit the time spent on untrusted input.
+///
+/// With `pad_to`, the input is taken to be the padded output of `compress` with the same `pad_to`.
+///
+/// With `trailer_checksum`, the trailer appended by `compress` with the same `trailer_checksum` is
+/// checked before decompressing, raising `DecompressionError` if the compressed data was corrupted.
+///
+/// With `exact=True`, and no `output_len`, the data is decompressed twice, first only to count its
+/// decompressed length, so the output is allocated once rather than grown as it's decompressed.
+///
+/// `format` is that of `compress`; `"framed"`, the default, or `"raw"`, a single snappy block
+/// without the stream framing, as in Parquet files. A raw block records its decompressed length,
+/// which is used when `output_len` isn't given; `recover`, `timeout_ms` and `exact` only apply to
+/// the framed format, raising `ValueError` otherwise.
+///
 /// Python Example
 /// --------------
 /// ```python
 /// >>> # bytes or bytearray; bytearray is faster
-/// >>> cramjam.snappy.decompress(compressed_bytes, output_len=Optional[None])
+/// >>> cramjam.snappy.decompress(compressed_bytes, output_len=Optional[None], recover=False, timeout_ms=Optional[int], pad_to=Optional[int], trailer_checksum=Optional[str], exact=False)
+/// >>> cramjam.snappy.decompress(compressed_raw_bytes, format="raw")
 /// ```
 #[pyfunction]
-pub fn decompress(data: BytesType, output_len: Option<usize>) -> PyResult<RustyBuffer> {
-    crate::generic!(decompress(data), output_len = output_len)
+#[allow(clippy::too_many_arguments)]
+pub fn decompress(
+    py: Python,
+    mut data: BytesType,
+    output_len: Option<usize>,
+    recover: Option<bool>,
+    timeout_ms: Option<u64>,
+    pad_to: Option<usize>,
+    trailer_checksum: Option<&str>,
+    exact: Option<bool>,
+    format: Option<&str>,
+) -> PyResult<PyObject> {
+    let recover = recover.unwrap_or(false);
+    let raw = is_raw(format)?;
+    if raw && (recover || timeout_ms.is_some() || exact.unwrap_or(false)) {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "recover, timeout_ms and exact only apply to the framed format",
+        ));
+    }
+    let compressed = crate::unpadded(&mut data, pad_to)?;
+    let mut compressed = crate::TrailerChecked::new(compressed, trailer_checksum)?;
+    if raw {
+        let mut input = vec![];
+        compressed.read_to_end(&mut input)?;
+        let input = BytesType::RustyBuffer(PyCell::new(py, RustyBuffer::from(input))?);
+        return decompress_raw(input, output_len, None).map(|buffer| buffer.into_py(py));
+    }
+    if recover || timeout_ms.is_some() {
+        return crate::decompress_with(py, internal::decoder(compressed), recover, timeout_ms, false);
+    }
+    if exact.unwrap_or(false) && output_len.is_none() {
+        return crate::decompress_exact(compressed, |input, output| internal::decompress(input, output))
+            .map(|buffer| buffer.into_py(py));
+    }
+    crate::generic!(decompress(compressed), output_len = output_len).map(|buffer: RustyBuffer| buffer.into_py(py))
 }
 
 /// Snappy compression.
 ///
+/// `chunk_size` sets the maximum number of uncompressed bytes in each chunk of the frame,
+/// for readers with stricter limits than the format's, and defaults to the maximum of 65536.
+///
+/// With `pad_to`, the output is padded with zeros to a multiple of that many bytes, hiding its exact
+/// length, ie. before encrypting it; decompress it with the same `pad_to`.
+///
+/// `trailer_checksum="crc32"`, or `"xxh3"` which is faster for large outputs, appends a checksum
+/// of the compressed data, before any padding, so corruption in transport is caught before
+/// decompressing; decompress it with the same `trailer_checksum`.
+///
+/// `format` is `"framed"`, the default, for the snappy stream format, or `"raw"` for a single
+/// snappy block without its framing, as in Parquet files and the snappy codecs of many Java tools;
+/// the two aren't compatible, so decompress it with the same `format`. `chunk_size` only applies
+/// to the framed format, raising `ValueError` otherwise.
+///
 /// Python Example
 /// --------------
 /// ```python
 /// >>> _ = cramjam.snappy.compress(b'some bytes here')
 /// >>> _ = cramjam.snappy.compress(bytearray(b'this avoids double allocation in rust side, and thus faster!'))  # <- use bytearray where possible
+/// >>> _ = cramjam.snappy.compress(b'some bytes here', chunk_size=4096)
+/// >>> _ = cramjam.snappy.compress(b'some bytes here', pad_to=64)
+/// >>> _ = cramjam.snappy.compress(b'some bytes here', format="raw")
 /// ```
 #[pyfunction]
-pub fn compress(data: BytesType, output_len: Option<usize>) -> PyResult<RustyBuffer> {
-    crate::generic!(compress(data), output_len = output_len)
+pub fn compress(
+    data: BytesType,
+    output_len: Option<usize>,
+    chunk_size: Option<usize>,
+    pad_to: Option<usize>,
+    trailer_checksum: Option<&str>,
+    format: Option<&str>,
+) -> PyResult<RustyBuffer> {
+    let compressed = if is_raw(format)? {
+        if chunk_size.is_some() {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "chunk_size only applies to the framed format",
+            ));
+        }
+        compress_raw(data, output_len, None)
+    } else {
+        crate::generic!(compress(data), output_len = output_len, chunk_size = chunk_size)
+    };
+    compressed
+        .and_then(|compressed| crate::with_trailer(compressed, trailer_checksum))
+        .and_then(|compressed| crate::pad(compressed, pad_to))
+}
+
+/// Whether `format`, of `compress` and `decompress`, is `"raw"` rather than `"framed"`, the default
+fn is_raw(format: Option<&str>) -> PyResult<bool> {
+    match format.unwrap_or("framed") {
+        "framed" => Ok(false),
+        "raw" => Ok(true),
+        format => Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "Unknown format '{}', should be one of 'framed' or 'raw'",
+            format
+        ))),
+    }
 }
 
 /// Snappy decompression, raw
 /// This does not use the snappy 'framed' encoding of compressed bytes.
+/// The raw format records the decompressed length itself, so `output_len` isn't required; when
+/// given, `DecompressionError` is raised should the data decompress to more than it.
+///
+/// `store_size` must be `True` if it was given to `compress_raw`; the stored size is then checked
+/// against the decompressed length, raising `DecompressionError` if they differ.
 ///
 /// Python Example
 /// --------------
 /// ```python
-/// >>> cramjam.snappy.decompress_raw(compressed_raw_bytes)
+/// >>> cramjam.snappy.decompress_raw(compressed_raw_bytes, store_size=Optional[bool])
 /// ```
 #[pyfunction]
-#[allow(unused_variables)]
-pub fn decompress_raw(data: BytesType, output_len: Option<usize>) -> PyResult<RustyBuffer> {
-    let mut decoder = snap::raw::Decoder::new();
-    let output = to_py_err!(DecompressionError -> decoder.decompress_vec(data.as_bytes()))?;
+pub fn decompress_raw(
+    mut data: BytesType,
+    output_len: Option<usize>,
+    store_size: Option<bool>,
+) -> PyResult<RustyBuffer> {
+    let mut input = vec![];
+    data.read_to_end(&mut input)?;
+    let (stored_len, compressed) = match store_size.unwrap_or(false) {
+        true => {
+            to_py_err!(DecompressionError -> crate::split_size_prefix(&input)).map(|(len, data)| (Some(len), data))?
+        }
+        false => (None, input.as_slice()),
+    };
+    let output = to_py_err!(DecompressionError -> internal::decompress_raw(compressed, output_len))?;
+    if let Some(stored_len) = stored_len {
+        crate::check_stored_size(output.len(), stored_len)?;
+    }
     Ok(RustyBuffer::from(output))
 }
 
 /// Snappy compression raw.
 /// This does not use the snappy 'framed' encoding of compressed bytes.
 ///
+/// `store_size` (default `False`) prefixes the output with the input's length as a little-endian
+/// u32, as `lz4.compress_block` does; the raw format already records it, so this is only for
+/// readers expecting the `lz4.block` layout. Such data must be decompressed with `store_size=True`.
+///
 /// Python Example
 /// --------------
 /// ```python
-/// >>> cramjam.snappy.compress_raw(b'some bytes here')
+/// >>> cramjam.snappy.compress_raw(b'some bytes here', store_size=Optional[bool])
 /// ```
 #[pyfunction]
 #[allow(unused_variables)]
-pub fn compress_raw(data: BytesType, output_len: Option<usize>) -> PyResult<RustyBuffer> {
-    let mut encoder = snap::raw::Encoder::new();
-    let output = to_py_err!(CompressionError -> encoder.compress_vec(data.as_bytes()))?;
+pub fn compress_raw(mut data: BytesType, output_len: Option<usize>, store_size: Option<bool>) -> PyResult<RustyBuffer> {
+    let mut input = vec![];
+    data.read_to_end(&mut input)?;
+    let output = to_py_err!(CompressionError -> snap::raw::Encoder::new().compress_vec(&input))?;
+    if store_size.unwrap_or(false) {
+        let output = to_py_err!(CompressionError -> crate::with_size_prefix(input.len(), &output))?;
+        return Ok(RustyBuffer::from(output));
+    }
     Ok(RustyBuffer::from(output))
 }
 
-/// Compress directly into an output buffer
+/// Compress `text`, a `str`, encoded with `encoding` (default `"utf-8"`).
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.snappy.compress_str("some text here", encoding="utf-8")
+/// ```
+#[pyfunction]
+pub fn compress_str(text: &PyString, encoding: Option<&str>) -> PyResult<RustyBuffer> {
+    compress(crate::encode_str(text, encoding)?, None, None, None, None, None)
+}
+
+/// Decompress to a `str`, decoding the decompressed data with `encoding` (default `"utf-8"`).
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.snappy.decompress_str(compressed_bytes, encoding="utf-8")
+/// 'some text here'
+/// ```
+#[pyfunction]
+pub fn decompress_str<'a>(py: Python<'a>, data: BytesType, encoding: Option<&str>) -> PyResult<&'a PyString> {
+    let mut output = vec![];
+    to_py_err!(DecompressionError -> internal::decompress(data, &mut output))?;
+    crate::decode_str(py, &output, encoding)
+}
+
+/// Check `data` decompresses without error, returning `True` if it does and `False` if it doesn't,
+/// ie. it's truncated or corrupt; the decompressed data is discarded as it's decoded, rather than
+/// kept in an output buffer.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.snappy.is_valid(compressed_bytes)
+/// True
+/// ```
+#[pyfunction]
+pub fn is_valid(data: BytesType) -> bool {
+    internal::decompress(data, &mut std::io::sink()).is_ok()
+}
+
+/// Check each of `items` decompresses, and when given, to the corresponding item of `expected`;
+/// returns the index of the first which doesn't, or `None` if all of them do. The items are
+/// checked in parallel with the GIL released.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.snappy.verify_many([compressed_bytes, other_compressed_bytes], expected=Optional[List[bytes]])
+/// ```
+#[pyfunction]
+pub fn verify_many(py: Python, items: Vec<BytesType>, expected: Option<Vec<BytesType>>) -> PyResult<Option<usize>> {
+    crate::verify_many(py, items, expected, |input, output| internal::decompress(input, output))
+}
+
+/// Decompress each of `items` into successive regions of `array`, a `numpy.array` with `dtype=np.uint8`,
+/// returning the `(offset, length)` of each region; raises `BufferError` if the array fills up first,
+/// and `ValueError` if an item overlaps the array, ie. is a view of it.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> array = np.empty(total_len, dtype=np.uint8)
+/// >>> regions = cramjam.snappy.decompress_many_into([compressed_bytes, other_compressed_bytes], array)
+/// ```
+#[pyfunction]
+pub fn decompress_many_into(items: Vec<BytesType>, array: &PyArray1<u8>) -> PyResult<Vec<(usize, usize)>> {
+    crate::decompress_many_into(items, array, |input, output| internal::decompress(input, output))
+}
+
+/// Compress, unless that doesn't achieve a ratio of uncompressed to compressed length of at least
+/// `min_ratio` (default 1.1); returns a tuple of the compressed data and `True`, or a copy of the
+/// data as is and `False`, to be given to `maybe_decompress`.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> data, compressed = cramjam.snappy.maybe_compress(b'some bytes here', min_ratio=1.1)
+/// >>> bytes(cramjam.snappy.maybe_decompress(data, compressed))
+/// b'some bytes here'
+/// ```
+#[pyfunction]
+pub fn maybe_compress(data: BytesType, min_ratio: Option<f64>) -> PyResult<(RustyBuffer, bool)> {
+    crate::maybe_compress(data, min_ratio, |input, output| internal::compress(input, output, None))
+}
+
+/// Decompress the data returned by `maybe_compress` if its flag, `compressed`, is `True`, otherwise
+/// return a copy of it as is.
+#[pyfunction]
+pub fn maybe_decompress(data: BytesType, compressed: bool) -> PyResult<RustyBuffer> {
+    crate::maybe_decompress(data, compressed, |input, output| internal::decompress(input, output))
+}
+
+/// Structural metadata from the header of a snappy frame, without decompressing it; see `cramjam.peek`.
+/// Raises `DecompressionError` if the data doesn't start with a snappy frame.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.snappy.peek(compressed_bytes)
+/// ```
+#[pyfunction]
+pub fn peek<'a>(py: Python<'a>, mut data: BytesType) -> PyResult<&'a PyDict> {
+    crate::detect::peek_header(py, &mut data, Some("snappy"))
+}
+
+/// Get the maximum length of the compressed output for `data_len` bytes of input, the size
+/// of buffer which is always big enough to be passed to `compress_into`
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> output = bytearray(cramjam.snappy.compress_bound(len(data)))
+/// >>> n_bytes = cramjam.snappy.compress_into(data, output)
+/// ```
+#[pyfunction]
+pub fn compress_bound(data_len: usize) -> PyResult<usize> {
+    crate::checked_bound(internal::compress_bound(data_len), data_len)
+}
+
+/// Compress directly into an output buffer, returning the compressed length; `CompressionError` is
+/// raised with `compress_bound` of the input's length if the buffer is too small to hold it.
+#[pyfunction]
+pub fn compress_into(input: BytesType, output: BytesType) -> PyResult<usize> {
+    crate::compress_into(input, output, internal::compress_bound, |input, output| {
+        internal::compress(input, output, None)
+    })
+}
+
+/// Compress using `scratch`, a `numpy.array` with `dtype=np.uint8`, as the output buffer, returning
+/// a copy of the compressed data as `bytes`. The array is resized as needed, and can be reused across
+/// calls to avoid allocating a new output buffer each time; as with `numpy.ndarray.resize`, it must
+/// own its data and not be referenced by any other array.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> scratch = np.empty(0, dtype=np.uint8)
+/// >>> for data in chunks:
+/// ...     compressed = cramjam.snappy.compress_reuse(data, scratch)
+/// ```
 #[pyfunction]
-pub fn compress_into(input: BytesType, mut output: BytesType) -> PyResult<usize> {
-    let r = internal::compress(input, &mut output)?;
-    Ok(r)
+pub fn compress_reuse<'a>(py: Python<'a>, data: BytesType, scratch: &PyArray1<u8>) -> PyResult<&'a PyBytes> {
+    crate::io::compress_reuse(py, scratch, |output| internal::compress(data, output, None))
+}
+
+/// Compress a `numpy.array` of any dtype, including non-contiguous views such as `arr[::2]`.
+/// The elements are gathered into a contiguous buffer, in C order, before being compressed.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> arr = np.arange(100, dtype=np.float64).reshape(10, 10)
+/// >>> cramjam.snappy.compress_strided(arr[::2, 1::3], output_len=Optional[int])
+/// ```
+#[pyfunction]
+pub fn compress_strided(array: RustyStridedNumpyArray, output_len: Option<usize>) -> PyResult<RustyBuffer> {
+    crate::generic!(compress(array), output_len = output_len, chunk_size = None)
+}
+
+/// Decompress into a new `numpy.memmap` of `dtype` (default `"uint8"`) at `path`, which is created
+/// or overwritten, returning it; for data too large to decompress into memory. The data is
+/// decompressed twice, first to find its length.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> array = cramjam.snappy.decompress_to_memmap(compressed_bytes, "/tmp/data.bin", dtype="float64")
+/// ```
+#[pyfunction]
+pub fn decompress_to_memmap<'a>(
+    py: Python<'a>,
+    data: BytesType,
+    path: &str,
+    dtype: Option<&str>,
+) -> PyResult<&'a PyAny> {
+    crate::decompress_to_memmap(py, data, path, dtype, None, |input, output| {
+        internal::decompress(input, output)
+    })
 }
 
 /// Decompress directly into an output buffer
 #[pyfunction]
 pub fn decompress_into(input: BytesType, mut output: BytesType) -> PyResult<usize> {
+    output.check_writable()?;
     let r = internal::decompress(input, &mut output)?;
     Ok(r as usize)
 }
 
+/// Compress from the file descriptor `in_fd` until its end, writing to `out_fd`, ie. either end of
+/// an `os.pipe()`, returning the number of bytes written; neither descriptor is closed.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.snappy.compress_fd(in_fd, out_fd)
+/// ```
+#[cfg(unix)]
+#[pyfunction]
+pub fn compress_fd(py: Python, in_fd: i32, out_fd: i32) -> PyResult<usize> {
+    crate::io::with_fds::<CompressionError, _>(py, in_fd, out_fd, |input, output| {
+        internal::compress(input, output, None).map(|_| ())
+    })
+}
+
+/// Decompress from the file descriptor `in_fd` until its end, writing to `out_fd`, returning
+/// the number of bytes written; neither descriptor is closed.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.snappy.decompress_fd(in_fd, out_fd)
+/// ```
+#[cfg(unix)]
+#[pyfunction]
+pub fn decompress_fd(py: Python, in_fd: i32, out_fd: i32) -> PyResult<usize> {
+    crate::io::with_fds::<DecompressionError, _>(py, in_fd, out_fd, |input, output| {
+        internal::decompress(input, output).map(|_| ())
+    })
+}
+
 /// Compress raw format directly into an output buffer
 #[pyfunction]
 pub fn compress_raw_into(input: BytesType, mut output: BytesType) -> PyResult<usize> {
+    output.check_writable()?;
     let mut encoder = snap::raw::Encoder::new();
     let output = encoder.compress(input.as_bytes(), output.as_bytes_mut());
     to_py_err!(CompressionError -> output)
@@ -104,6 +464,7 @@ pub fn compress_raw_into(input: BytesType, mut output: BytesType) -> PyResult<us
 /// Decompress raw format directly into an output buffer
 #[pyfunction]
 pub fn decompress_raw_into(input: BytesType, mut output: BytesType) -> PyResult<usize> {
+    output.check_writable()?;
     let mut decoder = snap::raw::Decoder::new();
     let output = decoder.decompress(input.as_bytes(), output.as_bytes_mut());
     to_py_err!(DecompressionError -> output)
@@ -123,21 +484,194 @@ pub fn decompress_raw_len(data: BytesType) -> PyResult<usize> {
     to_py_err!(DecompressionError -> snap::raw::decompress_len(data.as_bytes()))
 }
 
+/// Get the exact decompressed length of `data`, in the given `format`, `"framed"`, the default,
+/// or `"raw"`, as for `decompress`, without decompressing it; the size of buffer to pass to
+/// `decompress_into`. Both formats record the length of each block, so this only reads the headers.
+/// Raises `DecompressionError` if `data` isn't snappy compressed data of that format.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> output = bytearray(cramjam.snappy.decompress_len(compressed_bytes, format=Optional[str]))
+/// >>> n_bytes = cramjam.snappy.decompress_into(compressed_bytes, output)
+/// ```
+#[pyfunction]
+pub fn decompress_len(data: BytesType, format: Option<&str>) -> PyResult<usize> {
+    match is_raw(format)? {
+        true => to_py_err!(DecompressionError -> snap::raw::decompress_len(data.as_bytes())),
+        false => to_py_err!(DecompressionError -> internal::framed_decompress_len(data.as_bytes())),
+    }
+}
+
+/// Decompress line by line; returns an iterator over the lines of decompressed data
+/// as `bytes`, without materializing the entire decompressed data. See [`Lines`](../io/struct.RustyLines.html)
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> for line in cramjam.snappy.decompress_lines(compressed_bytes):
+/// ...     print(line)
+/// ```
+#[pyfunction]
+pub fn decompress_lines(mut data: BytesType) -> PyResult<RustyLines> {
+    let mut compressed = vec![];
+    data.read_to_end(&mut compressed)?;
+    let decoder = to_py_err!(DecompressionError -> internal::decoder(Cursor::new(compressed)))?;
+    Ok(RustyLines::new(decoder))
+}
+
+/// Replace the `[start, end)` range of the decompressed data with `replacement`, returning the edited
+/// data compressed again, without returning the decompressed data to Python. Raises `ValueError`
+/// if `start` is greater than `end`, and `DecompressionError` if the range is beyond the end of the data.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.snappy.recompress_range(compressed_bytes, start, end, b'replacement')
+/// ```
+#[pyfunction]
+pub fn recompress_range(data: BytesType, start: u64, end: u64, replacement: BytesType) -> PyResult<RustyBuffer> {
+    crate::io::recompress_range(internal::decoder(data), start, end, replacement, |input, output| {
+        internal::compress(input, output, None)
+    })
+}
+
 pub(crate) mod internal {
+    use crate::io::FinishWrite;
     use snap::read::{FrameDecoder, FrameEncoder};
-    use std::io::{Error, Read, Write};
+    use snap::write::FrameEncoder as FrameWriteEncoder;
+    use std::io::{Error, ErrorKind, Read, Write};
+
+    /// Maximum number of uncompressed bytes in a chunk of the snappy frame format
+    pub const MAX_CHUNK_SIZE: usize = 65536;
+
+    /// Largest chunk header, of its type, length and checksum of the uncompressed data
+    const CHUNK_HEADER_LEN: usize = 8;
+
+    /// Length of the stream identifier at the start of the snappy frame format
+    const STREAM_IDENTIFIER_LEN: usize = 10;
+
+    /// Largest output of `compress` with the default `chunk_size`, for `input_len` bytes of input;
+    /// chunks which don't compress are stored as is. `None` if it's too large to be represented.
+    pub fn compress_bound(input_len: usize) -> Option<usize> {
+        let n_chunks = input_len.max(1).div_ceil(MAX_CHUNK_SIZE);
+        n_chunks
+            .checked_mul(CHUNK_HEADER_LEN)?
+            .checked_add(input_len)?
+            .checked_add(STREAM_IDENTIFIER_LEN)
+    }
+
+    /// Limits each read from `inner` to `chunk_size` bytes; snap's `FrameEncoder` compresses
+    /// each read from its input into a chunk of its own.
+    struct ChunkedReader<R> {
+        inner: R,
+        chunk_size: usize,
+    }
+
+    impl<R: Read> Read for ChunkedReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+            let len = buf.len().min(self.chunk_size);
+            self.inner.read(&mut buf[..len])
+        }
+    }
+
+    /// Decompressed length of `input` in the snappy frame format, from the headers of its chunks
+    pub fn framed_decompress_len(input: &[u8]) -> Result<usize, Error> {
+        let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, msg.to_string());
+        if input.first().is_some_and(|chunk_type| *chunk_type != 0xff) {
+            return Err(invalid("Expected the snappy frame format's stream identifier"));
+        }
+        let (mut len, mut pos) = (0, 0);
+        while pos < input.len() {
+            let header = input
+                .get(pos..pos + 4)
+                .ok_or_else(|| invalid("Truncated snappy chunk header"))?;
+            let chunk_len = u32::from_le_bytes([header[1], header[2], header[3], 0]) as usize;
+            let chunk = input
+                .get(pos + 4..pos + 4 + chunk_len)
+                .ok_or_else(|| invalid("Truncated snappy chunk"))?;
+            // the data of compressed and uncompressed chunks follows a checksum of 4 bytes
+            let data = || chunk.get(4..).ok_or_else(|| invalid("Truncated snappy chunk checksum"));
+            match header[0] {
+                0x00 => len += snap::raw::decompress_len(data()?).map_err(|err| invalid(&err.to_string()))?,
+                0x01 => len += data()?.len(),
+                0x02..=0x7f => return Err(invalid("Unsupported unskippable snappy chunk")),
+                // the stream identifier, padding and other skippable chunks
+                _ => (),
+            }
+            pos += 4 + chunk_len;
+        }
+        Ok(len)
+    }
+
+    /// Decompress a raw snappy block, into an output of `output_len` bytes, or of the decompressed
+    /// length recorded in the block when `None`
+    pub fn decompress_raw(input: &[u8], output_len: Option<usize>) -> Result<Vec<u8>, snap::Error> {
+        let len = output_len.map_or_else(|| snap::raw::decompress_len(input), Ok)?;
+        let mut output = crate::alloc::output_vec(Some(len));
+        let n_bytes = snap::raw::Decoder::new().decompress(input, &mut output)?;
+        output.truncate(n_bytes);
+        Ok(output)
+    }
+
+    /// Decoder reading compressed data from `input`
+    pub fn decoder<R: Read>(input: R) -> Result<FrameDecoder<R>, Error> {
+        Ok(FrameDecoder::new(input))
+    }
+
+    /// Encoder writing compressed data to `output`
+    pub fn encoder<W: Write>(output: W) -> Result<FrameWriteEncoder<W>, Error> {
+        Ok(FrameWriteEncoder::new(output))
+    }
 
     /// Decompress snappy data framed
     pub fn decompress<W: Write + ?Sized, R: Read>(input: R, output: &mut W) -> Result<usize, Error> {
-        let mut decoder = FrameDecoder::new(input);
-        let n_bytes = std::io::copy(&mut decoder, output)?;
+        let mut decoder = decoder(input)?;
+        let n_bytes = std::io::copy(&mut decoder, output).map_err(checksum_error)?;
         Ok(n_bytes as usize)
     }
 
-    /// Decompress snappy data framed
-    pub fn compress<W: Write + ?Sized, R: Read>(data: R, output: &mut W) -> Result<usize, Error> {
-        let mut encoder = FrameEncoder::new(data);
+    /// Replace snap's error for a chunk failing its CRC-32C check with one telling corrupted
+    /// data apart from data which isn't snappy framed at all
+    fn checksum_error(err: Error) -> Error {
+        match err.get_ref().and_then(|inner| inner.downcast_ref::<snap::Error>()) {
+            Some(snap::Error::Checksum { expected, got }) => Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "snappy chunk checksum mismatch (expected {:#010x}, got {:#010x})",
+                    expected, got
+                ),
+            ),
+            _ => err,
+        }
+    }
+
+    /// Compress snappy data framed, with at most `chunk_size` uncompressed bytes in each chunk
+    pub fn compress<W: Write + ?Sized, R: Read>(
+        data: R,
+        output: &mut W,
+        chunk_size: Option<usize>,
+    ) -> Result<usize, Error> {
+        let chunk_size = chunk_size.unwrap_or(MAX_CHUNK_SIZE);
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            let msg = format!(
+                "chunk_size must be between 1 and {}, got {}",
+                MAX_CHUNK_SIZE, chunk_size
+            );
+            return Err(Error::new(ErrorKind::InvalidInput, msg));
+        }
+        let mut encoder = FrameEncoder::new(ChunkedReader {
+            inner: data,
+            chunk_size,
+        });
         let n_bytes = std::io::copy(&mut encoder, output)?;
         Ok(n_bytes as usize)
     }
+
+    impl<W: Write + Send> FinishWrite for FrameWriteEncoder<W> {
+        fn finish(self: Box<Self>) -> Result<(), Error> {
+            let mut output = self.into_inner().map_err(|err| err.into_error())?;
+            output.flush()
+        }
+    }
 }
@@ -0,0 +1,145 @@
+//! zlib de/compression interface, of deflate data in the zlib wrapper (RFC 1950) as written by
+//! Python's `zlib.compress`; see the deflate module for raw deflate data without it
+use crate::exceptions::{CompressionError, DecompressionError};
+use crate::io::{AsBytes, RustyBuffer};
+use crate::{to_py_err, BytesType};
+use pyo3::prelude::*;
+use pyo3::wrap_pyfunction;
+use pyo3::PyResult;
+use std::io::Cursor;
+
+pub(crate) fn init_py_module(m: &PyModule) -> PyResult<()> {
+    m.add("DEFAULT_LEVEL", internal::DEFAULT_LEVEL)?;
+    m.add("MAX_LEVEL", internal::MAX_LEVEL)?;
+    m.add_function(wrap_pyfunction!(compress, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_bound, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_into, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_into, m)?)?;
+    m.add_function(wrap_pyfunction!(adler32, m)?)?;
+    Ok(())
+}
+
+/// zlib decompression.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.zlib.decompress(compressed_bytes, output_len=Optional[int])
+/// ```
+#[pyfunction]
+pub fn decompress(data: BytesType, output_len: Option<usize>) -> PyResult<RustyBuffer> {
+    crate::generic!(decompress(data), output_len = output_len)
+}
+
+/// zlib compression.
+///
+/// `level` ranges from 0, which stores the data without compressing it, to 9; it defaults to 6,
+/// like Python's `zlib`, when `None` or omitted. `CompressionError` is raised for any other level.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.zlib.compress(b'some bytes here', level=6, output_len=Optional[int])
+/// ```
+#[pyfunction]
+pub fn compress(data: BytesType, level: Option<u32>, output_len: Option<usize>) -> PyResult<RustyBuffer> {
+    to_py_err!(CompressionError -> internal::checked_level(level))?;
+    crate::generic!(compress(data), output_len = output_len, level = level)
+}
+
+/// Get the maximum length of the compressed output for `data_len` bytes of input, the size
+/// of buffer which is always big enough to be passed to `compress_into`
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> output = bytearray(cramjam.zlib.compress_bound(len(data)))
+/// >>> n_bytes = cramjam.zlib.compress_into(data, output)
+/// ```
+#[pyfunction]
+pub fn compress_bound(data_len: usize) -> PyResult<usize> {
+    crate::checked_bound(internal::compress_bound(data_len), data_len)
+}
+
+/// Adler-32 of `data`, the checksum in the trailer of zlib data, as by Python's `zlib.adler32`.
+/// `value` is the Adler-32 of any data before it, by default 1, to checksum data given in chunks.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.zlib.adler32(b'some bytes here')
+/// 778372544
+/// >>> cramjam.zlib.adler32(b' here', value=cramjam.zlib.adler32(b'some bytes'))
+/// 778372544
+/// ```
+#[pyfunction]
+pub fn adler32(data: BytesType, value: Option<u32>) -> u32 {
+    let mut hasher = adler2::Adler32::from_checksum(value.unwrap_or(1));
+    hasher.write_slice(data.as_bytes());
+    hasher.checksum()
+}
+
+/// Compress directly into an output buffer, returning the compressed length; `CompressionError` is
+/// raised with `compress_bound` of the input's length if the buffer is too small to hold it.
+#[pyfunction]
+pub fn compress_into(input: BytesType, output: BytesType, level: Option<u32>) -> PyResult<usize> {
+    crate::compress_into(input, output, internal::compress_bound, |input, output| {
+        internal::compress(input, output, level)
+    })
+}
+
+/// Decompress directly into an output buffer
+#[pyfunction]
+pub fn decompress_into(input: BytesType, mut output: BytesType) -> PyResult<usize> {
+    output.check_writable()?;
+    let r = to_py_err!(DecompressionError -> internal::decompress(input, &mut output))?;
+    Ok(r)
+}
+
+pub(crate) mod internal {
+    use flate2::read::{ZlibDecoder, ZlibEncoder};
+    use flate2::Compression;
+    use std::io::prelude::*;
+    use std::io::Error;
+
+    /// Compression level used when none is given
+    pub(crate) const DEFAULT_LEVEL: u32 = 6;
+
+    /// Highest compression level
+    pub(crate) const MAX_LEVEL: u32 = 9;
+
+    /// `level`, or the default, checked to be at most [`MAX_LEVEL`], as flate2 panics beyond it
+    pub(crate) fn checked_level(level: Option<u32>) -> Result<u32, Error> {
+        crate::checked_level("zlib", level, DEFAULT_LEVEL, 0, MAX_LEVEL)
+    }
+
+    /// zlib's header and trailing checksum
+    const WRAPPER_LEN: usize = 6;
+
+    /// Largest output of `compress` for `input_len` bytes of input; `None` if it's too large to be represented
+    pub fn compress_bound(input_len: usize) -> Option<usize> {
+        crate::deflate::internal::compress_bound(input_len)?.checked_add(WRAPPER_LEN)
+    }
+
+    /// Decoder reading compressed data from `input`
+    pub fn decoder<R: Read>(input: R) -> Result<ZlibDecoder<R>, Error> {
+        Ok(ZlibDecoder::new(input))
+    }
+
+    /// Decompress zlib data
+    pub fn decompress<W: Write + ?Sized, R: Read>(input: R, output: &mut W) -> Result<usize, Error> {
+        let mut decoder = decoder(input)?;
+        let n_bytes = std::io::copy(&mut decoder, output)?;
+        Ok(n_bytes as usize)
+    }
+
+    /// Compress zlib data
+    pub fn compress<W: Write + ?Sized, R: Read>(input: R, output: &mut W, level: Option<u32>) -> Result<usize, Error> {
+        let level = checked_level(level)?;
+
+        let mut encoder = ZlibEncoder::new(input, Compression::new(level));
+        let n_bytes = std::io::copy(&mut encoder, output)?;
+        Ok(n_bytes as usize)
+    }
+}
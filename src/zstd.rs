@@ -1,74 +1,1895 @@
 //! zstd de/compression interface
 use crate::exceptions::{CompressionError, DecompressionError};
-use crate::io::RustyBuffer;
-use crate::{to_py_err, BytesType};
+use crate::io::{AsBytes, RustyBuffer, RustyLines, RustyStridedNumpyArray};
+use crate::{to_py_err, BytesType, Level};
+use numpy::PyArray1;
 use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyList, PyString};
 use pyo3::wrap_pyfunction;
-use pyo3::PyResult;
-use std::io::Cursor;
+use pyo3::{PyObjectProtocol, PyResult};
+use std::io::{Cursor, Error, Read};
 
 pub(crate) fn init_py_module(m: &PyModule) -> PyResult<()> {
+    m.add("DEFAULT_LEVEL", internal::DEFAULT_LEVEL)?;
+    m.add("MIN_LEVEL", zstd_safe::min_c_level())?;
+    m.add("MAX_LEVEL", zstd_safe::max_c_level())?;
     m.add_function(wrap_pyfunction!(compress, m)?)?;
     m.add_function(wrap_pyfunction!(decompress, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_bound, m)?)?;
     m.add_function(wrap_pyfunction!(compress_into, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_reuse, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_strided, m)?)?;
     m.add_function(wrap_pyfunction!(decompress_into, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_to_memmap, m)?)?;
+    #[cfg(unix)]
+    m.add_function(wrap_pyfunction!(compress_fd, m)?)?;
+    #[cfg(unix)]
+    m.add_function(wrap_pyfunction!(decompress_fd, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_file, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_file, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_lines, m)?)?;
+    m.add_function(wrap_pyfunction!(recompress_range, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_chunked, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_chunk, m)?)?;
+    m.add_function(wrap_pyfunction!(append, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_delta, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_delta, m)?)?;
+    m.add_function(wrap_pyfunction!(train_dict, m)?)?;
+    m.add_function(wrap_pyfunction!(train_dict_cover, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_str, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_str, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_many, m)?)?;
+    m.add_function(wrap_pyfunction!(is_valid, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_many, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_stream, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_many, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_many_into, m)?)?;
+    m.add_function(wrap_pyfunction!(maybe_compress, m)?)?;
+    m.add_function(wrap_pyfunction!(maybe_decompress, m)?)?;
+    m.add_function(wrap_pyfunction!(peek, m)?)?;
+    m.add_function(wrap_pyfunction!(frame_content_size, m)?)?;
+    m.add_function(wrap_pyfunction!(frame_header, m)?)?;
+    m.add_class::<Compressor>()?;
+    m.add_class::<AdaptiveCompressor>()?;
+    m.add_function(wrap_pyfunction!(decompress_adaptive, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_with_dicts, m)?)?;
     Ok(())
 }
 
 /// ZSTD decompression.
 ///
+/// `output_len` is the length the output is allocated with; should the data decompress to more,
+/// the output is grown to hold all of it, so it's never truncated at `output_len`.
+///
+/// With `recover=True`, decoding stops at the first corrupted or truncated section of the input
+/// rather than raising; a tuple of the `Buffer` decompressed up to that point, and whether
+/// the input was found to be damaged, is returned instead.
+///
+/// With `partial=True`, data which ends part way into a frame, ie. as received so far over a
+/// network, decompresses to what it holds so far rather than raising; unlike `recover`, corrupted
+/// data still raises `DecompressionError`, and the `Buffer` alone is returned.
+///
+/// With `timeout_ms`, `DecompressionError` is raised if decompression takes longer than that
+/// many milliseconds, ie. to limit the time spent on untrusted input.
+///
+/// With `pad_to`, the input is taken to be the padded output of `compress` with the same `pad_to`.
+///
+/// With `trailer_checksum`, the trailer appended by `compress` with the same `trailer_checksum` is
+/// checked before decompressing, raising `DecompressionError` if the compressed data was corrupted.
+///
+/// With `exact=True`, and no `output_len`, the data is decompressed twice, first only to count its
+/// decompressed length, so the output is allocated once rather than grown as it's decompressed.
+/// Without either, the output is still allocated once for a frame which records its decompressed
+/// length, as with `include_content_size` when compressing.
+///
+/// With `dict`, the data is taken to be compressed with that dictionary, as given to `compress`.
+///
 /// Python Example
 /// --------------
 /// ```python
-/// >>> cramjam.zstd.decompress(compressed_bytes, output_len=Optional[int])
+/// >>> cramjam.zstd.decompress(compressed_bytes, output_len=Optional[int], recover=False, timeout_ms=Optional[int], pad_to=Optional[int], trailer_checksum=Optional[str], exact=False)
+/// >>> cramjam.zstd.decompress(compressed_bytes, dict=dictionary)
+/// >>> cramjam.zstd.decompress(compressed_bytes[:1000], partial=True)
 /// ```
 #[pyfunction]
-pub fn decompress(data: BytesType, output_len: Option<usize>) -> PyResult<RustyBuffer> {
-    crate::generic!(decompress(data), output_len = output_len)
+#[allow(clippy::too_many_arguments)]
+pub fn decompress(
+    py: Python,
+    mut data: BytesType,
+    output_len: Option<usize>,
+    recover: Option<bool>,
+    timeout_ms: Option<u64>,
+    pad_to: Option<usize>,
+    trailer_checksum: Option<&str>,
+    exact: Option<bool>,
+    dict: Option<BytesType>,
+    partial: Option<bool>,
+) -> PyResult<PyObject> {
+    let recover = recover.unwrap_or(false);
+    let partial = partial.unwrap_or(false);
+    let dict = dict
+        .as_ref()
+        .map(|dict| internal::DecoderDictionary::new(dict.as_bytes()));
+    let dict = dict.as_ref();
+    // the frame header is only at the start of the data without cramjam's own header or trailer
+    let decompressed_len = match (pad_to, trailer_checksum) {
+        (None, None) => recorded_content_size(&mut data)?,
+        _ => None,
+    };
+    let compressed_len = data.remaining_len()?;
+    let compressed = crate::unpadded(&mut data, pad_to)?;
+    let compressed = crate::TrailerChecked::new(compressed, trailer_checksum)?;
+    if recover || partial || timeout_ms.is_some() {
+        let decoder = internal::decoder_with_dict(compressed, dict);
+        return crate::decompress_with(py, decoder, recover, timeout_ms, partial);
+    }
+    if exact.unwrap_or(false) && output_len.is_none() {
+        return crate::decompress_exact(compressed, |input, output| {
+            internal::decompress_with_dict(input, output, dict)
+        })
+        .map(|buffer| buffer.into_py(py));
+    }
+    if output_len.is_none() {
+        let mut output = crate::presized_output(decompressed_len, compressed_len);
+        to_py_err!(DecompressionError -> internal::decompress_with_dict(compressed, &mut output, dict))?;
+        return Ok(RustyBuffer::from(output).into_py(py));
+    }
+    crate::generic!(decompress_with_dict(compressed), output_len = output_len, dict = dict)
+        .map(|buffer: RustyBuffer| buffer.into_py(py))
 }
 
 /// ZSTD compression.
 ///
+/// `level` ranges from negative levels, the fastest, to 22; it defaults to 3, `cramjam.zstd.DEFAULT_LEVEL`,
+/// when `None`, omitted or 0, rather than to whatever default the bundled zstd has. Levels 20 to 22 are
+/// zstd's "ultra" levels, using windows of up to 128MiB and as much memory; `CompressionError` is raised
+/// for levels beyond -131072 to 22, `cramjam.zstd.MIN_LEVEL` to `cramjam.zstd.MAX_LEVEL`, which zstd
+/// would otherwise clamp them to.
+///
+/// `include_content_size` (default `True`) writes the length of the input into the frame header,
+/// allowing the decompressed size to be known before decompressing.
+///
+/// With `pad_to`, the output is padded with zeros to a multiple of that many bytes, hiding its exact
+/// length, ie. before encrypting it; decompress it with the same `pad_to`.
+///
+/// `trailer_checksum="crc32"`, or `"xxh3"` which is faster for large outputs, appends a checksum
+/// of the compressed data, before any padding, so corruption in transport is caught before
+/// decompressing; decompress it with the same `trailer_checksum`.
+///
+/// `strategy` overrides the match finding strategy implied by `level`, one of `"fast"`, `"dfast"`,
+/// `"greedy"`, `"lazy"`, `"lazy2"`, `"btlazy2"`, `"btopt"`, `"btultra"` or `"btultra2"`, from the
+/// fastest to the strongest.
+///
+/// `tune_window=True` shrinks the window to the smallest covering the input, down to 1KiB, rather
+/// than that of `level`, so less memory is needed to de/compress small inputs; zstd already shrinks
+/// it so when the input's length is recorded with `include_content_size`, but not otherwise.
+///
+/// `dict` is a dictionary to compress with, ie. from `train_dict`, which greatly improves the ratio
+/// of small inputs similar to those it was trained on; decompress with the same `dict`.
+///
+/// `workers` is the number of threads compressing the input in parallel, for large inputs of tens of
+/// MiB; when `None`, omitted or 0, it's compressed on the calling thread. The output is a single frame
+/// either way, decompressed as any other.
+///
 /// Python Example
 /// --------------
 /// ```python
-/// >>> cramjam.zstd.compress(b'some bytes here', level=0, output_len=Optional[int])  # level defaults to 11
+/// >>> cramjam.zstd.compress(b'some bytes here', level=0, output_len=Optional[int], include_content_size=True)
+/// >>> cramjam.zstd.compress(b'some bytes here', level="auto")  # 19 for inputs below 1MiB, 3 below 64MiB, otherwise 1
+/// >>> cramjam.zstd.compress(b'some bytes here', strategy="btultra2")
+/// >>> cramjam.zstd.compress(b'some bytes here', include_content_size=False, tune_window=True)
+/// >>> cramjam.zstd.compress(b'some bytes here', dict=dictionary)
+/// >>> cramjam.zstd.compress(large_bytes, workers=4)
 /// ```
 #[pyfunction]
-pub fn compress(data: BytesType, level: Option<i32>, output_len: Option<usize>) -> PyResult<RustyBuffer> {
-    crate::generic!(compress(data), output_len = output_len, level = level)
+#[allow(clippy::too_many_arguments)]
+pub fn compress(
+    mut data: BytesType,
+    level: Option<Level<i32>>,
+    output_len: Option<usize>,
+    include_content_size: Option<bool>,
+    pad_to: Option<usize>,
+    strategy: Option<&str>,
+    trailer_checksum: Option<&str>,
+    tune_window: Option<bool>,
+    dict: Option<BytesType>,
+    workers: Option<u32>,
+) -> PyResult<RustyBuffer> {
+    let level = level.map(|level| level.resolve(&mut data, auto_level)).transpose()?;
+    to_py_err!(CompressionError -> internal::checked_level(level))?;
+    let content_size = content_size(&mut data, include_content_size)?;
+    let strategy = strategy.map(self::strategy).transpose()?;
+    let window_log = match tune_window.unwrap_or(false) {
+        true => Some(to_py_err!(CompressionError -> internal::tuned_window_log(level, data.remaining_len()?))?),
+        false => None,
+    };
+    let dict = dict
+        .as_ref()
+        .map(|dict| to_py_err!(CompressionError -> internal::encoder_dictionary(dict.as_bytes(), level)))
+        .transpose()?;
+    let options = internal::CompressOptions {
+        level,
+        content_size,
+        strategy,
+        window_log,
+        dict: dict.as_ref(),
+        workers,
+    };
+    crate::generic!(compress(data), output_len = output_len, options = &options)
+        .and_then(|compressed| crate::with_trailer(compressed, trailer_checksum))
+        .and_then(|compressed| crate::pad(compressed, pad_to))
 }
 
-/// Compress directly into an output buffer
+/// Level used with `level="auto"`, by the length of the input
+fn auto_level(input_len: u64) -> i32 {
+    match input_len {
+        len if len < crate::AUTO_LEVEL_SMALL_INPUT => 19,
+        len if len < crate::AUTO_LEVEL_LARGE_INPUT => 3,
+        _ => 1,
+    }
+}
+
+/// Names accepted by `compress`'s `strategy`, in the order of zstd's own enumeration
+const STRATEGIES: &[&str] = &[
+    "fast", "dfast", "greedy", "lazy", "lazy2", "btlazy2", "btopt", "btultra", "btultra2",
+];
+
+fn strategy(name: &str) -> PyResult<zstd_safe::Strategy> {
+    use zstd_safe::Strategy::*;
+    match name {
+        "fast" => Ok(ZSTD_fast),
+        "dfast" => Ok(ZSTD_dfast),
+        "greedy" => Ok(ZSTD_greedy),
+        "lazy" => Ok(ZSTD_lazy),
+        "lazy2" => Ok(ZSTD_lazy2),
+        "btlazy2" => Ok(ZSTD_btlazy2),
+        "btopt" => Ok(ZSTD_btopt),
+        "btultra" => Ok(ZSTD_btultra),
+        "btultra2" => Ok(ZSTD_btultra2),
+        _ => {
+            let names = STRATEGIES.iter().map(|name| format!("'{}'", name)).collect::<Vec<_>>();
+            Err(CompressionError::new_err(format!(
+                "Unknown strategy '{}', should be one of {}",
+                name,
+                names.join(", ")
+            )))
+        }
+    }
+}
+
+/// Compress `text`, a `str`, encoded with `encoding` (default `"utf-8"`); `level` is as for `compress`.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.zstd.compress_str("some text here", encoding="utf-8", level=Optional[int])
+/// ```
 #[pyfunction]
-pub fn compress_into(input: BytesType, mut output: BytesType, level: Option<i32>) -> PyResult<usize> {
-    let r = internal::compress(input, &mut output, level)?;
-    Ok(r)
+pub fn compress_str(text: &PyString, encoding: Option<&str>, level: Option<Level<i32>>) -> PyResult<RustyBuffer> {
+    compress(
+        crate::encode_str(text, encoding)?,
+        level,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+}
+
+/// Decompress to a `str`, decoding the decompressed data with `encoding` (default `"utf-8"`).
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.zstd.decompress_str(compressed_bytes, encoding="utf-8")
+/// 'some text here'
+/// ```
+#[pyfunction]
+pub fn decompress_str<'a>(py: Python<'a>, data: BytesType, encoding: Option<&str>) -> PyResult<&'a PyString> {
+    let mut output = vec![];
+    to_py_err!(DecompressionError -> internal::decompress(data, &mut output))?;
+    crate::decode_str(py, &output, encoding)
+}
+
+/// Check `data` decompresses without error, returning `True` if it does and `False` if it doesn't,
+/// ie. it's truncated or corrupt; the decompressed data is discarded as it's decoded, rather than
+/// kept in an output buffer.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.zstd.is_valid(compressed_bytes)
+/// True
+/// ```
+#[pyfunction]
+pub fn is_valid(data: BytesType) -> bool {
+    internal::decompress(data, &mut std::io::sink()).is_ok()
+}
+
+/// Check each of `items` decompresses, and when given, to the corresponding item of `expected`;
+/// returns the index of the first which doesn't, or `None` if all of them do. The items are
+/// checked in parallel with the GIL released.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.zstd.verify_many([compressed_bytes, other_compressed_bytes], expected=Optional[List[bytes]])
+/// ```
+#[pyfunction]
+pub fn verify_many(py: Python, items: Vec<BytesType>, expected: Option<Vec<BytesType>>) -> PyResult<Option<usize>> {
+    crate::verify_many(py, items, expected, |input, output| internal::decompress(input, output))
+}
+
+/// Compress from `reader`, any object with a `read(n)` method returning `bytes`, ie. `io.BytesIO`
+/// or a file opened with `"rb"`, at `level` as for `compress`. The reader is read in chunks as
+/// they're compressed, rather than whole first; an exception raised by its `read` is raised as
+/// `CompressionError`.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> with open("data.bin", "rb") as f:
+/// ...     compressed = cramjam.zstd.compress_stream(f, level=Optional[int])
+/// ```
+#[pyfunction]
+pub fn compress_stream(reader: crate::io::PyFileReader, level: Option<i32>) -> PyResult<RustyBuffer> {
+    let mut output = Cursor::new(vec![]);
+    let options = internal::CompressOptions {
+        level,
+        ..Default::default()
+    };
+    to_py_err!(CompressionError -> internal::compress(reader, &mut output, &options))?;
+    Ok(RustyBuffer::from(output.into_inner()))
+}
+
+/// Compress each of `buffers` at `level`, as `compress` does, returning a list of the compressed
+/// `bytes`. The buffers are copied, then compressed one after the other with the GIL released,
+/// reusing the one zstd context and output buffer, which saves the overhead of a call for each
+/// when compressing many small buffers, ie. a column of blobs.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.zstd.compress_many([b'some bytes here', b'some more bytes'], level=Optional[int])
+/// ```
+#[pyfunction]
+pub fn compress_many<'a>(py: Python<'a>, buffers: Vec<BytesType>, level: Option<i32>) -> PyResult<Vec<&'a PyBytes>> {
+    let level = to_py_err!(CompressionError -> internal::checked_level(level))?;
+    let buffers = copied(buffers)?;
+    let compressed = py.allow_threads(|| internal::compress_many(&buffers, level));
+    let compressed = to_py_err!(CompressionError -> compressed)?;
+    Ok(compressed
+        .iter()
+        .map(|compressed| PyBytes::new(py, compressed))
+        .collect())
+}
+
+/// Decompress each of `buffers`, returning a list of the decompressed `bytes`; the counterpart of
+/// `compress_many`, ie. for the compressed pages of a Parquet column. Each output is allocated
+/// with room for the corresponding item of `output_lens`, when given, otherwise for the length
+/// recorded in its frame, if any. The buffers are copied, then decompressed one after the other
+/// with the GIL released. Raises `DecompressionError` for the first which fails to decompress.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.zstd.decompress_many([compressed_bytes, other_compressed_bytes], output_lens=Optional[List[int]])
+/// ```
+#[pyfunction]
+pub fn decompress_many<'a>(
+    py: Python<'a>,
+    buffers: Vec<BytesType>,
+    output_lens: Option<Vec<usize>>,
+) -> PyResult<Vec<&'a PyBytes>> {
+    if let Some(output_lens) = &output_lens {
+        if output_lens.len() != buffers.len() {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Got {} output_lens for {} buffers",
+                output_lens.len(),
+                buffers.len()
+            )));
+        }
+    }
+    let buffers = copied(buffers)?;
+    let decompressed = py.allow_threads(|| internal::decompress_many(&buffers, output_lens.as_deref()));
+    let decompressed = decompressed
+        .map_err(|(index, err)| DecompressionError::new_err(format!("Unable to decompress item {}: {}", index, err)))?;
+    Ok(decompressed
+        .iter()
+        .map(|decompressed| PyBytes::new(py, decompressed))
+        .collect())
+}
+
+/// Copy of the remaining bytes of each of `buffers`, to be de/compressed with the GIL released
+fn copied(buffers: Vec<BytesType>) -> PyResult<Vec<Vec<u8>>> {
+    let read_all = |mut buffer: BytesType| {
+        let mut bytes = vec![];
+        buffer.read_to_end(&mut bytes).map(|_| bytes)
+    };
+    Ok(buffers.into_iter().map(read_all).collect::<std::io::Result<_>>()?)
+}
+
+/// Decompress each of `items` into successive regions of `array`, a `numpy.array` with `dtype=np.uint8`,
+/// returning the `(offset, length)` of each region; raises `BufferError` if the array fills up first,
+/// and `ValueError` if an item overlaps the array, ie. is a view of it.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> array = np.empty(total_len, dtype=np.uint8)
+/// >>> regions = cramjam.zstd.decompress_many_into([compressed_bytes, other_compressed_bytes], array)
+/// ```
+#[pyfunction]
+pub fn decompress_many_into(items: Vec<BytesType>, array: &PyArray1<u8>) -> PyResult<Vec<(usize, usize)>> {
+    crate::decompress_many_into(items, array, |input, output| internal::decompress(input, output))
+}
+
+/// Compress, unless that doesn't achieve a ratio of uncompressed to compressed length of at least
+/// `min_ratio` (default 1.1); returns a tuple of the compressed data and `True`, or a copy of the
+/// data as is and `False`, to be given to `maybe_decompress`. `level` is as for `compress`.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> data, compressed = cramjam.zstd.maybe_compress(b'some bytes here', min_ratio=1.1, level=Optional[int])
+/// >>> bytes(cramjam.zstd.maybe_decompress(data, compressed))
+/// b'some bytes here'
+/// ```
+#[pyfunction]
+pub fn maybe_compress(
+    mut data: BytesType,
+    min_ratio: Option<f64>,
+    level: Option<Level<i32>>,
+) -> PyResult<(RustyBuffer, bool)> {
+    let level = level.map(|level| level.resolve(&mut data, auto_level)).transpose()?;
+    crate::maybe_compress(data, min_ratio, |input, output| {
+        internal::compress(
+            input,
+            output,
+            &internal::CompressOptions {
+                level,
+                content_size: Some(input.len() as u64),
+                ..Default::default()
+            },
+        )
+    })
+}
+
+/// Decompress the data returned by `maybe_compress` if its flag, `compressed`, is `True`, otherwise
+/// return a copy of it as is.
+#[pyfunction]
+pub fn maybe_decompress(data: BytesType, compressed: bool) -> PyResult<RustyBuffer> {
+    crate::maybe_decompress(data, compressed, |input, output| internal::decompress(input, output))
+}
+
+/// Decompressed length recorded in the header of the zstd frame at the current position of the data,
+/// which is unchanged, or `None` if the frame doesn't record it, ie. as compressed with
+/// `include_content_size=False`. Raises `DecompressionError` if the data doesn't start with a zstd
+/// frame header.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.zstd.frame_content_size(cramjam.zstd.compress(b'some bytes here'))
+/// 15
+/// >>> cramjam.zstd.frame_content_size(cramjam.zstd.compress(b'some bytes here', include_content_size=False))
+/// None
+/// ```
+#[pyfunction]
+pub fn frame_content_size(mut data: BytesType) -> PyResult<Option<u64>> {
+    let header = read_frame_header(&mut data)?;
+    match header.frameContentSize {
+        zstd_safe::CONTENTSIZE_UNKNOWN => Ok(None),
+        content_size => Ok(Some(content_size)),
+    }
+}
+
+/// The header of the zstd frame at the current position of the data, which is unchanged, as a `dict`:
+/// its `frame_type`, `"frame"` or `"skippable"`, its decompressed `content_size`, or `None` if it
+/// isn't recorded, the `window_size` and `block_size_max` needed to decompress it, its `header_size`
+/// in bytes, the `dict_id` of the dictionary it was compressed with, or `None`, and whether it ends
+/// with a checksum, `has_checksum`. For skippable frames, `content_size` is the length of what's
+/// skipped. Raises `DecompressionError` if the data doesn't start with a zstd frame header.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.zstd.frame_header(cramjam.zstd.compress(b'some bytes here'))
+/// {'frame_type': 'frame', 'content_size': 15, 'window_size': 15, 'block_size_max': 15, 'header_size': 6, 'dict_id': None, 'has_checksum': False}
+/// ```
+#[pyfunction]
+pub fn frame_header<'a>(py: Python<'a>, mut data: BytesType) -> PyResult<&'a PyDict> {
+    let header = read_frame_header(&mut data)?;
+    let skippable = header.frameType == zstd_sys::ZSTD_frameType_e::ZSTD_skippableFrame;
+    let metadata = PyDict::new(py);
+    metadata.set_item("frame_type", if skippable { "skippable" } else { "frame" })?;
+    metadata.set_item(
+        "content_size",
+        Some(header.frameContentSize).filter(|size| *size != zstd_safe::CONTENTSIZE_UNKNOWN),
+    )?;
+    metadata.set_item("window_size", header.windowSize)?;
+    metadata.set_item("block_size_max", header.blockSizeMax)?;
+    metadata.set_item("header_size", header.headerSize)?;
+    metadata.set_item("dict_id", Some(header.dictID).filter(|id| *id != 0 && !skippable))?;
+    metadata.set_item("has_checksum", header.checksumFlag != 0)?;
+    Ok(metadata)
+}
+
+/// Structural metadata from the header of a zstd frame, without decompressing it; see `cramjam.peek`.
+/// Raises `DecompressionError` if the data doesn't start with a zstd frame.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.zstd.peek(compressed_bytes)
+/// ```
+#[pyfunction]
+pub fn peek<'a>(py: Python<'a>, mut data: BytesType) -> PyResult<&'a PyDict> {
+    crate::detect::peek_header(py, &mut data, Some("zstd"))
+}
+
+/// Compressor of a stream given in chunks, ie. as it arrives from a network stream or generator,
+/// without buffering all of it. The `bytes` returned by `compress`, `flush` and `finish` together are
+/// a single zstd frame of all the chunks, without their length recorded in its header; or a frame
+/// for each dictionary switched to with `set_dict`.
+///
+/// With `flush_threshold`, `compress` flushes once more than that many bytes were given to it since
+/// the last flush, returning all of them compressed, so they're not held until zstd has a block to
+/// write, ie. for long streams which would otherwise be flushed explicitly to bound memory.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> compressor = cramjam.zstd.Compressor(level=Optional[int], flush_threshold=Optional[int])
+/// >>> compressed = compressor.compress(b'some bytes ')  # b'' until zstd has a block to write
+/// >>> compressed += compressor.compress(b'here')
+/// >>> compressed += compressor.finish()
+/// >>> bytes(cramjam.zstd.decompress(compressed))
+/// b'some bytes here'
+/// >>> packets = compressor.compress(chunk, packetize=1400)  # a list of bytes of up to 1400 bytes each
+/// ```
+#[pyclass]
+pub struct Compressor {
+    level: i32,
+    encoder: Option<zstd::stream::write::Encoder<'static, Vec<u8>>>,
+    flush_threshold: Option<usize>,
+    /// Number of bytes given to `compress` since the last flush
+    unflushed: usize,
+}
+
+#[pymethods]
+impl Compressor {
+    /// Instantiate the compressor, at `level` as for `compress`, flushing past `flush_threshold`
+    #[new]
+    pub fn __init__(level: Option<i32>, flush_threshold: Option<usize>) -> PyResult<Self> {
+        let level = to_py_err!(CompressionError -> internal::checked_level(level))?;
+        let encoder = to_py_err!(CompressionError -> internal::encoder(vec![], Some(level)))?;
+        Ok(Self {
+            level,
+            encoder: Some(encoder),
+            flush_threshold,
+            unflushed: 0,
+        })
+    }
+    /// Compress `chunk`, anything in [`BytesType`](../enum.BytesType.html), returning the compressed
+    /// `bytes` written so far, which are empty while zstd buffers the input towards a block, unless
+    /// that's past `flush_threshold`. With `packetize`, as for each of the methods returning
+    /// compressed data, they're returned as a list of `bytes` of up to that many bytes each, ie. to
+    /// send as network packets; the list is empty when there's nothing to return.
+    pub fn compress(&mut self, py: Python, mut chunk: BytesType, packetize: Option<usize>) -> PyResult<PyObject> {
+        check_packetize(packetize)?;
+        let encoder = self.encoder()?;
+        let n_bytes = to_py_err!(CompressionError -> std::io::copy(&mut chunk, encoder))?;
+        self.unflushed += n_bytes as usize;
+        let output = match self.flush_threshold {
+            Some(threshold) if self.unflushed > threshold => self.flushed()?,
+            _ => std::mem::take(self.encoder()?.get_mut()),
+        };
+        Ok(packets(py, &output, packetize))
+    }
+    /// Compress and return all the input so far, so it can be decompressed up to here without waiting
+    /// for more; this does not end the frame, see `finish`.
+    pub fn flush(&mut self, py: Python, packetize: Option<usize>) -> PyResult<PyObject> {
+        check_packetize(packetize)?;
+        let output = self.flushed()?;
+        Ok(packets(py, &output, packetize))
+    }
+    /// End the current frame, returning its remaining compressed `bytes`, and compress the chunks
+    /// given afterwards into a new frame with `dict`, at the compressor's level. Each frame records
+    /// the id of its dictionary, so the output decompresses with `decompress_with_dicts` given all
+    /// of them; as that's how they're told apart, `ValueError` is raised for a `dict` without an id,
+    /// ie. one which isn't from `train_dict` or `train_dict_cover`.
+    pub fn set_dict(&mut self, py: Python, mut dict: BytesType, packetize: Option<usize>) -> PyResult<PyObject> {
+        check_packetize(packetize)?;
+        self.encoder()?;
+        let mut dictionary = vec![];
+        dict.read_to_end(&mut dictionary)?;
+        if internal::dict_id(&dictionary) == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "dict has no dictionary id to match frames compressed with it; train it with train_dict",
+            ));
+        }
+        let encoder = to_py_err!(CompressionError -> internal::encoder_with_dict(vec![], self.level, &dictionary))?;
+        let finished = std::mem::replace(self.encoder()?, encoder);
+        let output = to_py_err!(CompressionError -> finished.finish())?;
+        self.unflushed = 0;
+        Ok(packets(py, &output, packetize))
+    }
+    /// End the frame, returning the remaining compressed `bytes`. The compressor can't be given
+    /// more chunks afterwards; finishing it again returns `b''`.
+    pub fn finish(&mut self, py: Python, packetize: Option<usize>) -> PyResult<PyObject> {
+        check_packetize(packetize)?;
+        let output = match self.encoder.take() {
+            Some(encoder) => to_py_err!(CompressionError -> encoder.finish())?,
+            None => vec![],
+        };
+        Ok(packets(py, &output, packetize))
+    }
+}
+
+impl Compressor {
+    fn encoder(&mut self) -> PyResult<&mut zstd::stream::write::Encoder<'static, Vec<u8>>> {
+        self.encoder
+            .as_mut()
+            .ok_or_else(|| pyo3::exceptions::PyValueError::new_err("Compressor is already finished"))
+    }
+    /// Flush the encoder, taking all it's written
+    fn flushed(&mut self) -> PyResult<Vec<u8>> {
+        let encoder = self.encoder()?;
+        to_py_err!(CompressionError -> std::io::Write::flush(encoder))?;
+        self.unflushed = 0;
+        Ok(std::mem::take(self.encoder()?.get_mut()))
+    }
+}
+
+fn check_packetize(packetize: Option<usize>) -> PyResult<()> {
+    if packetize == Some(0) {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "packetize must be greater than 0",
+        ));
+    }
+    Ok(())
+}
+
+/// `output` as `bytes`, or with `packetize`, split into a list of `bytes` of up to that many bytes
+fn packets(py: Python, output: &[u8], packetize: Option<usize>) -> PyObject {
+    match packetize {
+        Some(size) => PyList::new(py, output.chunks(size).map(|packet| PyBytes::new(py, packet))).into(),
+        None => PyBytes::new(py, output).into(),
+    }
+}
+
+/// Compressor of a stream of records whose contents drift over time, ie. log lines or telemetry,
+/// each into a frame of its own with a dictionary which is re-trained every `retrain_interval`
+/// records (default 1000) from those records. Each dictionary, of up to `dict_size` bytes (default
+/// 16KiB), is written in a skippable frame ahead of the first frame compressed with it, so the
+/// concatenated output of `compress` decompresses with `decompress_adaptive` alone. Should there be
+/// too little data to train a dictionary from, the previous one is kept, or none for the first.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> compressor = cramjam.zstd.AdaptiveCompressor(level=Optional[int], retrain_interval=1000, dict_size=16384)
+/// >>> compressed = b"".join(compressor.compress(line) for line in log_lines)
+/// >>> bytes(cramjam.zstd.decompress_adaptive(compressed)) == b"".join(log_lines)
+/// True
+/// ```
+#[pyclass]
+pub struct AdaptiveCompressor {
+    level: i32,
+    retrain_interval: usize,
+    dict_size: usize,
+    /// The records since the dictionary was last trained
+    samples: Vec<Vec<u8>>,
+    dictionary: Option<(u32, internal::EncoderDictionary<'static>)>,
+    /// Skippable frame of a dictionary not yet written ahead of the frames compressed with it
+    pending_dictionary: Vec<u8>,
+}
+
+#[pymethods]
+impl AdaptiveCompressor {
+    /// Instantiate the compressor; see above for the arguments.
+    #[new]
+    pub fn __init__(level: Option<i32>, retrain_interval: Option<usize>, dict_size: Option<usize>) -> PyResult<Self> {
+        let retrain_interval = retrain_interval.unwrap_or(1000);
+        if retrain_interval == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "retrain_interval must be greater than 0",
+            ));
+        }
+        let dict_size = dict_size.unwrap_or(16 * 1024);
+        check_dict_size(dict_size)?;
+        Ok(Self {
+            level: to_py_err!(CompressionError -> internal::checked_level(level))?,
+            retrain_interval,
+            dict_size,
+            samples: Vec::with_capacity(retrain_interval),
+            dictionary: None,
+            pending_dictionary: vec![],
+        })
+    }
+    /// Compress `record`, anything in [`BytesType`](../enum.BytesType.html), into a frame of its own,
+    /// returned as `bytes`, preceded by the frame of a newly trained dictionary it's compressed with.
+    pub fn compress<'a>(&mut self, py: Python<'a>, record: BytesType) -> PyResult<&'a PyBytes> {
+        let record = record.as_bytes();
+        let mut output = std::mem::take(&mut self.pending_dictionary);
+        let dictionary = self.dictionary.as_ref().map(|(_, dictionary)| dictionary);
+        let options = internal::CompressOptions {
+            level: Some(self.level),
+            content_size: Some(record.len() as u64),
+            dict: dictionary,
+            ..Default::default()
+        };
+        to_py_err!(CompressionError -> internal::compress(record, &mut output, &options))?;
+        self.samples.push(record.to_vec());
+        if self.samples.len() >= self.retrain_interval {
+            self.retrain();
+        }
+        Ok(PyBytes::new(py, &output))
+    }
+    /// Id of the dictionary records are being compressed with, or `None` before one was trained
+    #[getter]
+    pub fn dict_id(&self) -> Option<u32> {
+        self.dictionary.as_ref().map(|(dict_id, _)| *dict_id)
+    }
+}
+
+impl AdaptiveCompressor {
+    fn retrain(&mut self) {
+        let samples = self.samples.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        // zstd fails to train from too few or too short samples, which leaves the current dictionary
+        if let Ok(dictionary) = internal::train_dict(&samples, self.dict_size) {
+            let dict_id = internal::dict_id(&dictionary);
+            self.pending_dictionary = internal::dictionary_frame(&dictionary);
+            self.dictionary = Some((dict_id, internal::EncoderDictionary::copy(&dictionary, self.level)));
+        }
+        self.samples.clear();
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for AdaptiveCompressor {
+    fn __repr__(&self) -> String {
+        format!(
+            "cramjam.zstd.AdaptiveCompressor(retrain_interval={}, dict_size={}, dict_id={})",
+            self.retrain_interval,
+            self.dict_size,
+            self.dict_id()
+                .map(|dict_id| dict_id.to_string())
+                .unwrap_or_else(|| "None".to_string())
+        )
+    }
+}
+
+/// Decompress the output of `AdaptiveCompressor`, each frame with the dictionary written before it,
+/// to the concatenation of the records; raises `DecompressionError` if a frame was compressed with
+/// a dictionary which isn't written before it. Frames compressed without a dictionary, ie. by
+/// `compress`, are decompressed as they are.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.zstd.decompress_adaptive(compressed_bytes)
+/// ```
+#[pyfunction]
+pub fn decompress_adaptive(data: BytesType) -> PyResult<RustyBuffer> {
+    let mut output = vec![];
+    to_py_err!(DecompressionError -> internal::decompress_adaptive(data.as_bytes(), &mut output))?;
+    Ok(RustyBuffer::from(output))
+}
+
+/// Decompress data of frames compressed with different dictionaries, ie. the output of a `Compressor`
+/// switching between them with `set_dict`, each frame with the one of `dicts` with the dictionary id
+/// recorded in its header; raises `DecompressionError` if a frame's dictionary isn't in `dicts`.
+/// Frames compressed without a dictionary are decompressed as they are.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.zstd.decompress_with_dicts(compressed_bytes, [dictionary, other_dictionary])
+/// ```
+#[pyfunction]
+pub fn decompress_with_dicts(mut data: BytesType, dicts: Vec<BytesType>) -> PyResult<RustyBuffer> {
+    let mut compressed = vec![];
+    data.read_to_end(&mut compressed)?;
+    let dicts = dicts
+        .into_iter()
+        .map(|mut dict| {
+            let mut dictionary = vec![];
+            dict.read_to_end(&mut dictionary).map(|_| dictionary)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let dicts = dicts.iter().map(Vec::as_slice).collect::<Vec<_>>();
+    let mut output = vec![];
+    to_py_err!(DecompressionError -> internal::decompress_with_dicts(&compressed, &dicts, &mut output))?;
+    Ok(RustyBuffer::from(output))
+}
+
+#[pyproto]
+impl PyObjectProtocol for Compressor {
+    fn __repr__(&self) -> String {
+        format!(
+            "cramjam.zstd.Compressor(finished={})",
+            if self.encoder.is_none() { "True" } else { "False" }
+        )
+    }
+}
+
+/// Get the maximum length of the compressed output for `data_len` bytes of input, the size
+/// of buffer which is always big enough to be passed to `compress_into`
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> output = bytearray(cramjam.zstd.compress_bound(len(data)))
+/// >>> n_bytes = cramjam.zstd.compress_into(data, output)
+/// ```
+#[pyfunction]
+pub fn compress_bound(data_len: usize) -> PyResult<usize> {
+    crate::checked_bound(internal::compress_bound(data_len), data_len)
+}
+
+/// Compress directly into an output buffer, returning the compressed length; `CompressionError` is
+/// raised with `compress_bound` of the input's length if the buffer is too small to hold it.
+#[pyfunction]
+pub fn compress_into(
+    mut input: BytesType,
+    output: BytesType,
+    level: Option<i32>,
+    include_content_size: Option<bool>,
+) -> PyResult<usize> {
+    let content_size = content_size(&mut input, include_content_size)?;
+    crate::compress_into(input, output, internal::compress_bound, |input, output| {
+        internal::compress(
+            input,
+            output,
+            &internal::CompressOptions {
+                level,
+                content_size,
+                ..Default::default()
+            },
+        )
+    })
+}
+
+/// Compress using `scratch`, a `numpy.array` with `dtype=np.uint8`, as the output buffer, returning
+/// a copy of the compressed data as `bytes`. The array is resized as needed, and can be reused across
+/// calls to avoid allocating a new output buffer each time; as with `numpy.ndarray.resize`, it must
+/// own its data and not be referenced by any other array.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> scratch = np.empty(0, dtype=np.uint8)
+/// >>> for data in chunks:
+/// ...     compressed = cramjam.zstd.compress_reuse(data, scratch, level=Optional[int])
+/// ```
+#[pyfunction]
+pub fn compress_reuse<'a>(
+    py: Python<'a>,
+    mut data: BytesType,
+    scratch: &PyArray1<u8>,
+    level: Option<i32>,
+) -> PyResult<&'a PyBytes> {
+    let content_size = content_size(&mut data, None)?;
+    crate::io::compress_reuse(py, scratch, |output| {
+        internal::compress(
+            data,
+            output,
+            &internal::CompressOptions {
+                level,
+                content_size,
+                ..Default::default()
+            },
+        )
+    })
+}
+
+/// Compress a `numpy.array` of any dtype, including non-contiguous views such as `arr[::2]`.
+/// The elements are gathered into a contiguous buffer, in C order, before being compressed.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> arr = np.arange(100, dtype=np.float64).reshape(10, 10)
+/// >>> cramjam.zstd.compress_strided(arr[::2, 1::3], level=Optional[int], output_len=Optional[int])
+/// ```
+#[pyfunction]
+pub fn compress_strided(
+    array: RustyStridedNumpyArray,
+    level: Option<i32>,
+    output_len: Option<usize>,
+) -> PyResult<RustyBuffer> {
+    let content_size = Some(array.len() as u64);
+    let options = internal::CompressOptions {
+        level,
+        content_size,
+        ..Default::default()
+    };
+    crate::generic!(compress(array), output_len = output_len, options = &options)
+}
+
+/// Decompress into a new `numpy.memmap` of `dtype` (default `"uint8"`) at `path`, which is created
+/// or overwritten, returning it; for data too large to decompress into memory. The data is
+/// decompressed once if it's a single frame recording its length, as by default, otherwise twice,
+/// first to find it.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> array = cramjam.zstd.decompress_to_memmap(compressed_bytes, "/tmp/data.bin", dtype="float64")
+/// ```
+#[pyfunction]
+pub fn decompress_to_memmap<'a>(
+    py: Python<'a>,
+    mut data: BytesType,
+    path: &str,
+    dtype: Option<&str>,
+) -> PyResult<&'a PyAny> {
+    // only a single frame's recorded length is that of all the data from the current position
+    let remaining_len = data.remaining_len()?;
+    let remaining = crate::detect::read_sample(&mut data, remaining_len)?;
+    let single_frame = zstd_safe::find_frame_compressed_size(&remaining) == Ok(remaining.len());
+    drop(remaining);
+    let decompressed_len = if single_frame {
+        recorded_content_size(&mut data)?
+    } else {
+        None
+    };
+    crate::decompress_to_memmap(py, data, path, dtype, decompressed_len, |input, output| {
+        internal::decompress(input, output)
+    })
 }
 
 /// Decompress directly into an output buffer
 #[pyfunction]
-pub fn decompress_into<'a>(_py: Python<'a>, input: BytesType<'a>, mut output: BytesType<'a>) -> PyResult<usize> {
-    let r = internal::decompress(input, &mut output)?;
+pub fn decompress_into<'a>(_py: Python<'a>, mut input: BytesType<'a>, mut output: BytesType<'a>) -> PyResult<usize> {
+    output.check_writable()?;
+    let decompressed_len = recorded_content_size(&mut input)?;
+    let r = crate::decompress_into_presized(input, &mut output, decompressed_len, |input, output| {
+        internal::decompress(input, output)
+    })?;
     Ok(r)
 }
 
+/// Compress from the file descriptor `in_fd` until its end, writing to `out_fd`, ie. either end of
+/// an `os.pipe()`, returning the number of bytes written; neither descriptor is closed.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.zstd.compress_fd(in_fd, out_fd, level=Optional[int])
+/// ```
+#[cfg(unix)]
+#[pyfunction]
+pub fn compress_fd(py: Python, in_fd: i32, out_fd: i32, level: Option<i32>) -> PyResult<usize> {
+    crate::io::with_fds::<CompressionError, _>(py, in_fd, out_fd, |input, output| {
+        internal::compress(
+            input,
+            output,
+            &internal::CompressOptions {
+                level,
+                ..Default::default()
+            },
+        )
+        .map(|_| ())
+    })
+}
+
+/// Decompress from the file descriptor `in_fd` until its end, writing to `out_fd`, returning
+/// the number of bytes written; neither descriptor is closed.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.zstd.decompress_fd(in_fd, out_fd)
+/// ```
+#[cfg(unix)]
+#[pyfunction]
+pub fn decompress_fd(py: Python, in_fd: i32, out_fd: i32) -> PyResult<usize> {
+    crate::io::with_fds::<DecompressionError, _>(py, in_fd, out_fd, |input, output| {
+        internal::decompress(input, output).map(|_| ())
+    })
+}
+
+/// Compress the file at `input_path` into the file at `output_path`, which is created or overwritten,
+/// returning the number of bytes written; the files are streamed through, so neither is held in
+/// memory, with the GIL released.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.zstd.compress_file("data.csv", "data.csv.zst", level=Optional[int])
+/// ```
+#[pyfunction]
+pub fn compress_file(py: Python, input_path: &str, output_path: &str, level: Option<i32>) -> PyResult<usize> {
+    to_py_err!(CompressionError -> internal::checked_level(level))?;
+    crate::io::with_paths(py, input_path, output_path, |input, output| {
+        let options = internal::CompressOptions {
+            level,
+            ..Default::default()
+        };
+        to_py_err!(CompressionError -> internal::compress(input, output, &options))
+    })
+}
+
+/// Decompress the file at `input_path` into the file at `output_path`, which is created or overwritten,
+/// returning the number of bytes written; as for `compress_file`, neither is held in memory.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.zstd.decompress_file("data.csv.zst", "data.csv")
+/// ```
+#[pyfunction]
+pub fn decompress_file(py: Python, input_path: &str, output_path: &str) -> PyResult<usize> {
+    crate::io::with_paths(
+        py,
+        input_path,
+        output_path,
+        |input, output| to_py_err!(DecompressionError -> internal::decompress(input, output)),
+    )
+}
+
+/// Decompress line by line; returns an iterator over the lines of decompressed data
+/// as `bytes`, without materializing the entire decompressed data. See [`Lines`](../io/struct.RustyLines.html)
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> for line in cramjam.zstd.decompress_lines(compressed_bytes):
+/// ...     print(line)
+/// ```
+#[pyfunction]
+pub fn decompress_lines(mut data: BytesType) -> PyResult<RustyLines> {
+    let mut compressed = vec![];
+    data.read_to_end(&mut compressed)?;
+    let decoder = to_py_err!(DecompressionError -> internal::decoder(Cursor::new(compressed)))?;
+    Ok(RustyLines::new(decoder))
+}
+
+/// Replace the `[start, end)` range of the decompressed data with `replacement`, returning the edited
+/// data compressed again, without returning the decompressed data to Python. Raises `ValueError`
+/// if `start` is greater than `end`, and `DecompressionError` if the range is beyond the end of the data.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.zstd.recompress_range(compressed_bytes, start, end, b'replacement', level=Optional[int])
+/// ```
+#[pyfunction]
+pub fn recompress_range(
+    data: BytesType,
+    start: u64,
+    end: u64,
+    replacement: BytesType,
+    level: Option<i32>,
+) -> PyResult<RustyBuffer> {
+    crate::io::recompress_range(internal::decoder(data), start, end, replacement, |input, output| {
+        internal::compress(
+            input,
+            output,
+            &internal::CompressOptions {
+                level,
+                ..Default::default()
+            },
+        )
+    })
+}
+
+/// Compress in chunks of `chunk_size` bytes, each into a zstd frame of its own, followed by an
+/// index of the frames; any chunk can then be decompressed alone with `decompress_chunk`.
+/// The index is written as a skippable frame, so the output can still be decompressed
+/// as a whole by `decompress`, or any other zstd decoder.
+///
+/// `on_chunk`, if given, is called as each chunk's frame is written with
+/// `(plaintext_offset, compressed_offset, compressed_len)`, to build an index of your own.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> compressed = cramjam.zstd.compress_chunked(b'some bytes here', chunk_size=4, level=Optional[int], on_chunk=None)
+/// >>> bytes(cramjam.zstd.decompress_chunk(compressed, 1))
+/// b' byt'
+/// ```
+#[pyfunction]
+pub fn compress_chunked(
+    data: BytesType,
+    chunk_size: usize,
+    level: Option<i32>,
+    on_chunk: Option<&PyAny>,
+) -> PyResult<RustyBuffer> {
+    let mut output = vec![];
+    let mut callback_err = None;
+    let result = internal::compress_chunked(
+        data,
+        &mut output,
+        chunk_size,
+        level,
+        |plaintext_offset, compressed_offset, compressed_len| match on_chunk {
+            Some(callback) => callback
+                .call1((plaintext_offset, compressed_offset, compressed_len))
+                .map(|_| ())
+                .map_err(|err| {
+                    callback_err = Some(err);
+                    Error::other("on_chunk callback raised")
+                }),
+            None => Ok(()),
+        },
+    );
+    if let Some(err) = callback_err {
+        return Err(err);
+    }
+    to_py_err!(CompressionError -> result)?;
+    Ok(RustyBuffer::from(output))
+}
+
+/// Decompress the chunk at `index` of the output of `compress_chunked`, without decompressing
+/// any other chunk; raises `IndexError` if there's no such chunk.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.zstd.decompress_chunk(compressed_bytes, index)
+/// ```
+#[pyfunction]
+pub fn decompress_chunk(mut data: BytesType, index: usize) -> PyResult<RustyBuffer> {
+    let mut compressed = vec![];
+    data.read_to_end(&mut compressed)?;
+    let frames = to_py_err!(DecompressionError -> internal::chunk_frames(&compressed))?;
+    let frame = frames.get(index).ok_or_else(|| {
+        pyo3::exceptions::PyIndexError::new_err(format!("Chunk {} out of range for {} chunks", index, frames.len()))
+    })?;
+    let mut output = vec![];
+    to_py_err!(DecompressionError -> internal::decompress(&compressed[frame.clone()], &mut output))?;
+    Ok(RustyBuffer::from(output))
+}
+
+/// Append `new_data`, compressed into a frame of its own, to `existing`, a stream of zstd frames;
+/// the combined stream decompresses to the concatenation of both, without recompressing `existing`.
+/// Raises `DecompressionError` if `existing` isn't a sequence of complete frames.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> combined = cramjam.zstd.append(cramjam.zstd.compress(b'some bytes '), b'here', level=Optional[int])
+/// >>> bytes(cramjam.zstd.decompress(combined))
+/// b'some bytes here'
+/// ```
+#[pyfunction]
+pub fn append(mut existing: BytesType, mut new_data: BytesType, level: Option<i32>) -> PyResult<RustyBuffer> {
+    let mut output = vec![];
+    existing.read_to_end(&mut output)?;
+    to_py_err!(DecompressionError -> internal::check_frames(&output))?;
+    let content_size = content_size(&mut new_data, None)?;
+    let options = internal::CompressOptions {
+        level,
+        content_size,
+        ..Default::default()
+    };
+    to_py_err!(CompressionError -> internal::compress(new_data, &mut output, &options))?;
+    Ok(RustyBuffer::from(output))
+}
+
+/// Compress `target` as a delta against `base`, a previous version of it, which must be given again
+/// to `decompress_delta` to reconstruct `target`; for similar buffers the output is much smaller
+/// than compressing `target` alone. `level` is as for `compress`.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> patch = cramjam.zstd.compress_delta(b'some bytes here', b'some other bytes here', level=Optional[int])
+/// >>> bytes(cramjam.zstd.decompress_delta(b'some bytes here', patch))
+/// b'some other bytes here'
+/// ```
+#[pyfunction]
+pub fn compress_delta(base: BytesType, target: BytesType, level: Option<i32>) -> PyResult<RustyBuffer> {
+    let patch = to_py_err!(CompressionError -> internal::compress_delta(base.as_bytes(), target.as_bytes(), level))?;
+    Ok(RustyBuffer::from(patch))
+}
+
+/// Reconstruct the target given to `compress_delta` from its output, `patch`, and the same `base`.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> cramjam.zstd.decompress_delta(base_bytes, patch_bytes)
+/// ```
+#[pyfunction]
+pub fn decompress_delta(base: BytesType, patch: BytesType) -> PyResult<RustyBuffer> {
+    let target = to_py_err!(DecompressionError -> internal::decompress_delta(base.as_bytes(), patch.as_bytes()))?;
+    Ok(RustyBuffer::from(target))
+}
+
+/// Train a dictionary of up to `dict_size` bytes from `samples`, for `compress` and `decompress`'s
+/// `dict`, with zstd's default trainer; see `train_dict_cover` to tune its parameters. `ValueError`
+/// is raised for a `dict_size` below 256 bytes or above 128MiB.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> dictionary = cramjam.zstd.train_dict([b'{"id": 1, "name": "a"}', b'{"id": 2, "name": "b"}', ...], 4096)
+/// >>> cramjam.zstd.compress(b'{"id": 3, "name": "c"}', dict=dictionary)
+/// ```
+#[pyfunction]
+pub fn train_dict(samples: Vec<BytesType>, dict_size: usize) -> PyResult<RustyBuffer> {
+    check_dict_size(dict_size)?;
+    let samples = samples.iter().map(|sample| sample.as_bytes()).collect::<Vec<_>>();
+    let dictionary = to_py_err!(CompressionError -> internal::train_dict(&samples, dict_size))?;
+    Ok(RustyBuffer::from(dictionary))
+}
+
+fn check_dict_size(dict_size: usize) -> PyResult<()> {
+    if dict_size < internal::MIN_DICT_SIZE {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "dict_size must be at least {} bytes",
+            internal::MIN_DICT_SIZE
+        )));
+    }
+    // the dictionary is allocated up front at its full size, before any training
+    if dict_size > internal::MAX_DICT_SIZE {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "dict_size must be at most {} bytes",
+            internal::MAX_DICT_SIZE
+        )));
+    }
+    Ok(())
+}
+
+/// Train a dictionary of up to `dict_size` bytes from `samples` with zstd's COVER algorithm.
+/// `k` is the size of the segments selected into the dictionary and `d` that of the substrings
+/// (dmers) scored within them, with `0 < d <= k <= dict_size`; either left out is searched for,
+/// trying `steps` values of `k` and `d` of 6 and 8, which is slower but finds good values for them.
+/// `ValueError` is raised for a `dict_size` below 256 bytes or above 128MiB.
+///
+/// Python Example
+/// --------------
+/// ```python
+/// >>> dictionary = cramjam.zstd.train_dict_cover(samples, 4096, k=Optional[int], d=Optional[int], steps=Optional[int])
+/// ```
+#[pyfunction]
+pub fn train_dict_cover(
+    samples: Vec<BytesType>,
+    dict_size: usize,
+    k: Option<u32>,
+    d: Option<u32>,
+    steps: Option<u32>,
+) -> PyResult<RustyBuffer> {
+    check_dict_size(dict_size)?;
+    if k == Some(0) || d == Some(0) || steps == Some(0) {
+        return Err(CompressionError::new_err("k, d and steps must be greater than 0"));
+    }
+    if let (Some(k), Some(d)) = (k, d) {
+        if d > k {
+            return Err(CompressionError::new_err(format!(
+                "d ({}) must not be greater than k ({})",
+                d, k
+            )));
+        }
+    }
+    if k.unwrap_or(0) as usize > dict_size {
+        return Err(CompressionError::new_err("k must not be greater than dict_size"));
+    }
+
+    let mut buffer = vec![];
+    let mut sample_sizes = Vec::with_capacity(samples.len());
+    for sample in &samples {
+        buffer.extend_from_slice(sample.as_bytes());
+        sample_sizes.push(sample.as_bytes().len());
+    }
+    let params = internal::CoverParams {
+        k: k.unwrap_or(0),
+        d: d.unwrap_or(0),
+        steps: steps.unwrap_or(0),
+    };
+    let dictionary =
+        to_py_err!(CompressionError -> internal::train_dict_cover(&buffer, &sample_sizes, dict_size, params))?;
+    Ok(RustyBuffer::from(dictionary))
+}
+
+/// Largest zstd frame header, `ZSTD_FRAMEHEADERSIZE_MAX`
+const MAX_FRAME_HEADER_LEN: u64 = 18;
+
+/// Decompressed length recorded in the header of the zstd frame at the current position of `data`, if any
+fn recorded_content_size(data: &mut BytesType) -> PyResult<Option<u64>> {
+    let header = crate::detect::read_sample(data, MAX_FRAME_HEADER_LEN)?;
+    match zstd_safe::get_frame_content_size(&header) {
+        zstd_safe::CONTENTSIZE_ERROR | zstd_safe::CONTENTSIZE_UNKNOWN => Ok(None),
+        content_size => Ok(Some(content_size)),
+    }
+}
+
+/// Header of the zstd frame at the current position of `data`, which is unchanged; raises
+/// `DecompressionError` if it's truncated or isn't a zstd frame header.
+fn read_frame_header(data: &mut BytesType) -> PyResult<zstd_sys::ZSTD_frameHeader> {
+    let header = crate::detect::read_sample(data, MAX_FRAME_HEADER_LEN)?;
+    let mut frame_header = std::mem::MaybeUninit::<zstd_sys::ZSTD_frameHeader>::zeroed();
+    let code =
+        unsafe { zstd_sys::ZSTD_getFrameHeader(frame_header.as_mut_ptr(), header.as_ptr() as *const _, header.len()) };
+    if unsafe { zstd_sys::ZSTD_isError(code) } != 0 {
+        return Err(DecompressionError::new_err(format!(
+            "Invalid zstd frame header: {}",
+            zstd_safe::get_error_name(code)
+        )));
+    }
+    // otherwise it's the length of the header, should the data be too short to hold all of it
+    if code != 0 {
+        return Err(DecompressionError::new_err(format!(
+            "Truncated zstd frame header, of {} bytes with {} remaining",
+            code,
+            header.len()
+        )));
+    }
+    Ok(unsafe { frame_header.assume_init() })
+}
+
+fn content_size(data: &mut BytesType, include_content_size: Option<bool>) -> PyResult<Option<u64>> {
+    match include_content_size.unwrap_or(true) {
+        true => Ok(Some(data.remaining_len()?)),
+        false => Ok(None),
+    }
+}
+
 pub(crate) mod internal {
 
-    use std::io::{Error, Read, Write};
+    use crate::io::FinishWrite;
+    use std::convert::TryFrom;
+    use std::io::{BufReader, Error, ErrorKind, Read, Write};
+    use std::ops::Range;
+    use zstd::stream::raw::{InBuffer, Operation, OutBuffer};
+    use zstd::stream::read::Decoder;
+    use zstd::stream::zio;
+    use zstd_safe::{CCtx, CParameter, DCtx, ResetDirective, Strategy};
+
+    pub use zstd::dict::{DecoderDictionary, EncoderDictionary};
+
+    /// Streaming zstd compression context; used in place of `zstd::stream::read::Encoder`
+    /// to get access to frame parameters it doesn't expose, such as the pledged source size.
+    struct Encoder<'a>(CCtx<'a>);
+
+    impl<'a> Encoder<'a> {
+        fn new(level: i32, options: &CompressOptions<'a>) -> Result<Self, Error> {
+            let mut ctx = CCtx::create();
+            if let Some(dict) = options.dict {
+                ctx.ref_cdict(dict.as_cdict()).map_err(map_error_code)?;
+            }
+            ctx.set_parameter(CParameter::CompressionLevel(level))
+                .map_err(map_error_code)?;
+            // after the level, which would otherwise reset them to the level's defaults
+            if let Some(strategy) = options.strategy {
+                ctx.set_parameter(CParameter::Strategy(strategy))
+                    .map_err(map_error_code)?;
+            }
+            if let Some(window_log) = options.window_log {
+                ctx.set_parameter(CParameter::WindowLog(window_log))
+                    .map_err(map_error_code)?;
+            }
+            // left unset for 0, so compression is exactly as it is without workers
+            if let Some(workers) = options.workers.filter(|workers| *workers > 0) {
+                ctx.set_parameter(CParameter::NbWorkers(workers))
+                    .map_err(map_error_code)?;
+            }
+            match options.content_size {
+                Some(size) => ctx.set_pledged_src_size(size).map_err(map_error_code)?,
+                None => ctx
+                    .set_parameter(CParameter::ContentSizeFlag(false))
+                    .map_err(map_error_code)?,
+            };
+            Ok(Self(ctx))
+        }
+    }
+
+    impl Operation for Encoder<'_> {
+        fn run(&mut self, input: &mut InBuffer<'_>, output: &mut OutBuffer<'_>) -> Result<usize, Error> {
+            self.0.compress_stream(output, input).map_err(map_error_code)
+        }
+        fn flush(&mut self, output: &mut OutBuffer<'_>) -> Result<usize, Error> {
+            self.0.flush_stream(output).map_err(map_error_code)
+        }
+        fn reinit(&mut self) -> Result<(), Error> {
+            self.0
+                .reset(ResetDirective::ZSTD_reset_session_only)
+                .map_err(map_error_code)?;
+            Ok(())
+        }
+        fn finish(&mut self, output: &mut OutBuffer<'_>, _finished_frame: bool) -> Result<usize, Error> {
+            self.0.end_stream(output).map_err(map_error_code)
+        }
+    }
+
+    fn map_error_code(code: usize) -> Error {
+        Error::other(zstd_safe::get_error_name(code))
+    }
+
+    /// Compression level used when none is given, or 0, which zstd itself takes as its own default;
+    /// fixed here so output doesn't change should zstd change its default
+    pub(crate) const DEFAULT_LEVEL: i32 = 3;
+
+    /// Largest output of `compress` for `input_len` bytes of input, as given by zstd; `None` if it's
+    /// too large to be represented, where zstd's own bound wraps around to less than the input.
+    pub fn compress_bound(input_len: usize) -> Option<usize> {
+        Some(zstd_safe::compress_bound(input_len)).filter(|bound| *bound >= input_len)
+    }
+
+    /// Compress each of `buffers` into a frame of its own at `level`, with one context, and one
+    /// output buffer which each is copied out of
+    pub fn compress_many(buffers: &[Vec<u8>], level: i32) -> Result<Vec<Vec<u8>>, Error> {
+        let mut compressor = zstd::block::Compressor::new();
+        let mut output = vec![];
+        buffers
+            .iter()
+            .map(|buffer| {
+                output.resize(zstd_safe::compress_bound(buffer.len()), 0);
+                let len = compressor.compress_to_buffer(buffer, &mut output, level)?;
+                Ok(output[..len].to_vec())
+            })
+            .collect()
+    }
+
+    /// Decompress each of `buffers`, into an output with room for the corresponding item of
+    /// `output_lens`, or the length recorded in its frame, if plausible; the error is given with
+    /// the index of the first which fails
+    pub fn decompress_many(buffers: &[Vec<u8>], output_lens: Option<&[usize]>) -> Result<Vec<Vec<u8>>, (usize, Error)> {
+        buffers
+            .iter()
+            .enumerate()
+            .map(|(index, buffer)| {
+                let capacity = match output_lens {
+                    Some(output_lens) => output_lens[index],
+                    // as for `crate::presized_output`, a recorded length which is implausibly long is ignored
+                    None => match zstd_safe::get_frame_content_size(buffer) {
+                        zstd_safe::CONTENTSIZE_ERROR | zstd_safe::CONTENTSIZE_UNKNOWN => 0,
+                        len if len > (buffer.len() as u64).saturating_mul(crate::MAX_PRESIZE_RATIO) => 0,
+                        len => len as usize,
+                    },
+                };
+                let mut output = Vec::with_capacity(capacity);
+                decompress(buffer.as_slice(), &mut output).map_err(|err| (index, err))?;
+                Ok(output)
+            })
+            .collect()
+    }
+
+    /// Window log of `level` for `input_len` bytes of input; the smallest covering the input, down to
+    /// zstd's minimum of 1KiB, unless the level's own window is smaller still
+    pub fn tuned_window_log(level: Option<i32>, input_len: u64) -> Result<u32, Error> {
+        let params = unsafe { zstd_sys::ZSTD_getCParams(checked_level(level)?, input_len, 0) };
+        Ok(params.windowLog)
+    }
 
-    /// Decompress gzip data
+    /// Decoder reading compressed data from `input`
+    pub fn decoder<R: Read>(input: R) -> Result<Decoder<'static, BufReader<R>>, Error> {
+        decoder_with_dict(input, None)
+    }
+
+    /// Decoder reading data compressed with `dict`, if any, from `input`
+    pub fn decoder_with_dict<'a, R: Read>(
+        input: R,
+        dict: Option<&DecoderDictionary<'a>>,
+    ) -> Result<Decoder<'a, BufReader<R>>, Error> {
+        match dict {
+            Some(dict) => Decoder::with_prepared_dictionary(BufReader::new(input), dict),
+            None => Decoder::new(input),
+        }
+    }
+
+    /// Dictionary to compress with at `level`, or the default; when compressing with a dictionary,
+    /// zstd takes the level from it rather than from the compression context
+    pub fn encoder_dictionary(dict: &[u8], level: Option<i32>) -> Result<EncoderDictionary<'_>, Error> {
+        Ok(EncoderDictionary::new(dict, checked_level(level)?))
+    }
+
+    /// `level`, or the default, checked to be within zstd's range rather than clamped to it as zstd
+    /// does; this includes the "ultra" levels 20 to 22, with windows of up to 128MiB, which zstd's
+    /// decoders accept by default, and which the `zstd` command only allows with `--ultra` for that reason.
+    pub(crate) fn checked_level(level: Option<i32>) -> Result<i32, Error> {
+        let level = level.filter(|level| *level != 0);
+        let (min, max) = (zstd_safe::min_c_level(), zstd_safe::max_c_level());
+        crate::checked_level("zstd", level, DEFAULT_LEVEL, min, max)
+    }
+
+    /// Encoder writing compressed data to `output`
+    pub fn encoder<W: Write>(output: W, level: Option<i32>) -> Result<zstd::stream::write::Encoder<'static, W>, Error> {
+        let level = checked_level(level)?;
+        zstd::stream::write::Encoder::new(output, level)
+    }
+
+    /// Encoder writing data compressed with `dict` to `output`; unlike [`encoder_dictionary`], `dict`
+    /// is copied, so the encoder doesn't borrow it
+    pub fn encoder_with_dict<W: Write>(
+        output: W,
+        level: i32,
+        dict: &[u8],
+    ) -> Result<zstd::stream::write::Encoder<'static, W>, Error> {
+        zstd::stream::write::Encoder::with_dictionary(output, level, dict)
+    }
+
+    /// Id of `dict`, as recorded in the header of frames compressed with it; 0 for a dictionary
+    /// of raw content, which has none
+    pub fn dict_id(dict: &[u8]) -> u32 {
+        unsafe { zstd_sys::ZDICT_getDictID(dict.as_ptr() as *const _, dict.len()) }
+    }
+
+    /// Decompress zstd data
     pub fn decompress<W: Write + ?Sized, R: Read>(input: R, output: &mut W) -> Result<usize, Error> {
-        let mut decoder = zstd::stream::read::Decoder::new(input)?;
+        decompress_with_dict(input, output, None)
+    }
+
+    /// Decompress zstd data compressed with `dict`, if any
+    pub fn decompress_with_dict<W: Write + ?Sized, R: Read>(
+        input: R,
+        output: &mut W,
+        dict: Option<&DecoderDictionary>,
+    ) -> Result<usize, Error> {
+        let mut decoder = decoder_with_dict(input, dict)?;
         let n_bytes = std::io::copy(&mut decoder, output)?;
         Ok(n_bytes as usize)
     }
 
-    /// Compress gzip data
-    pub fn compress<W: Write + ?Sized, R: Read>(input: R, output: &mut W, level: Option<i32>) -> Result<usize, Error> {
-        let level = level.unwrap_or_else(|| 0); // 0 will use zstd's default, currently 3
-        let mut encoder = zstd::stream::read::Encoder::new(input, level)?;
+    /// Settings of [`compress`]; the default compresses at [`DEFAULT_LEVEL`] without recording
+    /// the content size, ie. `&CompressOptions { level, ..Default::default() }`.
+    #[derive(Default)]
+    pub struct CompressOptions<'a> {
+        pub level: Option<i32>,
+        /// If given, the exact number of bytes which will be read from `input`, recorded in the frame header
+        pub content_size: Option<u64>,
+        /// If given, overrides the strategy implied by `level`
+        pub strategy: Option<Strategy>,
+        /// If given, overrides the window log implied by `level`
+        pub window_log: Option<u32>,
+        /// With it, the level it was prepared with by [`encoder_dictionary`] is used instead of `level`
+        pub dict: Option<&'a EncoderDictionary<'a>>,
+        /// If given and not 0, the number of threads compressing in parallel
+        pub workers: Option<u32>,
+    }
+
+    /// Compress zstd data with `options`
+    pub fn compress<W: Write + ?Sized, R: Read>(
+        input: R,
+        output: &mut W,
+        options: &CompressOptions,
+    ) -> Result<usize, Error> {
+        let level = checked_level(options.level)?;
+        let encoder = Encoder::new(level, options)?;
+        let mut encoder = zio::Reader::new(BufReader::new(input), encoder);
         let n_bytes = std::io::copy(&mut encoder, output)?;
         Ok(n_bytes as usize)
     }
+
+    /// Magic number of the skippable frame holding the index written by `compress_chunked`
+    const CHUNK_INDEX_MAGIC: u32 = 0x184D_2A5C;
+
+    /// Compress `input` in chunks of `chunk_size` bytes, each into a frame of its own, followed by
+    /// a skippable frame indexing them; its content is `crate::FRAME_VERSION`, the compressed size
+    /// of each frame as a u64, ending with the number of frames as a u32.
+    /// `on_chunk` is called after each frame with its chunk's offset in `input`, and its offset
+    /// and length in `output`.
+    pub fn compress_chunked<W: Write + ?Sized, R: Read, F: FnMut(usize, usize, usize) -> Result<(), Error>>(
+        mut input: R,
+        output: &mut W,
+        chunk_size: usize,
+        level: Option<i32>,
+        mut on_chunk: F,
+    ) -> Result<usize, Error> {
+        if chunk_size == 0 {
+            return Err(Error::new(ErrorKind::InvalidInput, "chunk_size must be greater than 0"));
+        }
+        // grown past 1MiB only as read, so a huge chunk_size of a short input isn't allocated up front
+        let mut chunk = Vec::with_capacity(chunk_size.min(1 << 20));
+        let mut index = vec![crate::FRAME_VERSION];
+        let mut n_chunks: u32 = 0;
+        let mut n_bytes = 0;
+        let mut n_plaintext = 0;
+        loop {
+            chunk.clear();
+            (&mut input).take(chunk_size as u64).read_to_end(&mut chunk)?;
+            if chunk.is_empty() {
+                break;
+            }
+            let frame_len = compress(
+                chunk.as_slice(),
+                output,
+                &CompressOptions {
+                    level,
+                    content_size: Some(chunk.len() as u64),
+                    ..Default::default()
+                },
+            )?;
+            index.extend_from_slice(&crate::frame_u64(frame_len as u64));
+            on_chunk(n_plaintext, n_bytes, frame_len)?;
+            n_plaintext += chunk.len();
+            n_bytes += frame_len;
+            n_chunks = n_chunks
+                .checked_add(1)
+                .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Too many chunks, use a larger chunk_size"))?;
+        }
+        index.extend_from_slice(&crate::frame_u32(n_chunks));
+
+        let index_len = u32::try_from(index.len())
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "Too many chunks, use a larger chunk_size"))?;
+        output.write_all(&CHUNK_INDEX_MAGIC.to_le_bytes())?;
+        output.write_all(&index_len.to_le_bytes())?;
+        output.write_all(&index)?;
+        Ok(n_bytes + 8 + index.len())
+    }
+
+    /// Ranges of each chunk's frame in `data`, as written by `compress_chunked`, from its index
+    pub fn chunk_frames(data: &[u8]) -> Result<Vec<Range<usize>>, Error> {
+        let invalid = || {
+            Error::new(
+                ErrorKind::InvalidData,
+                "Missing or invalid chunk index, expected the output of compress_chunked",
+            )
+        };
+        let read_u32 = |pos: usize| {
+            let bytes = data.get(pos..pos + 4).ok_or_else(invalid)?;
+            Ok::<_, Error>([bytes[0], bytes[1], bytes[2], bytes[3]])
+        };
+
+        let n_chunks = crate::read_frame_u32(read_u32(data.len().checked_sub(4).ok_or_else(invalid)?)?) as usize;
+        let index_len = n_chunks
+            .checked_mul(8)
+            .and_then(|len| len.checked_add(5))
+            .ok_or_else(invalid)?;
+        let index_start = data.len().checked_sub(index_len + 8).ok_or_else(invalid)?;
+        // the skippable frame's own header is little endian, as are all of zstd's
+        if u32::from_le_bytes(read_u32(index_start)?) != CHUNK_INDEX_MAGIC
+            || u32::from_le_bytes(read_u32(index_start + 4)?) as usize != index_len
+        {
+            return Err(invalid());
+        }
+        crate::check_frame_version(data[index_start + 8])?;
+
+        let mut frames = Vec::with_capacity(n_chunks);
+        let mut start: usize = 0;
+        for size in data[index_start + 9..data.len() - 4].chunks_exact(8) {
+            let mut frame_len = [0; 8];
+            frame_len.copy_from_slice(size);
+            let end = usize::try_from(crate::read_frame_u64(frame_len))
+                .ok()
+                .and_then(|len| start.checked_add(len))
+                .filter(|end| *end <= index_start)
+                .ok_or_else(invalid)?;
+            frames.push(start..end);
+            start = end;
+        }
+        Ok(frames)
+    }
+
+    /// Check `data` consists of complete frames, including skippable ones, without decompressing them
+    pub fn check_frames(data: &[u8]) -> Result<(), Error> {
+        let mut pos = 0;
+        while pos < data.len() {
+            let frame_len = zstd_safe::find_frame_compressed_size(&data[pos..]).map_err(|code| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "Invalid zstd frame at offset {}: {}",
+                        pos,
+                        zstd_safe::get_error_name(code)
+                    ),
+                )
+            })?;
+            pos += frame_len;
+        }
+        Ok(())
+    }
+
+    /// Largest window used by [`compress_delta`], that of the default limit of zstd's decoders, 128MiB
+    const DELTA_MAX_WINDOW_LOG: u32 = 27;
+
+    /// Compress `target` with `base` as a prefix it may refer back into, as zstd's `--patch-from`
+    /// does, so the output mostly holds where `target` differs from `base`. The window covers both
+    /// of them, up to 128MiB, beyond which the start of `base` can't be referred to.
+    pub fn compress_delta(base: &[u8], target: &[u8], level: Option<i32>) -> Result<Vec<u8>, Error> {
+        let total_len = (base.len() + target.len()).max(1) as u64;
+        let window_log = (64 - (total_len - 1).leading_zeros()).clamp(10, DELTA_MAX_WINDOW_LOG);
+        let mut ctx = CCtx::default();
+        ctx.set_parameter(CParameter::CompressionLevel(checked_level(level)?))
+            .map_err(map_error_code)?;
+        ctx.set_parameter(CParameter::WindowLog(window_log))
+            .map_err(map_error_code)?;
+        ctx.ref_prefix(base).map_err(map_error_code)?;
+        let mut output = vec![0; zstd_safe::compress_bound(target.len())];
+        let len = ctx.compress2(&mut output, target).map_err(map_error_code)?;
+        output.truncate(len);
+        Ok(output)
+    }
+
+    /// Smallest dictionary zstd's trainers accept, `ZDICT_DICTSIZE_MIN`
+    pub const MIN_DICT_SIZE: usize = 256;
+
+    /// Largest dictionary the trainers are asked for, 128MiB, the largest window zstd decompresses
+    /// by default (`1 << ZSTD_WINDOWLOG_LIMIT_DEFAULT`); dictionaries are typically tens of KiB
+    pub const MAX_DICT_SIZE: usize = 1 << 27;
+
+    /// Train a dictionary of up to `dict_size` bytes from `samples` with zstd's default trainer
+    pub fn train_dict(samples: &[&[u8]], dict_size: usize) -> Result<Vec<u8>, Error> {
+        zstd::dict::from_samples(samples, dict_size)
+    }
+
+    /// Magic number of the skippable frames holding the dictionaries written by `AdaptiveCompressor`
+    const DICTIONARY_MAGIC: u32 = 0x184D_2A5D;
+
+    /// Skippable frame holding `dictionary`, for [`decompress_adaptive`]; its content is
+    /// `crate::FRAME_VERSION` followed by the dictionary.
+    pub fn dictionary_frame(dictionary: &[u8]) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(9 + dictionary.len());
+        frame.extend_from_slice(&DICTIONARY_MAGIC.to_le_bytes());
+        frame.extend_from_slice(&(dictionary.len() as u32 + 1).to_le_bytes());
+        frame.push(crate::FRAME_VERSION);
+        frame.extend_from_slice(dictionary);
+        frame
+    }
+
+    /// Decompress the frames of `data`, each with the dictionary of the [`dictionary_frame`] last
+    /// before it, checking it's the one the frame was compressed with
+    pub fn decompress_adaptive<W: Write + ?Sized>(data: &[u8], output: &mut W) -> Result<usize, Error> {
+        let mut dictionary: Option<(u32, DecoderDictionary<'static>)> = None;
+        let mut n_bytes = 0;
+        let mut pos = 0;
+        while pos < data.len() {
+            let frame = frame_at(data, pos)?;
+            let frame_len = frame.len();
+            if frame.get(..4) == Some(&DICTIONARY_MAGIC.to_le_bytes()[..]) {
+                let content = frame
+                    .get(8..)
+                    .filter(|content| !content.is_empty())
+                    .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Empty dictionary frame"))?;
+                crate::check_frame_version(content[0])?;
+                let dict_id = dict_id(&content[1..]);
+                dictionary = Some((dict_id, DecoderDictionary::copy(&content[1..])));
+            } else {
+                let dict_id = zstd_safe::get_dict_id_from_frame(frame);
+                let frame_dictionary = match &dictionary {
+                    Some((id, dictionary)) if *id == dict_id => Some(dictionary),
+                    _ if dict_id == 0 => None,
+                    _ => {
+                        let msg = format!("Missing dictionary {} of the zstd frame at offset {}", dict_id, pos);
+                        return Err(Error::new(ErrorKind::InvalidData, msg));
+                    }
+                };
+                n_bytes += decompress_with_dict(frame, output, frame_dictionary)?;
+            }
+            pos += frame_len;
+        }
+        Ok(n_bytes)
+    }
+
+    /// Decompress the frames of `data`, each with the one of `dicts` with the dictionary id recorded
+    /// in its header, or without one for frames which record none
+    pub fn decompress_with_dicts<W: Write + ?Sized>(
+        data: &[u8],
+        dicts: &[&[u8]],
+        output: &mut W,
+    ) -> Result<usize, Error> {
+        let dicts = dicts
+            .iter()
+            .map(|dict| (dict_id(dict), DecoderDictionary::copy(dict)))
+            .collect::<Vec<_>>();
+        let mut n_bytes = 0;
+        let mut pos = 0;
+        while pos < data.len() {
+            let frame = frame_at(data, pos)?;
+            let frame_dictionary = match zstd_safe::get_dict_id_from_frame(frame) {
+                0 => None,
+                dict_id => match dicts.iter().find(|(id, _)| *id == dict_id) {
+                    Some((_, dictionary)) => Some(dictionary),
+                    None => {
+                        let msg = format!("Missing dictionary {} of the zstd frame at offset {}", dict_id, pos);
+                        return Err(Error::new(ErrorKind::InvalidData, msg));
+                    }
+                },
+            };
+            n_bytes += decompress_with_dict(frame, output, frame_dictionary)?;
+            pos += frame.len();
+        }
+        Ok(n_bytes)
+    }
+
+    /// The whole frame, or skippable frame, starting at `pos` in `data`
+    fn frame_at(data: &[u8], pos: usize) -> Result<&[u8], Error> {
+        let frame = &data[pos..];
+        let frame_len = zstd_safe::find_frame_compressed_size(frame).map_err(|code| {
+            let msg = format!(
+                "Invalid zstd frame at offset {}: {}",
+                pos,
+                zstd_safe::get_error_name(code)
+            );
+            Error::new(ErrorKind::InvalidData, msg)
+        })?;
+        Ok(&frame[..frame_len])
+    }
+
+    /// Parameters of [`train_dict_cover`]; `k` and `d` of 0 are searched for, trying `steps` of `k`
+    pub struct CoverParams {
+        pub k: u32,
+        pub d: u32,
+        pub steps: u32,
+    }
+
+    /// Train a dictionary of up to `dict_size` bytes with COVER from `samples`, the concatenation
+    /// of samples of `sample_sizes`; with `k` and `d` given it's trained once with them, otherwise
+    /// `ZDICT_optimizeTrainFromBuffer_cover` trains with a range of them, keeping the best.
+    pub fn train_dict_cover(
+        samples: &[u8],
+        sample_sizes: &[usize],
+        dict_size: usize,
+        params: CoverParams,
+    ) -> Result<Vec<u8>, Error> {
+        let n_samples = u32::try_from(sample_sizes.len())
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "Too many samples to train a dictionary"))?;
+        let mut cover_params = zstd_sys::ZDICT_cover_params_t {
+            k: params.k,
+            d: params.d,
+            steps: params.steps,
+            nbThreads: 1,
+            splitPoint: 0.,
+            shrinkDict: 0,
+            shrinkDictMaxRegression: 0,
+            zParams: zstd_sys::ZDICT_params_t {
+                compressionLevel: 0,
+                notificationLevel: 0,
+                dictID: 0,
+            },
+        };
+        let mut dictionary = vec![0; dict_size];
+        let len = unsafe {
+            zstd_sys::ZDICT_optimizeTrainFromBuffer_cover(
+                dictionary.as_mut_ptr() as *mut _,
+                dictionary.len(),
+                samples.as_ptr() as *const _,
+                sample_sizes.as_ptr(),
+                n_samples,
+                &mut cover_params,
+            )
+        };
+        if unsafe { zstd_sys::ZDICT_isError(len) } != 0 {
+            return Err(map_error_code(len));
+        }
+        dictionary.truncate(len);
+        Ok(dictionary)
+    }
+
+    /// Reconstruct the `target` given to [`compress_delta`] from `patch` and the same `base`
+    pub fn decompress_delta(base: &[u8], patch: &[u8]) -> Result<Vec<u8>, Error> {
+        let len = match zstd_safe::get_frame_content_size(patch) {
+            zstd_safe::CONTENTSIZE_ERROR | zstd_safe::CONTENTSIZE_UNKNOWN => {
+                return Err(Error::new(ErrorKind::InvalidData, "Invalid zstd delta patch"))
+            }
+            len => usize::try_from(len).map_err(|_| Error::new(ErrorKind::InvalidData, "Invalid zstd delta patch"))?,
+        };
+        let mut ctx = DCtx::default();
+        ctx.ref_prefix(base).map_err(map_error_code)?;
+        // as for `crate::presized_output`, a recorded length which is implausibly long is only grown
+        // into as it's decoded, rather than allocated up front
+        let capacity = match len as u64 {
+            len if len > (patch.len() as u64).saturating_mul(crate::MAX_PRESIZE_RATIO) => 0,
+            _ => len,
+        };
+        let mut output = Vec::with_capacity(capacity);
+        let mut chunk = vec![0; DCtx::out_size()];
+        let mut input = InBuffer::around(patch);
+        loop {
+            let mut out = OutBuffer::around(&mut chunk[..]);
+            let remaining = ctx.decompress_stream(&mut out, &mut input).map_err(map_error_code)?;
+            output.extend_from_slice(out.as_slice());
+            if remaining == 0 {
+                return Ok(output);
+            }
+            if input.pos == patch.len() && out.pos < out.dst.len() {
+                return Err(Error::new(ErrorKind::UnexpectedEof, "Truncated zstd delta patch"));
+            }
+        }
+    }
+
+    impl<W: Write + Send> FinishWrite for zstd::stream::write::Encoder<'static, W> {
+        fn finish(self: Box<Self>) -> Result<(), Error> {
+            zstd::stream::write::Encoder::finish(*self)?.flush()
+        }
+    }
 }
@@ -0,0 +1,380 @@
+import pytest
+import cramjam
+
+
+@pytest.mark.parametrize(
+    "algorithm", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd", "bzip2", "lzma", "zlib")
+)
+def test_generic_builtin(algorithm):
+    data = b"some bytes to compress 123" * 1000
+    compressed = bytes(cramjam.compress(data, algorithm))
+    assert compressed == bytes(getattr(cramjam, algorithm).compress(data))
+    assert bytes(cramjam.decompress(compressed, algorithm)) == data
+    assert bytes(cramjam.decompress(compressed, algorithm=algorithm, output_len=len(data))) == data
+
+
+def test_generic_default_algorithm():
+    data = b"some bytes to compress 123" * 1000
+    assert bytes(cramjam.compress(data)) == bytes(cramjam.zstd.compress(data))
+    compressed = bytes(cramjam.zstd.compress(data, level=1))
+    assert bytes(cramjam.compress(data, level=1, output_len=len(compressed))) == compressed
+    assert bytes(cramjam.decompress(compressed)) == data
+
+    with pytest.raises(ValueError, match="Unknown algorithm 'zip', should be one of .*'zstd'.*'zlib'"):
+        cramjam.compress(data, algorithm="zip")
+    with pytest.raises(ValueError, match="Cannot replace the built-in algorithm 'lzma'"):
+        cramjam.register_codec("lzma", bytes, bytes)
+
+
+def test_generic_passes_kwargs():
+    data = b"some bytes to compress 123" * 1000
+    compressed = cramjam.compress(data, "gzip", level=1)
+    assert bytes(compressed) == bytes(cramjam.gzip.compress(data, level=1))
+
+
+@pytest.mark.parametrize(
+    "algorithm", ("snappy", "brotli", "lz4", "gzip", "zstd", "bzip2", "lzma", "zlib")
+)
+def test_decompress_detects_algorithm(algorithm):
+    data = b"some bytes to compress 123" * 1000
+    compressed = getattr(cramjam, algorithm).compress(data)
+    assert bytes(cramjam.decompress(compressed)) == data
+    assert bytes(cramjam.decompress(bytes(compressed), output_len=len(data))) == data
+
+
+def test_decompress_detects_algorithm_of_file(tmp_path):
+    data = b"some bytes to compress 123" * 1000
+    path = tmp_path / "data.br"
+    path.write_bytes(bytes(cramjam.brotli.compress(data)))
+    assert bytes(cramjam.decompress(cramjam.File(str(path)))) == data
+
+
+def test_decompress_undetected():
+    with pytest.raises(cramjam.DecompressionError, match="Unable to detect"):
+        cramjam.decompress(b"some bytes which aren't compressed")
+    with pytest.raises(cramjam.DecompressionError, match="Detected zip data"):
+        cramjam.decompress(b"PK\x03\x04 and the rest of a zip file")
+    # data with a magic number isn't tried as any other format
+    with pytest.raises(cramjam.DecompressionError):
+        cramjam.decompress(bytes(cramjam.gzip.compress(b"some bytes here"))[:-4])
+
+
+def test_register_codec():
+    def xor(data, key=0x5A):
+        return bytes(b ^ key for b in bytes(data))
+
+    cramjam.register_codec("xor", xor, xor)
+
+    data = b"some bytes to compress 123"
+    compressed = cramjam.compress(data, "xor")
+    assert compressed != data
+    assert cramjam.decompress(compressed, "xor") == data
+
+    compressed = cramjam.compress(data, "xor", key=1)
+    assert compressed == xor(data, key=1)
+    assert cramjam.decompress(compressed, "xor", key=1) == data
+
+
+def test_register_codec_invalid():
+    with pytest.raises(ValueError, match="built-in"):
+        cramjam.register_codec("zstd", bytes, bytes)
+    with pytest.raises(TypeError, match="callable"):
+        cramjam.register_codec("not-callable", bytes, None)
+    with pytest.raises(ValueError, match="Unknown algorithm 'missing'"):
+        cramjam.compress(b"data", "missing")
+
+
+@pytest.mark.parametrize(
+    "algorithm", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_compress_tagged(algorithm):
+    data = b"some bytes to compress 123" * 1000
+    level = None if algorithm == "snappy" else 1
+    compressed = bytes(cramjam.compress_tagged(data, algorithm, level=level))
+    assert compressed[:4] == b"CJTG" and compressed[4] == cramjam.FRAME_VERSION
+    assert bytes(cramjam.decompress_tagged(compressed)) == data
+    assert bytes(cramjam.decompress_tagged(cramjam.compress_tagged(data, algorithm))) == data
+
+
+def test_compress_tagged_invalid():
+    compressed = bytes(cramjam.compress_tagged(b"some bytes here", "zstd"))
+    with pytest.raises(ValueError, match="Unknown algorithm 'nope'"):
+        cramjam.compress_tagged(b"some bytes here", "nope")
+    with pytest.raises(cramjam.CompressionError, match="no levels"):
+        cramjam.compress_tagged(b"some bytes here", "snappy", level=1)
+    with pytest.raises(cramjam.DecompressionError, match="Missing tagged header"):
+        cramjam.decompress_tagged(b"CJ")
+    with pytest.raises(cramjam.DecompressionError, match="Missing tagged header"):
+        cramjam.decompress_tagged(b"XXXX" + compressed[4:])
+    with pytest.raises(cramjam.DecompressionError, match="Unknown algorithm id 200"):
+        cramjam.decompress_tagged(compressed[:5] + bytes([200]) + compressed[6:])
+
+
+def test_pipeline():
+    def xor(data, key):
+        return bytes(b ^ key for b in bytes(data))
+
+    cramjam.register_codec("xor-pipeline", xor, xor)
+
+    data = b"some bytes to compress 123" * 1000
+    pipeline = cramjam.Pipeline(
+        [("xor-pipeline", {"key": 7}, {"key": 7}), ("gzip", {"level": 1}), "zstd"]
+    )
+    assert repr(pipeline) == "cramjam.Pipeline(stages=['xor-pipeline', 'gzip', 'zstd'])"
+
+    compressed = pipeline.compress(data)
+    expected = cramjam.zstd.compress(cramjam.gzip.compress(xor(data, 7), level=1))
+    assert bytes(compressed) == bytes(expected)
+    assert bytes(pipeline.decompress(compressed)) == data
+
+
+def test_pipeline_invalid():
+    with pytest.raises(ValueError, match="Unknown algorithm 'missing'"):
+        cramjam.Pipeline(["zstd", "missing"])
+    with pytest.raises(ValueError, match="at least one stage"):
+        cramjam.Pipeline([])
+
+
+@pytest.mark.parametrize(
+    "algorithm", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_is_probably_compressed(algorithm):
+    import random
+
+    rng = random.Random(0)
+    text = b"".join(b"line %d of some plain text, value %d\n" % (i, rng.getrandbits(32)) for i in range(10000))
+    assert not cramjam.is_probably_compressed(text)
+
+    compressed = cramjam.compress(text, algorithm)
+    assert cramjam.is_probably_compressed(compressed)
+    assert compressed.tell() == 0  # the position is unchanged
+
+    randbytes = bytes(rng.getrandbits(8) for _ in range(10000))
+    assert cramjam.is_probably_compressed(randbytes)
+
+
+@pytest.mark.parametrize("algorithm", ("gzip", "zstd", "lz4", "snappy"))
+def test_peek(algorithm):
+    data = b"some bytes here" * 100
+    compressed = getattr(cramjam, algorithm).compress(data)
+
+    metadata = cramjam.peek(compressed)
+    assert metadata == getattr(cramjam, algorithm).peek(compressed)
+    assert set(metadata) == {"format", "content_size", "dict_id", "flags"}
+    assert metadata["format"] == algorithm
+    assert metadata["content_size"] == (len(data) if algorithm == "zstd" else None)
+    assert metadata["dict_id"] is None
+    assert (metadata["flags"] is None) == (algorithm == "snappy")
+    assert compressed.tell() == 0  # the position is unchanged
+
+    with pytest.raises(cramjam.DecompressionError, match="Unrecognized format"):
+        getattr(cramjam, algorithm).peek(b"plain text")
+
+
+def test_peek_lz4_header_fields():
+    # FLG with the content size and dictionary id bits set, BD, content size, dictionary id, HC
+    header = b"\x04\x22\x4d\x18" + bytes([0x69, 0x40]) + (1234).to_bytes(8, "little") + (7).to_bytes(4, "little") + b"\x00"
+    assert cramjam.lz4.peek(header) == {"format": "lz4", "content_size": 1234, "dict_id": 7, "flags": 0x69}
+    with pytest.raises(cramjam.DecompressionError, match="Truncated lz4 frame header"):
+        cramjam.lz4.peek(header[:10])
+
+    with pytest.raises(cramjam.DecompressionError, match="Unrecognized format"):
+        cramjam.peek(b"plain text")
+    with pytest.raises(cramjam.DecompressionError, match="Unrecognized format"):
+        cramjam.zstd.peek(cramjam.gzip.compress(b"some bytes here"))
+
+
+def test_entropy():
+    import random
+
+    rng = random.Random(0)
+    randbytes = bytes(rng.getrandbits(8) for _ in range(100000))
+    assert 7.99 < cramjam.entropy(randbytes) <= 8.0
+    assert cramjam.entropy(b"some repetitive text " * 1000) < 4.0
+    assert cramjam.entropy(b"a" * 1000) == 0.0
+    assert cramjam.entropy(b"") == 0.0
+
+    # only the sample is examined
+    assert cramjam.entropy(b"a" * 1000 + randbytes, sample_bytes=1000) == 0.0
+    assert cramjam.entropy(b"ab" * 10, sample_bytes=2) == 1.0
+
+
+@pytest.mark.parametrize("algorithm", ("gzip", "zstd", "lz4", "snappy"))
+@pytest.mark.parametrize("chunk_size", (1, 7, 4096))
+def test_auto_decompressor(algorithm, chunk_size):
+    data = b"".join(b"line %d of some data\n" % i for i in range(10000))
+    compressed = bytes(getattr(cramjam, algorithm).compress(data))
+
+    decompressor = cramjam.AutoDecompressor()
+    # a single byte is too short to detect any of the formats
+    output = decompressor.decompress(compressed[:1])
+    assert output == b"" and decompressor.format is None
+
+    for i in range(1, len(compressed), chunk_size):
+        output += decompressor.decompress(compressed[i : i + chunk_size])
+    output += decompressor.finish()
+    assert decompressor.format == algorithm
+    assert output == data
+
+
+@pytest.mark.parametrize("chunk_size", (1, 7, 4096))
+def test_auto_decompressor_gzip_multi_member(chunk_size):
+    first, second = b"first member " * 1000, b"second member " * 1000
+    compressed = bytes(cramjam.gzip.compress(first)) + bytes(cramjam.gzip.compress(second))
+
+    decompressor = cramjam.AutoDecompressor()
+    output = b""
+    for i in range(0, len(compressed), chunk_size):
+        output += decompressor.decompress(compressed[i : i + chunk_size])
+    output += decompressor.finish()
+    assert decompressor.format == "gzip"
+    assert output == first + second
+
+
+
+@pytest.mark.parametrize("algorithm", ("gzip", "zstd", "lz4", "snappy"))
+def test_auto_decompressor_max_buffered(algorithm):
+    data = b"".join(b"line %d of some data\n" % i for i in range(500000))
+    compressed = bytes(getattr(cramjam, algorithm).compress(data))
+
+    decompressor = cramjam.AutoDecompressor(max_buffered=1_000_000)
+    output = b"".join(decompressor.decompress(compressed[i : i + 100]) for i in range(0, len(compressed), 100))
+    assert output + decompressor.finish() == data
+
+    decompressor = cramjam.AutoDecompressor(max_buffered=1_000_000)
+    with pytest.raises(cramjam.DecompressionError, match="^output backpressure exceeded$"):
+        decompressor.decompress(compressed)
+    with pytest.raises(ValueError, match="already finished"):
+        decompressor.decompress(compressed)
+
+def test_auto_decompressor_stats():
+    data = b"".join(b"line %d of some data\n" % i for i in range(10000))
+    compressed = bytes(cramjam.zstd.compress(data))
+
+    decompressor = cramjam.AutoDecompressor()
+    assert decompressor.stats == {"bytes_in": 0, "bytes_out": 0, "elapsed_ns": 0}
+    for i in range(0, len(compressed), 1000):
+        decompressor.decompress(compressed[i : i + 1000])
+    decompressor.finish()
+
+    stats = decompressor.stats
+    assert stats["bytes_in"] == len(compressed)
+    assert stats["bytes_out"] == len(data)
+    assert stats["elapsed_ns"] > 0
+
+
+@pytest.mark.parametrize("algorithm", ("gzip", "zstd", "lz4", "snappy"))
+def test_auto_decompressor_truncated(algorithm):
+    compressed = bytes(getattr(cramjam, algorithm).compress(b"some bytes here" * 100))
+    decompressor = cramjam.AutoDecompressor()
+    decompressor.decompress(compressed[:-3])
+    with pytest.raises(cramjam.DecompressionError, match="ended"):
+        decompressor.finish()
+
+
+def test_auto_decompressor_invalid():
+    with pytest.raises(cramjam.DecompressionError, match="Unrecognized format"):
+        cramjam.AutoDecompressor().decompress(b"plain text")
+    with pytest.raises(cramjam.DecompressionError, match="detected"):
+        cramjam.AutoDecompressor().finish()
+    with pytest.raises(cramjam.DecompressionError, match="can't decompress"):
+        cramjam.AutoDecompressor().decompress(b"BZh91AY&SY")
+
+
+@pytest.mark.parametrize("algorithm", ("zstd", "snappy"))
+@pytest.mark.parametrize("split", ("header", "frame", "between", "end"))
+def test_auto_decompressor_resume(algorithm, split):
+    data = b"".join(b"line %d of some data\n" % i for i in range(10000))
+    # two frames, so the decode is resumed from both within and between them
+    compressed = bytes(getattr(cramjam, algorithm).compress(data)) * 2
+    split = dict(header=1, frame=5000, between=len(compressed) // 2, end=len(compressed) - 3)[split]
+
+    decompressor = cramjam.AutoDecompressor(resumable=True)
+    first = decompressor.decompress(compressed[:split])
+    state = decompressor.get_state()
+    assert isinstance(state, bytes)
+
+    resumed = cramjam.AutoDecompressor()
+    resumed.set_state(state)
+    assert resumed.format == decompressor.format
+    rest = resumed.decompress(compressed[split:]) + resumed.finish()
+    assert first + rest == data * 2
+    assert resumed.stats["bytes_in"] == len(compressed)
+    assert resumed.stats["bytes_out"] == len(data) * 2
+
+    # getting the state leaves the original as it was
+    assert decompressor.decompress(compressed[split:]) + decompressor.finish() == rest
+
+
+def test_auto_decompressor_resume_unsupported():
+    with pytest.raises(ValueError, match="resumable=True"):
+        cramjam.AutoDecompressor().get_state()
+
+    for algorithm in ("gzip", "lz4"):
+        decompressor = cramjam.AutoDecompressor(resumable=True)
+        decompressor.decompress(bytes(getattr(cramjam, algorithm).compress(b"some bytes here"))[:-3])
+        with pytest.raises(ValueError, match="%s streams can't be resumed" % algorithm):
+            decompressor.get_state()
+
+    with pytest.raises(ValueError, match="Invalid AutoDecompressor state"):
+        cramjam.AutoDecompressor().set_state(b"\x01\x01")
+    with pytest.raises(ValueError, match="frame version 2"):
+        cramjam.AutoDecompressor().set_state(b"\x02" + bytes(34))
+
+
+def test_recommend_algorithm():
+    import random
+
+    rng = random.Random(0)
+    text = b"".join(b"line %d of some plain text, value %d\n" % (i, rng.getrandbits(32)) for i in range(10000))
+    randbytes = bytes(rng.getrandbits(8) for _ in range(100000))
+
+    # by ratio, the smallest output of compressible data, which depends only on the data; otherwise
+    # the fastest, which depends on timing, so is only checked to be one of them
+    assert cramjam.recommend_algorithm(text) == cramjam.recommend_algorithm(text, prefer="ratio") == "brotli"
+    algorithms = ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+    for sample in (text, randbytes, b""):
+        for prefer in ("ratio", "speed"):
+            algorithm = cramjam.recommend_algorithm(sample, prefer=prefer)
+            assert algorithm in algorithms
+            assert bytes(cramjam.decompress(cramjam.compress(sample, algorithm), algorithm)) == sample
+
+    with pytest.raises(ValueError, match="prefer"):
+        cramjam.recommend_algorithm(text, prefer="size")
+
+
+def test_split_records():
+    import struct
+
+    records = [b"first", b"", b"x" * 300, b"last record"]
+    packed = b"".join(struct.pack("<I", len(record)) + record for record in records)
+
+    views = cramjam.split_records(packed)
+    assert [bytes(view) for view in views] == records
+    # the views refer to the packed data rather than copy it
+    assert all(isinstance(view, memoryview) and view.obj is packed for view in views)
+
+    buffer = bytearray(packed)
+    views = cramjam.split_records(buffer, prefix="u32le")
+    buffer[4:9] = b"FIRST"
+    assert bytes(views[0]) == b"FIRST"
+
+    for prefix, fmt in (("u16le", "<H"), ("u64le", "<Q")):
+        packed = b"".join(struct.pack(fmt, len(record)) + record for record in records)
+        assert [bytes(view) for view in cramjam.split_records(packed, prefix=prefix)] == records
+
+    # varints, 300 takes two bytes
+    packed = b"\x05first" + b"\x00" + b"\xac\x02" + b"x" * 300 + b"\x0blast record"
+    assert [bytes(view) for view in cramjam.split_records(packed, prefix="varint")] == records
+
+    assert cramjam.split_records(b"") == []
+    with pytest.raises(ValueError, match="Truncated record at offset 0"):
+        cramjam.split_records(b"\x05\x00\x00\x00abc")
+    with pytest.raises(ValueError, match="Truncated record length at offset 9"):
+        cramjam.split_records(b"\x05\x00\x00\x00abcde\x01\x00")
+    with pytest.raises(ValueError, match="Truncated record length"):
+        cramjam.split_records(b"\x80\x80", prefix="varint")
+    with pytest.raises(ValueError, match="longer than 64 bits"):
+        cramjam.split_records(b"\xff" * 10 + b"\x01", prefix="varint")
+    with pytest.raises(ValueError, match="Unknown prefix 'u32be'"):
+        cramjam.split_records(b"", prefix="u32be")
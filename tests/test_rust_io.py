@@ -1,5 +1,6 @@
 import pytest
 
+import cramjam
 from cramjam import File, Buffer
 
 
@@ -55,3 +56,176 @@ def test_obj_api(tmpdir, Obj):
     buf.truncate()
     buf.seek(0)
     assert buf.read() == b""
+
+
+@pytest.mark.parametrize("Obj", (File, Buffer))
+def test_obj_shared_across_threads(tmpdir, Obj):
+    import threading
+
+    if Obj == File:
+        buf = File(str(tmpdir.join("file.txt")))
+    else:
+        buf = Buffer()
+
+    def writer():
+        for _ in range(100):
+            buf.write(b"chunk")
+
+    # their methods hold the GIL throughout, so concurrent writes are serialized rather than failing
+    threads = [threading.Thread(target=writer) for _ in range(4)]
+    for thread in threads:
+        thread.start()
+    for thread in threads:
+        thread.join()
+
+    assert len(buf) == 4 * 100 * len(b"chunk")
+    buf.seek(0)
+    assert buf.read() == b"chunk" * 400
+
+
+def test_obj_borrowed_across_threads():
+    import threading
+
+    # a RingCompressor calls drain while it's borrowed by write, so a write from another thread
+    # meanwhile is refused by pyo3's borrow checking, rather than racing
+    errors = []
+
+    def other_writer():
+        try:
+            compressor.write(b"more")
+        except RuntimeError as err:
+            errors.append(err)
+
+    def drain(n):
+        thread = threading.Thread(target=other_writer)
+        thread.start()
+        thread.join()
+
+    ring = bytearray(16)
+    compressor = cramjam.RingCompressor("gzip", ring, drain)
+    compressor.write(bytes(range(256)) * 10)
+    compressor.finish()
+
+    assert errors and all(str(err) == "Already borrowed" for err in errors)
+
+
+@pytest.mark.parametrize(
+    "algorithm", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_open_round_trip(tmpdir, algorithm):
+    path = str(tmpdir.join(f"file.txt.{algorithm}"))
+    text = "some text to compress\n" * 100
+
+    with cramjam.open(path, "wb", algorithm) as f:
+        assert f.writable() and not f.readable()
+        assert f.write(text.encode()) == len(text)
+    assert f.closed
+
+    with open(path, "rb") as raw:
+        assert len(raw.read()) < len(text)
+
+    with cramjam.open(path, "rb", algorithm) as f:
+        assert f.read(4) == b"some"
+        assert f.read().decode() == text[4:]
+
+    with pytest.raises(ValueError, match="closed file"):
+        f.read()
+
+
+def test_open_invalid_args(tmpdir):
+    path = str(tmpdir.join("file.txt"))
+    with pytest.raises(ValueError, match="Invalid mode"):
+        cramjam.open(path, "ab", "zstd")
+    with pytest.raises(ValueError, match="Unknown algorithm"):
+        cramjam.open(path, "wb", "bzip3")
+    with pytest.raises(ValueError, match="snappy has no compression levels"):
+        cramjam.open(path, "wb", "snappy", level=1)
+
+
+def test_open_invalid_args_keeps_existing_file(tmpdir):
+    path = str(tmpdir.join("file.txt"))
+    with open(path, "wb") as f:
+        f.write(b"existing data")
+
+    # the arguments are checked before the file is truncated
+    with pytest.raises(ValueError, match="Unknown algorithm"):
+        cramjam.open(path, "wb", "bogus")
+    with pytest.raises(cramjam.CompressionError):
+        cramjam.open(path, "wb", "gzip", level=10)
+    with open(path, "rb") as f:
+        assert f.read() == b"existing data"
+
+
+@pytest.mark.parametrize("algorithm", ("lz4", "zstd"))
+def test_compressed_file_finished_without_close(tmpdir, algorithm):
+    path = str(tmpdir.join(f"file.txt.{algorithm}"))
+    data = b"some bytes to compress\n" * 1000
+
+    f = cramjam.open(path, "wb", algorithm)
+    f.write(data)
+    del f  # garbage collected without close, which finishes the stream then
+
+    with cramjam.open(path, "rb", algorithm) as f:
+        assert f.read() == data
+
+
+@pytest.mark.parametrize(
+    "algorithm,level,expected",
+    [("zstd", 7, 7), ("gzip", None, 6), ("brotli", 1, 1), ("lz4", None, 4), ("snappy", None, None)],
+)
+def test_compressed_file_params(tmpdir, algorithm, level, expected):
+    path = str(tmpdir.join(f"file.txt.{algorithm}"))
+
+    with cramjam.open(path, "wb", algorithm, level=level) as f:
+        assert f.params() == {"algorithm": algorithm, "mode": "wb", "level": expected}
+        f.write(b"some bytes")
+
+    with cramjam.open(path, "rb", algorithm) as f:
+        assert f.params() == {"algorithm": algorithm, "mode": "rb", "level": None}
+
+
+@pytest.mark.parametrize(
+    "algorithm", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_ring_compressor(algorithm):
+    import random
+
+    rng = random.Random(0)
+    chunks = [b"".join(b"record %d\n" % rng.randrange(10 ** 6) for _ in range(1000)) for _ in range(50)]
+
+    ring = bytearray(1024)
+    drained = []
+
+    def drain(n):
+        assert 0 < n <= len(ring)
+        drained.append(bytes(ring[:n]))
+
+    compressor = cramjam.RingCompressor(algorithm, ring, drain)
+    for chunk in chunks:
+        assert compressor.write(chunk) == len(chunk)
+    compressor.finish()
+    compressor.finish()
+
+    # never a larger output buffer than the ring, so it was drained many times over
+    assert len(ring) == 1024
+    assert len(drained) > 10
+    decompressed = bytes(getattr(cramjam, algorithm).decompress(b"".join(drained)))
+    assert decompressed == b"".join(chunks)
+
+    with pytest.raises(ValueError, match="already finished"):
+        compressor.write(b"more")
+
+
+def test_ring_compressor_drain_error():
+    def drain(n):
+        raise KeyError("drain failed")
+
+    compressor = cramjam.RingCompressor("gzip", bytearray(16), drain)
+    with pytest.raises(KeyError, match="drain failed"):
+        compressor.write(bytes(range(256)) * 10)
+        compressor.finish()
+
+    with pytest.raises(ValueError, match="must not be empty"):
+        cramjam.RingCompressor("gzip", bytearray(), drain)
+    with pytest.raises(BufferError, match="read-only"):
+        cramjam.RingCompressor("gzip", memoryview(bytes(16)), drain)
@@ -36,6 +36,19 @@ def test_variants_simple(variant_str, is_bytearray):
     assert isinstance(decompressed, cramjam.Buffer)
 
 
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd", "bzip2", "lzma", "zlib")
+)
+def test_variants_output_len_too_small(variant_str):
+    variant = getattr(cramjam, variant_str)
+    uncompressed = b"some bytes to compress 123" * 100000
+    compressed = bytes(variant.compress(uncompressed))
+
+    # the output is grown past output_len to hold all of the data, rather than truncated at it
+    for output_len in (0, 1, len(uncompressed) // 2, len(uncompressed) - 1):
+        assert bytes(variant.decompress(compressed, output_len=output_len)) == uncompressed
+
+
 @pytest.mark.parametrize(
     "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
 )
@@ -173,6 +186,83 @@ def test_variant_snappy_raw_into():
     assert same_same(decompressed_buffer[:n_bytes], data)
 
 
+def test_snappy_format(tmpdir):
+    data = b"oh what a beautiful morning, oh what a beautiful day!!" * 1000
+
+    framed = bytes(cramjam.snappy.compress(data, format="framed"))
+    raw = bytes(cramjam.snappy.compress(data, format="raw"))
+    assert framed == bytes(cramjam.snappy.compress(data))
+    assert raw == bytes(cramjam.snappy.compress_raw(data))
+    assert bytes(cramjam.snappy.decompress(framed, format="framed")) == data
+    assert bytes(cramjam.snappy.decompress(raw, format="raw")) == data
+    assert bytes(cramjam.snappy.decompress(raw, format="raw", output_len=len(data))) == data
+    assert bytes(cramjam.snappy.decompress(raw, format="raw", pad_to=None, trailer_checksum=None)) == data
+
+    # the formats aren't compatible
+    with pytest.raises(cramjam.DecompressionError):
+        cramjam.snappy.decompress(raw)
+    with pytest.raises(cramjam.DecompressionError):
+        cramjam.snappy.decompress(framed, format="raw")
+
+    # blocks as the reference snappy library, used by Parquet writers, encodes them; a literal, and
+    # a literal followed by a copy of it
+    assert bytes(cramjam.snappy.compress(b"hello world", format="raw")) == b"\x0b\x28hello world"
+    assert bytes(cramjam.snappy.decompress(b"\x0b\x28hello world", format="raw")) == b"hello world"
+    assert bytes(cramjam.snappy.decompress(b"\x0c\x08abc\x15\x03", format="raw")) == b"abc" * 4
+
+    with pytest.raises(ValueError, match="Unknown format 'block'"):
+        cramjam.snappy.compress(data, format="block")
+    with pytest.raises(ValueError, match="chunk_size"):
+        cramjam.snappy.compress(data, format="raw", chunk_size=4096)
+    with pytest.raises(ValueError, match="framed format"):
+        cramjam.snappy.decompress(raw, format="raw", recover=True)
+
+    # a too small output_len is an error rather than ignored, as for decompress_raw
+    with pytest.raises(cramjam.DecompressionError):
+        cramjam.snappy.decompress_raw(raw, output_len=len(data) - 1)
+    with pytest.raises(cramjam.DecompressionError):
+        cramjam.snappy.decompress(raw, format="raw", output_len=len(data) - 1)
+
+    # both read a cramjam.File from its position
+    file = cramjam.File(str(tmpdir.join("data.bin")))
+    file.write(b"prefix" + data)
+    for compress in (cramjam.snappy.compress_raw, lambda data: cramjam.snappy.compress(data, format="raw")):
+        file.seek(6)
+        assert bytes(compress(file)) == raw
+    file.seek(0)
+    file.truncate()
+    file.write(b"prefix" + raw)
+    for decompress in (cramjam.snappy.decompress_raw, lambda data: cramjam.snappy.decompress(data, format="raw")):
+        file.seek(6)
+        assert bytes(decompress(file)) == data
+
+
+@pytest.mark.parametrize("format", ("framed", "raw"))
+def test_snappy_decompress_len(format):
+    import random
+
+    # several chunks of the framed format, compressed and stored uncompressed
+    random.seed(0)
+    data = b"".join(b"line %d of some data\n" % i for i in range(10000))
+    data += bytes(random.getrandbits(8) for _ in range(100000))
+    for input in (b"", b"x", data):
+        compressed = bytes(cramjam.snappy.compress(input, format=format))
+        decompressed = cramjam.snappy.decompress(compressed, format=format)
+        assert cramjam.snappy.decompress_len(compressed, format=format) == len(decompressed) == len(input)
+
+    # the exact size of buffer to decompress into
+    output = bytearray(cramjam.snappy.decompress_len(compressed, format=format))
+    decompress_into = cramjam.snappy.decompress_into if format == "framed" else cramjam.snappy.decompress_raw_into
+    assert decompress_into(compressed, output) == len(data)
+    assert output == data
+
+    with pytest.raises(cramjam.DecompressionError):
+        cramjam.snappy.decompress_len(b"\xff" * 10, format=format)
+    if format == "framed":
+        with pytest.raises(cramjam.DecompressionError):
+            cramjam.snappy.decompress_len(compressed[: len(compressed) // 2])
+
+
 @pytest.mark.parametrize("Obj", (cramjam.File, cramjam.Buffer))
 def test_dunders(Obj, tmpdir):
     if Obj == cramjam.File:
@@ -221,3 +311,2018 @@ def test_lz4_block(compress_kwargs):
         output_len=len(data) if not compress_kwargs["store_size"] else None,
     )
     assert bytes(out) == data
+
+
+@pytest.mark.parametrize("include_content_size", (False, True))
+def test_lz4_frame(include_content_size):
+    import shutil
+    import subprocess
+    from cramjam import lz4
+
+    data = b"".join(b"line %d of some data\n" % i for i in range(10000))
+    compressed = bytes(lz4.compress(data, include_content_size=include_content_size))
+
+    assert compressed[:4] == b"\x04\x22\x4d\x18"
+    expected_size = len(data) if include_content_size else None
+    assert cramjam.peek(compressed)["content_size"] == expected_size
+    assert bytes(lz4.decompress(compressed)) == data
+
+    if shutil.which("lz4"):
+        out = subprocess.run(["lz4", "-dc"], input=compressed, stdout=subprocess.PIPE, check=True)
+        assert out.stdout == data
+
+
+def test_lz4_block_acceleration():
+    from cramjam import lz4
+
+    data = b"".join(b"line %d of some data\n" % i for i in range(10000))
+    slowest = bytes(lz4.compress_block(data, acceleration=1))
+    fastest = bytes(lz4.compress_block(data, acceleration=1000))
+    assert len(slowest) < len(fastest)
+    assert bytes(lz4.decompress_block(slowest)) == bytes(lz4.decompress_block(fastest)) == data
+
+    # without a mode, acceleration selects the fast mode
+    assert fastest == bytes(lz4.compress_block(data, mode="fast", acceleration=1000))
+    assert bytes(lz4.compress_block(data, mode="default", acceleration=1000)) == bytes(lz4.compress_block(data))
+    # as it does with a dict
+    with_dict = bytes(lz4.compress_block(data, acceleration=1000, dict=data[:1000]))
+    assert with_dict != bytes(lz4.compress_block(data, dict=data[:1000]))
+    assert bytes(lz4.decompress_block(with_dict, dict=data[:1000])) == data
+
+
+def test_lz4_frame_content_checksum():
+    import random
+    from cramjam import lz4
+
+    rng = random.Random(0)
+    data = bytes(rng.getrandbits(8) for _ in range(100000))
+    with_checksum = bytes(lz4.compress(data))
+    without_checksum = bytes(lz4.compress(data, content_checksum=False))
+
+    assert with_checksum[4] & 0x04 and not without_checksum[4] & 0x04
+    assert len(without_checksum) == len(with_checksum) - 4
+    assert bytes(lz4.decompress(without_checksum)) == data
+
+    middle = len(with_checksum) // 2
+    for position in (middle, len(with_checksum) - 1):  # a block's data, or the content checksum itself
+        corrupted = bytearray(with_checksum)
+        corrupted[position] ^= 0xFF
+        with pytest.raises(cramjam.DecompressionError):
+            lz4.decompress(bytes(corrupted))
+
+
+def test_lz4_block_dict():
+    from cramjam import lz4
+
+    record = b'{"id": %d, "name": "user %d", "email": "user%d@example.com", "active": true}'
+    dictionary = b"".join(record % (i, i, i) for i in range(100))
+    payloads = [record % (i, i, i) for i in range(1000, 1010)]
+
+    for payload in payloads:
+        compressed = bytes(lz4.compress_block(payload, dict=dictionary))
+        assert len(compressed) < len(bytes(lz4.compress_block(payload)))
+        assert bytes(lz4.decompress_block(compressed, dict=dictionary)) == payload
+
+        raw = bytes(lz4.compress_block(payload, store_size=False, dict=dictionary))
+        assert bytes(lz4.decompress_block(raw, output_len=len(payload), dict=dictionary)) == payload
+
+        # the block refers back into the dictionary, so can't be decompressed without it
+        with pytest.raises(cramjam.DecompressionError):
+            lz4.decompress_block(compressed)
+        with pytest.raises(cramjam.DecompressionError):
+            lz4.decompress_block(compressed, dict=b"another dictionary")
+
+    # the fast mode's acceleration applies with a dictionary too
+    for acceleration in (1, 8, 64):
+        compressed = bytes(lz4.compress_block(payloads[0], acceleration=acceleration, dict=dictionary))
+        assert bytes(lz4.decompress_block(compressed, dict=dictionary)) == payloads[0]
+
+    with pytest.raises(cramjam.CompressionError, match="mode='default' or mode='fast'"):
+        lz4.compress_block(payloads[0], mode="high_compression", dict=dictionary)
+
+
+@pytest.mark.parametrize("include_content_size", (True, False))
+def test_zstd_include_content_size(include_content_size):
+    data = b"oh what a beautiful morning, oh what a beautiful day!!" * 10000
+
+    # Only compress what's left after the current position of the buffer
+    buf = cramjam.Buffer(b"skipped" + data)
+    buf.seek(len(b"skipped"))
+
+    compressed = cramjam.zstd.compress(buf, include_content_size=include_content_size)
+
+    # Frame header descriptor; either of the FCS or single segment flags means the size is stored
+    descriptor = bytes(compressed)[4]
+    assert bool(descriptor & 0b11100000) is include_content_size
+    assert bytes(cramjam.zstd.decompress(compressed)) == data
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_variants_decompress_lines(variant_str):
+    variant = getattr(cramjam, variant_str)
+
+    lines = [b"line %d" % i for i in range(10000)] + [b"", b"last"]
+    data = b"\r\n".join(lines[:100]) + b"\r\n" + b"\n".join(lines[100:])  # no trailing newline
+
+    compressed = variant.compress(data)
+    assert list(variant.decompress_lines(compressed)) == lines
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_variants_compress_strided(variant_str):
+    variant = getattr(cramjam, variant_str)
+
+    arr = np.arange(10000, dtype=np.float64).reshape(100, 100)
+    view = arr[::3, 1::2]
+    assert not view.flags["C_CONTIGUOUS"]
+
+    compressed = variant.compress_strided(view)
+    decompressed = variant.decompress(compressed)
+    assert bytes(decompressed) == view.tobytes()
+    assert np.array_equal(np.frombuffer(decompressed, dtype=np.float64).reshape(view.shape), view)
+
+    # the elements of an object array are pointers, not data
+    for dtype in (object, [("name", object), ("id", np.int64)]):
+        with pytest.raises(TypeError, match="^Arrays of a dtype holding Python objects can't be compressed$"):
+            variant.compress_strided(np.zeros(10, dtype=dtype)[::2])
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_variants_decompress_recover(variant_str):
+    variant = getattr(cramjam, variant_str)
+
+    data = b"".join(b"line %d of some data\n" % i for i in range(20000))
+    compressed = bytes(variant.compress(data))
+    truncated = compressed[: len(compressed) // 2]
+
+    with pytest.raises(cramjam.DecompressionError):
+        variant.decompress(truncated)
+
+    decompressed, is_truncated = variant.decompress(truncated, recover=True)
+    assert is_truncated
+    assert len(decompressed) > 0
+    assert data.startswith(bytes(decompressed))
+
+    decompressed, is_truncated = variant.decompress(compressed, recover=True)
+    assert not is_truncated
+    assert bytes(decompressed) == data
+
+
+@pytest.mark.parametrize("variant_str", ("gzip", "zstd"))
+def test_variants_decompress_partial(variant_str):
+    variant = getattr(cramjam, variant_str)
+
+    data = b"".join(b"line %d of some data\n" % i for i in range(20000))
+    compressed = bytes(variant.compress(data))
+    truncated = compressed[: len(compressed) // 2]
+
+    with pytest.raises(cramjam.DecompressionError):
+        variant.decompress(truncated)
+
+    decompressed = variant.decompress(truncated, partial=True)
+    assert len(decompressed) > 0
+    assert data.startswith(bytes(decompressed))
+
+    assert bytes(variant.decompress(compressed, partial=True)) == data
+
+    # corrupted, rather than truncated, data still raises
+    corrupted = b"\xff" * 4 + truncated[4:]
+    with pytest.raises(cramjam.DecompressionError):
+        variant.decompress(corrupted, partial=True)
+
+
+def test_snappy_compress_chunk_size():
+    data = b"some bytes to compress 123" * 1000
+
+    def chunks(compressed):
+        compressed = bytes(compressed)
+        assert compressed.startswith(b"\xff\x06\x00\x00sNaPpY")
+        pos, n_chunks = 10, 0
+        while pos < len(compressed):
+            chunk_type = compressed[pos]
+            length = int.from_bytes(compressed[pos + 1 : pos + 4], "little")
+            assert chunk_type in (0x00, 0x01)  # compressed or uncompressed data
+            pos += 4 + length
+            n_chunks += 1
+        assert pos == len(compressed)
+        return n_chunks
+
+    assert chunks(cramjam.snappy.compress(data)) == 1
+
+    compressed = cramjam.snappy.compress(data, chunk_size=1000)
+    assert chunks(compressed) == 26
+    assert bytes(cramjam.snappy.decompress(compressed)) == data
+
+    with pytest.raises(cramjam.CompressionError, match="chunk_size"):
+        cramjam.snappy.compress(data, chunk_size=65537)
+
+
+def test_snappy_checksum_mismatch():
+    import random
+
+    rng = random.Random(0)
+    data = bytes(rng.getrandbits(8) for _ in range(10000))
+    compressed = bytearray(bytes(cramjam.snappy.compress(data)))
+    # random data is stored in an uncompressed chunk, after its type, length and CRC-32C
+    assert compressed[10] == 0x01
+    compressed[10 + 8 + 100] ^= 0xFF
+
+    with pytest.raises(cramjam.DecompressionError, match="snappy chunk checksum mismatch"):
+        cramjam.snappy.decompress(bytes(compressed))
+    with pytest.raises(cramjam.DecompressionError) as exc_info:
+        cramjam.snappy.decompress(b"not snappy data")
+    assert "checksum" not in str(exc_info.value)
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_variants_ctypes_buffer(variant_str):
+    import ctypes
+
+    variant = getattr(cramjam, variant_str)
+    data = b"some bytes to compress 123" * 1000
+
+    # create_string_buffer appends a null terminator
+    buffer = ctypes.create_string_buffer(data)
+    compressed = variant.compress(buffer)
+    assert bytes(variant.decompress(compressed)) == data + b"\x00"
+
+    array = (ctypes.c_char * len(data)).from_buffer_copy(data)
+    compressed = variant.compress(array)
+    assert bytes(variant.decompress(compressed)) == data
+
+    # and as output
+    output = (ctypes.c_char * len(data))()
+    compressed.seek(0)
+    assert variant.decompress_into(compressed, output) == len(data)
+    assert bytes(output) == data
+
+
+def _through_pipes(op, data):
+    """Run op(in_fd, out_fd) between two pipes, feeding data and draining the output from threads"""
+    import os
+    import threading
+
+    in_read, in_write = os.pipe()
+    out_read, out_write = os.pipe()
+    output = []
+
+    def feed():
+        with os.fdopen(in_write, "wb") as f:
+            f.write(data)
+
+    def drain():
+        with os.fdopen(out_read, "rb") as f:
+            output.append(f.read())
+
+    threads = [threading.Thread(target=feed), threading.Thread(target=drain)]
+    for thread in threads:
+        thread.start()
+    try:
+        n_bytes = op(in_read, out_write)
+    finally:
+        os.close(in_read)
+        os.close(out_write)
+        for thread in threads:
+            thread.join()
+    return n_bytes, output[0]
+
+
+@pytest.mark.skipif(not hasattr(cramjam.zstd, "compress_fd"), reason="file descriptors are unix only")
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_variants_compress_fd(variant_str):
+    variant = getattr(cramjam, variant_str)
+    # larger than a pipe's buffer, so both ends have to be serviced while it runs
+    data = b"".join(b"line %d of some data\n" % i for i in range(50000))
+
+    n_bytes, compressed = _through_pipes(variant.compress_fd, data)
+    assert n_bytes == len(compressed)
+    assert bytes(variant.decompress(compressed)) == data
+
+    n_bytes, decompressed = _through_pipes(variant.decompress_fd, compressed)
+    assert n_bytes == len(data)
+    assert decompressed == data
+
+    with pytest.raises(cramjam.DecompressionError):
+        _through_pipes(variant.decompress_fd, b"not compressed data" * 10)
+
+    for fds in ((-1, 1), (0, -1)):
+        with pytest.raises(ValueError, match="Invalid file descriptor -1"):
+            variant.compress_fd(*fds)
+        with pytest.raises(ValueError, match="Invalid file descriptor -1"):
+            variant.decompress_fd(*fds)
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_variants_decompress_into_large(variant_str):
+    variant = getattr(cramjam, variant_str)
+    # several megabytes, so the decoder's reads are far shorter than the output
+    data = b"".join(b"line %d of some data\n" % i for i in range(250000))
+    compressed = bytes(variant.compress(data))
+
+    for output in (bytearray(len(data)), memoryview(bytearray(len(data)))):
+        assert variant.decompress_into(compressed, output) == len(data)
+        assert bytes(output) == data
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd", "bzip2", "lzma", "zlib")
+)
+def test_variants_compress_into_too_small(variant_str):
+    variant = getattr(cramjam, variant_str)
+    data = b"".join(b"line %d of some data\n" % i for i in range(1000))
+    compressed_len = len(variant.compress(data))
+
+    # the error gives the codec's bound of the length needed, which is then enough
+    bound = variant.compress_bound(len(data))
+    with pytest.raises(cramjam.CompressionError, match=f"^output buffer too small: needed up to {bound} bytes$"):
+        variant.compress_into(data, memoryview(bytearray(10)))
+    output = memoryview(bytearray(bound))
+    assert variant.compress_into(data, output) == compressed_len
+    assert bytes(variant.decompress(bytes(output[:compressed_len]))) == data
+
+    with pytest.raises(cramjam.CompressionError, match=f"needed up to {bound} bytes"):
+        variant.compress_into(data, np.zeros(10, dtype=np.uint8))
+
+
+@pytest.mark.parametrize("variant_str", ("gzip", "zstd"))
+def test_variants_decompress_into_bytearray_presized(variant_str):
+    variant = getattr(cramjam, variant_str)
+    # decompresses to far more than its length, so a bytearray output would be grown many times
+    data = b"".join(b"%d" % (i % 10) * 1000 for i in range(10000))
+    compressed = bytes(variant.compress(data))
+
+    output = bytearray()
+    assert variant.decompress_into(compressed, output) == len(data)
+    assert output == data
+
+    # a longer bytearray is written from its start, and keeps its length
+    output = bytearray(b"x" * (len(data) + 10))
+    assert variant.decompress_into(compressed, output) == len(data)
+    assert output == data + b"x" * 10
+
+    # on failure, the bytearray is shrunk back from the recorded length to what was written
+    output = bytearray()
+    with pytest.raises(Exception):
+        variant.decompress_into(compressed[:1000] + compressed[-100:], output)
+    assert len(output) < len(data)
+
+
+@pytest.mark.parametrize("variant_str", ("brotli", "deflate"))
+def test_variants_into_bytearray_exact_len(variant_str):
+    variant = getattr(cramjam, variant_str)
+    data = b"".join(b"line %d of some data\n" % i for i in range(10000))
+    compressed = bytes(variant.compress(data))
+
+    # a bytearray is grown by what's written to it, so it's left without trailing padding
+    output = bytearray()
+    assert variant.decompress_into(compressed, output) == len(output) == len(data)
+    assert output == data
+
+    output = bytearray()
+    assert variant.compress_into(data, output) == len(output) == len(compressed)
+    assert bytes(variant.decompress(output)) == data
+
+
+def test_gzip_decompress_into_bytearray_presized_multi_member():
+    # the trailer of the last member only records its own length, not that of all the members
+    first, last = b"first member " * 10, b"last member " * 10000
+    compressed = bytes(cramjam.gzip.compress(first)) + bytes(cramjam.gzip.compress(last))
+    output = bytearray()
+    assert cramjam.gzip.decompress_into(compressed, output) == len(first) + len(last)
+    assert output == first + last
+
+    compressed = bytes(cramjam.gzip.compress(last)) + bytes(cramjam.gzip.compress(first))
+    output = bytearray()
+    assert cramjam.gzip.decompress_into(compressed, output) == len(first) + len(last)
+    assert output == last + first
+
+
+def test_gzip_decompress_multi_member():
+    import gzip
+
+    first, second = b"first member " * 1000, b"second member " * 1000
+    compressed = bytes(cramjam.gzip.compress(first)) + bytes(cramjam.gzip.compress(second, level=1))
+    assert bytes(cramjam.gzip.decompress(compressed)) == first + second
+    assert bytes(cramjam.gzip.decompress(compressed, output_len=10)) == first + second
+    assert bytes(cramjam.gzip.decompress(compressed, exact=True)) == first + second
+    assert gzip.decompress(compressed) == first + second
+
+    # as written by Python's gzip module too, including an empty member between them
+    compressed = gzip.compress(first) + gzip.compress(b"") + bytes(cramjam.gzip.compress(second))
+    assert bytes(cramjam.gzip.decompress(compressed)) == first + second
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_variants_readonly_memoryview(variant_str):
+    variant = getattr(cramjam, variant_str)
+    data = b"some bytes to compress 123" * 1000
+
+    # read-only buffers are fine as input
+    compressed = bytes(variant.compress(memoryview(data)))
+    output = bytearray(len(data))
+    assert variant.decompress_into(memoryview(compressed), output) == len(data)
+    assert bytes(output) == data
+
+    # but not as output, unlike writable ones
+    output = memoryview(bytearray(len(data)))
+    assert variant.decompress_into(compressed, output) == len(data)
+    assert bytes(output) == data
+    with pytest.raises(BufferError, match="output buffer is read-only"):
+        variant.decompress_into(compressed, memoryview(bytes(len(data))))
+    with pytest.raises(BufferError, match="output buffer is read-only"):
+        variant.compress_into(data, memoryview(bytes(len(data))))
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_variants_output_buffer(variant_str):
+    variant = getattr(cramjam, variant_str)
+    data = b"some bytes to compress 123" * 1000
+
+    # outputs are returned as a cramjam.Buffer, which owns the output rather than copying it to bytes
+    compressed = variant.compress(data)
+    assert isinstance(compressed, cramjam.Buffer)
+    view = memoryview(compressed)
+    assert len(compressed) == view.nbytes == len(bytes(compressed))
+    assert view.tobytes() == bytes(compressed)
+    del view
+
+    decompressed = variant.decompress(compressed)
+    assert isinstance(decompressed, cramjam.Buffer) and len(decompressed) == len(data)
+    assert memoryview(decompressed).tobytes() == data
+    # and can be given as the input of another de/compression, from its start
+    decompressed.seek(0)
+    assert bytes(variant.decompress(variant.compress(decompressed))) == data
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_variants_memoryview(variant_str):
+    variant = getattr(cramjam, variant_str)
+    data = bytearray(b"some bytes to compress 123" * 1000)
+
+    compressed = variant.compress(memoryview(data))
+    assert bytes(variant.decompress(memoryview(bytearray(bytes(compressed))))) == data
+
+    # a contiguous slice is read in place, from its offset
+    sliced = memoryview(data)[10:-10]
+    compressed = bytes(variant.compress(sliced))
+    assert bytes(variant.decompress(memoryview(compressed)[:])) == data[10:-10]
+    padded = b"xx" + compressed + b"xx"
+    assert bytes(variant.decompress(memoryview(padded)[2:-2])) == data[10:-10]
+
+    with pytest.raises(TypeError, match="not C contiguous"):
+        variant.compress(memoryview(data)[::2])
+    with pytest.raises(TypeError, match="has 2 dimensions"):
+        variant.compress(memoryview(data).cast("B", [1000, 26]))
+    assert bytes(variant.compress(memoryview(data).cast("B", [1000, 26]).cast("B"))) == bytes(variant.compress(data))
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_variants_numpy_any_dtype(variant_str):
+    variant = getattr(cramjam, variant_str)
+    arr = np.arange(1000, dtype=np.float64)
+
+    compressed = variant.compress(arr)
+    output = np.zeros(arr.nbytes, dtype=np.uint8)
+    assert variant.decompress_into(compressed, output) == arr.nbytes
+    assert np.array_equal(output.view(np.float64), arr)
+
+    # contiguous arrays of more dimensions are read as their bytes in C order
+    matrix = np.arange(1000, dtype=np.int64).reshape(10, 100)
+    assert bytes(variant.decompress(variant.compress(matrix))) == matrix.tobytes()
+
+    with pytest.raises(TypeError, match="ascontiguousarray"):
+        variant.compress(matrix[:, ::2])
+    with pytest.raises(TypeError, match="ascontiguousarray"):
+        variant.compress(np.asfortranarray(matrix))
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_variants_pickle_buffer(variant_str):
+    import pickle
+
+    variant = getattr(cramjam, variant_str)
+    arr = np.arange(10000, dtype=np.float64)
+
+    buffer = pickle.PickleBuffer(arr)
+    compressed = variant.compress(buffer)
+    decompressed = variant.decompress(pickle.PickleBuffer(bytearray(bytes(compressed))))
+    assert np.array_equal(np.frombuffer(decompressed, dtype=np.float64), arr)
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_variants_pickle_out_of_band(variant_str):
+    import pickle
+
+    variant = getattr(cramjam, variant_str)
+
+    # the out-of-band buffers of protocol 5, compressed and restored as a serializer would
+    buffers = []
+    payload = pickle.dumps(bytearray(b"out of band data" * 1000), protocol=5, buffer_callback=buffers.append)
+    compressed = [bytes(variant.compress(buffer)) for buffer in buffers]
+    restored = pickle.loads(payload, buffers=[bytearray(variant.decompress(c)) for c in compressed])
+    assert restored == bytearray(b"out of band data" * 1000)
+
+
+@pytest.mark.parametrize(
+    "variant_str,small_level,medium_level",
+    (
+        ("brotli", 11, 6),
+        ("lz4", 9, 4),
+        ("gzip", 9, 6),
+        ("deflate", 9, 6),
+        ("zstd", 19, 3),
+    ),
+)
+def test_variants_compress_level_auto(variant_str, small_level, medium_level):
+    variant = getattr(cramjam, variant_str)
+
+    small = b"".join(b"line %d of some data\n" % i for i in range(100))
+    medium = b"".join(b"line %d of some data\n" % i for i in range(100000))
+    assert len(small) < 1 << 20 <= len(medium) < 64 << 20
+
+    for data, level in ((small, small_level), (medium, medium_level)):
+        compressed = variant.compress(data, level="auto")
+        assert bytes(compressed) == bytes(variant.compress(data, level=level))
+        assert bytes(variant.decompress(compressed)) == data
+
+    with pytest.raises(ValueError, match="auto"):
+        variant.compress(small, level="fast")
+
+
+@pytest.mark.parametrize(
+    "variant_str,default_level,zero_is_default",
+    (
+        ("brotli", 11, False),
+        ("lz4", 4, False),
+        ("gzip", 6, False),
+        ("deflate", 6, False),
+        ("zstd", 3, True),  # zstd itself treats level 0 as its default level
+    ),
+)
+def test_variants_compress_level_none_or_zero(variant_str, default_level, zero_is_default):
+    variant = getattr(cramjam, variant_str)
+    data = b"".join(b"line %d of some data\n" % i for i in range(10000))
+
+    omitted = bytes(variant.compress(data))
+    assert bytes(variant.compress(data, level=None)) == omitted
+    assert bytes(variant.compress(data, level=default_level)) == omitted
+
+    zero = bytes(variant.compress(data, level=0))
+    assert (zero == omitted) is zero_is_default
+    assert bytes(variant.decompress(zero)) == data
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_variants_compress_str(variant_str):
+    variant = getattr(cramjam, variant_str)
+    text = "naïve café, Ελληνικά, 日本語 🎉\n" * 100
+
+    compressed = bytes(variant.compress_str(text))
+    assert bytes(variant.decompress(compressed)) == text.encode("utf-8")
+    assert variant.decompress_str(compressed) == text
+
+    compressed = bytes(variant.compress_str(text, encoding="utf-16"))
+    assert bytes(variant.decompress(compressed)) == text.encode("utf-16")
+    assert variant.decompress_str(compressed, encoding="utf-16") == text
+
+    with pytest.raises(UnicodeEncodeError):
+        variant.compress_str(text, encoding="ascii")
+    with pytest.raises(UnicodeDecodeError):
+        variant.decompress_str(variant.compress_str(text), encoding="ascii")
+    with pytest.raises(LookupError):
+        variant.compress_str(text, encoding="not-an-encoding")
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_variants_verify_many(variant_str):
+    variant = getattr(cramjam, variant_str)
+
+    data = [b"".join(b"item %d line %d\n" % (i, j) for j in range(1000)) for i in range(50)]
+    items = [bytes(variant.compress(d)) for d in data]
+    assert variant.verify_many(items) is None
+    assert variant.verify_many(items, expected=data) is None
+    assert variant.verify_many([]) is None
+
+    corrupted = list(items)
+    corrupted[37] = items[37][: len(items[37]) // 2]
+    corrupted[42] = b"not compressed data"
+    assert variant.verify_many(corrupted) == 37
+
+    mismatched = list(data)
+    mismatched[12] = data[13]
+    assert variant.verify_many(items, expected=mismatched) == 12
+
+    with pytest.raises(ValueError):
+        variant.verify_many(items, expected=data[:-1])
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_variants_is_valid(variant_str):
+    import random
+
+    variant = getattr(cramjam, variant_str)
+
+    data = b"".join(b"line %d of some data\n" % i for i in range(20000))
+    compressed = bytes(variant.compress(data))
+    assert variant.is_valid(compressed) is True
+    assert variant.is_valid(bytearray(compressed)) is True
+    assert variant.is_valid(compressed[: len(compressed) // 2]) is False
+    assert variant.is_valid(compressed[:-1]) is False
+    rng = random.Random(0)
+    assert variant.is_valid(bytes(rng.getrandbits(8) for _ in range(1000))) is False
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_variants_decompress_many_into(variant_str):
+    variant = getattr(cramjam, variant_str)
+
+    data = [b"".join(b"item %d line %d\n" % (i, j) for j in range(100 * i)) for i in range(5)]
+    items = [bytes(variant.compress(d)) for d in data]
+    total_len = sum(len(d) for d in data)
+
+    array = np.zeros(total_len + 10, dtype=np.uint8)
+    regions = variant.decompress_many_into(items, array)
+    assert [length for _, length in regions] == [len(d) for d in data]
+    offset = 0
+    for d, (region_offset, length) in zip(data, regions):
+        assert region_offset == offset
+        assert array[offset : offset + length].tobytes() == d
+        offset += length
+    assert not array[offset:].any()
+
+    # an item in the array would be overwritten as it's decompressed
+    array[: len(items[1])] = np.frombuffer(items[1], dtype=np.uint8)
+    with pytest.raises(ValueError, match="^Item 1 overlaps the array it'd be decompressed into$"):
+        variant.decompress_many_into([items[0], array[: len(items[1])]], array)
+
+    with pytest.raises(BufferError):
+        variant.decompress_many_into(items, np.zeros(total_len - 1, dtype=np.uint8))
+    with pytest.raises(cramjam.DecompressionError, match="item 1"):
+        variant.decompress_many_into([items[0], b"not compressed data"], array)
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_variants_maybe_compress(variant_str):
+    import random
+
+    variant = getattr(cramjam, variant_str)
+    rng = random.Random(0)
+    text = b"".join(b"line %d of some text\n" % i for i in range(1000))
+    randbytes = bytes(rng.getrandbits(8) for _ in range(10000))
+
+    data, compressed = variant.maybe_compress(text)
+    assert compressed and len(data) < len(text)
+    assert bytes(variant.maybe_decompress(data, compressed)) == text
+
+    data, compressed = variant.maybe_compress(randbytes)
+    assert not compressed and bytes(data) == randbytes
+    assert bytes(variant.maybe_decompress(data, compressed)) == randbytes
+
+    # an unreachable ratio keeps even compressible data as is
+    data, compressed = variant.maybe_compress(text, min_ratio=1000.0)
+    assert not compressed and bytes(data) == text
+
+    # the level is as for compress, including "auto"
+    if variant_str != "snappy":
+        data, compressed = variant.maybe_compress(text, level="auto")
+        assert compressed and bytes(variant.maybe_decompress(data, compressed)) == text
+        with pytest.raises(ValueError, match="Invalid level 'fastest'"):
+            variant.maybe_compress(text, level="fastest")
+
+
+def test_zstd_compress_many():
+    buffers = [b"blob %d of a column, %s" % (i, b"x" * (i % 50)) for i in range(1000)]
+    buffers[10] = b""
+
+    compressed = cramjam.zstd.compress_many(buffers)
+    assert len(compressed) == len(buffers)
+    assert all(isinstance(item, bytes) for item in compressed)
+    for item, buffer in zip(compressed, buffers):
+        assert bytes(cramjam.zstd.decompress(item)) == buffer
+
+    # any type of buffer, at any level
+    compressed = cramjam.zstd.compress_many([bytearray(buffers[1]), memoryview(buffers[2])], level=19)
+    assert [bytes(cramjam.zstd.decompress(item)) for item in compressed] == buffers[1:3]
+    assert cramjam.zstd.compress_many([]) == []
+
+    with pytest.raises(cramjam.CompressionError):
+        cramjam.zstd.compress_many(buffers, level=cramjam.zstd.MAX_LEVEL + 1)
+
+
+def test_zstd_decompress_many():
+    buffers = [b"page %d of a column, %s" % (i, b"x" * (i % 50)) for i in range(1000)]
+    compressed = cramjam.zstd.compress_many(buffers)
+
+    decompressed = cramjam.zstd.decompress_many(compressed)
+    assert all(isinstance(item, bytes) for item in decompressed)
+    assert decompressed == buffers
+    assert cramjam.zstd.decompress_many(compressed, output_lens=[len(b) for b in buffers]) == buffers
+    # the lengths are only hints, and streamed frames, without a recorded length, decompress too
+    streamed = [bytes(cramjam.zstd.compress(b)) for b in buffers[:10]]
+    assert cramjam.zstd.decompress_many(streamed, output_lens=[1] * 10) == buffers[:10]
+    assert cramjam.zstd.decompress_many([]) == []
+
+    with pytest.raises(ValueError, match="Got 2 output_lens for 1000 buffers"):
+        cramjam.zstd.decompress_many(compressed, output_lens=[1, 2])
+    with pytest.raises(cramjam.DecompressionError, match="item 3"):
+        cramjam.zstd.decompress_many(compressed[:3] + [b"not zstd data"] + compressed[4:])
+
+
+def test_zstd_compress_chunked():
+    data = b"".join(b"line %d of some data\n" % i for i in range(10000))
+    chunk_size = 4096
+    chunks = [data[i : i + chunk_size] for i in range(0, len(data), chunk_size)]
+
+    compressed = bytes(cramjam.zstd.compress_chunked(data, chunk_size=chunk_size))
+    for index in (0, 7, len(chunks) - 1, 3):
+        decompressed = cramjam.zstd.decompress_chunk(compressed, index)
+        assert bytes(decompressed) == chunks[index]
+
+    with pytest.raises(IndexError):
+        cramjam.zstd.decompress_chunk(compressed, len(chunks))
+
+    # the index is a skippable frame, the whole output is still regular zstd
+    assert bytes(cramjam.zstd.decompress(compressed)) == data
+
+    with pytest.raises(cramjam.DecompressionError, match="chunk index"):
+        cramjam.zstd.decompress_chunk(cramjam.zstd.compress(data), 0)
+
+    # a chunk_size far larger than the input isn't allocated up front
+    compressed = bytes(cramjam.zstd.compress_chunked(data, chunk_size=2 ** 62))
+    assert bytes(cramjam.zstd.decompress_chunk(compressed, 0)) == data
+
+
+def test_zstd_compress_chunked_on_chunk():
+    data = b"".join(b"line %d of some data\n" % i for i in range(10000))
+    chunk_size = 4096
+
+    index = []
+    compressed = bytes(
+        cramjam.zstd.compress_chunked(data, chunk_size=chunk_size, on_chunk=lambda *entry: index.append(entry))
+    )
+    assert len(index) == len(range(0, len(data), chunk_size))
+    assert [plaintext_offset for plaintext_offset, _, _ in index] == list(range(0, len(data), chunk_size))
+
+    # each entry locates a frame which decompresses alone to its chunk
+    plaintext_offset, compressed_offset, compressed_len = index[5]
+    frame = compressed[compressed_offset : compressed_offset + compressed_len]
+    assert bytes(cramjam.zstd.decompress(frame)) == data[plaintext_offset : plaintext_offset + chunk_size]
+
+    def raises(*_):
+        raise KeyError("stop")
+
+    with pytest.raises(KeyError, match="stop"):
+        cramjam.zstd.compress_chunked(data, chunk_size=chunk_size, on_chunk=raises)
+
+
+def test_zstd_append():
+    old = b"".join(b"old line %d\n" % i for i in range(1000))
+    new = b"".join(b"new line %d\n" % i for i in range(1000))
+
+    existing = bytes(cramjam.zstd.compress(old))
+    combined = cramjam.zstd.append(existing, new, level=5)
+    assert bytes(combined)[: len(existing)] == existing
+    assert bytes(cramjam.zstd.decompress(combined)) == old + new
+
+    # appending again, and to an empty stream
+    combined = cramjam.zstd.append(bytes(combined), b"more")
+    assert bytes(cramjam.zstd.decompress(combined)) == old + new + b"more"
+    assert bytes(cramjam.zstd.decompress(cramjam.zstd.append(b"", new))) == new
+
+    with pytest.raises(cramjam.DecompressionError, match="Invalid zstd frame"):
+        cramjam.zstd.append(existing[:-1], new)
+    with pytest.raises(cramjam.DecompressionError, match="Invalid zstd frame"):
+        cramjam.zstd.append(b"not zstd data", new)
+
+
+def test_zstd_compress_delta():
+    import random
+
+    rng = random.Random(0)
+    base = bytes(rng.getrandbits(8) for _ in range(100000))
+    target = bytearray(base)
+    for _ in range(10):
+        target[rng.randrange(len(target))] = rng.getrandbits(8)
+    target = bytes(target[:50000]) + b"inserted" + bytes(target[50000:])
+
+    patch = bytes(cramjam.zstd.compress_delta(base, target))
+    assert len(patch) < len(bytes(cramjam.zstd.compress(target))) // 50
+    assert bytes(cramjam.zstd.decompress_delta(base, patch)) == target
+    assert bytes(cramjam.zstd.decompress_delta(b"", cramjam.zstd.compress_delta(b"", b"new data", level=1))) == b"new data"
+
+    # the patch is only meaningful against the same base
+    with pytest.raises(cramjam.DecompressionError):
+        cramjam.zstd.decompress_delta(b"", patch)
+    with pytest.raises(cramjam.DecompressionError, match="Invalid zstd delta patch"):
+        cramjam.zstd.decompress_delta(base, b"not a patch")
+
+    # a patch far shorter than its target, repeats of the base, is grown into rather than presized
+    patch = bytes(cramjam.zstd.compress_delta(base, base * 3))
+    assert len(patch) * 1032 < len(base) * 3
+    assert bytes(cramjam.zstd.decompress_delta(base, patch)) == base * 3
+
+    # a forged frame header claiming 2**56 bytes isn't allocated for
+    forged = b"\x28\xb5\x2f\xfd\xe0" + (1 << 56).to_bytes(8, "little") + b"\x19\x00\x00abc"
+    with pytest.raises(cramjam.DecompressionError):
+        cramjam.zstd.decompress_delta(base, forged)
+
+
+def test_zstd_compress_ultra_levels():
+    import random
+
+    # a repeat further back than level 19's 8MiB window, but within level 22's
+    rng = random.Random(0)
+    block = bytes(rng.getrandbits(8) for _ in range(256 * 1024))
+    data = block + bytes(9 << 20) + block
+
+    ultra = bytes(cramjam.zstd.compress(data, level=22))
+    assert len(ultra) < len(bytes(cramjam.zstd.compress(data, level=19))) * 0.6
+    assert bytes(cramjam.zstd.decompress(ultra)) == data
+
+    for level in (23, -131073):
+        with pytest.raises(cramjam.CompressionError, match="between -131072 and 22"):
+            cramjam.zstd.compress(data, level=level)
+
+
+@pytest.mark.parametrize(
+    "variant_str, default_level, max_level",
+    (
+        ("brotli", 11, 11),
+        ("lz4", 4, 12),
+        ("gzip", 6, 9),
+        ("deflate", 6, 9),
+        ("zlib", 6, 9),
+        ("bzip2", 9, 9),
+        ("lzma", 6, 9),
+        ("zstd", 3, 22),
+    ),
+)
+def test_variants_level_constants(variant_str, default_level, max_level):
+    variant = getattr(cramjam, variant_str)
+    assert variant.DEFAULT_LEVEL == default_level
+    assert variant.MAX_LEVEL == max_level
+
+    data = b"".join(b"line %d of some data\n" % i for i in range(1000))
+    compressed = variant.compress(data, level=variant.MAX_LEVEL)
+    assert bytes(variant.decompress(compressed)) == data
+    assert bytes(variant.compress(data)) == bytes(variant.compress(data, level=variant.DEFAULT_LEVEL))
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("brotli", "lz4", "gzip", "deflate", "zlib", "bzip2", "lzma", "zstd")
+)
+def test_variants_level_out_of_range(variant_str):
+    variant = getattr(cramjam, variant_str)
+    data = b"some bytes here"
+    message = r"%s level must be between -?\d+ and %d, got 99" % (variant_str, variant.MAX_LEVEL)
+    with pytest.raises(cramjam.CompressionError, match=message):
+        variant.compress(data, level=99)
+    # raised before an output of output_len bytes is allocated
+    with pytest.raises(cramjam.CompressionError, match="got 99"):
+        variant.compress(data, level=99, output_len=1 << 40)
+    with pytest.raises(cramjam.CompressionError, match="got %d" % (variant.MAX_LEVEL + 1)):
+        variant.compress(data, level=variant.MAX_LEVEL + 1)
+
+
+def test_zstd_level_range():
+    assert cramjam.zstd.MIN_LEVEL == -131072
+    data = b"".join(b"line %d of some data\n" % i for i in range(1000))
+    assert bytes(cramjam.zstd.decompress(cramjam.zstd.compress(data, level=cramjam.zstd.MIN_LEVEL))) == data
+    with pytest.raises(cramjam.CompressionError):
+        cramjam.zstd.compress(data, level=cramjam.zstd.MAX_LEVEL + 1)
+
+
+def test_brotli_lgwin():
+    # repeats beyond a 1KiB window, so the window size makes a difference
+    data = b"".join(b"line %d of some data\n" % (i % 5000) for i in range(50000))
+    small = bytes(cramjam.brotli.compress(data, lgwin=10))
+    large = bytes(cramjam.brotli.compress(data, lgwin=24))
+    assert bytes(cramjam.brotli.decompress(small)) == data
+    assert bytes(cramjam.brotli.decompress(large)) == data
+    assert len(large) < len(small)
+    assert bytes(cramjam.brotli.compress(data, lgwin=22)) == bytes(cramjam.brotli.compress(data))
+
+    for lgwin in (9, 25):
+        with pytest.raises(cramjam.CompressionError, match="lgwin must be between 10 and 24, got %d" % lgwin):
+            cramjam.brotli.compress(data, lgwin=lgwin)
+
+
+def test_brotli_mode():
+    html = b"".join(
+        b'<tr><td class="name">Item number %d</td><td class="price">$%d.99</td></tr>\n' % (i, i * 7 % 100)
+        for i in range(2000)
+    )
+    html = b"<html><body><table>\n" + html + b"</table></body></html>\n"
+
+    generic = bytes(cramjam.brotli.compress(html))
+    assert generic == bytes(cramjam.brotli.compress(html, mode="generic"))
+    for mode in ("text", "font"):
+        assert bytes(cramjam.brotli.decompress(cramjam.brotli.compress(html, mode=mode))) == html
+    # the mode is only a hint to the encoder, which doesn't always make use of it
+    assert len(cramjam.brotli.compress(html, mode="text")) <= len(generic)
+    assert bytes(cramjam.brotli.compress(html, mode="font")) != generic
+
+    with pytest.raises(ValueError, match="Unknown mode 'html'"):
+        cramjam.brotli.compress(html, mode="html")
+
+
+def test_zstd_default_level():
+    # the default is pinned here rather than left to the bundled zstd, so it can't drift with it
+    assert cramjam.zstd.DEFAULT_LEVEL == 3
+
+    data = b"".join(b"line %d of some data, %d\n" % (i, i * 7 % 13) for i in range(10000))
+    default = bytes(cramjam.zstd.compress(data))
+    assert default == bytes(cramjam.zstd.compress(data, level=3))
+    assert default == bytes(cramjam.zstd.compress(data, level=0))
+    assert default != bytes(cramjam.zstd.compress(data, level=1))
+    assert default != bytes(cramjam.zstd.compress(data, level=19))
+
+
+def test_zstd_train_dict_cover():
+    import random
+
+    rng = random.Random(0)
+    words = [b"alpha", b"beta", b"gamma", b"delta", b"user", b"id", b"name"]
+    samples = [
+        b'{"%s": %d, "%s": "%s"}' % (rng.choice(words), rng.randrange(1000), rng.choice(words), rng.choice(words))
+        for _ in range(2000)
+    ]
+
+    for params in (dict(k=64, d=8), dict(steps=4)):
+        dictionary = bytes(cramjam.zstd.train_dict_cover(samples, 4096, **params))
+        assert 0 < len(dictionary) <= 4096
+        # zstd's dictionary magic number, followed by a dictionary id
+        assert dictionary[:4] == (0xEC30A437).to_bytes(4, "little")
+        assert dictionary[4:8] != bytes(4)
+
+        # as a prefix, the dictionary makes a single sample compress smaller than alone
+        sample = samples[7]
+        patch = bytes(cramjam.zstd.compress_delta(dictionary, sample))
+        assert len(patch) < len(bytes(cramjam.zstd.compress(sample)))
+        assert bytes(cramjam.zstd.decompress_delta(dictionary, patch)) == sample
+
+    with pytest.raises(cramjam.CompressionError, match="must not be greater than k"):
+        cramjam.zstd.train_dict_cover(samples, 4096, k=8, d=16)
+    with pytest.raises(cramjam.CompressionError, match="greater than 0"):
+        cramjam.zstd.train_dict_cover(samples, 4096, k=0)
+    with pytest.raises(cramjam.CompressionError, match="dict_size"):
+        cramjam.zstd.train_dict_cover(samples, 4096, k=8192)
+    with pytest.raises(ValueError, match="at least 256 bytes"):
+        cramjam.zstd.train_dict_cover(samples, 100)
+    # rejected before the dictionary is allocated
+    with pytest.raises(ValueError, match="at most 134217728 bytes"):
+        cramjam.zstd.train_dict_cover(samples, 2**62)
+    with pytest.raises(ValueError, match="at most 134217728 bytes"):
+        cramjam.zstd.train_dict(samples, 2**62)
+
+
+def test_zstd_train_dict():
+    import random
+
+    rng = random.Random(0)
+    words = [b"alpha", b"beta", b"gamma", b"delta", b"user", b"id", b"name"]
+    samples = [
+        b'{"%s": %d, "%s": "%s"}' % (rng.choice(words), rng.randrange(1000), rng.choice(words), rng.choice(words))
+        for _ in range(2000)
+    ]
+    dictionary = bytes(cramjam.zstd.train_dict(samples, 4096))
+    assert 0 < len(dictionary) <= 4096
+    assert dictionary[:4] == (0xEC30A437).to_bytes(4, "little")
+
+    sample = samples[7]
+    with_dict = bytes(cramjam.zstd.compress(sample, dict=dictionary))
+    assert len(with_dict) < len(bytes(cramjam.zstd.compress(sample)))
+    assert bytes(cramjam.zstd.decompress(with_dict, dict=dictionary)) == sample
+    assert bytes(cramjam.zstd.decompress(with_dict, dict=dictionary, exact=True)) == sample
+    assert bytes(cramjam.zstd.decompress(with_dict, dict=dictionary, timeout_ms=10000)) == sample
+
+    # the dictionary is needed to decompress
+    with pytest.raises(cramjam.DecompressionError):
+        cramjam.zstd.decompress(with_dict)
+
+    with pytest.raises(ValueError, match="at least 256 bytes"):
+        cramjam.zstd.train_dict(samples, 100)
+
+
+def test_zstd_compress_strategy():
+    data = b"".join(b"line %d of some text, %d\n" % (i, i % 7) for i in range(10000))
+
+    sizes = {}
+    for strategy in ("fast", "dfast", "greedy", "lazy", "lazy2", "btlazy2", "btopt", "btultra", "btultra2"):
+        compressed = bytes(cramjam.zstd.compress(data, strategy=strategy))
+        assert bytes(cramjam.zstd.decompress(compressed)) == data
+        sizes[strategy] = len(compressed)
+    assert sizes["btultra2"] < sizes["fast"]
+
+    with pytest.raises(cramjam.CompressionError, match="Unknown strategy 'best'"):
+        cramjam.zstd.compress(data, strategy="best")
+
+
+def test_zstd_compress_tune_window():
+    def window_size(frame):
+        # frame header descriptor, then the window descriptor, absent from single segment frames
+        assert frame[4] & 0x20 == 0
+        exponent, mantissa = frame[5] >> 3, frame[5] & 0x07
+        base = 1 << (10 + exponent)
+        return base + base // 8 * mantissa
+
+    data = b"".join(b"line %d of some text, %d\n" % (i, i % 7) for i in range(100))
+    default = bytes(cramjam.zstd.compress(data, include_content_size=False))
+    tuned = bytes(cramjam.zstd.compress(data, include_content_size=False, tune_window=True))
+    assert bytes(cramjam.zstd.decompress(tuned)) == data
+    assert window_size(tuned) < window_size(default)
+    assert len(data) <= window_size(tuned) < 2 * len(data)
+    # a window covering all the data loses no matches, so the output is no larger
+    assert len(tuned) <= len(default)
+
+    # down to the smallest window zstd allows
+    tuned = bytes(cramjam.zstd.compress(b"some bytes here", include_content_size=False, tune_window=True))
+    assert window_size(tuned) == 1024
+    assert bytes(cramjam.zstd.decompress(tuned)) == b"some bytes here"
+
+
+def test_zstd_compress_workers():
+    import random
+
+    # ~32MiB, compressible but not trivially so, to be split between the workers
+    rng = random.Random(0)
+    words = [b"alpha", b"beta", b"gamma", b"delta", b"epsilon"]
+    data = b" ".join(rng.choice(words) for _ in range(5 * 1024 * 1024))
+
+    compressed = bytes(cramjam.zstd.compress(data, workers=4))
+    assert bytes(cramjam.zstd.decompress(compressed)) == data
+
+    single = bytes(cramjam.zstd.compress(data[:1 << 20]))
+    assert bytes(cramjam.zstd.compress(data[:1 << 20], workers=0)) == single
+    assert bytes(cramjam.zstd.compress(data[:1 << 20], workers=None)) == single
+
+
+def test_zstd_compress_file(tmp_path):
+    data = b"".join(b"line %d of some data\n" % i for i in range(200000))
+    original, compressed, decompressed = tmp_path / "data.txt", tmp_path / "data.zst", tmp_path / "out.txt"
+    original.write_bytes(data)
+
+    n_bytes = cramjam.zstd.compress_file(str(original), str(compressed), level=5)
+    assert n_bytes == compressed.stat().st_size < len(data)
+    assert bytes(cramjam.zstd.decompress(compressed.read_bytes())) == data
+
+    decompressed.write_bytes(b"existing content, which is overwritten" * 100000)
+    assert cramjam.zstd.decompress_file(str(compressed), str(decompressed)) == len(data)
+    assert decompressed.read_bytes() == data
+
+    with pytest.raises(FileNotFoundError):
+        cramjam.zstd.compress_file(str(tmp_path / "missing"), str(compressed))
+    with pytest.raises(ValueError, match="same file"):
+        cramjam.zstd.compress_file(str(original), str(original))
+    assert original.read_bytes() == data
+    with pytest.raises(cramjam.CompressionError, match="zstd level"):
+        cramjam.zstd.compress_file(str(original), str(compressed), level=23)
+    # an invalid level is raised before the output is truncated
+    assert bytes(cramjam.zstd.decompress(compressed.read_bytes())) == data
+    with pytest.raises(cramjam.DecompressionError):
+        cramjam.zstd.decompress_file(str(original), str(decompressed))
+
+
+def test_zstd_frame_header():
+    data = b"".join(b"line %d of some data\n" % i for i in range(10000))
+
+    compressed = cramjam.zstd.compress(data)
+    assert cramjam.zstd.frame_content_size(compressed) == len(data)
+    header = cramjam.zstd.frame_header(compressed)
+    assert header["frame_type"] == "frame" and header["content_size"] == len(data)
+    assert header["dict_id"] is None and header["has_checksum"] is False
+    assert compressed.tell() == 0
+
+    compressed = bytes(cramjam.zstd.compress(data, include_content_size=False))
+    assert cramjam.zstd.frame_content_size(compressed) is None
+    header = cramjam.zstd.frame_header(compressed)
+    assert header["content_size"] is None and header["window_size"] >= header["block_size_max"] > 0
+    assert header["header_size"] < len(compressed)
+
+    dictionary = cramjam.zstd.train_dict([b"record %d with some fields" % i for i in range(1000)], 4096)
+    compressed = cramjam.zstd.compress(b"record 1 with some fields", dict=dictionary)
+    assert cramjam.zstd.frame_header(compressed)["dict_id"] == cramjam.zstd.peek(compressed)["dict_id"] != None
+
+    skippable = b"\x50\x2a\x4d\x18" + (5).to_bytes(4, "little") + b"fives"
+    assert cramjam.zstd.frame_header(skippable)["frame_type"] == "skippable"
+    assert cramjam.zstd.frame_header(skippable)["content_size"] == 5
+
+    with pytest.raises(cramjam.DecompressionError, match="Invalid zstd frame header"):
+        cramjam.zstd.frame_content_size(b"not zstd data")
+    with pytest.raises(cramjam.DecompressionError, match="Truncated zstd frame header"):
+        cramjam.zstd.frame_header(bytes(compressed)[:5])
+
+
+def test_zstd_compressor():
+    data = b"".join(b"line %d of some data\n" % i for i in range(100000))
+    chunks = [data[:1000], data[1000:500000], data[500000:]]
+
+    compressor = cramjam.zstd.Compressor()
+    outputs = [compressor.compress(chunk) for chunk in chunks]
+    assert all(isinstance(output, bytes) for output in outputs)
+    compressed = b"".join(outputs) + compressor.finish()
+    assert bytes(cramjam.zstd.decompress(compressed)) == data
+    # the same frame as compressing all of it at once, but for its length in the header
+    assert compressed == bytes(cramjam.zstd.compress(data, include_content_size=False))
+    assert repr(compressor) == "cramjam.zstd.Compressor(finished=True)"
+
+    # flushing makes what's given so far decompressable, without ending the frame
+    compressor = cramjam.zstd.Compressor(level=19)
+    flushed = compressor.compress(chunks[0]) + compressor.flush()
+    assert bytes(cramjam.AutoDecompressor().decompress(flushed)) == chunks[0]
+    compressed = flushed + compressor.compress(chunks[1]) + compressor.compress(chunks[2]) + compressor.finish()
+    assert bytes(cramjam.zstd.decompress(compressed)) == data
+
+    assert compressor.finish() == b""
+    with pytest.raises(ValueError, match="already finished"):
+        compressor.compress(b"more")
+    with pytest.raises(cramjam.CompressionError, match="zstd level"):
+        cramjam.zstd.Compressor(level=23)
+
+
+def test_zstd_compressor_flush_threshold():
+    data = b"".join(b"line %d of some data\n" % i for i in range(1000))
+    assert len(data) > 4096
+
+    # buffered until zstd has a block to write
+    assert cramjam.zstd.Compressor().compress(data) == b""
+
+    compressor = cramjam.zstd.Compressor(flush_threshold=4096)
+    flushed = compressor.compress(data)
+    assert bytes(cramjam.AutoDecompressor().decompress(flushed)) == data
+    # below the threshold since the last flush, it's buffered again
+    assert compressor.compress(b"more") == b""
+    compressed = flushed + compressor.finish()
+    assert bytes(cramjam.zstd.decompress(compressed)) == data + b"more"
+
+
+def test_zstd_compressor_packetize():
+    import random
+
+    rng = random.Random(0)
+    data = b"".join(b"line %d of some data %d\n" % (i, rng.getrandbits(32)) for i in range(50000))
+    chunks = [data[i : i + 100000] for i in range(0, len(data), 100000)]
+
+    compressor = cramjam.zstd.Compressor()
+    packets = []
+    for chunk in chunks:
+        packets += compressor.compress(chunk, packetize=1400)
+    packets += compressor.flush(packetize=1400)
+    packets += compressor.finish(packetize=1400)
+    assert len(packets) > 1
+    assert all(isinstance(packet, bytes) and 0 < len(packet) <= 1400 for packet in packets)
+    assert bytes(cramjam.zstd.decompress(b"".join(packets))) == data
+
+    compressor = cramjam.zstd.Compressor()
+    assert compressor.compress(b"small", packetize=1400) == []
+    with pytest.raises(ValueError, match="packetize"):
+        compressor.compress(b"more", packetize=0)
+    # nothing was consumed by the failing call
+    assert bytes(cramjam.zstd.decompress(b"".join(compressor.finish(packetize=1400)))) == b"small"
+
+
+def test_zstd_compressor_set_dict():
+    import random
+
+    rng = random.Random(0)
+
+    def records(fields, n):
+        return [b'{"%s": %d, "%s": "x"}' % (rng.choice(fields), rng.randrange(1000), rng.choice(fields)) for _ in range(n)]
+
+    old, new = records([b"user", b"name", b"email"], 2000), records([b"host", b"port", b"status"], 2000)
+    old_dict = bytes(cramjam.zstd.train_dict(old, 1024))
+    new_dict = bytes(cramjam.zstd.train_dict(new, 1024))
+
+    compressor = cramjam.zstd.Compressor()
+    plain = compressor.compress(b"".join(old[:10]))
+    plain += compressor.set_dict(old_dict)
+    with_old = compressor.compress(b"".join(old[10:20]))
+    with_old += compressor.set_dict(new_dict)
+    with_new = compressor.compress(b"".join(new[:10])) + compressor.finish()
+
+    # each frame records the id of the dictionary it's compressed with
+    assert cramjam.zstd.frame_header(plain)["dict_id"] is None
+    assert cramjam.zstd.frame_header(with_old)["dict_id"] == int.from_bytes(old_dict[4:8], "little")
+    assert cramjam.zstd.frame_header(with_new)["dict_id"] == int.from_bytes(new_dict[4:8], "little")
+    assert bytes(cramjam.zstd.decompress(with_old, dict=old_dict)) == b"".join(old[10:20])
+    assert bytes(cramjam.zstd.decompress(with_new, dict=new_dict)) == b"".join(new[:10])
+
+    compressed = plain + with_old + with_new
+    expected = b"".join(old[:20] + new[:10])
+    assert bytes(cramjam.zstd.decompress_with_dicts(compressed, [new_dict, old_dict])) == expected
+    with pytest.raises(cramjam.DecompressionError, match="Missing dictionary"):
+        cramjam.zstd.decompress_with_dicts(compressed, [old_dict])
+
+    with pytest.raises(ValueError, match="no dictionary id"):
+        cramjam.zstd.Compressor().set_dict(b"raw content" * 100)
+    with pytest.raises(ValueError, match="already finished"):
+        compressor.set_dict(old_dict)
+
+
+def test_zstd_adaptive_compressor():
+    import random
+
+    rng = random.Random(0)
+    users, hosts = [b"alice", b"bob", b"carol"], [b"web-1", b"web-2", b"db-1"]
+    old = [
+        b"GET /users/%s/profile 200 %dms\n" % (rng.choice(users), rng.randrange(1000)) for _ in range(1000)
+    ]
+    # the records drift to a format the first dictionary knows nothing of
+    new = [
+        b'{"host": "%s", "cpu": %d, "mem": %d}\n' % (rng.choice(hosts), rng.randrange(100), rng.randrange(1 << 20))
+        for _ in range(2000)
+    ]
+
+    compressor = cramjam.zstd.AdaptiveCompressor(retrain_interval=1000, dict_size=4096)
+    assert compressor.dict_id is None
+    outputs = [compressor.compress(record) for record in old]
+    first_dict_id = compressor.dict_id
+    assert first_dict_id is not None
+    outputs += [compressor.compress(record) for record in new]
+    assert compressor.dict_id not in (None, first_dict_id)
+
+    # frames of new records with the old dictionary, then the one re-trained on them; the first
+    # output with a new dictionary holds its frame as well, so it's left out
+    before = sum(len(output) for output in outputs[1001:2000])
+    after = sum(len(output) for output in outputs[2001:3000])
+    assert after < before * 0.8
+
+    compressed = b"".join(outputs)
+    assert bytes(cramjam.zstd.decompress_adaptive(compressed)) == b"".join(old + new)
+    # plain frames decompress as they are, but not those without their dictionary
+    assert bytes(cramjam.zstd.decompress_adaptive(bytes(cramjam.zstd.compress(b"plain")))) == b"plain"
+    with pytest.raises(cramjam.DecompressionError, match="Missing dictionary"):
+        cramjam.zstd.decompress_adaptive(outputs[1500])
+
+    with pytest.raises(ValueError, match="retrain_interval"):
+        cramjam.zstd.AdaptiveCompressor(retrain_interval=0)
+    with pytest.raises(ValueError, match="at least 256 bytes"):
+        cramjam.zstd.AdaptiveCompressor(dict_size=100)
+
+
+@pytest.mark.parametrize("variant, name", (("gzip", "crc32"), ("zlib", "adler32")))
+def test_checksums(variant, name):
+    import zlib
+
+    checksum, expected = getattr(getattr(cramjam, variant), name), getattr(zlib, name)
+    data = b"".join(b"line %d of some data\n" % i for i in range(10000))
+    for value in (b"", b"a", b"some bytes here", data):
+        assert checksum(value) == expected(value)
+        assert checksum(bytearray(value)) == expected(value)
+
+    value = None
+    for i in range(0, len(data), 1000):
+        value = checksum(data[i : i + 1000], value=value)
+    assert value == expected(data)
+    assert checksum(b"", value=12345) == 12345
+
+
+def test_gzip_header_fields():
+    import gzip
+
+    data = b"some bytes to compress 123" * 1000
+    compressed = bytes(cramjam.gzip.compress(data, filename="data.txt", mtime=1600000000, comment="café"))
+    assert cramjam.gzip.read_header(compressed) == dict(
+        filename="data.txt", comment="café", mtime=1600000000, os=255
+    )
+    assert bytes(cramjam.gzip.decompress(compressed)) == data
+    assert gzip.decompress(compressed) == data
+
+    # without them, the header only depends on the data and level
+    compressed = bytes(cramjam.gzip.compress(data))
+    assert cramjam.gzip.read_header(compressed) == dict(filename=None, comment=None, mtime=0, os=255)
+    assert compressed == bytes(cramjam.gzip.compress(data, mtime=0))
+
+    # headers written by Python's gzip module, read from the current position
+    buffer = cramjam.Buffer(b"xx" + gzip.compress(data, mtime=1234))
+    buffer.seek(2)
+    assert cramjam.gzip.read_header(buffer)["mtime"] == 1234
+    assert buffer.tell() == 2
+
+    with pytest.raises(cramjam.DecompressionError):
+        cramjam.gzip.read_header(b"not gzip data")
+    with pytest.raises(cramjam.DecompressionError):
+        cramjam.gzip.read_header(compressed[:5])
+    with pytest.raises(ValueError, match="latin-1"):
+        cramjam.gzip.compress(data, filename="日本.txt")
+    with pytest.raises(ValueError, match="latin-1"):
+        cramjam.gzip.compress(data, comment="a\x00b")
+
+
+def test_gzip_decompressor():
+    data = b"".join(b"line %d of some data\n" % i for i in range(10000))
+    compressed = bytes(cramjam.gzip.compress(data))
+
+    for size in (1, 7, 1000, len(compressed)):
+        decompressor = cramjam.gzip.Decompressor()
+        chunks = [compressed[i : i + size] for i in range(0, len(compressed), size)]
+        outputs = [decompressor.decompress(chunk) for chunk in chunks]
+        assert all(isinstance(output, bytes) for output in outputs)
+        assert b"".join(outputs) + decompressor.finish() == data
+    assert repr(decompressor) == "cramjam.gzip.Decompressor(finished=True)"
+
+    # concatenated members are decompressed one after the other, as by the `gzip` command
+    decompressor = cramjam.gzip.Decompressor()
+    assert decompressor.decompress(compressed + compressed) + decompressor.finish() == data + data
+
+    with pytest.raises(ValueError, match="already finished"):
+        decompressor.decompress(compressed)
+    with pytest.raises(ValueError, match="already finished"):
+        decompressor.finish()
+
+    decompressor = cramjam.gzip.Decompressor()
+    decompressor.decompress(compressed[: len(compressed) // 2])
+    with pytest.raises(cramjam.DecompressionError):
+        decompressor.finish()
+    with pytest.raises(cramjam.DecompressionError):
+        cramjam.gzip.Decompressor().decompress(b"not gzip data")
+
+
+
+def test_gzip_decompressor_max_buffered():
+    data = b"".join(b"line %d of some data\n" % i for i in range(500000))
+    compressed = bytes(cramjam.gzip.compress(data))
+
+    # draining every small chunk's output keeps within the limit
+    decompressor = cramjam.gzip.Decompressor(max_buffered=1_000_000)
+    chunks = [compressed[i : i + 100] for i in range(0, len(compressed), 100)]
+    assert b"".join(decompressor.decompress(chunk) for chunk in chunks) + decompressor.finish() == data
+
+    # the whole stream at once decompresses to more than the limit
+    decompressor = cramjam.gzip.Decompressor(max_buffered=1_000_000)
+    with pytest.raises(cramjam.DecompressionError, match="^output backpressure exceeded$"):
+        decompressor.decompress(compressed)
+    with pytest.raises(ValueError, match="already finished"):
+        decompressor.decompress(compressed)
+
+def test_gzip_compress_into_file():
+    import io
+
+    data = b"".join(b"line %d of some data\n" % i for i in range(10000))
+    fileobj = io.BytesIO()
+    n_bytes = cramjam.gzip.compress_into_file(data, fileobj, level=6)
+    assert n_bytes == len(fileobj.getvalue()) > 0
+    assert fileobj.getvalue() == bytes(cramjam.gzip.compress(data, level=6))
+    assert bytes(cramjam.gzip.decompress(fileobj.getvalue())) == data
+
+    # any object with a write method, whose own exceptions are raised as they are
+    class Chunks:
+        def __init__(self):
+            self.chunks = []
+
+        def write(self, chunk):
+            self.chunks.append(bytes(chunk))
+
+    chunks = Chunks()
+    assert cramjam.gzip.compress_into_file(data, chunks) == sum(map(len, chunks.chunks))
+    assert bytes(cramjam.gzip.decompress(b"".join(chunks.chunks))) == data
+
+    class Full:
+        def write(self, chunk):
+            raise OSError("no space left")
+
+    with pytest.raises(OSError, match="no space left"):
+        cramjam.gzip.compress_into_file(data, Full())
+    with pytest.raises(AttributeError):
+        cramjam.gzip.compress_into_file(data, object())
+    with pytest.raises(cramjam.CompressionError):
+        cramjam.gzip.compress_into_file(data, io.BytesIO(), level=10)
+
+
+def test_zstd_compress_stream():
+    import io
+
+    data = b"".join(b"line %d of some data\n" % i for i in range(100000))
+    compressed = cramjam.zstd.compress_stream(io.BytesIO(data), level=3)
+    assert bytes(cramjam.zstd.decompress(compressed)) == data
+
+    # read in chunks, rather than whole
+    class Reader(io.BytesIO):
+        sizes = []
+
+        def read(self, n=-1):
+            self.sizes.append(n)
+            return super().read(n)
+
+    reader = Reader(data)
+    assert bytes(cramjam.zstd.decompress(cramjam.zstd.compress_stream(reader))) == data
+    assert len(reader.sizes) > 1 and max(reader.sizes) < len(data)
+
+    class Broken:
+        def read(self, n):
+            raise OSError("connection reset")
+
+    with pytest.raises(cramjam.CompressionError, match="connection reset"):
+        cramjam.zstd.compress_stream(Broken())
+    with pytest.raises(TypeError):
+        cramjam.zstd.compress_stream(b"not a reader")
+
+    # read may return any buffer, rather than bytes
+    for wrap in (bytearray, memoryview):
+
+        class BufferReader(io.BytesIO):
+            def read(self, n=-1):
+                return wrap(super().read(n))
+
+        compressed = cramjam.zstd.compress_stream(BufferReader(data))
+        assert bytes(cramjam.zstd.decompress(compressed)) == data
+
+    class TextReader:
+        def read(self, n):
+            return "text"
+
+    with pytest.raises(cramjam.CompressionError, match="Unable to read"):
+        cramjam.zstd.compress_stream(TextReader())
+
+
+def test_frame_version_1():
+    assert cramjam.FRAME_VERSION == 1
+    assert cramjam.FRAME_BYTE_ORDER == "little"
+
+    # version, compressed length, zstd frame of b"some bytes here", zero padding
+    padded = (
+        b"\x01\x18\x00\x00\x00"
+        b"(\xb5/\xfd \x0fy\x00\x00some bytes here"
+        b"\x00\x00\x00"
+    )
+    assert bytes(cramjam.zstd.decompress(padded, pad_to=32)) == b"some bytes here"
+
+    # zstd frames of b"abc" and b"def", then the skippable frame indexing them
+    chunked = (
+        b"(\xb5/\xfd \x03\x19\x00\x00abc"
+        b"(\xb5/\xfd \x03\x19\x00\x00def"
+        b"\\*M\x18\x15\x00\x00\x00\x01"
+        b"\x0c\x00\x00\x00\x00\x00\x00\x00\x0c\x00\x00\x00\x00\x00\x00\x00\x02\x00\x00\x00"
+    )
+    assert bytes(cramjam.zstd.decompress_chunk(chunked, 1)) == b"def"
+
+    # the headers written now match the pinned ones
+    assert bytes(cramjam.zstd.compress(b"some bytes here", pad_to=32))[:5] == padded[:5]
+    assert bytes(cramjam.zstd.compress_chunked(b"abcdef", chunk_size=3))[-29:] == chunked[-29:]
+    assert bytes(cramjam.lz4.compress_block(b"some bytes here"))[:4] == (15).to_bytes(4, cramjam.FRAME_BYTE_ORDER)
+
+    unknown_version = bytes([cramjam.FRAME_VERSION + 1])
+    with pytest.raises(cramjam.DecompressionError, match="frame version 2"):
+        cramjam.zstd.decompress(unknown_version + padded[1:], pad_to=32)
+    with pytest.raises(cramjam.DecompressionError, match="frame version 2"):
+        cramjam.zstd.decompress_chunk(chunked[:-21] + unknown_version + chunked[-20:], 1)
+
+
+@pytest.mark.parametrize(
+    "compress,decompress",
+    (
+        ("snappy.compress_raw", "snappy.decompress_raw"),
+        ("lz4.compress_block", "lz4.decompress_block"),
+        ("deflate.compress", "deflate.decompress"),
+    ),
+)
+def test_raw_decompress_without_output_len(compress, decompress):
+    def func(path):
+        module, name = path.split(".")
+        return getattr(getattr(cramjam, module), name)
+
+    data = b"some bytes to compress 123" * 1000
+    kwargs = {"store_size": True} if compress == "lz4.compress_block" else {}
+    compressed = func(compress)(data, **kwargs)
+    assert bytes(func(decompress)(compressed)) == data
+
+
+def test_lz4_block_store_size():
+    data = b"some bytes to compress 123" * 1000
+    compressed = bytes(cramjam.lz4.compress_block(data, store_size=True))
+    assert int.from_bytes(compressed[:4], "little") == len(data)
+    assert compressed[4:] == bytes(cramjam.lz4.compress_block(data, store_size=False))
+
+    with pytest.raises(cramjam.DecompressionError, match="store_size"):
+        cramjam.lz4.decompress_block(b"\x00")
+
+
+@pytest.mark.parametrize(
+    "compress,decompress",
+    (
+        (cramjam.snappy.compress_raw, cramjam.snappy.decompress_raw),
+        (cramjam.deflate.compress, cramjam.deflate.decompress),
+    ),
+)
+def test_raw_store_size(compress, decompress):
+    data = b"some bytes to compress 123" * 1000
+    compressed = bytes(compress(data, store_size=True))
+    assert int.from_bytes(compressed[:4], "little") == len(data)
+    assert compressed[4:] == bytes(compress(data))
+    assert bytes(decompress(compressed, store_size=True)) == data
+
+    # a stored size which doesn't match the decompressed data
+    forged = (len(data) + 1).to_bytes(4, "little") + compressed[4:]
+    with pytest.raises(cramjam.DecompressionError, match="stored size"):
+        decompress(forged, store_size=True)
+    with pytest.raises(cramjam.DecompressionError, match="store_size"):
+        decompress(b"\x00", store_size=True)
+
+
+def test_deflate_store_size_with_trailer_and_padding():
+    data = b"some bytes to compress 123" * 1000
+    kwargs = {"store_size": True, "trailer_checksum": "crc32", "pad_to": 64}
+    compressed = bytes(cramjam.deflate.compress(data, **kwargs))
+    assert bytes(cramjam.deflate.decompress(compressed, **kwargs)) == data
+
+    with pytest.raises(ValueError, match="store_size"):
+        cramjam.deflate.decompress(compressed, output_len=len(data), **kwargs)
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_variants_compress_reuse(variant_str):
+    variant = getattr(cramjam, variant_str)
+
+    scratch = np.empty(0, dtype=np.uint8)
+    for i in range(1, 50):
+        data = b"".join(b"line %d of some data\n" % j for j in range(i * 100))
+        compressed = variant.compress_reuse(data, scratch)
+        assert isinstance(compressed, bytes)
+        assert compressed == bytes(variant.compress(data))
+        assert bytes(variant.decompress(compressed)) == data
+
+    # the scratch array is grown as needed, and kept for the next call
+    assert len(scratch) >= len(compressed)
+
+
+@pytest.mark.parametrize("variant_str", ("lz4", "zstd"))
+def test_variants_decompress_timeout(variant_str):
+    variant = getattr(cramjam, variant_str)
+
+    data = b"".join(b"line %d of some data\n" % i for i in range(1_500_000))
+    compressed = bytes(variant.compress(data))
+
+    # a timeout of 0 has always passed, however fast decompression is
+    with pytest.raises(cramjam.DecompressionError, match="timed out"):
+        variant.decompress(compressed, timeout_ms=0)
+
+    # a timeout isn't recoverable
+    with pytest.raises(cramjam.DecompressionError, match="timed out"):
+        variant.decompress(compressed, timeout_ms=0, recover=True)
+
+    decompressed = variant.decompress(compressed, timeout_ms=60_000)
+    assert bytes(decompressed) == data
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+@pytest.mark.parametrize("pad_to", (1, 64, 4096))
+def test_variants_compress_pad_to(variant_str, pad_to):
+    variant = getattr(cramjam, variant_str)
+
+    for data in (b"", b"some bytes here", b"".join(b"line %d of some data\n" % i for i in range(10000))):
+        padded = bytes(variant.compress(data, pad_to=pad_to))
+        assert len(padded) % pad_to == 0
+        assert len(padded) >= len(bytes(variant.compress(data))) + 4
+        assert bytes(variant.decompress(padded, pad_to=pad_to)) == data
+
+    # other decompress options still apply to the unpadded data
+    decompressed, truncated = variant.decompress(padded, pad_to=pad_to, recover=True)
+    assert bytes(decompressed) == data and not truncated
+
+    if pad_to > 1:
+        with pytest.raises(cramjam.DecompressionError, match="pad_to"):
+            variant.decompress(padded + b"\x00", pad_to=pad_to)
+    with pytest.raises(cramjam.CompressionError, match="pad_to"):
+        variant.compress(data, pad_to=0)
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_variants_decompress_exact(variant_str):
+    variant = getattr(cramjam, variant_str)
+    data = b"".join(b"line %d of some data\n" % i for i in range(100000))
+    compressed = bytes(variant.compress(data))
+
+    decompressed = variant.decompress(compressed, exact=True)
+    assert isinstance(decompressed, cramjam.Buffer)
+    assert len(decompressed) == len(data)
+    assert bytes(decompressed) == bytes(variant.decompress(compressed)) == data
+    assert bytes(variant.decompress(variant.compress(b""), exact=True)) == b""
+
+    # applies after the other options which change the compressed data
+    padded = bytes(variant.compress(data, pad_to=64, trailer_checksum="crc32"))
+    assert bytes(variant.decompress(padded, pad_to=64, trailer_checksum="crc32", exact=True)) == data
+
+    with pytest.raises(cramjam.DecompressionError):
+        variant.decompress(compressed[: len(compressed) // 2], exact=True)
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_variants_compress_trailer_checksum(variant_str):
+    variant = getattr(cramjam, variant_str)
+    data = b"".join(b"line %d of some data\n" % i for i in range(10000))
+
+    compressed = bytes(variant.compress(data, trailer_checksum="crc32"))
+    assert len(compressed) == len(bytes(variant.compress(data))) + 5
+    assert compressed[-5] == cramjam.FRAME_VERSION
+    assert bytes(variant.decompress(compressed, trailer_checksum="crc32")) == data
+
+    corrupted = bytearray(compressed)
+    corrupted[len(corrupted) // 2] ^= 0x01
+    with pytest.raises(cramjam.DecompressionError, match="Trailer checksum mismatch"):
+        variant.decompress(bytes(corrupted), trailer_checksum="crc32")
+
+    # the trailer goes inside any padding
+    padded = bytes(variant.compress(data, pad_to=64, trailer_checksum="crc32"))
+    assert bytes(variant.decompress(padded, pad_to=64, trailer_checksum="crc32")) == data
+
+    with pytest.raises(cramjam.DecompressionError):
+        variant.decompress(b"123", trailer_checksum="crc32")
+    with pytest.raises(ValueError, match="Unknown trailer_checksum 'md5'"):
+        variant.compress(data, trailer_checksum="md5")
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_variants_compress_trailer_checksum_xxh3(variant_str):
+    variant = getattr(cramjam, variant_str)
+    data = b"".join(b"line %d of some data\n" % i for i in range(10000))
+
+    # the trailer's version is followed by a 64 bit checksum
+    compressed = bytes(variant.compress(data, trailer_checksum="xxh3"))
+    assert len(compressed) == len(bytes(variant.compress(data))) + 9
+    assert compressed[-9] == cramjam.FRAME_VERSION
+    assert bytes(variant.decompress(compressed, trailer_checksum="xxh3")) == data
+
+    corrupted = bytearray(compressed)
+    corrupted[len(corrupted) // 2] ^= 0x01
+    with pytest.raises(cramjam.DecompressionError, match="Trailer checksum mismatch"):
+        variant.decompress(bytes(corrupted), trailer_checksum="xxh3")
+    with pytest.raises(cramjam.DecompressionError):
+        variant.decompress(compressed, trailer_checksum="crc32")
+
+    padded = bytes(variant.compress(data, pad_to=64, trailer_checksum="xxh3"))
+    assert bytes(variant.decompress(padded, pad_to=64, trailer_checksum="xxh3")) == data
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_variants_recompress_range(variant_str):
+    variant = getattr(cramjam, variant_str)
+
+    data = b"".join(b"line %d of some data\n" % i for i in range(10000))
+    compressed = bytes(variant.compress(data))
+
+    for start, end, replacement in (
+        (100, 200, b"replaced"),
+        (0, 0, b"prepended"),
+        (len(data), len(data), b"appended"),
+        (5000, 100000, b""),
+    ):
+        recompressed = variant.recompress_range(compressed, start, end, replacement)
+        expected = data[:start] + replacement + data[end:]
+        assert bytes(variant.decompress(recompressed)) == expected
+
+    with pytest.raises(ValueError):
+        variant.recompress_range(compressed, 10, 5, b"")
+    with pytest.raises(cramjam.DecompressionError, match="beyond the end"):
+        variant.recompress_range(compressed, len(data) + 1, len(data) + 2, b"")
+    with pytest.raises(cramjam.DecompressionError, match="beyond the end"):
+        variant.recompress_range(compressed, 0, len(data) + 1, b"")
+
+
+def test_deflate_compress_deterministic():
+    data = b"".join(b"reproducible line %d\n" % (i % 13) for i in range(200))
+
+    # golden output, from flate2 1.1 with miniz_oxide 0.9; a failure here means the deflate backend's
+    # output changed with an upgrade, which breaks deterministic=True's guarantee and needs noting
+    expected = bytes.fromhex(
+        "edd0bb0d803000c4d09e293202e1cf3a408a4811a048eccf0078000ab7aeee5e4d77bd8e67cf5b49a1e43385b6a99f16a1"
+        "75d07a6803b411da046d86b6405b69331ea12791aec820830c32c820830c32c820830c32c820c35f195e"
+    )
+    compressed = bytes(cramjam.deflate.compress(data, level=6, deterministic=True))
+    assert compressed == expected
+    assert bytes(cramjam.deflate.decompress(compressed)) == data
+
+    # however the input is passed
+    assert bytes(cramjam.deflate.compress(bytearray(data), level=6, deterministic=True)) == expected
+    assert bytes(cramjam.deflate.compress(cramjam.Buffer(data), level=6, deterministic=True)) == expected
+    assert bytes(cramjam.deflate.compress(b"", deterministic=True)) == b"\x03\x00"
+
+    with pytest.raises(ValueError, match="output_len"):
+        cramjam.deflate.compress(data, deterministic=True, output_len=len(data))
+
+
+def test_stdlib_keyword_aliases():
+    import gzip
+    import zlib
+
+    data = b"".join(b"line %d of some data\n" % i for i in range(10000))
+
+    # gzip's compresslevel behaves as level
+    compressed = bytes(cramjam.gzip.compress(data, compresslevel=9))
+    assert compressed == bytes(cramjam.gzip.compress(data, level=9))
+    assert compressed != bytes(cramjam.gzip.compress(data, level=1))
+    assert gzip.decompress(compressed) == data
+    with pytest.raises(TypeError, match="compresslevel"):
+        cramjam.gzip.compress(data, level=9, compresslevel=9)
+
+    # deflate is zlib's raw deflate, wbits=-15
+    compressed = bytes(cramjam.deflate.compress(data, wbits=-15))
+    assert zlib.decompress(compressed, wbits=-15) == data
+    compressor = zlib.compressobj(wbits=-15)
+    raw = compressor.compress(data) + compressor.flush()
+    assert bytes(cramjam.deflate.decompress(raw, wbits=-15)) == data
+    with pytest.raises(ValueError, match="Unsupported wbits 15"):
+        cramjam.deflate.compress(data, wbits=15)
+
+
+def test_bzip2():
+    import bz2
+
+    data = b"".join(b"line %d of some data\n" % i for i in range(10000))
+
+    compressed = cramjam.bzip2.compress(data)
+    assert bz2.decompress(bytes(compressed)) == data
+    assert bytes(cramjam.bzip2.decompress(bz2.compress(data, 1))) == data
+    assert bytes(cramjam.bzip2.compress(data, level=1)) == bz2.compress(data, 1)
+
+    output = bytearray(len(data))
+    assert cramjam.bzip2.decompress_into(compressed, output) == len(data)
+    assert bytes(output) == data
+
+    with pytest.raises(cramjam.CompressionError, match="between 1 and 9"):
+        cramjam.bzip2.compress(data, level=0)
+    with pytest.raises(cramjam.CompressionError, match="between 1 and 9"):
+        cramjam.bzip2.compress_into(data, bytearray(len(data)), level=10)
+
+
+def test_lzma():
+    import lzma
+
+    data = b"".join(b"line %d of some data\n" % i for i in range(10000))
+
+    compressed = cramjam.lzma.compress(data)
+    assert lzma.decompress(bytes(compressed)) == data
+    assert bytes(cramjam.lzma.decompress(lzma.compress(data, preset=1))) == data
+
+    # concatenated streams, as written by appending to a .xz file
+    concatenated = bytes(cramjam.lzma.compress(data, level=1)) + lzma.compress(b"appended")
+    assert bytes(cramjam.lzma.decompress(concatenated)) == data + b"appended"
+
+    output = bytearray(len(data))
+    assert cramjam.lzma.decompress_into(compressed, output) == len(data)
+    assert bytes(output) == data
+
+    with pytest.raises(cramjam.CompressionError, match="between 0 and 9"):
+        cramjam.lzma.compress(data, level=10)
+    with pytest.raises(cramjam.DecompressionError):
+        cramjam.lzma.decompress(bytes(compressed)[:-10])
+
+
+def test_zlib():
+    import zlib
+
+    # zlib.compress(b"hello hello hello hello"), at its default level of 6
+    expected = b"x\x9c\xcbH\xcd\xc9\xc9W\xc8@'\x01h\x03\x08\xb1"
+    assert bytes(cramjam.zlib.compress(b"hello hello hello hello")) == expected
+    assert bytes(cramjam.zlib.decompress(expected)) == b"hello hello hello hello"
+
+    data = b"".join(b"line %d of some data\n" % i for i in range(10000))
+    compressed = cramjam.zlib.compress(data, level=9)
+    assert zlib.decompress(bytes(compressed)) == data
+    assert bytes(cramjam.zlib.decompress(zlib.compress(data, 1))) == data
+
+    output = bytearray(len(data))
+    assert cramjam.zlib.decompress_into(compressed, output) == len(data)
+    assert bytes(output) == data
+
+    # raw deflate data lacks the zlib header
+    with pytest.raises(cramjam.DecompressionError):
+        cramjam.zlib.decompress(bytes(cramjam.deflate.compress(data)))
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd", "zlib", "bzip2", "lzma")
+)
+def test_variants_compress_bound(variant_str):
+    import random
+
+    variant = getattr(cramjam, variant_str)
+    rng = random.Random(0)
+    levels = [{}] if variant_str == "snappy" else [{}, {"level": 0}, {"level": 1}, {"level": 9}]
+    if variant_str == "bzip2":
+        # bzip2's levels are block sizes, of which there's none of 0
+        levels.remove({"level": 0})
+
+    # random data doesn't compress, so is the worst case
+    for size in (0, 1, 100, 8192, 8193, 65536, 65537, 300_000, 1 << 20):
+        data = rng.getrandbits(8 * size).to_bytes(size, "little")
+        bound = variant.compress_bound(size)
+        for level in levels:
+            assert len(variant.compress(data, **level)) <= bound
+
+        output = bytearray(bound)
+        n_bytes = variant.compress_into(data, output)
+        assert bytes(variant.decompress(bytes(output[:n_bytes]))) == data
+
+    # a bound too large to be represented is raised rather than wrapped around
+    with pytest.raises(OverflowError):
+        variant.compress_bound(2 ** 64 - 1)
+    assert variant.compress_bound(2 ** 60) >= 2 ** 60
+
+
+def test_arrow_buffer():
+    from cramjam import arrow
+
+    data = b"".join(b"line %d of some data\n" % i for i in range(10000))
+    for algorithm in ("lz4", "zstd"):
+        compressed = bytes(arrow.compress_buffer(data, algorithm))
+        assert int.from_bytes(compressed[:8], "little") == len(data)
+        assert bytes(getattr(cramjam, algorithm).decompress(compressed[8:])) == data
+        assert bytes(arrow.decompress_buffer(compressed, algorithm)) == data
+
+        # -1 for buffers left uncompressed, and no length at all for empty ones
+        assert bytes(arrow.decompress_buffer(b"\xff" * 8 + b"raw data", algorithm)) == b"raw data"
+        assert bytes(arrow.decompress_buffer(b"", algorithm)) == b""
+
+        wrong_len = (len(data) + 1).to_bytes(8, "little") + compressed[8:]
+        with pytest.raises(cramjam.DecompressionError, match="records a length of"):
+            arrow.decompress_buffer(wrong_len, algorithm)
+        with pytest.raises(cramjam.DecompressionError, match="Truncated"):
+            arrow.decompress_buffer(compressed[:4], algorithm)
+
+    # b"arrow buffer " * 10, with bodies written by the lz4 and zstd commands rather than cramjam
+    lz4_buffer = (
+        b'\x82\x00\x00\x00\x00\x00\x00\x00\x04"M\x18d@\xa7\x17\x00\x00\x00\xdfarrow buffer '
+        b"\r\x00]Pffer \x00\x00\x00\x00\xeb1Y\xc6"
+    )
+    zstd_buffer = (
+        b"\x82\x00\x00\x00\x00\x00\x00\x00(\xb5/\xfd$\x82\xa5\x00\x00harrow buffer "
+        b"\x01\x00\x12\xd2\xf9\x05\x99\xae\xa8\x0b"
+    )
+    assert bytes(arrow.decompress_buffer(lz4_buffer, "lz4")) == b"arrow buffer " * 10
+    assert bytes(arrow.decompress_buffer(zstd_buffer, "zstd")) == b"arrow buffer " * 10
+
+    with pytest.raises(ValueError, match="Unknown algorithm"):
+        arrow.compress_buffer(data, "gzip")
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_variants_decompress_to_memmap(variant_str, tmpdir):
+    import os
+
+    variant = getattr(cramjam, variant_str)
+
+    # 16MiB of float64, compressed as it's generated, so it's only ever whole in the map
+    n_items = 2 * 1024 * 1024
+    ring, drained = bytearray(1 << 20), []
+    compressor = cramjam.RingCompressor(variant_str, ring, lambda n: drained.append(bytes(ring[:n])))
+    for i in range(0, n_items, 65536):
+        compressor.write(np.arange(i, i + 65536, dtype=np.float64).tobytes())
+    compressor.finish()
+    compressed = b"".join(drained)
+
+    path = str(tmpdir.join("decompressed.bin"))
+    array = variant.decompress_to_memmap(compressed, path, dtype="float64")
+    assert isinstance(array, np.memmap)
+    assert array.shape == (n_items,)
+    for i in range(0, n_items, 65536):
+        assert np.array_equal(array[i : i + 65536], np.arange(i, i + 65536, dtype=np.float64))
+    del array
+    assert os.path.getsize(path) == n_items * 8
+
+    with pytest.raises(ValueError, match="multiple of the dtype"):
+        variant.decompress_to_memmap(variant.compress(b"12345"), path, dtype="float64")
+
+
+def test_zstd_decompress_to_memmap_empty_file(tmpdir):
+    # the frame is read from the file's position, past the frame of b"prefix"
+    data = cramjam.File(str(tmpdir.join("data.zst")))
+    data.write(bytes(cramjam.zstd.compress(b"prefix")))
+    position = data.tell()
+    data.write(bytes(cramjam.zstd.compress(b"")))
+    data.seek(position)
+
+    path = str(tmpdir.join("decompressed.bin"))
+    with pytest.raises(ValueError, match="^Decompressed length is 0, which can't be mapped by numpy.memmap$"):
+        cramjam.zstd.decompress_to_memmap(data, path)
+
+
+@pytest.mark.parametrize(
+    "variant_str", ("snappy", "brotli", "lz4", "gzip", "deflate", "zstd")
+)
+def test_variants_slab_output_allocator(variant_str):
+    variant = getattr(cramjam, variant_str)
+    uncompressed = b"some bytes to compress 123" * 1000
+
+    cramjam.set_output_allocator("slab", slab_size=64 * 1024, slab_count=2)
+    try:
+        stats = cramjam.output_allocator_stats()
+        assert stats == dict(strategy="slab", slab_size=65536, slab_count=2, free_slabs=2, hits=0, misses=0)
+
+        compressed = variant.compress(uncompressed)
+        decompressed = variant.decompress(compressed, output_len=len(uncompressed))
+        assert bytes(decompressed) == uncompressed
+        stats = cramjam.output_allocator_stats()
+        assert stats["hits"] == 2 and stats["free_slabs"] == 0
+
+        # beyond both the free slabs and the size of a slab, outputs are allocated as by default
+        assert bytes(variant.decompress(bytes(compressed))) == uncompressed
+        large = uncompressed * 10
+        assert bytes(variant.decompress(variant.compress(large), output_len=len(large))) == large
+        assert cramjam.output_allocator_stats()["misses"] >= 2
+
+        # dropped outputs are kept as slabs again
+        del compressed, decompressed
+        assert cramjam.output_allocator_stats()["free_slabs"] == 2
+    finally:
+        cramjam.set_output_allocator("default")
+    assert cramjam.output_allocator_stats() == dict(strategy="default")
+
+    with pytest.raises(ValueError, match="Unknown strategy 'arena'"):
+        cramjam.set_output_allocator("arena")
+    with pytest.raises(ValueError, match="greater than 0"):
+        cramjam.set_output_allocator("slab", slab_size=0)
+
+
+@pytest.mark.parametrize("variant_str", ("gzip", "zstd"))
+def test_variants_decompress_presized(variant_str):
+    variant = getattr(cramjam, variant_str)
+    data = b"".join(b"line %d of some data\n" % i for i in range(10000))
+    compressed = bytes(variant.compress(data))
+
+    assert bytes(variant.decompress(compressed)) == data
+    assert bytes(variant.decompress(variant.compress(b""))) == b""
+    if variant_str == "zstd":
+        # concatenated frames record the length of the first only
+        assert bytes(variant.decompress(compressed * 3)) == data * 3
+
+    # the output is allocated for the recorded length up front, so it's too large for a slab,
+    # while one of unknown length, as it's not looked for past a trailer, starts empty in a slab
+    with_trailer = bytes(variant.compress(data, trailer_checksum="crc32"))
+    cramjam.set_output_allocator("slab", slab_size=1024, slab_count=1)
+    try:
+        assert bytes(variant.decompress(compressed)) == data
+        assert cramjam.output_allocator_stats()["misses"] == 1
+        assert bytes(variant.decompress(with_trailer, trailer_checksum="crc32")) == data
+        assert cramjam.output_allocator_stats()["hits"] == 1
+    finally:
+        cramjam.set_output_allocator("default")
+
+
+@pytest.mark.parametrize(
+    "data,seed,expected32,expected64",
+    [
+        # reference vectors of the xxHash implementation
+        (b"", None, 0x02CC5D05, 0xEF46DB3751D8E999),
+        (b"", 1, 0x0B2CB792, 0xD5AFBA1336A3BE4B),
+        (b"abc", None, 0x32D153FF, 0x44BC2CF5AD770999),
+    ],
+)
+def test_xxhash(data, seed, expected32, expected64):
+    assert cramjam.hash.xxh32(data, seed=seed) == expected32
+    assert cramjam.hash.xxh64(data, seed=seed) == expected64
+    assert cramjam.hash.xxh64(bytearray(data), seed) == expected64
+
+
+def test_xxh32_lz4_content_checksum():
+    data = b"abc" * 1000
+    compressed = bytes(cramjam.lz4.compress(data))
+    assert compressed[4] & 0x04  # content checksum flag
+    assert int.from_bytes(compressed[-4:], "little") == cramjam.hash.xxh32(data)